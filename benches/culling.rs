@@ -0,0 +1,65 @@
+//! Sphere culling of 10 000 objects with [`Frustum`], one sphere at a time, against
+//! [`SimdFrustum`], eight at a time. Both are reported in one group, so the ratio for the
+//! machine at hand can be read off directly; build with `-C target-cpu=native` to let the
+//! SIMD path use AVX2.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use thorus::culling::{Frustum, SimdFrustum};
+use thorus::math::Mat4;
+
+const OBJECTS: usize = 10_000;
+
+fn spheres() -> (Vec<[f32; 3]>, Vec<f32>) {
+    let centers = (0..OBJECTS)
+        .map(|i| {
+            let i = i as f32;
+            [
+                (i * 0.37).sin() * 40.0,
+                (i * 0.11).cos() * 10.0,
+                -(i % 100.0),
+            ]
+        })
+        .collect();
+    let radii = (0..OBJECTS).map(|i| 0.5 + (i % 7) as f32 * 0.25).collect();
+    (centers, radii)
+}
+
+fn cull_spheres(c: &mut Criterion) {
+    let projection = Mat4::perspective(1.0, 16.0 / 9.0, 0.1, 100.0);
+    let view = Mat4::look_at([0.0, 2.0, 5.0], [0.0, 0.0, -10.0], [0.0, 1.0, 0.0]);
+    let frustum = Frustum::from_vp((projection * view).into());
+    let simd = SimdFrustum::new(&frustum);
+    let (centers, radii) = spheres();
+
+    let mut group = c.benchmark_group("10k_spheres");
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            centers
+                .iter()
+                .zip(&radii)
+                .filter(|&(&center, &radius)| frustum.contains_sphere(black_box(center), radius))
+                .count()
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            centers
+                .chunks_exact(8)
+                .zip(radii.chunks_exact(8))
+                .map(|(centers, radii)| {
+                    simd.cull_spheres_8(
+                        black_box(centers.try_into().unwrap()),
+                        radii.try_into().unwrap(),
+                    )
+                    .iter()
+                    .filter(|&&visible| visible)
+                    .count()
+                })
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cull_spheres);
+criterion_main!(benches);
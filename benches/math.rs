@@ -0,0 +1,65 @@
+//! [`Mat4`] multiplication and dot products, scalar against `std::simd`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use thorus::math::{Mat4, Quat, Vec4};
+
+const MULTIPLIES: usize = 1000;
+
+fn matrices() -> Vec<Mat4> {
+    (0..MULTIPLIES)
+        .map(|i| {
+            let angle = i as f32 * 0.01;
+            Mat4::translate([i as f32, 1.0, -2.0])
+                * Mat4::from_quat(Quat::from_axis_angle([0.0, 1.0, 0.0], angle))
+        })
+        .collect()
+}
+
+fn mat4_mul(c: &mut Criterion) {
+    let matrices = matrices();
+    let view = Mat4::look_at([0.0, 2.0, 5.0], [0.0; 3], [0.0, 1.0, 0.0]);
+
+    let mut group = c.benchmark_group("1000_mat4_muls");
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            for model in &matrices {
+                black_box(view.mul_scalar(black_box(*model)));
+            }
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            for model in &matrices {
+                black_box(view.mul_simd(black_box(*model)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn vec4_dot(c: &mut Criterion) {
+    let vectors: Vec<Vec4> = (0..MULTIPLIES)
+        .map(|i| Vec4([i as f32, 1.0, -2.0, 0.5]))
+        .collect();
+    let other = Vec4([0.25, -1.0, 3.0, 1.0]);
+
+    let mut group = c.benchmark_group("1000_vec4_dots");
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            for v in &vectors {
+                black_box(black_box(v).dot(other));
+            }
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            for v in &vectors {
+                black_box(black_box(v).dot_simd(other));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, mat4_mul, vec4_dot);
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+//! Allocation throughput of [`PooledMemoryAllocator`]'s uniform pool against
+//! `StandardMemoryAllocator`, for many small uniform buffers.
+//!
+//! Needs a Vulkan device; without one the benchmark is skipped.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::sync::Arc;
+use thorus::device::{select_physical_device, FeatureSet};
+use thorus::instance::InstanceBuilder;
+use thorus::memory::PooledMemoryAllocator;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, QueueCreateInfo};
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter, StandardMemoryAllocator,
+};
+use vulkano::VulkanLibrary;
+
+const ALLOCATIONS: usize = 10_000;
+
+fn device() -> Option<Arc<Device>> {
+    let instance = InstanceBuilder::new(VulkanLibrary::new().ok()?)
+        .build()
+        .ok()?;
+    let (physical_device, queue_family_index) = select_physical_device(
+        &instance,
+        None,
+        &DeviceExtensions::empty(),
+        FeatureSet::Minimum,
+    )
+    .ok()?;
+    let (enabled_extensions, enabled_features, _) =
+        FeatureSet::Minimum.device_setup(&physical_device);
+    let (device, _) = Device::new(
+        physical_device.clone(),
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..QueueCreateInfo::default()
+            }],
+            enabled_extensions: enabled_extensions
+                .union(&InstanceBuilder::device_extensions(&physical_device)),
+            enabled_features,
+            ..DeviceCreateInfo::default()
+        },
+    )
+    .ok()?;
+    Some(device)
+}
+
+fn allocate_uniforms(allocator: &Arc<dyn MemoryAllocator>) -> Vec<Subbuffer<[u8; 64]>> {
+    (0..ALLOCATIONS)
+        .map(|_| {
+            Buffer::new_sized(
+                allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::UNIFORM_BUFFER,
+                    ..BufferCreateInfo::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..AllocationCreateInfo::default()
+                },
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
+fn uniform_allocations(c: &mut Criterion) {
+    let Some(device) = device() else {
+        eprintln!("no Vulkan device, skipping the memory benchmarks");
+        return;
+    };
+    let standard: Arc<dyn MemoryAllocator> =
+        Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+    let pooled: Arc<dyn MemoryAllocator> = PooledMemoryAllocator::new_default(device)
+        .uniforms()
+        .clone();
+
+    let mut group = c.benchmark_group("10k_64_byte_uniforms");
+    group.sample_size(10);
+    for (name, allocator) in [("standard", &standard), ("pooled", &pooled)] {
+        // Buffers are dropped outside the measurement, which covers allocation only.
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || (),
+                |()| allocate_uniforms(allocator),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, uniform_allocations);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#version 460
+#extension GL_EXT_nonuniform_qualifier : require
+
+layout (location = 0) in vec2 v_uv;
+layout (location = 1) flat in uint v_texture_index;
+
+layout (set = 0, binding = 0) uniform sampler2D textures[];
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = texture(textures[nonuniformEXT(v_texture_index)], v_uv);
+}
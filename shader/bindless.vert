@@ -0,0 +1,19 @@
+#version 460
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+layout (location = 3) in uint texture_index;
+
+layout (push_constant) uniform BindlessParams {
+    mat4 view_proj;
+} params;
+
+layout (location = 0) out vec2 v_uv;
+layout (location = 1) flat out uint v_texture_index;
+
+void main() {
+    v_uv = uv;
+    v_texture_index = texture_index;
+    gl_Position = params.view_proj * vec4(position, 1.0);
+}
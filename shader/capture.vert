@@ -0,0 +1,17 @@
+#version 460
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+
+layout (push_constant) uniform CaptureParams {
+    mat4 transform;
+} params;
+
+layout (location = 0, xfb_buffer = 0, xfb_offset = 0, xfb_stride = 32) out vec4 captured_position;
+layout (location = 1, xfb_buffer = 0, xfb_offset = 16) out vec4 captured_normal;
+
+void main() {
+    captured_position = params.transform * vec4(position, 1.0);
+    captured_normal = vec4(normalize(mat3(params.transform) * normal), 0.0);
+    gl_Position = captured_position;
+}
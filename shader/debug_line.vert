@@ -0,0 +1,15 @@
+#version 460
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec4 color;
+
+layout (push_constant) uniform DebugParams {
+    mat4 view_proj;
+} params;
+
+layout (location = 0) out vec4 v_color;
+
+void main() {
+    v_color = color;
+    gl_Position = params.view_proj * vec4(position, 1.0);
+}
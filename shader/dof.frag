@@ -0,0 +1,59 @@
+#version 460
+
+layout (location = 0) in vec2 v_uv;
+
+layout (set = 0, binding = 0) uniform sampler2D color_buffer;
+layout (set = 0, binding = 1) uniform sampler2D depth_buffer;
+
+layout (push_constant) uniform DofConfig {
+    float focus_distance;
+    float focus_range;
+    float bokeh_radius;
+    uint sample_count;
+    float near;
+    float far;
+} config;
+
+layout (location = 0) out vec4 f_color;
+
+const uint MAX_SAMPLES = 16;
+const vec2 POISSON_DISK[MAX_SAMPLES] = vec2[](
+    vec2(-0.94201624, -0.39906216), vec2(0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870), vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432), vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845), vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554), vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023), vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507), vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367), vec2(0.14383161, -0.14100790)
+);
+
+float linearize(float depth) {
+    return config.near * config.far / (config.far - depth * (config.far - config.near));
+}
+
+// circle of confusion radius in pixels, must match `DofConfig::circle_of_confusion`
+float circle_of_confusion(float linear_depth) {
+    if (config.focus_range <= 0.0) {
+        return 0.0;
+    }
+    float defocus = abs(linear_depth - config.focus_distance) / config.focus_range;
+    return clamp(defocus, 0.0, 1.0) * config.bokeh_radius;
+}
+
+void main() {
+    vec4 center = texture(color_buffer, v_uv);
+    float coc = circle_of_confusion(linearize(texture(depth_buffer, v_uv).r));
+    if (coc < 0.5) {
+        f_color = center;
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(color_buffer, 0));
+    uint count = min(config.sample_count, MAX_SAMPLES);
+    vec4 sum = center;
+    for (uint i = 0; i < count; i++) {
+        sum += texture(color_buffer, v_uv + POISSON_DISK[i] * coc * texel);
+    }
+    f_color = sum / float(count + 1);
+}
@@ -0,0 +1,31 @@
+#version 460
+
+// matches `DropShadowParams`
+layout (push_constant) uniform DropShadowParams {
+    vec4 bounds;
+    vec4 shape;
+    vec4 color;
+    vec2 screen_size;
+    float corner_radius;
+    float blur_radius;
+} params;
+
+layout (location = 0) out vec4 f_color;
+
+// Signed distance from `p` to a box of half size `b` centered on the origin, with corners
+// rounded by `r`; negative inside.
+float sdRoundBox(vec2 p, vec2 b, float r) {
+    vec2 q = abs(p) - b + r;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - r;
+}
+
+void main() {
+    vec2 half_size = params.shape.zw;
+    float radius = clamp(params.corner_radius, 0.0, min(half_size.x, half_size.y));
+    float d = sdRoundBox(gl_FragCoord.xy - params.shape.xy, half_size, radius);
+    // Fades from the full color `blur_radius` inside the edge to nothing as far outside,
+    // which is where the quad ends. At least half a pixel keeps the edge antialiased.
+    float blur = max(params.blur_radius, 0.5);
+    float alpha = 1.0 - smoothstep(-blur, blur, d);
+    f_color = vec4(params.color.rgb, params.color.a * alpha);
+}
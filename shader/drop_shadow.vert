@@ -0,0 +1,18 @@
+#version 460
+
+// matches `DropShadowParams`
+layout (push_constant) uniform DropShadowParams {
+    vec4 bounds;
+    vec4 shape;
+    vec4 color;
+    vec2 screen_size;
+    float corner_radius;
+    float blur_radius;
+} params;
+
+void main() {
+    // Triangle strip over the corners (0, 0), (1, 0), (0, 1), (1, 1).
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    vec2 pixel = params.bounds.xy + corner * params.bounds.zw;
+    gl_Position = vec4(pixel / params.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
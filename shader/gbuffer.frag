@@ -0,0 +1,14 @@
+#version 460
+
+layout (location = 0) in vec3 v_normal;
+layout (location = 1) in vec4 v_clip;
+layout (location = 2) in vec4 v_prev_clip;
+
+layout (location = 0) out vec4 f_normal;
+// R16G16_SFLOAT
+layout (location = 1) out vec2 f_velocity;
+
+void main() {
+    f_normal = vec4(normalize(v_normal), 0.0);
+    f_velocity = v_clip.xy / v_clip.w - v_prev_clip.xy / v_prev_clip.w;
+}
@@ -0,0 +1,21 @@
+#version 460
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+
+layout (push_constant) uniform GBufferParams {
+    mat4 mvp;
+    mat4 prev_mvp;
+} params;
+
+layout (location = 0) out vec3 v_normal;
+layout (location = 1) out vec4 v_clip;
+layout (location = 2) out vec4 v_prev_clip;
+
+void main() {
+    v_normal = normal;
+    v_clip = params.mvp * vec4(position, 1.0);
+    v_prev_clip = params.prev_mvp * vec4(position, 1.0);
+    gl_Position = v_clip;
+}
@@ -0,0 +1,15 @@
+#version 460
+
+layout (input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput input_color;
+
+layout (push_constant) uniform TintParams {
+    vec4 tint;
+} params;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    vec4 color = subpassLoad(input_color);
+    // alpha of the tint is how strongly it applies
+    f_color = vec4(mix(color.rgb, color.rgb * params.tint.rgb, params.tint.a), color.a);
+}
@@ -0,0 +1,47 @@
+#version 460
+
+layout (location = 0) in vec2 v_uv;
+
+layout (set = 0, binding = 0) uniform sampler2D color_buffer;
+layout (set = 0, binding = 1) uniform sampler2D depth_buffer;
+// current_ndc - prev_ndc, only read when `use_velocity` is set
+layout (set = 0, binding = 2) uniform sampler2D velocity_buffer;
+
+layout (push_constant) uniform MotionBlurParams {
+    // previous view-projection times the inverse of the current one
+    mat4 reprojection;
+    float shutter_speed;
+    uint max_blur_pixels;
+    uint use_velocity;
+} params;
+
+layout (location = 0) out vec4 f_color;
+
+const uint SAMPLE_COUNT = 8;
+
+void main() {
+    vec2 velocity;
+    if (params.use_velocity != 0) {
+        velocity = texture(velocity_buffer, v_uv).xy;
+    } else {
+        vec4 ndc = vec4(v_uv * 2.0 - 1.0, texture(depth_buffer, v_uv).r, 1.0);
+        vec4 prev = params.reprojection * ndc;
+        velocity = ndc.xy - prev.xy / prev.w;
+    }
+
+    vec2 size = vec2(textureSize(color_buffer, 0));
+    // NDC spans two units across the screen
+    vec2 blur = velocity * 0.5 * size * params.shutter_speed;
+    float length_pixels = length(blur);
+    if (length_pixels > float(params.max_blur_pixels)) {
+        blur *= float(params.max_blur_pixels) / length_pixels;
+    }
+    vec2 step_uv = blur / size / float(SAMPLE_COUNT - 1);
+
+    vec4 sum = vec4(0.0);
+    for (uint i = 0; i < SAMPLE_COUNT; i++) {
+        float t = float(i) - float(SAMPLE_COUNT - 1) * 0.5;
+        sum += texture(color_buffer, v_uv + step_uv * t);
+    }
+    f_color = sum / float(SAMPLE_COUNT);
+}
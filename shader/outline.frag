@@ -0,0 +1,13 @@
+#version 460
+
+layout (push_constant) uniform OutlineParams {
+    vec4 color;
+    vec2 center;
+    float scale;
+} params;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = params.color;
+}
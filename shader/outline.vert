@@ -0,0 +1,13 @@
+#version 460
+
+layout (location = 0) in vec2 position;
+
+layout (push_constant) uniform OutlineParams {
+    vec4 color;
+    vec2 center;
+    float scale;
+} params;
+
+void main() {
+    gl_Position = vec4(params.center + (position - params.center) * params.scale, 0.0, 1.0);
+}
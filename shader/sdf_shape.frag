@@ -0,0 +1,45 @@
+#version 460
+
+layout (location = 0) in vec2 v_local;
+layout (location = 1) flat in vec2 v_half_size;
+layout (location = 2) flat in float v_corner_radius;
+layout (location = 3) flat in float v_border_width;
+layout (location = 4) flat in vec4 v_fill_color;
+layout (location = 5) flat in vec4 v_border_color;
+layout (location = 6) flat in uint v_shape_type;
+
+layout (location = 0) out vec4 f_color;
+
+// matches `SdfShapeType`
+const uint SHAPE_RECT = 0;
+const uint SHAPE_RING = 2;
+
+// Signed distance from `p` to a box of half size `b` centered on the origin, with corners
+// rounded by `r`; negative inside.
+float sdRoundBox(vec2 p, vec2 b, float r) {
+    vec2 q = abs(p) - b + r;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - r;
+}
+
+void main() {
+    float radius = min(v_half_size.x, v_half_size.y);
+    float d;
+    if (v_shape_type == SHAPE_RECT) {
+        d = sdRoundBox(v_local, v_half_size, clamp(v_corner_radius, 0.0, radius));
+    } else {
+        d = length(v_local) - radius;
+        if (v_shape_type == SHAPE_RING) {
+            // a band of `border_width` inside the circle
+            d = abs(d + v_border_width * 0.5) - v_border_width * 0.5;
+        }
+    }
+
+    vec4 color = v_fill_color;
+    if (v_shape_type != SHAPE_RING && v_border_width > 0.0) {
+        color = mix(color, v_border_color,
+            smoothstep(-v_border_width - 0.5, -v_border_width + 0.5, d));
+    }
+    // distances are in pixels, so the edge is antialiased over one pixel at any size
+    float coverage = 1.0 - smoothstep(-0.5, 0.5, d);
+    f_color = vec4(color.rgb, color.a * coverage);
+}
@@ -0,0 +1,39 @@
+#version 460
+
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec2 size;
+layout (location = 2) in float corner_radius;
+layout (location = 3) in float border_width;
+layout (location = 4) in vec4 fill_color;
+layout (location = 5) in vec4 border_color;
+layout (location = 6) in uint shape_type;
+
+// matches `SpriteParams`
+layout (push_constant) uniform SpriteParams {
+    vec2 screen_size;
+} params;
+
+// pixel offset from the center of the shape
+layout (location = 0) out vec2 v_local;
+layout (location = 1) flat out vec2 v_half_size;
+layout (location = 2) flat out float v_corner_radius;
+layout (location = 3) flat out float v_border_width;
+layout (location = 4) flat out vec4 v_fill_color;
+layout (location = 5) flat out vec4 v_border_color;
+layout (location = 6) flat out uint v_shape_type;
+
+void main() {
+    // Triangle strip over the corners (0, 0), (1, 0), (0, 1), (1, 1).
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    vec2 half_size = size * 0.5;
+    // one pixel of margin keeps the antialiased edge from being cut off
+    v_local = (corner * 2.0 - 1.0) * (half_size + 1.0);
+    v_half_size = half_size;
+    v_corner_radius = corner_radius;
+    v_border_width = border_width;
+    v_fill_color = fill_color;
+    v_border_color = border_color;
+    v_shape_type = shape_type;
+    vec2 pixel = position + half_size + v_local;
+    gl_Position = vec4(pixel / params.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -2,6 +2,10 @@
 
 layout (location = 0) in vec2 position;
 
+// The main pass tests with CompareOp::Equal against the depth written by the depth prepass,
+// so both must compute bit-identical positions.
+invariant gl_Position;
+
 void main() {
     gl_Position = vec4(position, 0.0, 1.0);
 }
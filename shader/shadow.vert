@@ -0,0 +1,11 @@
+#version 460
+
+layout (location = 0) in vec3 position;
+
+layout (push_constant) uniform ShadowParams {
+    mat4 light_view_proj;
+} params;
+
+void main() {
+    gl_Position = params.light_view_proj * vec4(position, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 460
+
+layout (location = 0) in vec2 v_uv;
+layout (location = 1) in vec4 v_color;
+
+layout (set = 0, binding = 0) uniform sampler2D sprite_texture;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = texture(sprite_texture, v_uv) * v_color;
+}
@@ -0,0 +1,21 @@
+#version 460
+
+layout (location = 0) in vec4 rect;
+layout (location = 1) in vec4 uv_rect;
+layout (location = 2) in vec4 color;
+
+layout (push_constant) uniform SpriteParams {
+    vec2 screen_size;
+} params;
+
+layout (location = 0) out vec2 v_uv;
+layout (location = 1) out vec4 v_color;
+
+void main() {
+    // Triangle strip over the corners (0, 0), (1, 0), (0, 1), (1, 1).
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    vec2 pixel = rect.xy + corner * rect.zw;
+    v_uv = uv_rect.xy + corner * uv_rect.zw;
+    v_color = color;
+    gl_Position = vec4(pixel / params.screen_size * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 460
+
+layout (location = 0) in vec3 v_normal;
+
+layout (location = 0) out vec4 f_color;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.3, 1.0, 0.5));
+
+void main() {
+    float diffuse = max(dot(normalize(v_normal), LIGHT_DIRECTION), 0.0);
+    f_color = vec4(vec3(0.1 + 0.9 * diffuse), 1.0);
+}
@@ -0,0 +1,16 @@
+#version 460
+#extension GL_EXT_multiview : require
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+
+layout (set = 0, binding = 0) uniform EyeMatrices {
+    mat4 view_proj[2];
+} eyes;
+
+layout (location = 0) out vec3 v_normal;
+
+void main() {
+    v_normal = normal;
+    gl_Position = eyes.view_proj[gl_ViewIndex] * vec4(position, 1.0);
+}
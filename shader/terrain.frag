@@ -0,0 +1,40 @@
+#version 460
+
+layout (location = 0) in vec2 v_uv;
+layout (location = 1) in float v_height;
+layout (location = 2) in vec3 v_normal;
+
+// layers: 0 grass, 1 rock, 2 snow, 3 sand
+layout (set = 0, binding = 1) uniform sampler2DArray splat;
+
+layout (push_constant) uniform TerrainParams {
+    mat4 view_proj;
+    float height_scale;
+    float world_size;
+    float splat_tiling;
+} params;
+
+layout (location = 0) out vec4 f_color;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.4, 1.0, 0.3));
+
+void main() {
+    vec3 normal = normalize(v_normal);
+    float slope = 1.0 - normal.y;
+
+    float sand = 1.0 - smoothstep(0.05, 0.15, v_height);
+    float snow = smoothstep(0.7, 0.85, v_height) * (1.0 - smoothstep(0.4, 0.6, slope));
+    float rock = smoothstep(0.2, 0.45, slope);
+    float grass = max(1.0 - sand - snow - rock, 0.0);
+    vec4 weights = vec4(grass, rock, snow, sand);
+    weights /= max(weights.x + weights.y + weights.z + weights.w, 1e-4);
+
+    vec2 tiled = v_uv * params.splat_tiling;
+    vec3 albedo = weights.x * texture(splat, vec3(tiled, 0.0)).rgb
+        + weights.y * texture(splat, vec3(tiled, 1.0)).rgb
+        + weights.z * texture(splat, vec3(tiled, 2.0)).rgb
+        + weights.w * texture(splat, vec3(tiled, 3.0)).rgb;
+
+    float diffuse = max(dot(normal, LIGHT_DIRECTION), 0.0);
+    f_color = vec4(albedo * (0.2 + 0.8 * diffuse), 1.0);
+}
@@ -0,0 +1,38 @@
+#version 460
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+
+layout (set = 0, binding = 0) uniform sampler2D heightmap;
+
+layout (push_constant) uniform TerrainParams {
+    mat4 view_proj;
+    float height_scale;
+    float world_size;
+    float splat_tiling;
+} params;
+
+layout (location = 0) out vec2 v_uv;
+layout (location = 1) out float v_height;
+layout (location = 2) out vec3 v_normal;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(heightmap, 0));
+    float height = textureLod(heightmap, uv, 0.0).r;
+    float left = textureLod(heightmap, uv - vec2(texel.x, 0.0), 0.0).r;
+    float right = textureLod(heightmap, uv + vec2(texel.x, 0.0), 0.0).r;
+    float down = textureLod(heightmap, uv - vec2(0.0, texel.y), 0.0).r;
+    float up = textureLod(heightmap, uv + vec2(0.0, texel.y), 0.0).r;
+
+    // central differences, in world units
+    vec2 step_size = 2.0 * params.world_size * texel;
+    v_normal = normalize(vec3(
+        (left - right) * params.height_scale * step_size.y,
+        step_size.x * step_size.y,
+        (down - up) * params.height_scale * step_size.x
+    ));
+    v_uv = uv;
+    v_height = height;
+    gl_Position = params.view_proj * vec4(position.x, height * params.height_scale, position.z, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 460
+
+layout (location = 0) in vec2 v_uv;
+layout (location = 1) flat in uint v_layer;
+
+layout (set = 0, binding = 0) uniform sampler2DArray tiles;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = texture(tiles, vec3(v_uv, float(v_layer)));
+}
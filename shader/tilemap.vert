@@ -0,0 +1,23 @@
+#version 460
+
+layout (location = 0) in vec4 rect;
+layout (location = 1) in uint layer;
+
+// matches `TilemapParams`
+layout (push_constant) uniform TilemapParams {
+    // left, top, width and height of the visible area in world units
+    vec4 camera_bounds;
+} params;
+
+layout (location = 0) out vec2 v_uv;
+layout (location = 1) flat out uint v_layer;
+
+void main() {
+    // Triangle strip over the corners (0, 0), (1, 0), (0, 1), (1, 1).
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    vec2 position = rect.xy + corner * rect.zw;
+    v_uv = corner;
+    v_layer = layer;
+    gl_Position = vec4((position - params.camera_bounds.xy) / params.camera_bounds.zw * 2.0 - 1.0,
+        0.0, 1.0);
+}
@@ -0,0 +1,167 @@
+use crate::compute::ComputePass;
+use crate::error::ThorusError;
+use crate::shader::load_skinning;
+use crate::vertex::{SkinnedVertex, Vertex3D};
+use std::sync::Arc;
+use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::WriteDescriptorSet;
+use vulkano::device::Device;
+
+/// Threads per workgroup of `shader/skinning.comp`.
+pub const SKINNING_GROUP_SIZE: u32 = 64;
+
+/// Mirrors the push constant block of `shader/skinning.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SkinningParams {
+    pub vertex_count: u32,
+}
+
+/// Skins vertices once per frame in a compute shader, so every pass drawing the mesh, the
+/// shadow pass included, reads the posed [`Vertex3D`]s instead of blending bones again in its
+/// vertex shader.
+pub struct GpuSkinningPass {
+    pass: ComputePass,
+}
+
+impl GpuSkinningPass {
+    pub fn new(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let module = load_skinning(device.clone())?;
+        Ok(Self {
+            pass: ComputePass::new(device, module)?,
+        })
+    }
+
+    pub fn pass(&self) -> &ComputePass {
+        &self.pass
+    }
+
+    /// Records skinning the first `vertex_count` vertices of `skinned_vertex_buffer` with the
+    /// column-major bone matrices of `bone_buffer` into `output_buffer`. All three buffers
+    /// need `STORAGE_BUFFER` usage, and the output also `VERTEX_BUFFER` usage to be drawn.
+    ///
+    /// The command buffer builder tracks the write to `output_buffer` and puts a barrier
+    /// before any later command of the same command buffer reading it, such as binding it as
+    /// a vertex buffer.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if either vertex buffer is shorter than `vertex_count`.
+    pub fn dispatch(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        skinned_vertex_buffer: Subbuffer<[SkinnedVertex]>,
+        bone_buffer: Subbuffer<[[[f32; 4]; 4]]>,
+        output_buffer: Subbuffer<[Vertex3D]>,
+        vertex_count: u32,
+    ) -> Result<(), ThorusError> {
+        assert!(
+            skinned_vertex_buffer.len() >= vertex_count as u64,
+            "fewer skinned vertices than the count to skin"
+        );
+        assert!(
+            output_buffer.len() >= vertex_count as u64,
+            "output buffer shorter than the count to skin"
+        );
+        if vertex_count == 0 {
+            return Ok(());
+        }
+
+        self.pass.bind(
+            builder,
+            [
+                WriteDescriptorSet::buffer(0, skinned_vertex_buffer),
+                WriteDescriptorSet::buffer(1, bone_buffer),
+                WriteDescriptorSet::buffer(2, output_buffer),
+            ],
+        )?;
+        self.pass
+            .push_constants(builder, SkinningParams { vertex_count })?;
+        self.pass
+            .dispatch(builder, [vertex_count.div_ceil(SKINNING_GROUP_SIZE), 1, 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+
+    fn storage_buffer<T: BufferContents>(
+        context: &TestContext,
+        data: impl ExactSizeIterator<Item = T>,
+    ) -> Subbuffer<[T]> {
+        Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..AllocationCreateInfo::default()
+            },
+            data,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn thousand_vertices_are_skinned() {
+        const VERTEX_COUNT: u32 = 1000;
+        let context = TestContext::new();
+        let skinning = GpuSkinningPass::new(context.queue.device().clone()).unwrap();
+        // even vertices follow the translating bone, odd ones the identity
+        let skinned = storage_buffer(
+            &context,
+            (0..VERTEX_COUNT).map(|i| SkinnedVertex {
+                position: [i as f32, 0.0, 0.0],
+                normal: [0.0, 1.0, 0.0],
+                tangent: [1.0, 0.0, 0.0, 1.0],
+                joints: [0, 1, 0, 0],
+                weights: [(1 - i % 2) as f32, (i % 2) as f32, 0.0, 0.0],
+                ..SkinnedVertex::default()
+            }),
+        );
+        let translation = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [1.0, 2.0, 3.0, 1.0],
+        ];
+        let identity = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let bones = storage_buffer(&context, [translation, identity].into_iter());
+        let output = storage_buffer(&context, (0..VERTEX_COUNT).map(|_| Vertex3D::default()));
+
+        let mut builder = context.command_buffer();
+        skinning
+            .dispatch(&mut builder, skinned, bones, output.clone(), VERTEX_COUNT)
+            .unwrap();
+        context.submit(builder);
+
+        for (i, vertex) in output.read().unwrap().iter().enumerate() {
+            let offset = if i % 2 == 0 {
+                [1.0, 2.0, 3.0]
+            } else {
+                [0.0; 3]
+            };
+            assert_eq!(
+                vertex.position,
+                [i as f32 + offset[0], offset[1], offset[2]],
+                "vertex {i}"
+            );
+            assert_eq!(vertex.normal, [0.0, 1.0, 0.0]);
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+}
@@ -0,0 +1,591 @@
+use crate::mesh::Mesh;
+use crate::texture::{is_ktx2, Texture, TextureError};
+use image::RgbaImage;
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::{fs, io, panic};
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use vulkano::command_buffer::allocator::{
+    StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::memory::allocator::MemoryAllocator;
+
+#[derive(Debug)]
+pub enum AssetError {
+    Texture(TextureError),
+    /// Reading a texture on a background worker failed.
+    TextureRead(io::Error),
+    /// Decoding a texture on a background worker failed.
+    TextureDecode(image::ImageError),
+    Mesh(io::Error),
+    /// The background worker panicked, with the panic message if it was a string.
+    Panicked(String),
+    /// The background worker was stopped before it finished, e.g. by dropping the cache.
+    Interrupted,
+}
+
+impl Display for AssetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Texture(e) => write!(f, "failed to load texture asset: {e}"),
+            Self::TextureRead(e) => write!(f, "failed to read texture asset: {e}"),
+            Self::TextureDecode(e) => write!(f, "failed to decode texture asset: {e}"),
+            Self::Mesh(e) => write!(f, "failed to load mesh asset: {e}"),
+            Self::Panicked(message) => write!(f, "asset loader panicked: {message}"),
+            Self::Interrupted => write!(f, "asset loader stopped before finishing"),
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Texture(e) => Some(e),
+            Self::TextureRead(e) => Some(e),
+            Self::TextureDecode(e) => Some(e),
+            Self::Mesh(e) => Some(e),
+            Self::Panicked(_) | Self::Interrupted => None,
+        }
+    }
+}
+
+impl From<TextureError> for AssetError {
+    fn from(e: TextureError) -> Self {
+        Self::Texture(e)
+    }
+}
+
+impl From<io::Error> for AssetError {
+    fn from(e: io::Error) -> Self {
+        Self::Mesh(e)
+    }
+}
+
+/// Loads textures and meshes at most once per path while any handle to them is alive.
+///
+/// Only weak references are kept, so an asset is freed as soon as the last user drops it and
+/// loaded again on the next request.
+pub struct AssetCache {
+    allocator: Arc<dyn MemoryAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    queue: Arc<Queue>,
+    /// Shared with background loads, which add their texture when it is uploaded.
+    textures: Arc<Mutex<HashMap<PathBuf, Weak<Texture>>>>,
+    /// Background loads in flight, shared by every handle requesting the same path.
+    pending_textures: HashMap<PathBuf, Weak<PendingLoad<Texture>>>,
+    meshes: HashMap<PathBuf, Weak<Mesh>>,
+    loaded_count: Arc<AtomicUsize>,
+    runtime: Option<Runtime>,
+    placeholder_texture: Option<Arc<Texture>>,
+}
+
+impl AssetCache {
+    pub fn new(allocator: Arc<dyn MemoryAllocator>, queue: Arc<Queue>) -> Self {
+        Self {
+            command_buffer_allocator: Arc::new(StandardCommandBufferAllocator::new(
+                queue.device().clone(),
+                StandardCommandBufferAllocatorCreateInfo::default(),
+            )),
+            allocator,
+            queue,
+            textures: Arc::default(),
+            pending_textures: HashMap::new(),
+            meshes: HashMap::new(),
+            loaded_count: Arc::default(),
+            runtime: None,
+            placeholder_texture: None,
+        }
+    }
+
+    pub fn load_texture(&mut self, path: impl AsRef<Path>) -> Result<Arc<Texture>, AssetError> {
+        let path = path.as_ref();
+        if let Some(texture) = self.cached_texture(path) {
+            return Ok(texture);
+        }
+        debug!("loading texture asset {path:?}");
+        let texture = Arc::new(Texture::from_file(
+            path,
+            self.allocator.clone(),
+            &self.command_buffer_allocator,
+            self.queue.clone(),
+        )?);
+        cache_texture(&self.textures, &self.loaded_count, path, &texture);
+        Ok(texture)
+    }
+
+    /// Reads and decodes the image on a worker thread; the upload happens in
+    /// [`AssetHandle::poll`] on the calling thread, after which the texture is cached like one
+    /// from [`load_texture`](Self::load_texture).
+    ///
+    /// Textures that are still alive are returned ready, and requests for a path that is
+    /// already loading share that load.
+    pub fn load_texture_async(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<AssetHandle<Texture>, AssetError> {
+        let path = path.as_ref().to_owned();
+        let placeholder = self.placeholder_texture()?;
+        if let Some(texture) = self.cached_texture(&path) {
+            return Ok(AssetHandle::ready(texture, placeholder));
+        }
+        if let Some(load) = self.pending_textures.get(&path).and_then(Weak::upgrade) {
+            if load.is_usable() {
+                return Ok(AssetHandle {
+                    load: Some(load),
+                    placeholder,
+                });
+            }
+        }
+
+        debug!("loading texture asset {path:?} in the background");
+        let progress = Arc::new(AtomicU32::new(0.0_f32.to_bits()));
+        let (sender, receiver) = oneshot::channel();
+        let allocator = self.allocator.clone();
+        let command_buffer_allocator = self.command_buffer_allocator.clone();
+        let queue = self.queue.clone();
+        let textures = self.textures.clone();
+        let loaded_count = self.loaded_count.clone();
+        let task_progress = progress.clone();
+        let task_path = path.clone();
+        let task = self.runtime().spawn_blocking(move || {
+            let decoded = panic::catch_unwind(|| read_texture(&task_path, &task_progress))
+                .unwrap_or_else(|payload| Err(AssetError::Panicked(panic_message(&*payload))));
+            let upload: Upload<Texture> = Box::new(move || {
+                let texture = Arc::new(match decoded? {
+                    DecodedTexture::Rgba8(image) => Texture::from_rgba8(
+                        [image.width(), image.height()],
+                        image.as_raw(),
+                        allocator,
+                        &command_buffer_allocator,
+                        queue,
+                    )?,
+                    DecodedTexture::Ktx2(bytes) => Texture::from_ktx2_bytes(
+                        &bytes,
+                        allocator,
+                        &command_buffer_allocator,
+                        queue,
+                    )?,
+                });
+                cache_texture(&textures, &loaded_count, &task_path, &texture);
+                Ok(texture)
+            });
+            // the receiver is gone if every handle was cancelled or dropped
+            let _ = sender.send(upload);
+        });
+
+        let load = Arc::new(PendingLoad {
+            state: Mutex::new(LoadState::Loading { task, receiver }),
+            progress,
+        });
+        self.pending_textures.insert(path, Arc::downgrade(&load));
+        Ok(AssetHandle {
+            load: Some(load),
+            placeholder,
+        })
+    }
+
+    fn cached_texture(&self, path: &Path) -> Option<Arc<Texture>> {
+        lock(&self.textures).get(path).and_then(Weak::upgrade)
+    }
+
+    /// 1×1 pink texture standing in for textures that are still loading.
+    pub fn placeholder_texture(&mut self) -> Result<Arc<Texture>, AssetError> {
+        if let Some(texture) = &self.placeholder_texture {
+            return Ok(texture.clone());
+        }
+        let texture = Arc::new(Texture::from_rgba8(
+            [1, 1],
+            &[255, 0, 255, 255],
+            self.allocator.clone(),
+            &self.command_buffer_allocator,
+            self.queue.clone(),
+        )?);
+        self.placeholder_texture = Some(texture.clone());
+        Ok(texture)
+    }
+
+    fn runtime(&mut self) -> &Runtime {
+        self.runtime.get_or_insert_with(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .thread_name("asset-loader")
+                .build()
+                .expect("failed to start the asset loading runtime")
+        })
+    }
+
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> Result<Arc<Mesh>, AssetError> {
+        let path = path.as_ref();
+        if let Some(mesh) = self.meshes.get(path).and_then(Weak::upgrade) {
+            return Ok(mesh);
+        }
+        debug!("loading mesh asset {path:?}");
+        let mesh = Arc::new(Mesh::from_obj(path)?);
+        self.meshes.insert(path.to_owned(), Arc::downgrade(&mesh));
+        self.loaded_count.fetch_add(1, Ordering::Relaxed);
+        Ok(mesh)
+    }
+
+    /// How many assets have been loaded, counting reloads of dropped ones but not background
+    /// loads that failed, were cancelled, or have not been polled to completion yet.
+    pub fn loaded_count(&self) -> usize {
+        self.loaded_count.load(Ordering::Relaxed)
+    }
+
+    /// Forgets the paths whose assets have been dropped.
+    pub fn purge(&mut self) {
+        lock(&self.textures).retain(|_, texture| texture.strong_count() > 0);
+        self.pending_textures
+            .retain(|_, load| load.strong_count() > 0);
+        self.meshes.retain(|_, mesh| mesh.strong_count() > 0);
+    }
+}
+
+fn cache_texture(
+    textures: &Mutex<HashMap<PathBuf, Weak<Texture>>>,
+    loaded_count: &AtomicUsize,
+    path: &Path,
+    texture: &Arc<Texture>,
+) {
+    lock(textures).insert(path.to_owned(), Arc::downgrade(texture));
+    loaded_count.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A texture read on a worker thread, ready to upload.
+enum DecodedTexture {
+    Rgba8(RgbaImage),
+    /// KTX2 files are parsed during the upload, which picks the format the device supports.
+    Ktx2(Vec<u8>),
+}
+
+fn read_texture(path: &Path, progress: &AtomicU32) -> Result<DecodedTexture, AssetError> {
+    let bytes = fs::read(path).map_err(AssetError::TextureRead)?;
+    set_progress(progress, 0.4);
+    if is_ktx2(path) {
+        return Ok(DecodedTexture::Ktx2(bytes));
+    }
+    let image = image::load_from_memory(&bytes)
+        .map_err(AssetError::TextureDecode)?
+        .into_rgba8();
+    set_progress(progress, 0.8);
+    Ok(DecodedTexture::Rgba8(image))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+/// Locks a mutex that is only held for map operations, which cannot leave it inconsistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Finishes a background load on the thread that polls it.
+type Upload<T> = Box<dyn FnOnce() -> Result<Arc<T>, AssetError> + Send>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AssetStatus {
+    Loading,
+    Ready,
+    Failed,
+    Cancelled,
+}
+
+enum LoadState<T> {
+    Loading {
+        task: JoinHandle<()>,
+        receiver: oneshot::Receiver<Upload<T>>,
+    },
+    Ready(Arc<T>),
+    Failed(Arc<AssetError>),
+}
+
+/// Background load shared by the [`AssetHandle`]s of one path; the worker is aborted when the
+/// last of them is cancelled or dropped.
+struct PendingLoad<T> {
+    state: Mutex<LoadState<T>>,
+    /// `f32` bits, from 0 when queued to 1 when uploaded.
+    progress: Arc<AtomicU32>,
+}
+
+impl<T> PendingLoad<T> {
+    /// Whether a new request may share this load rather than start over.
+    fn is_usable(&self) -> bool {
+        !matches!(*lock(&self.state), LoadState::Failed(_))
+    }
+
+    fn poll(&self) -> AssetStatus {
+        let mut state = lock(&self.state);
+        if let LoadState::Loading { receiver, .. } = &mut *state {
+            *state = match receiver.try_recv() {
+                Ok(upload) => match upload() {
+                    Ok(asset) => {
+                        set_progress(&self.progress, 1.0);
+                        LoadState::Ready(asset)
+                    }
+                    Err(e) => {
+                        warn!("background asset load failed: {e}");
+                        LoadState::Failed(Arc::new(e))
+                    }
+                },
+                Err(oneshot::error::TryRecvError::Empty) => return AssetStatus::Loading,
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    warn!("background asset loader stopped without a result");
+                    LoadState::Failed(Arc::new(AssetError::Interrupted))
+                }
+            };
+        }
+        Self::status(&state)
+    }
+
+    fn status(state: &LoadState<T>) -> AssetStatus {
+        match state {
+            LoadState::Loading { .. } => AssetStatus::Loading,
+            LoadState::Ready(_) => AssetStatus::Ready,
+            LoadState::Failed(_) => AssetStatus::Failed,
+        }
+    }
+}
+
+impl<T> Drop for PendingLoad<T> {
+    fn drop(&mut self) {
+        if let LoadState::Loading { task, .. } = &*lock(&self.state) {
+            task.abort();
+        }
+    }
+}
+
+/// Asset being loaded in the background by [`AssetCache`].
+pub struct AssetHandle<T> {
+    /// `None` once cancelled.
+    load: Option<Arc<PendingLoad<T>>>,
+    placeholder: Arc<T>,
+}
+
+impl<T> AssetHandle<T> {
+    fn ready(asset: Arc<T>, placeholder: Arc<T>) -> Self {
+        Self {
+            load: Some(Arc::new(PendingLoad {
+                state: Mutex::new(LoadState::Ready(asset)),
+                progress: Arc::new(AtomicU32::new(1.0_f32.to_bits())),
+            })),
+            placeholder,
+        }
+    }
+
+    /// Finishes the load once the worker is done; returns without blocking otherwise.
+    pub fn poll(&mut self) -> AssetStatus {
+        match &self.load {
+            Some(load) => load.poll(),
+            None => AssetStatus::Cancelled,
+        }
+    }
+
+    pub fn status(&self) -> AssetStatus {
+        match &self.load {
+            Some(load) => PendingLoad::status(&lock(&load.state)),
+            None => AssetStatus::Cancelled,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.load.as_ref().map_or(0.0, |load| {
+            f32::from_bits(load.progress.load(Ordering::Relaxed))
+        })
+    }
+
+    /// Stops waiting for the asset. The worker is aborted unless other handles share the load;
+    /// one that has already started runs to completion and its result is discarded.
+    pub fn cancel(&mut self) {
+        if self.status() == AssetStatus::Loading {
+            self.load = None;
+        }
+    }
+
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*lock(&self.load.as_ref()?.state) {
+            LoadState::Ready(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<Arc<AssetError>> {
+        match &*lock(&self.load.as_ref()?.state) {
+            LoadState::Failed(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+
+    /// The asset if it is ready, the placeholder otherwise.
+    pub fn or_default(&self) -> Arc<T> {
+        self.get().unwrap_or_else(|| self.placeholder.clone())
+    }
+}
+
+fn set_progress(progress: &AtomicU32, value: f32) {
+    progress.store(value.to_bits(), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use std::env;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("thorus-assets-{}-{name}", std::process::id()))
+    }
+
+    fn write_png(name: &str) -> PathBuf {
+        let path = temp_path(name);
+        RgbaImage::from_pixel(2, 3, image::Rgba([1, 2, 3, 4]))
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    fn cache(context: &TestContext) -> AssetCache {
+        AssetCache::new(context.memory_allocator.clone(), context.queue.clone())
+    }
+
+    fn poll_until_done(handle: &mut AssetHandle<Texture>) -> AssetStatus {
+        loop {
+            match handle.poll() {
+                AssetStatus::Loading => std::thread::yield_now(),
+                status => return status,
+            }
+        }
+    }
+
+    #[test]
+    fn images_are_decoded_on_the_worker() {
+        let path = write_png("decoded.png");
+        let progress = AtomicU32::new(0);
+        let Ok(DecodedTexture::Rgba8(image)) = read_texture(&path, &progress) else {
+            panic!("PNG not decoded to RGBA8");
+        };
+        assert_eq!(image.dimensions(), (2, 3));
+        assert_eq!(image.get_pixel(1, 2).0, [1, 2, 3, 4]);
+        assert_eq!(f32::from_bits(progress.into_inner()), 0.8);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ktx2_files_are_read_for_the_upload() {
+        let path = temp_path("raw.KTX2");
+        fs::write(&path, b"not parsed on the worker").unwrap();
+        let Ok(DecodedTexture::Ktx2(bytes)) = read_texture(&path, &AtomicU32::new(0)) else {
+            panic!("KTX2 file not passed through");
+        };
+        assert_eq!(bytes, b"not parsed on the worker");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_files_fail_on_the_worker() {
+        let result = read_texture(&temp_path("missing.png"), &AtomicU32::new(0));
+        assert!(matches!(result, Err(AssetError::TextureRead(_))));
+    }
+
+    #[test]
+    fn panic_messages_are_kept() {
+        let payload = panic::catch_unwind(|| panic!("decoder bug")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "decoder bug");
+        let payload = panic::catch_unwind(|| panic!("bad {}", "header")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "bad header");
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn same_texture_path_is_loaded_once() {
+        let context = TestContext::new();
+        let mut cache = cache(&context);
+        let path = write_png("deduplicated.png");
+        let first = cache.load_texture(&path).unwrap();
+        let second = cache.load_texture(&path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.loaded_count(), 1);
+
+        drop((first, second));
+        cache.load_texture(&path).unwrap();
+        assert_eq!(cache.loaded_count(), 2);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn same_mesh_path_is_loaded_once() {
+        let context = TestContext::new();
+        let mut cache = cache(&context);
+        let path = temp_path("triangle.obj");
+        fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let first = cache.load_mesh(&path).unwrap();
+        let second = cache.load_mesh(&path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.loaded_count(), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn cancelling_before_completion_does_not_panic() {
+        let context = TestContext::new();
+        let mut cache = cache(&context);
+        let path = write_png("cancelled.png");
+        let mut handle = cache.load_texture_async(&path).unwrap();
+        handle.cancel();
+        assert_eq!(handle.poll(), AssetStatus::Cancelled);
+        assert!(handle.get().is_none());
+        assert!(Arc::ptr_eq(
+            &handle.or_default(),
+            &cache.placeholder_texture().unwrap()
+        ));
+        drop(cache);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn loads_in_flight_are_shared_and_cached() {
+        let context = TestContext::new();
+        let mut cache = cache(&context);
+        let path = write_png("shared.png");
+        let mut first = cache.load_texture_async(&path).unwrap();
+        let mut second = cache.load_texture_async(&path).unwrap();
+        assert_eq!(poll_until_done(&mut first), AssetStatus::Ready);
+        assert_eq!(second.poll(), AssetStatus::Ready);
+        assert_eq!(first.progress(), 1.0);
+
+        let texture = first.get().unwrap();
+        assert!(Arc::ptr_eq(&texture, &second.get().unwrap()));
+        assert!(Arc::ptr_eq(&texture, &cache.load_texture(&path).unwrap()));
+        assert_eq!(cache.loaded_count(), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn failed_loads_are_not_counted() {
+        let context = TestContext::new();
+        let mut cache = cache(&context);
+        let mut handle = cache.load_texture_async(temp_path("missing.png")).unwrap();
+        assert_eq!(poll_until_done(&mut handle), AssetStatus::Failed);
+        assert!(matches!(
+            handle.error().as_deref(),
+            Some(AssetError::TextureRead(_))
+        ));
+        assert_eq!(cache.loaded_count(), 0);
+    }
+}
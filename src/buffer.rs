@@ -0,0 +1,800 @@
+use crate::error::ThorusError;
+use std::collections::HashMap;
+use std::mem::{align_of, size_of};
+use std::ops::Range;
+use std::sync::Arc;
+use vulkano::buffer::{
+    AllocateBufferError, Buffer, BufferContents, BufferCreateInfo, BufferReadGuard, BufferUsage,
+    Subbuffer,
+};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BufferCopy, BufferImageCopy, CommandBufferUsage, CopyBufferInfo,
+    CopyImageToBufferInfo, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract,
+};
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::format::Format;
+use vulkano::image::{Image, ImageAspects, ImageSubresourceLayers};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter};
+use vulkano::memory::MemoryPropertyFlags;
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{GpuFuture, HostAccessError};
+use vulkano::{DeviceSize, NonZeroDeviceSize, Validated, ValidationError};
+
+/// Host-visible, coherent, write-combined memory, mapped for the whole lifetime of the buffer.
+pub const UPLOAD_MEMORY: MemoryTypeFilter = MemoryTypeFilter::PREFER_DEVICE
+    .union(MemoryTypeFilter::HOST_SEQUENTIAL_WRITE)
+    .union(MemoryTypeFilter {
+        required_flags: MemoryPropertyFlags::HOST_COHERENT,
+        preferred_flags: MemoryPropertyFlags::empty(),
+        not_preferred_flags: MemoryPropertyFlags::empty(),
+    });
+
+/// Host-visible, coherent memory for data the host reads back, cached where possible.
+pub const READBACK_MEMORY: MemoryTypeFilter = MemoryTypeFilter::PREFER_HOST
+    .union(MemoryTypeFilter::HOST_RANDOM_ACCESS)
+    .union(MemoryTypeFilter {
+        required_flags: MemoryPropertyFlags::HOST_COHERENT,
+        preferred_flags: MemoryPropertyFlags::empty(),
+        not_preferred_flags: MemoryPropertyFlags::empty(),
+    });
+
+/// Buffer for data uploaded every frame, allocated once and written in place.
+pub struct StreamingBuffer<T: BufferContents> {
+    regions: Vec<Subbuffer<[T]>>,
+    current: usize,
+    len: DeviceSize,
+}
+
+impl<T: BufferContents + Copy> StreamingBuffer<T> {
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        usage: BufferUsage,
+        capacity: DeviceSize,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        Self::with_regions(allocator, usage, capacity, 1)
+    }
+
+    /// Two regions alternating by frame, so the host never writes to the one the GPU reads.
+    pub fn double_buffered(
+        allocator: Arc<dyn MemoryAllocator>,
+        usage: BufferUsage,
+        capacity: DeviceSize,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        Self::with_regions(allocator, usage, capacity, 2)
+    }
+
+    fn with_regions(
+        allocator: Arc<dyn MemoryAllocator>,
+        usage: BufferUsage,
+        capacity: DeviceSize,
+        region_count: DeviceSize,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let mut rest = Buffer::new_slice::<T>(
+            allocator,
+            BufferCreateInfo {
+                usage,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            capacity * region_count,
+        )?;
+        let mut regions = Vec::with_capacity(region_count as usize);
+        for _ in 1..region_count {
+            let (region, tail) = rest.split_at(capacity);
+            regions.push(region);
+            rest = tail;
+        }
+        regions.push(rest);
+
+        Ok(Self {
+            regions,
+            current: 0,
+            len: 0,
+        })
+    }
+
+    pub fn capacity(&self) -> DeviceSize {
+        self.regions[0].len()
+    }
+
+    /// Switches to the region of `frame_index` and forgets what was written before.
+    pub fn begin_frame(&mut self, frame_index: usize) {
+        self.current = frame_index % self.regions.len();
+        self.len = 0;
+    }
+
+    /// Copies `data` into the mapping, replacing the current contents.
+    pub fn write(&mut self, data: &[T]) -> Result<(), HostAccessError> {
+        let len = data.len() as DeviceSize;
+        assert!(
+            len <= self.capacity(),
+            "{len} elements do not fit into a streaming buffer of {capacity}",
+            capacity = self.capacity()
+        );
+        self.regions[self.current].write()?[..data.len()].copy_from_slice(data);
+        self.len = len;
+        Ok(())
+    }
+
+    /// The written part of the current region. Panics if nothing has been written this frame.
+    pub fn subbuffer(&self) -> Subbuffer<[T]> {
+        assert!(self.len > 0, "streaming buffer is empty");
+        self.regions[self.current].clone().slice(..self.len)
+    }
+}
+
+/// Arena handing out short-lived uniform subbuffers, e.g. per-draw parameters.
+///
+/// Allocation only bumps a cursor inside one coherent buffer; `reset` must be called once per
+/// frame, after the GPU has finished reading the previous frame's allocations.
+pub struct LinearAllocator {
+    arena: Subbuffer<[u8]>,
+    alignment: DeviceSize,
+    cursor: DeviceSize,
+}
+
+impl LinearAllocator {
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        size_bytes: DeviceSize,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let alignment = allocator
+            .device()
+            .physical_device()
+            .properties()
+            .min_uniform_buffer_offset_alignment
+            .as_devicesize();
+        let arena = Buffer::new_slice::<u8>(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            size_bytes,
+        )?;
+        Ok(Self {
+            arena,
+            alignment,
+            cursor: 0,
+        })
+    }
+
+    pub fn capacity(&self) -> DeviceSize {
+        self.arena.len()
+    }
+
+    pub fn cursor(&self) -> DeviceSize {
+        self.cursor
+    }
+
+    pub fn try_alloc<T: BufferContents>(&mut self) -> Option<Subbuffer<T>> {
+        let alignment = self.alignment.max(align_of::<T>() as DeviceSize);
+        let start = self.cursor.next_multiple_of(alignment);
+        let end = start + size_of::<T>() as DeviceSize;
+        if end > self.arena.len() {
+            return None;
+        }
+        self.cursor = end;
+        Some(self.arena.clone().slice(start..end).reinterpret())
+    }
+
+    /// Panics when the arena is exhausted.
+    pub fn alloc<T: BufferContents>(&mut self) -> Subbuffer<T> {
+        self.try_alloc().unwrap_or_else(|| {
+            panic!(
+                "linear allocator of {capacity} bytes is exhausted",
+                capacity = self.capacity()
+            )
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+    }
+}
+
+/// Host-writable buffer whose address shaders can dereference through `buffer_reference`
+/// types, passed to them e.g. as a push constant.
+///
+/// The device must have the `buffer_device_address` feature enabled.
+#[derive(Debug)]
+pub struct DeviceAddressBuffer<T: ?Sized> {
+    buffer: Subbuffer<T>,
+    address: NonZeroDeviceSize,
+}
+
+// Not derived, which would require `T: Clone` and rule out slices.
+impl<T: ?Sized> Clone for DeviceAddressBuffer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            address: self.address,
+        }
+    }
+}
+
+impl<T: BufferContents> DeviceAddressBuffer<T> {
+    pub fn from_data(
+        allocator: Arc<dyn MemoryAllocator>,
+        usage: BufferUsage,
+        data: T,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let buffer = Buffer::from_data(
+            allocator,
+            Self::create_info(usage),
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            data,
+        )?;
+        Self::new(buffer)
+    }
+}
+
+impl<T: BufferContents> DeviceAddressBuffer<[T]> {
+    pub fn from_iter<I>(
+        allocator: Arc<dyn MemoryAllocator>,
+        usage: BufferUsage,
+        iter: I,
+    ) -> Result<Self, Validated<AllocateBufferError>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let buffer = Buffer::from_iter(
+            allocator,
+            Self::create_info(usage),
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            iter,
+        )?;
+        Self::new(buffer)
+    }
+}
+
+impl<T: ?Sized> DeviceAddressBuffer<T> {
+    fn create_info(usage: BufferUsage) -> BufferCreateInfo {
+        BufferCreateInfo {
+            usage: usage | BufferUsage::SHADER_DEVICE_ADDRESS,
+            ..BufferCreateInfo::default()
+        }
+    }
+
+    fn new(buffer: Subbuffer<T>) -> Result<Self, Validated<AllocateBufferError>> {
+        let address = buffer
+            .device_address()
+            .map_err(Validated::ValidationError)?;
+        Ok(Self { buffer, address })
+    }
+
+    pub fn buffer(&self) -> &Subbuffer<T> {
+        &self.buffer
+    }
+
+    pub fn gpu_address(&self) -> u64 {
+        self.address.get()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MeshId(u32);
+
+/// One device-local buffer shared by the vertex and index data of many meshes.
+///
+/// Meshes are placed first-fit, so unloading leaves holes that `defragment` closes again.
+pub struct GeometryHeap {
+    buffer: Subbuffer<[u8]>,
+    alignment: DeviceSize,
+    ranges: HashMap<MeshId, Range<DeviceSize>>,
+    next_id: u32,
+}
+
+impl GeometryHeap {
+    pub const USAGE: BufferUsage = BufferUsage::VERTEX_BUFFER
+        .union(BufferUsage::INDEX_BUFFER)
+        .union(BufferUsage::TRANSFER_SRC)
+        .union(BufferUsage::TRANSFER_DST);
+
+    /// `alignment` must be a multiple of every vertex and index stride stored in the heap.
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        capacity: DeviceSize,
+        alignment: DeviceSize,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let buffer = Buffer::new_slice::<u8>(
+            allocator,
+            BufferCreateInfo {
+                usage: Self::USAGE,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..AllocationCreateInfo::default()
+            },
+            capacity,
+        )?;
+        Ok(Self {
+            buffer,
+            alignment,
+            ranges: HashMap::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn capacity(&self) -> DeviceSize {
+        self.buffer.len()
+    }
+
+    pub fn buffer(&self) -> &Subbuffer<[u8]> {
+        &self.buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Reserves `size` bytes in the first hole large enough, or returns `None` if there is none.
+    pub fn allocate(&mut self, size: DeviceSize) -> Option<MeshId> {
+        let mut start = 0;
+        for range in self.sorted_ranges() {
+            if start + size <= range.start {
+                break;
+            }
+            start = range.end.next_multiple_of(self.alignment);
+        }
+        if start + size > self.capacity() {
+            return None;
+        }
+        let id = MeshId(self.next_id);
+        self.next_id += 1;
+        self.ranges.insert(id, start..start + size);
+        Some(id)
+    }
+
+    pub fn free(&mut self, id: MeshId) -> Option<Range<DeviceSize>> {
+        self.ranges.remove(&id)
+    }
+
+    pub fn range(&self, id: MeshId) -> Option<Range<DeviceSize>> {
+        self.ranges.get(&id).cloned()
+    }
+
+    /// The bytes of `id`; must be fetched again after `defragment` has moved it.
+    pub fn subbuffer(&self, id: MeshId) -> Option<Subbuffer<[u8]>> {
+        self.range(id).map(|range| self.buffer.clone().slice(range))
+    }
+
+    fn sorted_ranges(&self) -> Vec<Range<DeviceSize>> {
+        let mut ranges: Vec<_> = self.ranges.values().cloned().collect();
+        ranges.sort_unstable_by_key(|range| range.start);
+        ranges
+    }
+
+    /// Holes between live ranges, up to the end of the last one; alignment padding is not
+    /// counted.
+    pub fn gaps(&self) -> Vec<Range<DeviceSize>> {
+        let mut cursor = 0;
+        let mut gaps = vec![];
+        for range in self.sorted_ranges() {
+            if range.start > cursor {
+                gaps.push(cursor..range.start);
+            }
+            cursor = range.end.next_multiple_of(self.alignment);
+        }
+        gaps
+    }
+
+    /// Share of the used span of the heap that is taken by holes.
+    pub fn fragmentation_ratio(&self) -> f32 {
+        let end = self
+            .ranges
+            .values()
+            .map(|range| range.end)
+            .max()
+            .unwrap_or(0);
+        if end == 0 {
+            return 0.0;
+        }
+        let holes: DeviceSize = self.gaps().iter().map(|gap| gap.end - gap.start).sum();
+        holes as f32 / end as f32
+    }
+
+    /// Moves every live range down to close the holes, if the GPU has finished the frame
+    /// guarded by `idle_fence`. Returns whether anything was recorded.
+    ///
+    /// A range is moved in chunks no longer than the distance it travels, so that the source
+    /// and destination of a single copy never overlap.
+    pub fn defragment<F: GpuFuture>(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        idle_fence: &FenceSignalFuture<F>,
+    ) -> Result<bool, Box<ValidationError>> {
+        if !idle_fence.is_signaled().unwrap_or(false) || self.gaps().is_empty() {
+            return Ok(false);
+        }
+
+        let mut ids: Vec<_> = self.ranges.keys().copied().collect();
+        ids.sort_unstable_by_key(|id| self.ranges[id].start);
+        let mut cursor = 0;
+        for id in ids {
+            let range = self.ranges[&id].clone();
+            let len = range.end - range.start;
+            let shift = range.start - cursor;
+            if shift > 0 {
+                let mut offset = 0;
+                while offset < len {
+                    let size = shift.min(len - offset);
+                    builder.copy_buffer(CopyBufferInfo {
+                        regions: [BufferCopy {
+                            src_offset: range.start + offset,
+                            dst_offset: cursor + offset,
+                            size,
+                            ..BufferCopy::default()
+                        }]
+                        .into(),
+                        ..CopyBufferInfo::buffers(self.buffer.clone(), self.buffer.clone())
+                    })?;
+                    offset += size;
+                }
+                self.ranges.insert(id, cursor..cursor + len);
+            }
+            cursor = (cursor + len).next_multiple_of(self.alignment);
+        }
+        Ok(true)
+    }
+}
+
+/// Host-readable copy of GPU buffers and images, e.g. to check what a compute shader wrote.
+///
+/// Every copy is submitted on its own and waited for on a fence, so this is meant for
+/// debugging and tests rather than for use every frame.
+pub struct Readback {
+    buffer: Subbuffer<[u8]>,
+}
+
+impl Readback {
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        size_bytes: DeviceSize,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let buffer = Buffer::new_slice::<u8>(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: READBACK_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            size_bytes,
+        )?;
+        Ok(Self { buffer })
+    }
+
+    pub fn size(&self) -> DeviceSize {
+        self.buffer.len()
+    }
+
+    pub fn buffer(&self) -> &Subbuffer<[u8]> {
+        &self.buffer
+    }
+
+    /// Copies the start of `src`, as much as fits, and blocks until the copy has completed.
+    /// `src` needs `TRANSFER_SRC` usage.
+    pub fn copy_from_buffer<T: ?Sized>(
+        &self,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+        src: Subbuffer<T>,
+    ) -> Result<(), ThorusError> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            cmd_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        builder.copy_buffer(CopyBufferInfo::buffers(src, self.buffer.clone()))?;
+        submit_and_wait(builder, queue)
+    }
+
+    /// The buffer contents as `T`s. Panics if its size is not a multiple of the size of `T`.
+    pub fn read_as<T: BufferContents>(&self) -> Result<BufferReadGuard<'_, [T]>, HostAccessError> {
+        self.buffer.reinterpret_ref::<[T]>().read()
+    }
+
+    /// Reads the first mip level and array layer of `image`, which needs `TRANSFER_SRC` usage,
+    /// as tightly packed RGBA8 rows. Only 8-bit RGBA and BGRA formats are supported; the
+    /// channels of the latter are swapped.
+    pub fn read_image_to_rgba8(
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+        image: Arc<Image>,
+    ) -> Result<Vec<u8>, ThorusError> {
+        let format = image.format();
+        let bgra = match format {
+            Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => false,
+            Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => true,
+            _ => {
+                return Err(Box::new(ValidationError {
+                    context: "image.format()".into(),
+                    problem: format!("{format:?} is not an 8-bit RGBA or BGRA format").into(),
+                    ..ValidationError::default()
+                })
+                .into())
+            }
+        };
+        let extent = image.extent();
+        let readback = Self::new(
+            allocator,
+            extent
+                .iter()
+                .map(|&dimension| dimension as DeviceSize)
+                .product::<DeviceSize>()
+                * 4,
+        )?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            cmd_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        builder.copy_image_to_buffer(CopyImageToBufferInfo {
+            regions: [BufferImageCopy {
+                image_subresource: ImageSubresourceLayers {
+                    aspects: ImageAspects::COLOR,
+                    mip_level: 0,
+                    array_layers: 0..1,
+                },
+                image_extent: extent,
+                ..BufferImageCopy::default()
+            }]
+            .into(),
+            ..CopyImageToBufferInfo::image_buffer(image, readback.buffer.clone())
+        })?;
+        submit_and_wait(builder, queue)?;
+
+        let mut pixels = readback.buffer.read()?.to_vec();
+        if bgra {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        Ok(pixels)
+    }
+}
+
+fn submit_and_wait(
+    builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    queue: Arc<Queue>,
+) -> Result<(), ThorusError> {
+    builder
+        .build()?
+        .execute(queue)?
+        .then_signal_fence_and_flush()?
+        .wait(None)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::ComputePass;
+    use crate::testing::TestContext;
+    use vulkano::descriptor_set::WriteDescriptorSet;
+    use vulkano::device::{DeviceExtensions, Features};
+    use vulkano::sync;
+
+    const ALLOCATIONS: u64 = 10_000;
+
+    mod write_42 {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 450
+
+                layout(local_size_x = 1) in;
+
+                layout(set = 0, binding = 0) buffer Data {
+                    float values[];
+                };
+
+                void main() {
+                    values[0] = 42.0;
+                }
+            ",
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn streaming_writes_reuse_the_first_allocation() {
+        const FRAMES: usize = 10;
+
+        let context = TestContext::new();
+        let mut streaming = StreamingBuffer::<[f32; 4]>::double_buffered(
+            context.memory_allocator.clone(),
+            BufferUsage::VERTEX_BUFFER,
+            64,
+        )
+        .unwrap();
+        let data: Vec<_> = (0..64).map(|index| [index as f32; 4]).collect();
+
+        streaming.begin_frame(0);
+        streaming.write(&data).unwrap();
+        let buffer = streaming.subbuffer().buffer().clone();
+        let offsets = [
+            0,
+            streaming.capacity() * size_of::<[f32; 4]>() as DeviceSize,
+        ];
+
+        for frame_index in 0..FRAMES {
+            streaming.begin_frame(frame_index);
+            for write in 0..ALLOCATIONS as usize / FRAMES {
+                let len = write % data.len() + 1;
+                streaming.write(&data[..len]).unwrap();
+                let subbuffer = streaming.subbuffer();
+                assert!(Arc::ptr_eq(subbuffer.buffer(), &buffer));
+                assert_eq!(subbuffer.offset(), offsets[frame_index % 2]);
+                assert_eq!(subbuffer.len(), len as DeviceSize);
+            }
+            assert_eq!(streaming.regions.len(), 2);
+        }
+        assert_eq!(*streaming.subbuffer().read().unwrap(), data[..40]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn linear_allocations_stay_within_the_arena() {
+        let context = TestContext::new();
+        let mut linear =
+            LinearAllocator::new(context.memory_allocator.clone(), ALLOCATIONS * 256).unwrap();
+
+        let mut previous_end = 0;
+        for _ in 0..ALLOCATIONS {
+            let subbuffer = linear.alloc::<[[f32; 4]; 4]>();
+            assert!(subbuffer.offset() >= previous_end);
+            assert_eq!(subbuffer.offset() % linear.alignment, 0);
+            previous_end = subbuffer.offset() + subbuffer.size();
+            assert!(previous_end <= linear.capacity());
+        }
+        assert_eq!(linear.cursor(), previous_end);
+
+        linear.reset();
+        assert_eq!(linear.cursor(), 0);
+        assert_eq!(linear.alloc::<u32>().offset(), 0);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn try_alloc_fails_once_the_arena_is_full() {
+        let context = TestContext::new();
+        let mut linear = LinearAllocator::new(context.memory_allocator.clone(), 64).unwrap();
+        assert!(linear.try_alloc::<[u8; 64]>().is_some());
+        assert!(linear.try_alloc::<u32>().is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn defragmenting_closes_the_holes_and_keeps_the_data() {
+        let context = TestContext::new();
+        let mut heap = GeometryHeap::new(context.memory_allocator.clone(), 4096, 256).unwrap();
+        let ids: Vec<_> = (0..8).map(|_| heap.allocate(256).unwrap()).collect();
+        let mut builder = context.command_buffer();
+        for (value, &id) in ids.iter().enumerate() {
+            let words = heap.subbuffer(id).unwrap().reinterpret::<[u32]>();
+            builder.fill_buffer(words, value as u32).unwrap();
+        }
+        context.submit(builder);
+
+        for &id in ids.iter().step_by(2) {
+            heap.free(id);
+        }
+        assert_eq!(heap.fragmentation_ratio(), 0.5);
+
+        let idle_fence = sync::now(context.queue.device().clone())
+            .then_signal_fence_and_flush()
+            .unwrap();
+        idle_fence.wait(None).unwrap();
+        let mut builder = context.command_buffer();
+        assert!(heap.defragment(&mut builder, &idle_fence).unwrap());
+        context.submit(builder);
+        assert_eq!(heap.fragmentation_ratio(), 0.0);
+        assert!(heap.gaps().is_empty());
+
+        let readback = Readback::new(context.memory_allocator.clone(), 4096).unwrap();
+        readback
+            .copy_from_buffer(
+                &context.command_buffer_allocator,
+                context.queue.clone(),
+                heap.buffer().clone(),
+            )
+            .unwrap();
+        let words = readback.read_as::<u32>().unwrap();
+        for (value, &id) in ids.iter().enumerate().skip(1).step_by(2) {
+            let range = heap.range(id).unwrap();
+            let moved = &words[range.start as usize / 4..range.end as usize / 4];
+            assert!(moved.iter().all(|&word| word == value as u32));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn readback_sees_what_a_compute_shader_wrote() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let pass = ComputePass::new(device.clone(), write_42::load(device).unwrap()).unwrap();
+        let values = Buffer::new_slice::<f32>(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..AllocationCreateInfo::default()
+            },
+            4,
+        )
+        .unwrap();
+        let mut builder = context.command_buffer();
+        pass.bind(
+            &mut builder,
+            [WriteDescriptorSet::buffer(0, values.clone())],
+        )
+        .unwrap();
+        pass.dispatch(&mut builder, [1, 1, 1]).unwrap();
+        context.submit(builder);
+
+        let readback = Readback::new(context.memory_allocator.clone(), values.size()).unwrap();
+        readback
+            .copy_from_buffer(
+                &context.command_buffer_allocator,
+                context.queue.clone(),
+                values,
+            )
+            .unwrap();
+        assert_eq!(readback.read_as::<f32>().unwrap()[0], 42.0);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn device_address_is_non_zero_and_shared_by_clones() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions::empty(),
+            Features {
+                buffer_device_address: true,
+                ..Features::empty()
+            },
+        );
+        let buffer = DeviceAddressBuffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            [1u32, 2, 3, 4],
+        )
+        .unwrap();
+        assert_ne!(buffer.gpu_address(), 0);
+
+        let clone = buffer.clone();
+        assert_eq!(clone.gpu_address(), buffer.gpu_address());
+        assert_eq!(
+            clone.buffer().device_address().unwrap().get(),
+            buffer.gpu_address()
+        );
+    }
+}
@@ -0,0 +1,334 @@
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self {
+        min: [f32::INFINITY; 3],
+        max: [f32::NEG_INFINITY; 3],
+    };
+
+    pub fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        Self { min, max }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: [0, 1, 2].map(|i| self.min[i].min(other.min[i])),
+            max: [0, 1, 2].map(|i| self.max[i].max(other.max[i])),
+        }
+    }
+
+    pub fn grow(&self, point: [f32; 3]) -> Self {
+        self.union(&Self::new(point, point))
+    }
+
+    pub fn centroid(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| (self.min[i] + self.max[i]) * 0.5)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let [x, y, z] = [0, 1, 2].map(|i| (self.max[i] - self.min[i]).max(0.0));
+        2.0 * (x * y + y * z + z * x)
+    }
+
+    /// Slab test; returns the entry distance, clamped to 0 when the origin is inside.
+    pub fn intersect_ray(&self, origin: [f32; 3], inv_direction: [f32; 3]) -> Option<f32> {
+        let mut t_min = 0.0_f32;
+        let mut t_max = f32::INFINITY;
+        for i in 0..3 {
+            let t1 = (self.min[i] - origin[i]) * inv_direction[i];
+            let t2 = (self.max[i] - origin[i]) * inv_direction[i];
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+        (t_min <= t_max).then_some(t_min)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    aabb: Aabb,
+    parent: Option<usize>,
+    /// First child for inner nodes (the second one follows it), first primitive for leaves.
+    first: usize,
+    /// Primitive count, zero for inner nodes.
+    count: usize,
+}
+
+/// Binary bounding volume hierarchy over axis-aligned boxes, built with binned SAH.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    aabbs: Vec<Aabb>,
+    ids: Vec<u32>,
+    /// Input slots in leaf order.
+    order: Vec<usize>,
+    /// Leaf node holding each input slot.
+    leaf_of: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(aabbs: &[(Aabb, u32)]) -> Self {
+        let mut bvh = Self {
+            nodes: vec![],
+            aabbs: aabbs.iter().map(|&(aabb, _)| aabb).collect(),
+            ids: aabbs.iter().map(|&(_, id)| id).collect(),
+            order: (0..aabbs.len()).collect(),
+            leaf_of: vec![0; aabbs.len()],
+        };
+        if !aabbs.is_empty() {
+            bvh.nodes.push(Node {
+                aabb: Aabb::EMPTY,
+                parent: None,
+                first: 0,
+                count: 0,
+            });
+            bvh.build(0, None, 0, aabbs.len());
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.aabbs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aabbs.is_empty()
+    }
+
+    fn bounds(&self, first: usize, count: usize) -> (Aabb, Aabb) {
+        self.order[first..first + count].iter().fold(
+            (Aabb::EMPTY, Aabb::EMPTY),
+            |(bounds, centroids), &slot| {
+                let aabb = &self.aabbs[slot];
+                (bounds.union(aabb), centroids.grow(aabb.centroid()))
+            },
+        )
+    }
+
+    fn build(&mut self, index: usize, parent: Option<usize>, first: usize, count: usize) {
+        let (aabb, centroids) = self.bounds(first, count);
+        self.nodes[index] = Node {
+            aabb,
+            parent,
+            first,
+            count,
+        };
+
+        let split = if count > 1 {
+            self.find_split(first, count, &aabb, &centroids)
+        } else {
+            None
+        };
+        let Some((axis, position)) = split else {
+            for &slot in &self.order[first..first + count] {
+                self.leaf_of[slot] = index;
+            }
+            return;
+        };
+
+        let (mut left, mut right) = (first, first + count);
+        while left < right {
+            if self.aabbs[self.order[left]].centroid()[axis] < position {
+                left += 1;
+            } else {
+                right -= 1;
+                self.order.swap(left, right);
+            }
+        }
+        let mut left_count = left - first;
+        if left_count == 0 || left_count == count {
+            left_count = count / 2;
+        }
+
+        let child = self.nodes.len();
+        self.nodes.extend([self.nodes[index]; 2]);
+        self.nodes[index].first = child;
+        self.nodes[index].count = 0;
+        self.build(child, Some(index), first, left_count);
+        self.build(
+            child + 1,
+            Some(index),
+            first + left_count,
+            count - left_count,
+        );
+    }
+
+    fn find_split(
+        &self,
+        first: usize,
+        count: usize,
+        aabb: &Aabb,
+        centroids: &Aabb,
+    ) -> Option<(usize, f32)> {
+        let mut best: Option<(f32, usize, f32)> = None;
+        for axis in 0..3 {
+            let (lo, hi) = (centroids.min[axis], centroids.max[axis]);
+            if hi <= lo {
+                continue;
+            }
+            let scale = BIN_COUNT as f32 / (hi - lo);
+            let mut bins = [(Aabb::EMPTY, 0usize); BIN_COUNT];
+            for &slot in &self.order[first..first + count] {
+                let aabb = &self.aabbs[slot];
+                let bin = (((aabb.centroid()[axis] - lo) * scale) as usize).min(BIN_COUNT - 1);
+                bins[bin] = (bins[bin].0.union(aabb), bins[bin].1 + 1);
+            }
+
+            let mut left_area = [0.0; BIN_COUNT - 1];
+            let mut left_count = [0; BIN_COUNT - 1];
+            let (mut acc, mut n) = (Aabb::EMPTY, 0);
+            for i in 0..BIN_COUNT - 1 {
+                acc = acc.union(&bins[i].0);
+                n += bins[i].1;
+                left_area[i] = acc.surface_area();
+                left_count[i] = n;
+            }
+            let (mut acc, mut n) = (Aabb::EMPTY, 0);
+            for i in (1..BIN_COUNT).rev() {
+                acc = acc.union(&bins[i].0);
+                n += bins[i].1;
+                let cost =
+                    left_count[i - 1] as f32 * left_area[i - 1] + n as f32 * acc.surface_area();
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, lo + i as f32 / scale));
+                }
+            }
+        }
+
+        let (cost, axis, position) = best?;
+        let leaf_cost = count as f32 * aabb.surface_area();
+        (cost < leaf_cost || count > MAX_LEAF_SIZE).then_some((axis, position))
+    }
+
+    /// Returns the closest hit distance along `direction` and the id of the box that was hit.
+    pub fn intersect_ray(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<(f32, u32)> {
+        let inv_direction = direction.map(|d| 1.0 / d);
+        let mut closest: Option<(f32, u32)> = None;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            match node.aabb.intersect_ray(origin, inv_direction) {
+                Some(t) if closest.is_none_or(|(best, _)| t < best) => {}
+                _ => continue,
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+                continue;
+            }
+            for &slot in &self.order[node.first..node.first + node.count] {
+                if let Some(t) = self.aabbs[slot].intersect_ray(origin, inv_direction) {
+                    if closest.is_none_or(|(best, _)| t < best) {
+                        closest = Some((t, self.ids[slot]));
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    /// Replaces the box of the `index`-th input and refits its ancestors without rebuilding.
+    pub fn update_leaf(&mut self, index: usize, new_aabb: Aabb) {
+        self.aabbs[index] = new_aabb;
+        let mut current = Some(self.leaf_of[index]);
+        while let Some(node_index) = current {
+            let node = self.nodes[node_index];
+            self.nodes[node_index].aabb = if node.count > 0 {
+                self.bounds(node.first, node.count).0
+            } else {
+                self.nodes[node.first]
+                    .aabb
+                    .union(&self.nodes[node.first + 1].aabb)
+            };
+            current = node.parent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::random_floats;
+
+    fn random_boxes(count: u32, random: &mut impl FnMut() -> f32) -> Vec<(Aabb, u32)> {
+        (0..count)
+            .map(|id| {
+                let min = [0; 3].map(|_| random() * 100.0);
+                let size = [0; 3].map(|_| 0.5 + random() * 2.0);
+                (Aabb::new(min, [0, 1, 2].map(|i| min[i] + size[i])), id)
+            })
+            .collect()
+    }
+
+    fn brute_force(
+        boxes: &[(Aabb, u32)],
+        origin: [f32; 3],
+        direction: [f32; 3],
+    ) -> Option<(f32, u32)> {
+        let inv_direction = direction.map(|d| 1.0 / d);
+        boxes
+            .iter()
+            .filter_map(|(aabb, id)| Some((aabb.intersect_ray(origin, inv_direction)?, *id)))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    #[test]
+    fn finds_the_same_closest_hits_as_brute_force() {
+        let mut random = random_floats(0x9e37_79b9);
+        let boxes = random_boxes(1000, &mut random);
+        let bvh = Bvh::new(&boxes);
+        assert_eq!(bvh.len(), 1000);
+
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let origin = [0; 3].map(|_| random() * 100.0);
+            let direction = [0; 3].map(|_| random() * 2.0 - 1.0);
+            let expected = brute_force(&boxes, origin, direction);
+            let actual = bvh.intersect_ray(origin, direction);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some((expected_t, _)), Some((t, id))) => {
+                    hits += 1;
+                    assert_eq!(t, expected_t);
+                    let inv_direction = direction.map(|d| 1.0 / d);
+                    assert_eq!(
+                        boxes[id as usize].0.intersect_ray(origin, inv_direction),
+                        Some(t)
+                    );
+                }
+                _ => panic!("expected {expected:?}, got {actual:?}"),
+            }
+        }
+        assert!(hits > 100, "only {hits} rays hit anything");
+    }
+
+    #[test]
+    fn update_leaf_refits_the_ancestors() {
+        let mut random = random_floats(0x85eb_ca6b);
+        let boxes = random_boxes(100, &mut random);
+        let mut bvh = Bvh::new(&boxes);
+        let origin = [500.0, 500.0, 0.0];
+        let direction = [0.0, 0.0, 1.0];
+        assert_eq!(bvh.intersect_ray(origin, direction), None);
+
+        bvh.update_leaf(42, Aabb::new([499.0, 499.0, 10.0], [501.0, 501.0, 12.0]));
+        assert_eq!(bvh.intersect_ray(origin, direction), Some((10.0, 42)));
+    }
+
+    #[test]
+    fn empty_bvh_hits_nothing() {
+        let bvh = Bvh::new(&[]);
+        assert!(bvh.is_empty());
+        assert_eq!(bvh.intersect_ray([0.0; 3], [1.0, 0.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::RenderConfig;
+use crate::device::FeatureSet;
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Command line options of the renderer.
+///
+/// Options that are given override the values of the config file.
+#[derive(Parser, Clone, PartialEq, Eq, Debug)]
+#[command(version, about)]
+pub struct CliArgs {
+    /// TOML file with renderer settings.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Initial window width in physical pixels.
+    #[arg(long, requires = "height")]
+    pub width: Option<u32>,
+    /// Initial window height in physical pixels.
+    #[arg(long, requires = "width")]
+    pub height: Option<u32>,
+    /// Waits for vertical blank; `--vsync false` prefers mailbox or immediate presentation.
+    #[arg(long)]
+    pub vsync: Option<bool>,
+    /// Enables `VK_LAYER_KHRONOS_validation` when it is installed; `--validation false` or
+    /// `--no-validation` disables it even if the config file enables it.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub validation: Option<bool>,
+    /// Same as `--validation false`.
+    #[arg(long, conflicts_with = "validation")]
+    pub no_validation: bool,
+    /// Lowest Vulkan version the device must support.
+    #[arg(long)]
+    pub feature_set: Option<FeatureSet>,
+    /// Samples per pixel of the color attachment; a power of two up to 64.
+    #[arg(long, value_parser = parse_sample_count)]
+    pub msaa_samples: Option<u32>,
+    /// Directory with `shader.vert` and `shader.frag` GLSL, compiled in the background, or
+    /// `shader.vert.spv` and `shader.frag.spv` replacing the built-in shaders.
+    #[arg(long)]
+    pub shader_dir: Option<PathBuf>,
+    /// Wavefront OBJ model drawn instead of the built-in triangle.
+    #[arg(long)]
+    pub model_path: Option<PathBuf>,
+    /// Writes a Chrome trace of the run that can be opened in Perfetto.
+    #[arg(long)]
+    pub trace: bool,
+    /// Reports a lost device at the given frame to exercise the recovery path.
+    #[arg(long, value_name = "FRAME")]
+    pub simulate_device_lost: Option<u64>,
+}
+
+impl CliArgs {
+    /// Overrides the values of `config` with every option that was given.
+    pub fn apply(&self, config: &mut RenderConfig) {
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            config.width = Some(width);
+            config.height = Some(height);
+        }
+        if let Some(vsync) = self.vsync {
+            config.vsync = vsync;
+        }
+        if let Some(validation) = self.validation.or(self.no_validation.then_some(false)) {
+            config.validation = validation;
+        }
+        if let Some(feature_set) = self.feature_set {
+            config.feature_set = feature_set;
+        }
+        if let Some(samples) = self.msaa_samples {
+            config.msaa_samples = samples;
+        }
+        if let Some(shader_dir) = &self.shader_dir {
+            config.shader_dir = Some(shader_dir.clone());
+        }
+        if let Some(model_path) = &self.model_path {
+            config.model_path = Some(model_path.clone());
+        }
+    }
+}
+
+fn parse_sample_count(value: &str) -> Result<u32, String> {
+    let samples: u32 = value.parse().map_err(|e| format!("{e}"))?;
+    if samples.is_power_of_two() && samples <= 64 {
+        Ok(samples)
+    } else {
+        Err(format!("{samples} is not a power of two up to 64"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_arguments_keep_the_config() {
+        let args = CliArgs::try_parse_from(["thorus"]).unwrap();
+        assert_eq!(args.width, None);
+        assert_eq!(args.vsync, None);
+        assert_eq!(args.validation, None);
+        assert!(!args.trace);
+
+        let mut config = RenderConfig::default();
+        args.apply(&mut config);
+        assert_eq!(config, RenderConfig::default());
+    }
+
+    #[test]
+    fn validation_can_be_switched_both_ways() {
+        let parse = |args: &[&str]| {
+            let mut config = RenderConfig {
+                validation: true,
+                ..RenderConfig::default()
+            };
+            CliArgs::try_parse_from(args).unwrap().apply(&mut config);
+            config.validation
+        };
+        assert!(parse(&["thorus"]));
+        assert!(parse(&["thorus", "--validation"]));
+        assert!(!parse(&["thorus", "--validation", "false"]));
+        assert!(!parse(&["thorus", "--no-validation"]));
+        assert!(CliArgs::try_parse_from(["thorus", "--validation", "--no-validation"]).is_err());
+    }
+
+    #[test]
+    fn invalid_sample_counts_are_rejected() {
+        assert!(CliArgs::try_parse_from(["thorus", "--msaa-samples", "3"]).is_err());
+        assert!(CliArgs::try_parse_from(["thorus", "--msaa-samples", "128"]).is_err());
+        let args = CliArgs::try_parse_from(["thorus", "--msaa-samples", "4"]).unwrap();
+        assert_eq!(args.msaa_samples, Some(4));
+        assert!(CliArgs::try_parse_from(["thorus", "--width", "800"]).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::error::ThorusError;
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::command_buffer::pool::{
+    CommandBufferAllocateInfo, CommandPool, CommandPoolAlloc, CommandPoolCreateFlags,
+    CommandPoolCreateInfo, CommandPoolResetFlags,
+};
+use vulkano::command_buffer::CommandBufferLevel;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::{ValidationError, VulkanError, VulkanObject};
+
+/// How the command buffers of a [`CommandPoolManager`] are recycled.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum CommandPoolStrategy {
+    /// The whole pool of a frame is reset at once by
+    /// [`CommandPoolManager::reset_frame`], the cheapest way to recycle command buffers
+    /// re-recorded every frame.
+    #[default]
+    ResetPool,
+    /// Command buffers are reset one at a time, which the pool has to be created for.
+    ResetIndividual,
+}
+
+impl CommandPoolStrategy {
+    /// Flags the pools are created with.
+    pub fn create_flags(self) -> CommandPoolCreateFlags {
+        match self {
+            Self::ResetPool => CommandPoolCreateFlags::TRANSIENT,
+            Self::ResetIndividual => CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        }
+    }
+
+    pub fn create_info(self, queue_family_index: u32) -> CommandPoolCreateInfo {
+        CommandPoolCreateInfo {
+            flags: self.create_flags(),
+            queue_family_index,
+            ..CommandPoolCreateInfo::default()
+        }
+    }
+}
+
+/// One command pool per frame in flight, created for a [`CommandPoolStrategy`], to allocate
+/// secondary command buffers recorded e.g. on worker threads.
+///
+/// Resetting a single command buffer is only valid in a pool created with
+/// `RESET_COMMAND_BUFFER`, so the pools are created with the flags of the strategy and the
+/// reset functions check it instead of leaving it to the validation layers.
+pub struct CommandPoolManager {
+    strategy: CommandPoolStrategy,
+    pools: Vec<CommandPool>,
+}
+
+impl CommandPoolManager {
+    pub fn new(
+        device: Arc<Device>,
+        queue_family_index: u32,
+        frames_in_flight: usize,
+        strategy: CommandPoolStrategy,
+    ) -> Result<Self, ThorusError> {
+        let pools = (0..frames_in_flight)
+            .map(|_| CommandPool::new(device.clone(), strategy.create_info(queue_family_index)))
+            .collect::<Result<_, _>>()?;
+        debug!("{frames_in_flight} command pools for {strategy:?}");
+        Ok(Self { strategy, pools })
+    }
+
+    pub fn strategy(&self) -> CommandPoolStrategy {
+        self.strategy
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn pool(&self, frame_index: usize) -> &CommandPool {
+        &self.pools[frame_index]
+    }
+
+    /// Allocates `count` secondary command buffers from the pool of `frame_index`.
+    pub fn allocate_secondary(
+        &self,
+        frame_index: usize,
+        count: u32,
+    ) -> Result<Vec<CommandPoolAlloc>, ThorusError> {
+        Ok(self.pools[frame_index]
+            .allocate_command_buffers(CommandBufferAllocateInfo {
+                level: CommandBufferLevel::Secondary,
+                command_buffer_count: count,
+                ..CommandBufferAllocateInfo::default()
+            })?
+            .collect())
+    }
+
+    /// Resets every command buffer allocated for `frame_index` when using
+    /// [`CommandPoolStrategy::ResetPool`]. Returns `false` and does nothing with
+    /// [`CommandPoolStrategy::ResetIndividual`], where command buffers are reset by
+    /// [`reset_command_buffer`](Self::reset_command_buffer) instead.
+    ///
+    /// # Safety
+    ///
+    /// No command buffer of the frame may be pending.
+    pub unsafe fn reset_frame(&self, frame_index: usize) -> Result<bool, ThorusError> {
+        if self.strategy != CommandPoolStrategy::ResetPool {
+            return Ok(false);
+        }
+        self.pools[frame_index].reset(CommandPoolResetFlags::empty())?;
+        Ok(true)
+    }
+
+    /// Resets a single command buffer; only valid with
+    /// [`CommandPoolStrategy::ResetIndividual`].
+    ///
+    /// # Safety
+    ///
+    /// `command_buffer` must have been allocated from the pool of `frame_index` and must not
+    /// be pending.
+    pub unsafe fn reset_command_buffer(
+        &self,
+        frame_index: usize,
+        command_buffer: &CommandPoolAlloc,
+    ) -> Result<(), ThorusError> {
+        let pool = &self.pools[frame_index];
+        if !pool
+            .flags()
+            .intersects(CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+        {
+            return Err(Box::new(ValidationError {
+                context: "reset_command_buffer".into(),
+                problem: "the command pool was not created with `RESET_COMMAND_BUFFER`".into(),
+                vuids: &["VUID-vkResetCommandBuffer-commandBuffer-00046"],
+                ..ValidationError::default()
+            })
+            .into());
+        }
+        let device = pool.device();
+        (device.fns().v1_0.reset_command_buffer)(
+            command_buffer.handle(),
+            ash::vk::CommandBufferResetFlags::empty(),
+        )
+        .result()
+        .map_err(VulkanError::from)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+
+    #[test]
+    fn strategies_select_the_pool_flags() {
+        assert_eq!(
+            CommandPoolStrategy::ResetPool.create_flags(),
+            CommandPoolCreateFlags::TRANSIENT
+        );
+        assert_eq!(
+            CommandPoolStrategy::ResetIndividual.create_flags(),
+            CommandPoolCreateFlags::RESET_COMMAND_BUFFER
+        );
+        let info = CommandPoolStrategy::ResetIndividual.create_info(3);
+        assert_eq!(info.flags, CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+        assert_eq!(info.queue_family_index, 3);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn pools_are_reset_the_way_the_strategy_allows() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let family = context.queue.queue_family_index();
+
+        let per_pool =
+            CommandPoolManager::new(device.clone(), family, 2, CommandPoolStrategy::ResetPool)
+                .unwrap();
+        assert_eq!(per_pool.frames_in_flight(), 2);
+        assert_eq!(per_pool.pool(1).flags(), CommandPoolCreateFlags::TRANSIENT);
+        let command_buffers = per_pool.allocate_secondary(0, 2).unwrap();
+        assert_eq!(command_buffers.len(), 2);
+        unsafe {
+            assert!(per_pool
+                .reset_command_buffer(0, &command_buffers[0])
+                .is_err());
+            assert!(per_pool.reset_frame(0).unwrap());
+        }
+
+        let individual =
+            CommandPoolManager::new(device, family, 2, CommandPoolStrategy::ResetIndividual)
+                .unwrap();
+        let command_buffers = individual.allocate_secondary(1, 1).unwrap();
+        unsafe {
+            individual
+                .reset_command_buffer(1, &command_buffers[0])
+                .unwrap();
+            assert!(!individual.reset_frame(1).unwrap());
+        }
+    }
+}
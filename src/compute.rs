@@ -0,0 +1,544 @@
+use crate::error::ThorusError;
+use crate::shader::{
+    load_dispatch_count, load_list_sum, load_radix_histogram, load_radix_scan, load_radix_scatter,
+    load_svo_trace,
+};
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, DispatchIndirectCommand, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter};
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{
+    ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+};
+use vulkano::shader::ShaderModule;
+
+/// Mirrors the push constant block of `shader/dispatch_count.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DispatchCountParams {
+    pub group_size: u32,
+}
+
+/// Mirrors the push constant block of `shader/list_sum.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ListSumParams {
+    /// Device address of a [`ListHeader`].
+    pub header: u64,
+}
+
+/// Keys handled by one workgroup of the radix sort shaders.
+pub const RADIX_SORT_BLOCK_SIZE: u32 = 1024;
+const RADIX_SORT_RADIX: u32 = 256;
+const RADIX_SORT_PASSES: u32 = 4;
+
+/// Mirrors the push constant block of the `shader/radix_*.comp` shaders.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct RadixSortParams {
+    pub count: u32,
+    /// Bit offset of the digit sorted by in this pass.
+    pub shift: u32,
+    pub block_count: u32,
+    /// Non-zero when values are moved along with the keys.
+    pub has_values: u32,
+}
+
+/// Head of a singly linked list walked through device addresses by `shader/list_sum.comp`.
+#[derive(BufferContents, Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct ListHeader {
+    /// Device address of the first [`ListNode`].
+    pub first: u64,
+    pub count: u32,
+    /// Written by the shader.
+    pub sum: u32,
+}
+
+#[derive(BufferContents, Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct ListNode {
+    /// Device address of the next node, 0 for the last one.
+    pub next: u64,
+    pub value: u32,
+    pub _padding: u32,
+}
+
+/// Workgroup counts that live on the device, so that an earlier pass can write them.
+#[derive(Clone, Debug)]
+pub struct DispatchIndirectBuffer {
+    buffer: Subbuffer<[DispatchIndirectCommand]>,
+}
+
+impl DispatchIndirectBuffer {
+    pub fn new(allocator: Arc<dyn MemoryAllocator>) -> Result<Self, ThorusError> {
+        let buffer = Buffer::new_slice(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::INDIRECT_BUFFER | BufferUsage::STORAGE_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..AllocationCreateInfo::default()
+            },
+            1,
+        )?;
+        Ok(Self { buffer })
+    }
+
+    pub fn buffer(&self) -> &Subbuffer<[DispatchIndirectCommand]> {
+        &self.buffer
+    }
+
+    /// Records `count_pass`, a [`ComputePass::dispatch_count`], so that the buffer ends up
+    /// holding enough groups of `group_size` invocations to cover the `u32` in `count`.
+    pub fn write_from_count(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        count_pass: &ComputePass,
+        count: Subbuffer<u32>,
+        group_size: u32,
+    ) -> Result<(), ThorusError> {
+        count_pass.bind(
+            builder,
+            [
+                WriteDescriptorSet::buffer(0, count),
+                WriteDescriptorSet::buffer(1, self.buffer.clone()),
+            ],
+        )?;
+        count_pass.push_constants(builder, DispatchCountParams { group_size })?;
+        count_pass.dispatch(builder, [1, 1, 1])
+    }
+}
+
+/// A compute pipeline with a single descriptor set.
+pub struct ComputePass {
+    pipeline: Arc<ComputePipeline>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+}
+
+impl ComputePass {
+    pub fn new(device: Arc<Device>, module: Arc<ShaderModule>) -> Result<Self, ThorusError> {
+        let cs = module
+            .entry_point("main")
+            .expect("compute shader has no main entry point");
+        let stage = PipelineShaderStageCreateInfo::new(cs);
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(device.clone())
+                .map_err(|e| e.error)?,
+        )?;
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )?;
+        debug!("compute pipeline: {pipeline:?}");
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+        })
+    }
+
+    /// Pass turning an element count into indirect dispatch parameters, see
+    /// [`DispatchIndirectBuffer::write_from_count`].
+    pub fn dispatch_count(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let module = load_dispatch_count(device.clone())?;
+        Self::new(device, module)
+    }
+
+    /// Pass summing a linked list of [`ListNode`]s in place, addressed by [`ListSumParams`].
+    pub fn list_sum(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let module = load_list_sum(device.clone())?;
+        Self::new(device, module)
+    }
+
+    /// Pass tracing primary rays through a
+    /// [`SparseVoxelOctree`](crate::voxel::SparseVoxelOctree) uploaded to binding 0 into the
+    /// `rgba8` storage image at binding 1, one invocation per pixel in groups of 8×8, with
+    /// [`SvoTraceParams`](crate::voxel::SvoTraceParams) as push constants.
+    pub fn svo_trace(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let module = load_svo_trace(device.clone())?;
+        Self::new(device, module)
+    }
+
+    pub fn pipeline(&self) -> &Arc<ComputePipeline> {
+        &self.pipeline
+    }
+
+    /// Binds the pipeline alone, for shaders that reach their data through device addresses.
+    pub fn bind_pipeline(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), ThorusError> {
+        builder.bind_pipeline_compute(self.pipeline.clone())?;
+        Ok(())
+    }
+
+    /// Binds the pipeline and a new descriptor set 0 made of `writes`.
+    pub fn bind(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        writes: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Result<(), ThorusError> {
+        let layout = self.pipeline.layout();
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.set_layouts()[0].clone(),
+            writes,
+            [],
+        )?;
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )?;
+        Ok(())
+    }
+
+    pub fn push_constants<Pc: BufferContents>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        push_constants: Pc,
+    ) -> Result<(), ThorusError> {
+        builder.push_constants(self.pipeline.layout().clone(), 0, push_constants)?;
+        Ok(())
+    }
+
+    pub fn dispatch(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        group_counts: [u32; 3],
+    ) -> Result<(), ThorusError> {
+        builder.dispatch(group_counts)?;
+        Ok(())
+    }
+
+    /// Dispatches with the group counts found in `indirect_buffer` when the command executes.
+    pub fn dispatch_indirect(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        indirect_buffer: &DispatchIndirectBuffer,
+    ) -> Result<(), ThorusError> {
+        builder.dispatch_indirect(indirect_buffer.buffer.clone())?;
+        Ok(())
+    }
+}
+
+/// Stable least-significant-digit radix sort of `u32` keys, optionally carrying `u32` values
+/// along, in four passes of 8 bits.
+///
+/// Every pass counts the digits per block of [`RADIX_SORT_BLOCK_SIZE`] keys, scans the counts
+/// into offsets and scatters the keys between the caller's buffers and scratch buffers sized
+/// for `capacity` keys. After the even number of passes the result is back in the caller's
+/// buffers.
+pub struct GpuRadixSort {
+    histogram: ComputePass,
+    scan: ComputePass,
+    scatter: ComputePass,
+    scratch_keys: Subbuffer<[u32]>,
+    scratch_values: Subbuffer<[u32]>,
+    /// Stand in for the values and their scratch buffer when sorting keys only. They are
+    /// separate buffers, as halves of one would bind at an offset below the device's
+    /// `min_storage_buffer_offset_alignment`.
+    unused_values: Subbuffer<[u32]>,
+    unused_scratch_values: Subbuffer<[u32]>,
+    histograms: Subbuffer<[u32]>,
+    capacity: u32,
+}
+
+impl GpuRadixSort {
+    pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<dyn MemoryAllocator>,
+        capacity: u32,
+    ) -> Result<Self, ThorusError> {
+        let storage = |len: u64| {
+            Buffer::new_slice::<u32>(
+                allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER,
+                    ..BufferCreateInfo::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                    ..AllocationCreateInfo::default()
+                },
+                len.max(1),
+            )
+            .map_err(ThorusError::from)
+        };
+        let max_blocks = capacity.div_ceil(RADIX_SORT_BLOCK_SIZE) as u64;
+        Ok(Self {
+            histogram: ComputePass::new(device.clone(), load_radix_histogram(device.clone())?)?,
+            scan: ComputePass::new(device.clone(), load_radix_scan(device.clone())?)?,
+            scatter: ComputePass::new(device.clone(), load_radix_scatter(device)?)?,
+            scratch_keys: storage(capacity as u64)?,
+            scratch_values: storage(capacity as u64)?,
+            unused_values: storage(1)?,
+            unused_scratch_values: storage(1)?,
+            histograms: storage(RADIX_SORT_RADIX as u64 * max_blocks)?,
+            capacity,
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Records sorting the first `count` `keys` ascending and permuting the first `count`
+    /// `values` the same way. Both buffers need `STORAGE_BUFFER` usage.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `count` exceeds the capacity or the length of either buffer.
+    pub fn sort(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        keys: Subbuffer<[u32]>,
+        values: Subbuffer<[u32]>,
+        count: u32,
+    ) -> Result<(), ThorusError> {
+        assert!(
+            values.len() >= count as u64,
+            "fewer values than keys to sort"
+        );
+        self.record(builder, keys, Some(values), count)
+    }
+
+    /// Like [`sort`](Self::sort) without values.
+    pub fn sort_keys_only(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        keys: Subbuffer<[u32]>,
+        count: u32,
+    ) -> Result<(), ThorusError> {
+        self.record(builder, keys, None, count)
+    }
+
+    fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        keys: Subbuffer<[u32]>,
+        values: Option<Subbuffer<[u32]>>,
+        count: u32,
+    ) -> Result<(), ThorusError> {
+        assert!(
+            count <= self.capacity,
+            "more keys than the sort was created for"
+        );
+        assert!(
+            keys.len() >= count as u64,
+            "fewer keys than the count to sort"
+        );
+        if count == 0 {
+            return Ok(());
+        }
+
+        let block_count = count.div_ceil(RADIX_SORT_BLOCK_SIZE);
+        let has_values = values.is_some() as u32;
+        let (values, scratch_values) = match values {
+            Some(values) => (values, self.scratch_values.clone()),
+            None => (
+                self.unused_values.clone(),
+                self.unused_scratch_values.clone(),
+            ),
+        };
+        for pass in 0..RADIX_SORT_PASSES {
+            let ((keys_in, values_in), (keys_out, values_out)) = {
+                let caller = (keys.clone(), values.clone());
+                let scratch = (self.scratch_keys.clone(), scratch_values.clone());
+                if pass % 2 == 0 {
+                    (caller, scratch)
+                } else {
+                    (scratch, caller)
+                }
+            };
+            let params = RadixSortParams {
+                count,
+                shift: pass * 8,
+                block_count,
+                has_values,
+            };
+
+            self.histogram.bind(
+                builder,
+                [
+                    WriteDescriptorSet::buffer(0, keys_in.clone()),
+                    WriteDescriptorSet::buffer(4, self.histograms.clone()),
+                ],
+            )?;
+            self.histogram.push_constants(builder, params)?;
+            self.histogram.dispatch(builder, [block_count, 1, 1])?;
+
+            self.scan.bind(
+                builder,
+                [WriteDescriptorSet::buffer(4, self.histograms.clone())],
+            )?;
+            self.scan.push_constants(builder, params)?;
+            self.scan.dispatch(builder, [1, 1, 1])?;
+
+            self.scatter.bind(
+                builder,
+                [
+                    WriteDescriptorSet::buffer(0, keys_in),
+                    WriteDescriptorSet::buffer(1, values_in),
+                    WriteDescriptorSet::buffer(2, keys_out),
+                    WriteDescriptorSet::buffer(3, values_out),
+                    WriteDescriptorSet::buffer(4, self.histograms.clone()),
+                ],
+            )?;
+            self.scatter.push_constants(builder, params)?;
+            self.scatter.dispatch(builder, [block_count, 1, 1])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{xorshift32, TestContext};
+
+    const KEY_COUNT: u32 = 10_000;
+
+    mod count_groups {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 460
+
+                layout(local_size_x = 1) in;
+
+                layout(set = 0, binding = 0) buffer Groups {
+                    uint groups;
+                };
+
+                void main() {
+                    atomicAdd(groups, 1);
+                }
+            ",
+        }
+    }
+
+    /// Distinct pseudo-random keys, as [`xorshift32`] repeats none within its period.
+    fn random_keys(count: u32) -> Vec<u32> {
+        let mut random = xorshift32(0x9e37_79b9);
+        (0..count).map(|_| random()).collect()
+    }
+
+    fn host_buffer(context: &TestContext, data: &[u32]) -> Subbuffer<[u32]> {
+        Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..AllocationCreateInfo::default()
+            },
+            data.iter().copied(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn sort_keys_only_matches_slice_sort() {
+        let context = TestContext::new();
+        let mut expected = random_keys(KEY_COUNT);
+        let keys = host_buffer(&context, &expected);
+        let sort = GpuRadixSort::new(
+            context.queue.device().clone(),
+            context.memory_allocator.clone(),
+            KEY_COUNT,
+        )
+        .unwrap();
+
+        let mut builder = context.command_buffer();
+        sort.sort_keys_only(&mut builder, keys.clone(), KEY_COUNT)
+            .unwrap();
+        context.submit(builder);
+
+        expected.sort();
+        assert_eq!(*keys.read().unwrap(), expected[..]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn sort_moves_values_with_their_keys() {
+        let context = TestContext::new();
+        let original = random_keys(KEY_COUNT);
+        let keys = host_buffer(&context, &original);
+        let indices: Vec<u32> = (0..KEY_COUNT).collect();
+        let values = host_buffer(&context, &indices);
+        let sort = GpuRadixSort::new(
+            context.queue.device().clone(),
+            context.memory_allocator.clone(),
+            KEY_COUNT,
+        )
+        .unwrap();
+
+        let mut builder = context.command_buffer();
+        sort.sort(&mut builder, keys.clone(), values.clone(), KEY_COUNT)
+            .unwrap();
+        context.submit(builder);
+
+        let mut expected = indices;
+        expected.sort_by_key(|&i| original[i as usize]);
+        assert_eq!(*values.read().unwrap(), expected[..]);
+        let keys = keys.read().unwrap();
+        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn indirect_dispatch_uses_the_gpu_written_group_count() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let count_pass = ComputePass::dispatch_count(device.clone()).unwrap();
+        let groups_pass =
+            ComputePass::new(device.clone(), count_groups::load(device).unwrap()).unwrap();
+        let indirect = DispatchIndirectBuffer::new(context.memory_allocator.clone()).unwrap();
+        let count = host_buffer(&context, &[1000]);
+        let groups = host_buffer(&context, &[0]);
+
+        let mut builder = context.command_buffer();
+        indirect
+            .write_from_count(&mut builder, &count_pass, count.index(0), 64)
+            .unwrap();
+        groups_pass
+            .bind(
+                &mut builder,
+                [WriteDescriptorSet::buffer(0, groups.clone())],
+            )
+            .unwrap();
+        groups_pass
+            .dispatch_indirect(&mut builder, &indirect)
+            .unwrap();
+        context.submit(builder);
+
+        assert_eq!(groups.read().unwrap()[0], 1000_u32.div_ceil(64));
+    }
+}
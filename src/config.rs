@@ -0,0 +1,305 @@
+use crate::device::FeatureSet;
+use crate::pipeline::DepthPrepassConfig;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use vulkano::format::{ClearValue, NumericType};
+use vulkano::image::ImageAspects;
+use vulkano::render_pass::{AttachmentDescription, AttachmentLoadOp, RenderPass};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ClearColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl ClearColor {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn black() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub const fn transparent() -> Self {
+        Self::new(0.0, 0.0, 0.0, 0.0)
+    }
+}
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        Self::new(0.3, 0.3, 0.3, 1.0)
+    }
+}
+
+impl From<ClearColor> for [f32; 4] {
+    fn from(color: ClearColor) -> Self {
+        [color.r, color.g, color.b, color.a]
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to access config file: {e}"),
+            Self::Parse(e) => write!(f, "invalid config file: {e}"),
+            Self::Serialize(e) => write!(f, "failed to serialize config: {e}"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Serialize(e) => Some(e),
+        }
+    }
+}
+
+/// Renderer settings, persisted as TOML; missing keys take their default values.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct RenderConfig {
+    pub clear_color: ClearColor,
+    pub depth_clear_value: f32,
+    pub stencil_clear_value: u32,
+    /// Initial window size in physical pixels; used only when both are set.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub vsync: bool,
+    pub validation: bool,
+    /// Lowest Vulkan version and required functionality of the device to pick.
+    pub feature_set: FeatureSet,
+    pub msaa_samples: u32,
+    pub shader_dir: Option<PathBuf>,
+    pub model_path: Option<PathBuf>,
+    /// Upper bound for the swapchain image count, and with it the frames in flight.
+    pub max_frames_in_flight: u32,
+    /// Block size of the device memory allocator; the allocator picks it per heap if unset.
+    pub memory_pool_size_mb: Option<u64>,
+    /// Multiplier of the distance used to select levels of detail; above one switches to
+    /// coarser levels sooner.
+    pub lod_bias: f32,
+    pub depth_prepass: DepthPrepassConfig,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            clear_color: ClearColor::default(),
+            depth_clear_value: 1.0,
+            stencil_clear_value: 0,
+            width: None,
+            height: None,
+            vsync: true,
+            validation: false,
+            feature_set: FeatureSet::default(),
+            msaa_samples: 1,
+            shader_dir: None,
+            model_path: None,
+            max_frames_in_flight: 3,
+            memory_pool_size_mb: None,
+            lod_bias: 1.0,
+            depth_prepass: DepthPrepassConfig::default(),
+        }
+    }
+}
+
+impl RenderConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let source = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        toml::from_str(&source).map_err(ConfigError::Parse)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let source = toml::to_string_pretty(self).map_err(ConfigError::Serialize)?;
+        fs::write(path, source).map_err(ConfigError::Io)
+    }
+
+    /// One clear value per attachment of `render_pass`, matching what the attachment's format
+    /// and load operations require; attachments that are not cleared on load get `None`.
+    pub fn clear_values(&self, render_pass: &RenderPass) -> Vec<Option<ClearValue>> {
+        render_pass
+            .attachments()
+            .iter()
+            .map(|attachment| self.clear_value(attachment))
+            .collect()
+    }
+
+    /// The clear color is converted to integers for `SINT` and `UINT` color formats, and only
+    /// the aspects of a depth/stencil format that are cleared get a value.
+    fn clear_value(&self, attachment: &AttachmentDescription) -> Option<ClearValue> {
+        let clears = |load_op| load_op == AttachmentLoadOp::Clear;
+        if let Some(numeric_format) = attachment.format.numeric_format_color() {
+            let color = <[f32; 4]>::from(self.clear_color);
+            return clears(attachment.load_op).then(|| match numeric_format.numeric_type() {
+                NumericType::Float => ClearValue::Float(color),
+                NumericType::Int => ClearValue::Int(color.map(|c| c as i32)),
+                NumericType::Uint => ClearValue::Uint(color.map(|c| c as u32)),
+            });
+        }
+
+        let aspects = attachment.format.aspects();
+        let depth = aspects.intersects(ImageAspects::DEPTH) && clears(attachment.load_op);
+        let stencil = aspects.intersects(ImageAspects::STENCIL)
+            && clears(attachment.stencil_load_op.unwrap_or(attachment.load_op));
+        match (depth, stencil) {
+            (true, true) => Some(ClearValue::DepthStencil((
+                self.depth_clear_value,
+                self.stencil_clear_value,
+            ))),
+            (true, false) => Some(ClearValue::Depth(self.depth_clear_value)),
+            (false, true) => Some(ClearValue::Stencil(self.stencil_clear_value)),
+            (false, false) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vulkano::format::Format;
+
+    fn attachment(
+        format: Format,
+        load_op: AttachmentLoadOp,
+        stencil_load_op: Option<AttachmentLoadOp>,
+    ) -> AttachmentDescription {
+        AttachmentDescription {
+            format,
+            load_op,
+            stencil_load_op,
+            ..AttachmentDescription::default()
+        }
+    }
+
+    #[test]
+    fn color_constructors() {
+        assert_eq!(<[f32; 4]>::from(ClearColor::black()), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(
+            <[f32; 4]>::from(ClearColor::transparent()),
+            [0.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn color_clear_value_follows_the_numeric_type() {
+        let config = RenderConfig {
+            clear_color: ClearColor::new(1.0, 0.0, 2.0, 1.0),
+            ..RenderConfig::default()
+        };
+        let clear = |format| config.clear_value(&attachment(format, AttachmentLoadOp::Clear, None));
+        assert_eq!(
+            clear(Format::R8G8B8A8_UNORM),
+            Some(ClearValue::Float([1.0, 0.0, 2.0, 1.0]))
+        );
+        assert_eq!(clear(Format::R32_SINT), Some(ClearValue::Int([1, 0, 2, 1])));
+        assert_eq!(
+            clear(Format::R16G16_UINT),
+            Some(ClearValue::Uint([1, 0, 2, 1]))
+        );
+        assert_eq!(
+            config.clear_value(&attachment(
+                Format::R8G8B8A8_UNORM,
+                AttachmentLoadOp::Load,
+                None
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn depth_stencil_clear_value_covers_only_cleared_aspects() {
+        let config = RenderConfig {
+            depth_clear_value: 0.0,
+            stencil_clear_value: 7,
+            ..RenderConfig::default()
+        };
+        let clear = |load_op, stencil_load_op| {
+            config.clear_value(&attachment(
+                Format::D24_UNORM_S8_UINT,
+                load_op,
+                stencil_load_op,
+            ))
+        };
+        assert_eq!(
+            clear(AttachmentLoadOp::Clear, None),
+            Some(ClearValue::DepthStencil((0.0, 7)))
+        );
+        assert_eq!(
+            clear(AttachmentLoadOp::Clear, Some(AttachmentLoadOp::DontCare)),
+            Some(ClearValue::Depth(0.0))
+        );
+        assert_eq!(
+            clear(AttachmentLoadOp::Load, Some(AttachmentLoadOp::Clear)),
+            Some(ClearValue::Stencil(7))
+        );
+        assert_eq!(clear(AttachmentLoadOp::DontCare, None), None);
+        assert_eq!(
+            config.clear_value(&attachment(
+                Format::D16_UNORM,
+                AttachmentLoadOp::Clear,
+                None
+            )),
+            Some(ClearValue::Depth(0.0))
+        );
+    }
+
+    #[test]
+    fn missing_keys_take_defaults() {
+        let config: RenderConfig = toml::from_str("vsync = false").unwrap();
+        assert!(!config.vsync);
+        assert_eq!(config.clear_color, ClearColor::default());
+        assert_eq!(config.depth_clear_value, 1.0);
+
+        let round_trip: RenderConfig =
+            toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(round_trip, config);
+    }
+
+    #[test]
+    fn saved_file_loads_back_unchanged() {
+        let config = RenderConfig {
+            clear_color: ClearColor::new(0.1, 0.2, 0.3, 0.4),
+            depth_clear_value: 0.0,
+            stencil_clear_value: 3,
+            width: Some(1280),
+            height: Some(720),
+            vsync: false,
+            validation: true,
+            feature_set: FeatureSet::V1_1,
+            msaa_samples: 4,
+            shader_dir: Some(PathBuf::from("shaders")),
+            model_path: Some(PathBuf::from("models/teapot.obj")),
+            max_frames_in_flight: 2,
+            memory_pool_size_mb: Some(64),
+            lod_bias: 1.5,
+            depth_prepass: DepthPrepassConfig { enabled: true },
+        };
+        let path = std::env::temp_dir().join(format!("thorus-config-{}.toml", std::process::id()));
+        config.save(&path).unwrap();
+        let loaded = RenderConfig::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), config);
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        let result = RenderConfig::load(Path::new("/nonexistent/thorus.toml"));
+        assert!(matches!(result, Err(ConfigError::Io(_))));
+    }
+}
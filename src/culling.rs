@@ -0,0 +1,251 @@
+#[cfg(feature = "simd")]
+use std::simd::cmp::SimdPartialOrd;
+#[cfg(feature = "simd")]
+use std::simd::{f32x8, mask32x8};
+
+/// Plane `n·p + d = 0` with a unit normal pointing into the frustum.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Plane {
+    pub normal: [f32; 3],
+    pub d: f32,
+}
+
+impl Plane {
+    fn from_coefficients([a, b, c, d]: [f32; 4]) -> Self {
+        let len = (a * a + b * b + c * c).sqrt();
+        Self {
+            normal: [a / len, b / len, c / len],
+            d: d / len,
+        }
+    }
+
+    pub fn signed_distance(&self, point: [f32; 3]) -> f32 {
+        self.normal
+            .iter()
+            .zip(point)
+            .map(|(n, p)| n * p)
+            .sum::<f32>()
+            + self.d
+    }
+}
+
+/// View frustum, planes ordered left, right, bottom, top, near, far in clip space terms.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of a column-major view-projection matrix (Gribb-Hartmann),
+    /// assuming Vulkan's `0..=1` clip depth range.
+    pub fn from_vp(vp: [[f32; 4]; 4]) -> Self {
+        let row = |i: usize| [vp[0][i], vp[1][i], vp[2][i], vp[3][i]];
+        let add = |a: [f32; 4], b: [f32; 4]| [a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]];
+        let sub = |a: [f32; 4], b: [f32; 4]| [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]];
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [add(w, x), sub(w, x), add(w, y), sub(w, y), z, sub(w, z)]
+                .map(Plane::from_coefficients),
+        }
+    }
+
+    pub fn contains_point(&self, point: [f32; 3]) -> bool {
+        self.contains_sphere(point, 0.0)
+    }
+
+    pub fn contains_sphere(&self, center: [f32; 3], radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    pub fn contains_aabb(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        self.planes.iter().all(|plane| {
+            let positive = [0, 1, 2].map(|i| {
+                if plane.normal[i] >= 0.0 {
+                    max[i]
+                } else {
+                    min[i]
+                }
+            });
+            plane.signed_distance(positive) >= 0.0
+        })
+    }
+}
+
+/// [`Frustum`] testing eight spheres or boxes at once, one per SIMD lane. The planes are
+/// stored as four vectors of their normal x, y, z and `d`, plane `i` in lane `i`.
+#[cfg(feature = "simd")]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SimdFrustum {
+    planes: [f32x8; 4],
+}
+
+#[cfg(feature = "simd")]
+impl SimdFrustum {
+    pub fn new(frustum: &Frustum) -> Self {
+        Self {
+            planes: [0, 1, 2, 3].map(|component| {
+                f32x8::from_array(std::array::from_fn(|lane| {
+                    frustum.planes.get(lane).map_or(0.0, |plane| {
+                        let [x, y, z] = plane.normal;
+                        [x, y, z, plane.d][component]
+                    })
+                }))
+            }),
+        }
+    }
+
+    /// Whether each sphere is at least partly inside, as [`Frustum::contains_sphere`].
+    pub fn cull_spheres_8(&self, centers: [[f32; 3]; 8], radii: [f32; 8]) -> [bool; 8] {
+        let center = [0, 1, 2].map(|axis| f32x8::from_array(centers.map(|c| c[axis])));
+        let min_distance = -f32x8::from_array(radii);
+        let mut visible = mask32x8::splat(true);
+        for plane in 0..6 {
+            visible &= self.signed_distance(plane, center).simd_ge(min_distance);
+        }
+        visible.to_array()
+    }
+
+    /// Whether each box is at least partly inside, as [`Frustum::contains_aabb`].
+    pub fn cull_aabbs_8(&self, mins: [[f32; 3]; 8], maxs: [[f32; 3]; 8]) -> [bool; 8] {
+        let min = [0, 1, 2].map(|axis| f32x8::from_array(mins.map(|c| c[axis])));
+        let max = [0, 1, 2].map(|axis| f32x8::from_array(maxs.map(|c| c[axis])));
+        let mut visible = mask32x8::splat(true);
+        for plane in 0..6 {
+            // the corner farthest along the normal
+            let positive = [0, 1, 2].map(|axis| {
+                if self.planes[axis][plane] >= 0.0 {
+                    max[axis]
+                } else {
+                    min[axis]
+                }
+            });
+            visible &= self
+                .signed_distance(plane, positive)
+                .simd_ge(f32x8::splat(0.0));
+        }
+        visible.to_array()
+    }
+
+    fn signed_distance(&self, plane: usize, [x, y, z]: [f32x8; 3]) -> f32x8 {
+        let [nx, ny, nz, d] = self.planes.map(|component| f32x8::splat(component[plane]));
+        nx * x + ny * y + nz * z + d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Mat4;
+
+    const RADIUS: f32 = 0.5;
+
+    /// 90° square frustum looking down -z from the origin, near 1 and far 10, so the side
+    /// planes pass through `±5` at `z = -5`.
+    fn frustum() -> Frustum {
+        Frustum::from_vp(Mat4::perspective(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 10.0).into())
+    }
+
+    /// Centers just inside and just outside each plane, left, right, bottom, top, near, far.
+    fn boundary_cases() -> [([f32; 3], [f32; 3]); 6] {
+        [
+            ([-4.9, 0.0, -5.0], [-6.0, 0.0, -5.0]),
+            ([4.9, 0.0, -5.0], [6.0, 0.0, -5.0]),
+            ([0.0, -4.9, -5.0], [0.0, -6.0, -5.0]),
+            ([0.0, 4.9, -5.0], [0.0, 6.0, -5.0]),
+            ([0.0, 0.0, -1.1], [0.0, 0.0, -0.3]),
+            ([0.0, 0.0, -9.9], [0.0, 0.0, -10.7]),
+        ]
+    }
+
+    #[test]
+    fn spheres_on_each_boundary() {
+        let frustum = frustum();
+        for (inside, outside) in boundary_cases() {
+            assert!(
+                frustum.contains_sphere(inside, RADIUS),
+                "{inside:?} is culled"
+            );
+            assert!(
+                !frustum.contains_sphere(outside, RADIUS),
+                "{outside:?} is kept"
+            );
+        }
+    }
+
+    #[test]
+    fn spheres_straddling_a_plane_are_kept() {
+        let frustum = frustum();
+        assert!(frustum.contains_sphere([-5.5, 0.0, -5.0], RADIUS));
+        assert!(!frustum.contains_point([-5.5, 0.0, -5.0]));
+    }
+
+    #[test]
+    fn aabbs_on_each_boundary() {
+        let frustum = frustum();
+        let aabb = |[x, y, z]: [f32; 3]| {
+            let half = RADIUS / 2.0;
+            (
+                [x - half, y - half, z - half],
+                [x + half, y + half, z + half],
+            )
+        };
+        for (inside, outside) in boundary_cases() {
+            let (min, max) = aabb(inside);
+            assert!(frustum.contains_aabb(min, max), "{inside:?} is culled");
+            let (min, max) = aabb(outside);
+            assert!(!frustum.contains_aabb(min, max), "{outside:?} is kept");
+        }
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod simd_tests {
+    use super::*;
+    use crate::math::Mat4;
+    use crate::testing::random_signed_floats;
+
+    fn random_frustum(random: &mut impl FnMut() -> f32) -> Frustum {
+        let eye = [random() * 10.0, random() * 10.0, random() * 10.0];
+        let center = [random() * 10.0, random() * 10.0, random() * 10.0];
+        let projection = Mat4::perspective(1.0 + random() * 0.5, 1.0 + random() * 0.5, 0.1, 50.0);
+        let view = Mat4::look_at(eye, center, [0.0, 1.0, 0.0]);
+        Frustum::from_vp((projection * view).into())
+    }
+
+    fn random_points(random: &mut impl FnMut() -> f32) -> [[f32; 3]; 8] {
+        std::array::from_fn(|_| [random() * 20.0, random() * 20.0, random() * 20.0])
+    }
+
+    #[test]
+    fn cull_spheres_8_matches_scalar() {
+        let mut random = random_signed_floats(0x9e37_79b9);
+        for _ in 0..200 {
+            let frustum = random_frustum(&mut random);
+            let simd = SimdFrustum::new(&frustum);
+            for _ in 0..50 {
+                let centers = random_points(&mut random);
+                let radii = std::array::from_fn(|_| random().abs() * 3.0);
+                let expected =
+                    std::array::from_fn(|i| frustum.contains_sphere(centers[i], radii[i]));
+                assert_eq!(simd.cull_spheres_8(centers, radii), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn cull_aabbs_8_matches_scalar() {
+        let mut random = random_signed_floats(0x85eb_ca6b);
+        for _ in 0..200 {
+            let frustum = random_frustum(&mut random);
+            let simd = SimdFrustum::new(&frustum);
+            for _ in 0..50 {
+                let mins = random_points(&mut random);
+                let maxs = mins.map(|min| min.map(|x| x + random().abs() * 3.0));
+                let expected = std::array::from_fn(|i| frustum.contains_aabb(mins[i], maxs[i]));
+                assert_eq!(simd.cull_aabbs_8(mins, maxs), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::bvh::Aabb;
+use crate::error::ThorusError;
+use crate::math::Mat4;
+use crate::pipeline::GraphicsPipelineBuilder;
+use crate::shader::{load_debug_line_fragment, load_debug_line_vertex};
+use crate::vertex::DebugVertex;
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::buffer::allocator::SubbufferAllocator;
+use vulkano::buffer::BufferContents;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{DynamicState, GraphicsPipeline, Pipeline};
+use vulkano::render_pass::RenderPass;
+
+/// Mirrors the push constant block of `shader/debug_line.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DebugParams {
+    pub view_proj: [[f32; 4]; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DebugLine {
+    vertices: [DebugVertex; 2],
+    /// Seconds left before the line expires; lines with none left live for one frame.
+    remaining: f32,
+    width: f32,
+}
+
+/// Immediate-mode line drawing for debugging.
+///
+/// Lines accumulate on the host and are uploaded when recorded.
+#[derive(Clone, Default, Debug)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+}
+
+impl DebugDraw {
+    pub const ARROW_HEAD_SEGMENTS: usize = 8;
+    pub const DEFAULT_LINE_WIDTH: f32 = 1.0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a line that stays visible for `duration` seconds, or a single frame if zero.
+    pub fn line(&mut self, a: [f32; 3], b: [f32; 3], color: [f32; 4], duration: f32) {
+        self.line_with_width(a, b, Self::DEFAULT_LINE_WIDTH, color, duration);
+    }
+
+    fn line_with_width(
+        &mut self,
+        a: [f32; 3],
+        b: [f32; 3],
+        width: f32,
+        color: [f32; 4],
+        duration: f32,
+    ) {
+        self.lines.push(DebugLine {
+            vertices: [a, b].map(|position| DebugVertex { position, color }),
+            remaining: duration,
+            width,
+        });
+    }
+
+    /// Queues a line of `length` from `origin` along `direction`, topped by a cone of
+    /// [`ARROW_HEAD_SEGMENTS`](Self::ARROW_HEAD_SEGMENTS) sides drawn as its base outline and
+    /// the edges to the tip. Nothing is queued for a zero `direction`.
+    pub fn arrow(
+        &mut self,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        length: f32,
+        color: [f32; 4],
+        duration: f32,
+    ) {
+        let Some([forward, side, up]) = basis(direction) else {
+            return;
+        };
+        let at = |distance: f32| [0, 1, 2].map(|i| origin[i] + forward[i] * distance);
+        let tip = at(length);
+        self.line(origin, tip, color, duration);
+
+        let head_length = length * 0.2;
+        let head_radius = head_length * 0.4;
+        let head_base = at(length - head_length);
+        let rim = (0..Self::ARROW_HEAD_SEGMENTS)
+            .map(|segment| {
+                let angle = segment as f32 / Self::ARROW_HEAD_SEGMENTS as f32 * TAU;
+                let (sin, cos) = angle.sin_cos();
+                [0, 1, 2].map(|i| head_base[i] + (side[i] * cos + up[i] * sin) * head_radius)
+            })
+            .collect::<Vec<_>>();
+        for (i, &point) in rim.iter().enumerate() {
+            self.line(point, rim[(i + 1) % rim.len()], color, duration);
+            self.line(point, tip, color, duration);
+        }
+    }
+
+    /// Queues a square grid of `cell_count`×`cell_count` cells of `cell_size` centered on
+    /// `center` in the plane orthogonal to `normal`. Nothing is queued for a zero `normal`.
+    pub fn grid(
+        &mut self,
+        center: [f32; 3],
+        normal: [f32; 3],
+        cell_size: f32,
+        cell_count: u32,
+        color: [f32; 4],
+        duration: f32,
+    ) {
+        let Some([_, side, up]) = basis(normal) else {
+            return;
+        };
+        let half = cell_size * cell_count as f32 / 2.0;
+        let point = |s: f32, t: f32| [0, 1, 2].map(|i| center[i] + side[i] * s + up[i] * t);
+        for line in 0..=cell_count {
+            let offset = line as f32 * cell_size - half;
+            self.line(point(offset, -half), point(offset, half), color, duration);
+            self.line(point(-half, offset), point(half, offset), color, duration);
+        }
+    }
+
+    /// Queues the 12 edges of the frustum whose column-major inverse view-projection matrix
+    /// is `vp_inverse`, assuming Vulkan's `0..=1` clip depth range.
+    pub fn frustum(&mut self, vp_inverse: [[f32; 4]; 4], color: [f32; 4], duration: f32) {
+        // Corner `i` has x from bit 0, y from bit 1 and depth from bit 2.
+        let corners: [[f32; 3]; 8] = std::array::from_fn(|i| {
+            let ndc = [
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+                1.0,
+            ];
+            let world = Mat4(vp_inverse).mul_vec4(ndc);
+            [0, 1, 2].map(|i| world[i] / world[3])
+        });
+        for a in 0..8 {
+            for bit in [1, 2, 4] {
+                if a & bit == 0 {
+                    self.line(corners[a], corners[a | bit], color, duration);
+                }
+            }
+        }
+    }
+
+    /// Queues the 12 edges of `aabb` drawn `width` pixels wide, which needs a pipeline with
+    /// a dynamic line width; other lines keep the default width.
+    pub fn draw_thick_aabb(&mut self, aabb: &Aabb, width: f32, color: [f32; 4], duration: f32) {
+        let corners: [[f32; 3]; 8] = std::array::from_fn(|i| {
+            [0, 1, 2].map(|axis| {
+                if i & (1 << axis) == 0 {
+                    aabb.min[axis]
+                } else {
+                    aabb.max[axis]
+                }
+            })
+        });
+        for a in 0..8 {
+            for bit in [1, 2, 4] {
+                if a & bit == 0 {
+                    self.line_with_width(corners[a], corners[a | bit], width, color, duration);
+                }
+            }
+        }
+    }
+
+    /// Records `set_line_width`, clamped to what the device supports; without the
+    /// `wide_lines` feature every width is drawn as 1.
+    pub fn set_line_width(
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        width: f32,
+    ) -> Result<(), ThorusError> {
+        let device = builder.device().clone();
+        let width = if device.enabled_features().wide_lines {
+            let [min, max] = device.physical_device().properties().line_width_range;
+            width.clamp(min, max)
+        } else {
+            Self::DEFAULT_LINE_WIDTH
+        };
+        builder.set_line_width(width)?;
+        Ok(())
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Ages every line by `dt` seconds and drops the expired ones.
+    pub fn tick(&mut self, dt: f32) {
+        self.lines.retain_mut(|line| {
+            line.remaining -= dt;
+            line.remaining > 0.0
+        });
+    }
+
+    pub fn vertices(&self) -> impl Iterator<Item = DebugVertex> + '_ {
+        self.lines.iter().flat_map(|line| line.vertices)
+    }
+
+    /// Uploads the queued lines through `allocator`, which must hand out host-visible
+    /// `VERTEX_BUFFER` memory, and draws them with a pipeline from [`DebugDraw::pipeline`].
+    ///
+    /// When the pipeline has a dynamic line width, lines are drawn in one batch per width and
+    /// the width is set back to the default afterwards.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: Arc<GraphicsPipeline>,
+        allocator: &SubbufferAllocator,
+        view_proj: [[f32; 4]; 4],
+    ) -> Result<(), ThorusError> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|a, b| a.width.total_cmp(&b.width));
+        let vertex_count = lines.len() as u64 * 2;
+        let buffer = allocator.allocate_slice::<DebugVertex>(vertex_count)?;
+        for (dst, src) in buffer
+            .write()?
+            .iter_mut()
+            .zip(lines.iter().flat_map(|line| line.vertices))
+        {
+            *dst = src;
+        }
+
+        let dynamic_line_width = pipeline.dynamic_state().contains(&DynamicState::LineWidth);
+        let layout = pipeline.layout().clone();
+        builder
+            .bind_pipeline_graphics(pipeline)?
+            .push_constants(layout, 0, DebugParams { view_proj })?
+            .bind_vertex_buffers(0, buffer)?;
+        let widths: Vec<_> = lines.iter().map(|line| line.width).collect();
+        for command in line_commands(&widths, dynamic_line_width) {
+            match command {
+                LineCommand::SetWidth(width) => Self::set_line_width(builder, width)?,
+                LineCommand::Draw {
+                    first_vertex,
+                    vertex_count,
+                } => {
+                    builder.draw(vertex_count, 1, first_vertex, 0)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Line-list pipeline drawing into subpass 0 of `render_pass`, with a dynamic line width.
+    pub fn pipeline(
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        viewport: Viewport,
+    ) -> Result<Arc<GraphicsPipeline>, ThorusError> {
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_debug_line_vertex(device.clone())?)
+            .fragment_shader(load_debug_line_fragment(device)?)
+            .vertex_input(DebugVertex::per_vertex())
+            .topology(PrimitiveTopology::LineList)
+            .render_pass(render_pass, 0)
+            .viewport(viewport)
+            .with_dynamic_line_width()
+            .build()?;
+        debug!("debug line pipeline: {pipeline:?}");
+        Ok(pipeline)
+    }
+}
+
+/// A step of [`DebugDraw::record`] after the vertex buffer is bound.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LineCommand {
+    SetWidth(f32),
+    Draw {
+        first_vertex: u32,
+        vertex_count: u32,
+    },
+}
+
+/// Commands drawing lines of the sorted `widths`: one draw of everything without a dynamic
+/// line width, otherwise one draw per width preceded by setting it, and the default width set
+/// again at the end if the last draw changed it.
+fn line_commands(widths: &[f32], dynamic_line_width: bool) -> Vec<LineCommand> {
+    if !dynamic_line_width {
+        return vec![LineCommand::Draw {
+            first_vertex: 0,
+            vertex_count: widths.len() as u32 * 2,
+        }];
+    }
+
+    let mut commands = vec![];
+    let mut first_vertex = 0;
+    for batch in widths.chunk_by(|a, b| a == b) {
+        let vertex_count = batch.len() as u32 * 2;
+        commands.push(LineCommand::SetWidth(batch[0]));
+        commands.push(LineCommand::Draw {
+            first_vertex,
+            vertex_count,
+        });
+        first_vertex += vertex_count;
+    }
+    if widths.last() != Some(&DebugDraw::DEFAULT_LINE_WIDTH) {
+        commands.push(LineCommand::SetWidth(DebugDraw::DEFAULT_LINE_WIDTH));
+    }
+    commands
+}
+
+/// `direction` normalized, followed by two unit vectors completing a right-handed
+/// orthonormal basis; `None` for a zero vector.
+fn basis(direction: [f32; 3]) -> Option<[[f32; 3]; 3]> {
+    let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if length <= f32::EPSILON {
+        return None;
+    }
+    let forward = direction.map(|c| c / length);
+    let helper = if forward[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let cross = |a: [f32; 3], b: [f32; 3]| {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    };
+    let side = cross(forward, helper);
+    let side_length = side.iter().map(|c| c * c).sum::<f32>().sqrt();
+    let side = side.map(|c| c / side_length);
+    Some([forward, side, cross(forward, side)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    #[test]
+    fn arrow_is_a_shaft_and_a_cone() {
+        let mut draw = DebugDraw::new();
+        draw.arrow([0.0; 3], [0.0, 0.0, 2.0], 1.0, WHITE, 0.0);
+        assert_eq!(draw.line_count(), 1 + 2 * DebugDraw::ARROW_HEAD_SEGMENTS);
+
+        draw.clear();
+        draw.arrow([0.0; 3], [0.0; 3], 1.0, WHITE, 0.0);
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn grid_and_frustum_line_counts() {
+        let mut draw = DebugDraw::new();
+        draw.grid([0.0; 3], [0.0, 1.0, 0.0], 1.0, 4, WHITE, 0.0);
+        assert_eq!(draw.line_count(), 2 * 5);
+
+        draw.clear();
+        draw.frustum(Mat4::IDENTITY.0, WHITE, 0.0);
+        assert_eq!(draw.line_count(), 12);
+        assert!(draw
+            .vertices()
+            .all(|vertex| vertex.position.iter().all(|c| c.abs() <= 1.0)));
+    }
+
+    #[test]
+    fn lines_expire() {
+        let mut draw = DebugDraw::new();
+        draw.line([0.0; 3], [1.0; 3], WHITE, 0.0);
+        draw.line([0.0; 3], [1.0; 3], WHITE, 1.0);
+        draw.tick(0.5);
+        assert_eq!(draw.line_count(), 1);
+        draw.tick(0.5);
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn line_width_is_set_before_drawing_and_restored() {
+        let mut draw = DebugDraw::new();
+        draw.line([0.0; 3], [1.0; 3], WHITE, 0.0);
+        draw.draw_thick_aabb(
+            &Aabb {
+                min: [0.0; 3],
+                max: [1.0; 3],
+            },
+            3.0,
+            WHITE,
+            0.0,
+        );
+        let mut widths: Vec<_> = draw.lines.iter().map(|line| line.width).collect();
+        widths.sort_by(f32::total_cmp);
+
+        assert_eq!(
+            line_commands(&widths, true),
+            [
+                LineCommand::SetWidth(1.0),
+                LineCommand::Draw {
+                    first_vertex: 0,
+                    vertex_count: 2,
+                },
+                LineCommand::SetWidth(3.0),
+                LineCommand::Draw {
+                    first_vertex: 2,
+                    vertex_count: 24,
+                },
+                LineCommand::SetWidth(DebugDraw::DEFAULT_LINE_WIDTH),
+            ]
+        );
+        assert_eq!(
+            line_commands(&widths, false),
+            [LineCommand::Draw {
+                first_vertex: 0,
+                vertex_count: 26,
+            }]
+        );
+        assert_eq!(
+            line_commands(&[1.0], true),
+            [
+                LineCommand::SetWidth(1.0),
+                LineCommand::Draw {
+                    first_vertex: 0,
+                    vertex_count: 2,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,1150 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::mem::ManuallyDrop;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::{
+    DescriptorSetAlloc, DescriptorSetAllocator, StandardDescriptorSetAllocator,
+};
+use vulkano::descriptor_set::layout::{
+    DescriptorBindingFlags, DescriptorSetLayout, DescriptorSetLayoutBinding,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType,
+};
+use vulkano::descriptor_set::pool::{
+    DescriptorPool, DescriptorPoolAlloc, DescriptorPoolCreateInfo, DescriptorSetAllocateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceOwned, Features};
+use vulkano::image::sampler::{Filter, Sampler, SamplerCreateInfo, SamplerMipmapMode};
+use vulkano::image::view::ImageView;
+use vulkano::pipeline::{PipelineBindPoint, PipelineLayout};
+use vulkano::shader::ShaderStages;
+use vulkano::{Validated, ValidationError, VulkanError};
+
+/// Everything that makes two descriptor set layouts interchangeable.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LayoutKey {
+    flags: DescriptorSetLayoutCreateFlags,
+    bindings: Vec<BindingKey>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct BindingKey {
+    binding: u32,
+    binding_flags: DescriptorBindingFlags,
+    descriptor_type: DescriptorType,
+    descriptor_count: u32,
+    stages: ShaderStages,
+    /// Immutable samplers compare by identity.
+    immutable_samplers: Vec<usize>,
+}
+
+impl LayoutKey {
+    pub fn new(
+        flags: DescriptorSetLayoutCreateFlags,
+        bindings: &BTreeMap<u32, DescriptorSetLayoutBinding>,
+    ) -> Self {
+        Self {
+            flags,
+            bindings: bindings
+                .iter()
+                .map(|(&binding, info)| BindingKey {
+                    binding,
+                    binding_flags: info.binding_flags,
+                    descriptor_type: info.descriptor_type,
+                    descriptor_count: info.descriptor_count,
+                    stages: info.stages,
+                    immutable_samplers: info
+                        .immutable_samplers
+                        .iter()
+                        .map(|sampler| Arc::as_ptr(sampler) as usize)
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Shares one `DescriptorSetLayout` between all users of the same bindings.
+#[derive(Default)]
+pub struct DescriptorLayoutCache {
+    layouts: HashMap<LayoutKey, Arc<DescriptorSetLayout>>,
+}
+
+impl DescriptorLayoutCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_create(
+        &mut self,
+        device: Arc<Device>,
+        bindings: BTreeMap<u32, DescriptorSetLayoutBinding>,
+    ) -> Result<Arc<DescriptorSetLayout>, Validated<VulkanError>> {
+        self.get_or_create_with_flags(device, DescriptorSetLayoutCreateFlags::empty(), bindings)
+    }
+
+    pub fn get_or_create_with_flags(
+        &mut self,
+        device: Arc<Device>,
+        flags: DescriptorSetLayoutCreateFlags,
+        bindings: BTreeMap<u32, DescriptorSetLayoutBinding>,
+    ) -> Result<Arc<DescriptorSetLayout>, Validated<VulkanError>> {
+        let key = LayoutKey::new(flags, &bindings);
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(layout.clone());
+        }
+        let layout = DescriptorSetLayout::new(
+            device,
+            DescriptorSetLayoutCreateInfo {
+                flags,
+                bindings,
+                ..DescriptorSetLayoutCreateInfo::default()
+            },
+        )?;
+        debug!("created descriptor set layout: {layout:?}");
+        self.layouts.insert(key, layout.clone());
+        Ok(layout)
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+}
+
+/// Collects bindings together with their contents, creating the layout through a
+/// [`DescriptorLayoutCache`] so that sets with the same shape share it.
+#[derive(Default)]
+pub struct DescriptorSetBuilder {
+    bindings: BTreeMap<u32, DescriptorSetLayoutBinding>,
+    writes: Vec<WriteDescriptorSet>,
+}
+
+impl DescriptorSetBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn uniform_buffer<T: ?Sized>(
+        self,
+        binding: u32,
+        stages: ShaderStages,
+        buffer: Subbuffer<T>,
+    ) -> Self {
+        self.with(
+            binding,
+            DescriptorType::UniformBuffer,
+            stages,
+            WriteDescriptorSet::buffer(binding, buffer.into_bytes()),
+        )
+    }
+
+    pub fn storage_buffer<T: ?Sized>(
+        self,
+        binding: u32,
+        stages: ShaderStages,
+        buffer: Subbuffer<T>,
+    ) -> Self {
+        self.with(
+            binding,
+            DescriptorType::StorageBuffer,
+            stages,
+            WriteDescriptorSet::buffer(binding, buffer.into_bytes()),
+        )
+    }
+
+    pub fn combined_image_sampler(
+        self,
+        binding: u32,
+        stages: ShaderStages,
+        view: Arc<ImageView>,
+        sampler: Arc<Sampler>,
+    ) -> Self {
+        self.with(
+            binding,
+            DescriptorType::CombinedImageSampler,
+            stages,
+            WriteDescriptorSet::image_view_sampler(binding, view, sampler),
+        )
+    }
+
+    pub fn storage_image(self, binding: u32, stages: ShaderStages, view: Arc<ImageView>) -> Self {
+        self.with(
+            binding,
+            DescriptorType::StorageImage,
+            stages,
+            WriteDescriptorSet::image_view(binding, view),
+        )
+    }
+
+    fn with(
+        mut self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        stages: ShaderStages,
+        write: WriteDescriptorSet,
+    ) -> Self {
+        self.bindings.insert(
+            binding,
+            DescriptorSetLayoutBinding {
+                stages,
+                ..DescriptorSetLayoutBinding::descriptor_type(descriptor_type)
+            },
+        );
+        self.writes.retain(|w| w.binding() != binding);
+        self.writes.push(write);
+        self
+    }
+
+    pub fn layout(
+        &self,
+        cache: &mut DescriptorLayoutCache,
+        device: Arc<Device>,
+    ) -> Result<Arc<DescriptorSetLayout>, Validated<VulkanError>> {
+        cache.get_or_create(device, self.bindings.clone())
+    }
+
+    pub fn build(
+        self,
+        cache: &mut DescriptorLayoutCache,
+        allocator: &StandardDescriptorSetAllocator,
+    ) -> Result<Arc<PersistentDescriptorSet>, Validated<VulkanError>> {
+        let layout = cache.get_or_create(allocator.device().clone(), self.bindings)?;
+        PersistentDescriptorSet::new(allocator, layout, self.writes, [])
+    }
+}
+
+const SETS_PER_POOL: u32 = 32;
+
+/// Free sets keyed by layout address and variable descriptor count; the sets keep their
+/// layout alive, so an address cannot be reused while it has entries.
+type FreeSets = HashMap<(usize, u32), Vec<(DescriptorPoolAlloc, Arc<SharedPool>)>>;
+
+/// A `VkDescriptorPool` is externally synchronized; it is only allocated from under the
+/// `PoolState` lock and never reset or freed from while sets are alive.
+struct SharedPool(DescriptorPool);
+
+unsafe impl Sync for SharedPool {}
+
+#[derive(Default)]
+struct PoolState {
+    free: FreeSets,
+    pool_count: usize,
+}
+
+/// Descriptor set allocator that recycles sets instead of freeing them.
+///
+/// Sets are carved out of fixed-size pools, one layout per pool, and go back to a free list
+/// when the last reference to them is dropped. A set therefore has to be held, e.g. by the
+/// command buffers using it, until the device is done with it.
+pub struct DescriptorSetPool {
+    device: Arc<Device>,
+    state: Arc<Mutex<PoolState>>,
+}
+
+/// A descriptor set from a [`DescriptorSetPool`].
+pub type PooledDescriptorSet = PersistentDescriptorSet<PooledDescriptorSetAlloc>;
+
+impl DescriptorSetPool {
+    pub fn new(device: Arc<Device>) -> Self {
+        Self {
+            device,
+            state: Arc::default(),
+        }
+    }
+
+    /// Takes a free set compatible with `layout`, or allocates one, and writes `writes` to it.
+    pub fn acquire(
+        &self,
+        layout: Arc<DescriptorSetLayout>,
+        writes: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Result<Arc<PooledDescriptorSet>, Validated<VulkanError>> {
+        PersistentDescriptorSet::new(self, layout, writes, [])
+    }
+
+    /// How many `VkDescriptorPool`s have been created so far.
+    pub fn pool_count(&self) -> usize {
+        self.state.lock().unwrap().pool_count
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.state.lock().unwrap().free.values().map(Vec::len).sum()
+    }
+}
+
+unsafe impl DeviceOwned for DescriptorSetPool {
+    fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+}
+
+unsafe impl DescriptorSetAllocator for DescriptorSetPool {
+    type Alloc = PooledDescriptorSetAlloc;
+
+    fn allocate(
+        &self,
+        layout: &Arc<DescriptorSetLayout>,
+        variable_descriptor_count: u32,
+    ) -> Result<Self::Alloc, Validated<VulkanError>> {
+        let key = (Arc::as_ptr(layout) as usize, variable_descriptor_count);
+        let mut state = self.state.lock().unwrap();
+        let free = state.free.entry(key).or_default();
+        if free.is_empty() {
+            let pool = DescriptorPool::new(
+                self.device.clone(),
+                DescriptorPoolCreateInfo {
+                    max_sets: SETS_PER_POOL,
+                    pool_sizes: layout
+                        .descriptor_counts()
+                        .iter()
+                        .map(|(&ty, &count)| (ty, count * SETS_PER_POOL))
+                        .collect(),
+                    ..DescriptorPoolCreateInfo::default()
+                },
+            )?;
+            let pool = Arc::new(SharedPool(pool));
+            let info = DescriptorSetAllocateInfo {
+                variable_descriptor_count,
+                ..DescriptorSetAllocateInfo::new(layout.clone())
+            };
+            // SAFETY: the pool is sized for exactly `SETS_PER_POOL` sets of this layout, and
+            // every set keeps the pool alive.
+            let sets = unsafe {
+                pool.0
+                    .allocate_descriptor_sets((0..SETS_PER_POOL).map(|_| info.clone()))?
+            };
+            free.extend(sets.map(|set| (set, pool.clone())));
+            state.pool_count += 1;
+            debug!("created descriptor pool #{}", state.pool_count);
+        }
+
+        let (inner, pool) = state
+            .free
+            .get_mut(&key)
+            .and_then(Vec::pop)
+            .expect("free list was just refilled");
+        Ok(PooledDescriptorSetAlloc {
+            inner: ManuallyDrop::new(inner),
+            pool,
+            key,
+            state: self.state.clone(),
+        })
+    }
+}
+
+pub struct PooledDescriptorSetAlloc {
+    inner: ManuallyDrop<DescriptorPoolAlloc>,
+    pool: Arc<SharedPool>,
+    key: (usize, u32),
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl DescriptorSetAlloc for PooledDescriptorSetAlloc {
+    fn inner(&self) -> &DescriptorPoolAlloc {
+        &self.inner
+    }
+
+    fn pool(&self) -> &DescriptorPool {
+        &self.pool.0
+    }
+}
+
+impl Drop for PooledDescriptorSetAlloc {
+    fn drop(&mut self) {
+        // SAFETY: `inner` is not used again after being taken.
+        let inner = unsafe { ManuallyDrop::take(&mut self.inner) };
+        self.state
+            .lock()
+            .unwrap()
+            .free
+            .entry(self.key)
+            .or_default()
+            .push((inner, self.pool.clone()));
+    }
+}
+
+/// Contents and sets of one layout in a [`DescriptorStreamCache`].
+struct StreamRing {
+    /// Keeps the layout, and with it the address used as key, alive.
+    layout: Arc<DescriptorSetLayout>,
+    writes: Vec<WriteDescriptorSet>,
+    /// Bumped whenever `writes` changes.
+    generation: u64,
+    slots: Vec<Option<(u64, Arc<PooledDescriptorSet>)>>,
+}
+
+/// Descriptor sets for bindings that change every frame, such as animated materials or
+/// dynamic lights.
+///
+/// Every layout gets a ring of `ring_size` sets and frame `n` uses slot `n % ring_size`, so
+/// a set is not rewritten while an earlier frame may still read it as long as `ring_size` is
+/// at least the number of frames in flight. A slot is only rewritten when the layout's
+/// contents changed since it was last written, and the sets come from a
+/// [`DescriptorSetPool`], so steady-state updates do not allocate.
+pub struct DescriptorStreamCache {
+    pool: DescriptorSetPool,
+    ring_size: usize,
+    rings: HashMap<usize, StreamRing>,
+}
+
+impl DescriptorStreamCache {
+    pub fn new(device: Arc<Device>, ring_size: usize) -> Self {
+        assert!(ring_size > 0, "descriptor stream ring must not be empty");
+        Self {
+            pool: DescriptorSetPool::new(device),
+            ring_size,
+            rings: HashMap::new(),
+        }
+    }
+
+    pub fn ring_size(&self) -> usize {
+        self.ring_size
+    }
+
+    /// Slot of the ring used for `frame_index`.
+    pub fn slot(&self, frame_index: u64) -> usize {
+        (frame_index % self.ring_size as u64) as usize
+    }
+
+    /// The set for `frame_index` with `layout`, up to date with `writes` and everything
+    /// written for the layout before.
+    ///
+    /// `writes` replace earlier writes to the same binding and array element; an empty
+    /// iterator keeps the current contents.
+    pub fn get(
+        &mut self,
+        frame_index: u64,
+        layout: &Arc<DescriptorSetLayout>,
+        writes: impl IntoIterator<Item = WriteDescriptorSet>,
+    ) -> Result<Arc<PooledDescriptorSet>, Validated<VulkanError>> {
+        let slot = self.slot(frame_index);
+        let ring_size = self.ring_size;
+        let ring = self
+            .rings
+            .entry(Arc::as_ptr(layout) as usize)
+            .or_insert_with(|| StreamRing {
+                layout: layout.clone(),
+                writes: vec![],
+                generation: 0,
+                slots: vec![None; ring_size],
+            });
+
+        let mut changed = false;
+        for write in writes {
+            ring.writes.retain(|w| {
+                (w.binding(), w.first_array_element())
+                    != (write.binding(), write.first_array_element())
+            });
+            ring.writes.push(write);
+            changed = true;
+        }
+        if changed {
+            ring.generation += 1;
+        }
+
+        if let Some((generation, set)) = &ring.slots[slot] {
+            if *generation == ring.generation {
+                return Ok(set.clone());
+            }
+        }
+        let set = self
+            .pool
+            .acquire(ring.layout.clone(), ring.writes.iter().cloned())?;
+        ring.slots[slot] = Some((ring.generation, set.clone()));
+        Ok(set)
+    }
+
+    /// Drops the sets of every layout; they return to the pool once no longer in use.
+    pub fn clear(&mut self) {
+        self.rings.clear();
+    }
+
+    pub fn pool(&self) -> &DescriptorSetPool {
+        &self.pool
+    }
+}
+
+#[derive(Debug)]
+pub enum PushDescriptorError {
+    /// `VK_KHR_push_descriptor` is not enabled on the device.
+    ExtensionNotEnabled,
+    Validation(Box<ValidationError>),
+}
+
+impl Display for PushDescriptorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExtensionNotEnabled => write!(
+                f,
+                "push descriptors need the VK_KHR_push_descriptor device extension, \
+                 use persistent descriptor sets instead"
+            ),
+            Self::Validation(e) => write!(f, "failed to push descriptors: {e}"),
+        }
+    }
+}
+
+impl Error for PushDescriptorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ExtensionNotEnabled => None,
+            Self::Validation(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<Box<ValidationError>> for PushDescriptorError {
+    fn from(e: Box<ValidationError>) -> Self {
+        Self::Validation(e)
+    }
+}
+
+/// Writes descriptors straight into the command buffer with `VK_KHR_push_descriptor`.
+///
+/// The target set of the pipeline layout must have been created with
+/// `DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR`, see
+/// [`DescriptorLayoutCache::get_or_create_with_flags`].
+#[derive(Default)]
+pub struct PushDescriptorWriter {
+    writes: Vec<WriteDescriptorSet>,
+}
+
+impl PushDescriptorWriter {
+    /// Whether the device can be created with push descriptor support.
+    pub fn is_supported(device: &Device) -> bool {
+        device
+            .physical_device()
+            .supported_extensions()
+            .khr_push_descriptor
+    }
+
+    pub fn new(device: &Device) -> Result<Self, PushDescriptorError> {
+        if !device.enabled_extensions().khr_push_descriptor {
+            return Err(PushDescriptorError::ExtensionNotEnabled);
+        }
+        Ok(Self::default())
+    }
+
+    pub fn uniform_buffer<T: ?Sized>(mut self, binding: u32, buffer: Subbuffer<T>) -> Self {
+        self.writes
+            .push(WriteDescriptorSet::buffer(binding, buffer.into_bytes()));
+        self
+    }
+
+    pub fn combined_image_sampler(
+        mut self,
+        binding: u32,
+        view: Arc<ImageView>,
+        sampler: Arc<Sampler>,
+    ) -> Self {
+        self.writes.push(WriteDescriptorSet::image_view_sampler(
+            binding, view, sampler,
+        ));
+        self
+    }
+
+    pub fn push(
+        self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline_bind_point: PipelineBindPoint,
+        layout: Arc<PipelineLayout>,
+        set_index: u32,
+    ) -> Result<(), PushDescriptorError> {
+        builder.push_descriptor_set(
+            pipeline_bind_point,
+            layout,
+            set_index,
+            self.writes.into_iter().collect(),
+        )?;
+        Ok(())
+    }
+}
+
+/// Mirrors the push constant block of `shader/bindless.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct BindlessParams {
+    pub view_proj: [[f32; 4]; 4],
+}
+
+#[derive(Debug)]
+pub enum BindlessError {
+    /// The device was created without one of [`BindlessTextureArray::REQUIRED_FEATURES`].
+    FeatureNotEnabled,
+    Vulkan(Validated<VulkanError>),
+}
+
+impl Display for BindlessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FeatureNotEnabled => write!(
+                f,
+                "bindless textures need the descriptor indexing features to be enabled"
+            ),
+            Self::Vulkan(e) => write!(f, "failed to create the bindless texture array: {e}"),
+        }
+    }
+}
+
+impl Error for BindlessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::FeatureNotEnabled => None,
+            Self::Vulkan(e) => Some(e),
+        }
+    }
+}
+
+impl From<Validated<VulkanError>> for BindlessError {
+    fn from(e: Validated<VulkanError>) -> Self {
+        Self::Vulkan(e)
+    }
+}
+
+/// All textures of a scene behind one descriptor, indexed in the shader by a per-instance
+/// [`TextureInstance`](crate::vertex::TextureInstance), see `shader/bindless.frag`.
+///
+/// Indices are handed out in registration order and never reused. The set is rebuilt with the
+/// current textures by the first `descriptor_set` call after a registration.
+pub struct BindlessTextureArray {
+    layout: Arc<DescriptorSetLayout>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    capacity: u32,
+    textures: Vec<(Arc<ImageView>, Arc<Sampler>)>,
+    descriptor_set: Option<Arc<PersistentDescriptorSet>>,
+}
+
+impl BindlessTextureArray {
+    pub const REQUIRED_FEATURES: Features = Features {
+        runtime_descriptor_array: true,
+        descriptor_binding_partially_bound: true,
+        descriptor_binding_variable_descriptor_count: true,
+        shader_sampled_image_array_non_uniform_indexing: true,
+        ..Features::empty()
+    };
+
+    /// Whether the device can be created with [`Self::REQUIRED_FEATURES`].
+    pub fn is_supported(device: &Device) -> bool {
+        device
+            .physical_device()
+            .supported_features()
+            .contains(&Self::REQUIRED_FEATURES)
+    }
+
+    /// Creates the layout of set 0 of `shader/bindless.frag` with room for `capacity` textures.
+    pub fn new(device: Arc<Device>, capacity: u32) -> Result<Self, BindlessError> {
+        if !device.enabled_features().contains(&Self::REQUIRED_FEATURES) {
+            return Err(BindlessError::FeatureNotEnabled);
+        }
+        let layout = DescriptorSetLayout::new(
+            device.clone(),
+            DescriptorSetLayoutCreateInfo {
+                bindings: [(
+                    0,
+                    DescriptorSetLayoutBinding {
+                        binding_flags: DescriptorBindingFlags::PARTIALLY_BOUND
+                            | DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
+                        descriptor_count: capacity,
+                        stages: ShaderStages::FRAGMENT,
+                        ..DescriptorSetLayoutBinding::descriptor_type(
+                            DescriptorType::CombinedImageSampler,
+                        )
+                    },
+                )]
+                .into(),
+                ..DescriptorSetLayoutCreateInfo::default()
+            },
+        )?;
+        debug!("bindless texture layout: {layout:?}");
+        Ok(Self {
+            layout,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device,
+                Default::default(),
+            ),
+            capacity,
+            textures: vec![],
+            descriptor_set: None,
+        })
+    }
+
+    pub fn layout(&self) -> &Arc<DescriptorSetLayout> {
+        &self.layout
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    /// Adds a texture and returns its index in the shader array. Panics when full.
+    pub fn register(&mut self, view: Arc<ImageView>, sampler: Arc<Sampler>) -> u32 {
+        let index = self.textures.len() as u32;
+        assert!(
+            index < self.capacity,
+            "bindless texture array of {capacity} textures is full",
+            capacity = self.capacity
+        );
+        self.textures.push((view, sampler));
+        self.descriptor_set = None;
+        index
+    }
+
+    pub fn get(&self, index: u32) -> Option<&(Arc<ImageView>, Arc<Sampler>)> {
+        self.textures.get(index as usize)
+    }
+
+    /// The set to bind at set 0, sized to the registered textures.
+    pub fn descriptor_set(
+        &mut self,
+    ) -> Result<Arc<PersistentDescriptorSet>, Validated<VulkanError>> {
+        if let Some(descriptor_set) = &self.descriptor_set {
+            return Ok(descriptor_set.clone());
+        }
+        let writes = (!self.textures.is_empty()).then(|| {
+            WriteDescriptorSet::image_view_sampler_array(0, 0, self.textures.iter().cloned())
+        });
+        let descriptor_set = PersistentDescriptorSet::new_variable(
+            &self.descriptor_set_allocator,
+            self.layout.clone(),
+            self.textures.len() as u32,
+            writes,
+            [],
+        )?;
+        debug!(
+            "rebuilt bindless texture set with {count} textures",
+            count = self.textures.len()
+        );
+        self.descriptor_set = Some(descriptor_set.clone());
+        Ok(descriptor_set)
+    }
+}
+
+/// Filtering of the sampler baked into an [`ImmutableSamplerLayout`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SamplerConfig {
+    Nearest,
+    Linear,
+    /// Linear filtering with up to the given anisotropy; needs the `sampler_anisotropy`
+    /// feature.
+    Anisotropic(f32),
+}
+
+impl SamplerConfig {
+    fn create_info(self) -> SamplerCreateInfo {
+        match self {
+            Self::Nearest => SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                mipmap_mode: SamplerMipmapMode::Nearest,
+                ..SamplerCreateInfo::simple_repeat_linear()
+            },
+            Self::Linear => SamplerCreateInfo::simple_repeat_linear(),
+            Self::Anisotropic(max_anisotropy) => SamplerCreateInfo {
+                anisotropy: Some(max_anisotropy),
+                ..SamplerCreateInfo::simple_repeat_linear()
+            },
+        }
+    }
+}
+
+/// Layout with a single combined image sampler whose sampler is part of the layout, so sets
+/// allocated from it only bind image views.
+///
+/// Materials sharing one filtering mode then need neither a sampler of their own nor a
+/// descriptor update for it.
+pub struct ImmutableSamplerLayout {
+    layout: Arc<DescriptorSetLayout>,
+    sampler: Arc<Sampler>,
+    binding: u32,
+}
+
+impl ImmutableSamplerLayout {
+    /// Creates the layout with the sampler at `binding`, visible to fragment shaders.
+    pub fn new(
+        device: Arc<Device>,
+        sampler_config: SamplerConfig,
+        binding: u32,
+    ) -> Result<Self, Validated<VulkanError>> {
+        let sampler = Sampler::new(device, sampler_config.create_info())?;
+        Self::with_sampler(sampler, binding)
+    }
+
+    /// Creates the layout around an existing sampler, e.g. one with a YCbCr conversion that
+    /// can only be used as an immutable sampler, see
+    /// [`YcbcrTexture`](crate::texture::YcbcrTexture).
+    pub fn with_sampler(
+        sampler: Arc<Sampler>,
+        binding: u32,
+    ) -> Result<Self, Validated<VulkanError>> {
+        let layout = DescriptorSetLayout::new(
+            sampler.device().clone(),
+            DescriptorSetLayoutCreateInfo {
+                bindings: [(
+                    binding,
+                    DescriptorSetLayoutBinding {
+                        stages: ShaderStages::FRAGMENT,
+                        immutable_samplers: vec![sampler.clone()],
+                        ..DescriptorSetLayoutBinding::descriptor_type(
+                            DescriptorType::CombinedImageSampler,
+                        )
+                    },
+                )]
+                .into(),
+                ..DescriptorSetLayoutCreateInfo::default()
+            },
+        )?;
+        debug!("immutable sampler layout: {layout:?}");
+        Ok(Self {
+            layout,
+            sampler,
+            binding,
+        })
+    }
+
+    pub fn with_nearest(device: Arc<Device>, binding: u32) -> Result<Self, Validated<VulkanError>> {
+        Self::new(device, SamplerConfig::Nearest, binding)
+    }
+
+    pub fn with_linear(device: Arc<Device>, binding: u32) -> Result<Self, Validated<VulkanError>> {
+        Self::new(device, SamplerConfig::Linear, binding)
+    }
+
+    pub fn with_anisotropic(
+        device: Arc<Device>,
+        binding: u32,
+        max_anisotropy: f32,
+    ) -> Result<Self, Validated<VulkanError>> {
+        Self::new(device, SamplerConfig::Anisotropic(max_anisotropy), binding)
+    }
+
+    pub fn layout(&self) -> &Arc<DescriptorSetLayout> {
+        &self.layout
+    }
+
+    pub fn sampler(&self) -> &Arc<Sampler> {
+        &self.sampler
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    /// A set sampling `view` with the baked-in sampler.
+    pub fn descriptor_set(
+        &self,
+        allocator: &StandardDescriptorSetAllocator,
+        view: Arc<ImageView>,
+    ) -> Result<Arc<PersistentDescriptorSet>, Validated<VulkanError>> {
+        PersistentDescriptorSet::new(
+            allocator,
+            self.layout.clone(),
+            [WriteDescriptorSet::image_view(self.binding, view)],
+            [],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocatorCreateInfo;
+    use vulkano::descriptor_set::DescriptorSet;
+    use vulkano::device::DeviceExtensions;
+    use vulkano::format::Format;
+    use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+    use vulkano::pipeline::layout::PipelineLayoutCreateInfo;
+
+    fn uniform(context: &TestContext, value: u32) -> Subbuffer<u32> {
+        Buffer::from_data(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            value,
+        )
+        .unwrap()
+    }
+
+    fn uniform_binding(stages: ShaderStages) -> BTreeMap<u32, DescriptorSetLayoutBinding> {
+        [(
+            0,
+            DescriptorSetLayoutBinding {
+                stages,
+                ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::UniformBuffer)
+            },
+        )]
+        .into()
+    }
+
+    #[test]
+    fn layout_keys_compare_the_bindings() {
+        let flags = DescriptorSetLayoutCreateFlags::empty();
+        let vertex = LayoutKey::new(flags, &uniform_binding(ShaderStages::VERTEX));
+        assert_eq!(
+            vertex,
+            LayoutKey::new(flags, &uniform_binding(ShaderStages::VERTEX))
+        );
+        assert_ne!(
+            vertex,
+            LayoutKey::new(flags, &uniform_binding(ShaderStages::FRAGMENT))
+        );
+        assert_ne!(
+            vertex,
+            LayoutKey::new(
+                DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR,
+                &uniform_binding(ShaderStages::VERTEX)
+            )
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn identical_builders_share_the_cached_layout() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let mut cache = DescriptorLayoutCache::new();
+        let builder = |value| {
+            DescriptorSetBuilder::new().uniform_buffer(
+                0,
+                ShaderStages::VERTEX,
+                uniform(&context, value),
+            )
+        };
+
+        let first = builder(1).layout(&mut cache, device.clone()).unwrap();
+        let second = builder(2).layout(&mut cache, device.clone()).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+
+        let fragment = DescriptorSetBuilder::new()
+            .uniform_buffer(0, ShaderStages::FRAGMENT, uniform(&context, 3))
+            .layout(&mut cache, device)
+            .unwrap();
+        assert!(!Arc::ptr_eq(&first, &fragment));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn recycled_sets_do_not_create_pools() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let layout = DescriptorLayoutCache::new()
+            .get_or_create(device.clone(), uniform_binding(ShaderStages::VERTEX))
+            .unwrap();
+        let pool = DescriptorSetPool::new(device);
+        let buffer = uniform(&context, 0);
+
+        let first = pool
+            .acquire(
+                layout.clone(),
+                [WriteDescriptorSet::buffer(0, buffer.clone())],
+            )
+            .unwrap();
+        drop(first);
+        assert_eq!(pool.pool_count(), 1);
+        assert_eq!(pool.free_count(), SETS_PER_POOL as usize);
+
+        for _ in 0..1000 {
+            let set = pool
+                .acquire(
+                    layout.clone(),
+                    [WriteDescriptorSet::buffer(0, buffer.clone())],
+                )
+                .unwrap();
+            assert_eq!(pool.free_count(), SETS_PER_POOL as usize - 1);
+            drop(set);
+        }
+        assert_eq!(pool.pool_count(), 1);
+        assert_eq!(pool.free_count(), SETS_PER_POOL as usize);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn push_descriptors_are_recorded() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions {
+                khr_push_descriptor: true,
+                ..DeviceExtensions::empty()
+            },
+            Features::empty(),
+        );
+        let device = context.queue.device().clone();
+        let set_layout = DescriptorLayoutCache::new()
+            .get_or_create_with_flags(
+                device.clone(),
+                DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR,
+                uniform_binding(ShaderStages::COMPUTE),
+            )
+            .unwrap();
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineLayoutCreateInfo {
+                set_layouts: vec![set_layout],
+                ..PipelineLayoutCreateInfo::default()
+            },
+        )
+        .unwrap();
+
+        let mut builder = context.command_buffer();
+        PushDescriptorWriter::new(&device)
+            .unwrap()
+            .uniform_buffer(0, uniform(&context, 7))
+            .push(&mut builder, PipelineBindPoint::Compute, layout, 0)
+            .unwrap();
+        context.submit(builder);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn push_descriptors_need_the_extension() {
+        let context = TestContext::new();
+        let device = context.queue.device();
+        if !device.enabled_extensions().khr_push_descriptor {
+            assert!(matches!(
+                PushDescriptorWriter::new(device),
+                Err(PushDescriptorError::ExtensionNotEnabled)
+            ));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn stream_ring_wraps_and_rewrites_changed_slots() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let layout = DescriptorLayoutCache::new()
+            .get_or_create(
+                device.clone(),
+                [(
+                    0,
+                    DescriptorSetLayoutBinding {
+                        stages: ShaderStages::FRAGMENT,
+                        ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::UniformBuffer)
+                    },
+                )]
+                .into(),
+            )
+            .unwrap();
+        let mut cache = DescriptorStreamCache::new(device, 2);
+        assert_eq!(cache.slot(0), cache.slot(2));
+        assert_ne!(cache.slot(1), cache.slot(2));
+
+        let write = |value| WriteDescriptorSet::buffer(0, uniform(&context, value));
+        let frame_0 = cache.get(0, &layout, [write(0)]).unwrap();
+        let frame_1 = cache.get(1, &layout, []).unwrap();
+        assert!(!Arc::ptr_eq(&frame_0, &frame_1));
+        // unchanged contents reuse the slot's set
+        let frame_2 = cache.get(2, &layout, []).unwrap();
+        assert!(Arc::ptr_eq(&frame_0, &frame_2));
+
+        // new contents rewrite the slot in use, leaving the other frame's set alone
+        let frame_3 = cache.get(3, &layout, [write(3)]).unwrap();
+        assert!(!Arc::ptr_eq(&frame_1, &frame_3));
+        let frame_4 = cache.get(4, &layout, []).unwrap();
+        assert!(!Arc::ptr_eq(&frame_2, &frame_4));
+        assert!(!Arc::ptr_eq(&frame_3, &frame_4));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn registered_textures_are_found_by_index() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions::empty(),
+            BindlessTextureArray::REQUIRED_FEATURES,
+        );
+        let device = context.queue.device().clone();
+        let sampler =
+            Sampler::new(device.clone(), SamplerCreateInfo::simple_repeat_linear()).unwrap();
+        let mut textures = BindlessTextureArray::new(device, 128).unwrap();
+        let views: Vec<_> = (0..100)
+            .map(|_| {
+                let image = Image::new(
+                    context.memory_allocator.clone(),
+                    ImageCreateInfo {
+                        format: Format::R8G8B8A8_UNORM,
+                        extent: [4, 4, 1],
+                        usage: ImageUsage::SAMPLED,
+                        ..ImageCreateInfo::default()
+                    },
+                    AllocationCreateInfo::default(),
+                )
+                .unwrap();
+                ImageView::new_default(image).unwrap()
+            })
+            .collect();
+
+        for (expected, view) in views.iter().enumerate() {
+            let index = textures.register(view.clone(), sampler.clone());
+            assert_eq!(index, expected as u32);
+        }
+        assert_eq!(textures.len(), 100);
+        for (index, view) in views.iter().enumerate() {
+            let (registered, _) = textures.get(index as u32).unwrap();
+            assert!(Arc::ptr_eq(registered, view));
+        }
+        assert!(textures.get(100).is_none());
+
+        let set = textures.descriptor_set().unwrap();
+        assert_eq!(set.variable_descriptor_count(), 100);
+        assert!(Arc::ptr_eq(&set, &textures.descriptor_set().unwrap()));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn immutable_sampler_sets_bind_only_the_image_view() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let layout = ImmutableSamplerLayout::with_nearest(device.clone(), 0).unwrap();
+        let binding = &layout.layout().bindings()[&0];
+        assert_eq!(binding.immutable_samplers.len(), 1);
+        assert!(Arc::ptr_eq(
+            &binding.immutable_samplers[0],
+            layout.sampler()
+        ));
+
+        let image = Image::new(
+            context.memory_allocator.clone(),
+            ImageCreateInfo {
+                format: Format::R8G8B8A8_UNORM,
+                extent: [4, 4, 1],
+                usage: ImageUsage::SAMPLED,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+        let allocator = StandardDescriptorSetAllocator::new(
+            device,
+            StandardDescriptorSetAllocatorCreateInfo::default(),
+        );
+        // no sampler is written at binding 0
+        layout
+            .descriptor_set(&allocator, ImageView::new_default(image).unwrap())
+            .unwrap();
+    }
+}
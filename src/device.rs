@@ -0,0 +1,504 @@
+use crate::error::{Context, ThorusError};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use tracing::{error, warn};
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::device::{Device, DeviceExtensions, Features, QueueFlags};
+use vulkano::instance::Instance;
+use vulkano::swapchain::Surface;
+use vulkano::{Version, VulkanError};
+
+/// Lowest Vulkan version a device must support, together with the functionality that is
+/// required rather than optional at that version.
+#[derive(
+    Serialize, Deserialize, ValueEnum, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug,
+)]
+pub enum FeatureSet {
+    /// Vulkan 1.0; everything newer is optional.
+    #[serde(rename = "minimum")]
+    #[value(name = "minimum")]
+    Minimum,
+    #[serde(rename = "1.1")]
+    #[value(name = "1.1")]
+    V1_1,
+    /// Requires timeline semaphores.
+    #[serde(rename = "1.2")]
+    #[value(name = "1.2")]
+    V1_2,
+    /// Requires timeline semaphores, dynamic rendering and synchronization2.
+    #[default]
+    #[serde(rename = "1.3")]
+    #[value(name = "1.3")]
+    V1_3,
+}
+
+/// Optional functionality that ended up enabled on a device, either from core or through its
+/// extension.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct DeviceCapabilities {
+    pub dynamic_rendering: bool,
+    pub timeline_semaphore: bool,
+    pub synchronization2: bool,
+}
+
+impl FeatureSet {
+    pub fn min_api_version(self) -> Version {
+        match self {
+            Self::Minimum => Version::V1_0,
+            Self::V1_1 => Version::V1_1,
+            Self::V1_2 => Version::V1_2,
+            Self::V1_3 => Version::V1_3,
+        }
+    }
+
+    /// Whether `physical_device` is new enough and has everything this set requires.
+    pub fn supports(self, physical_device: &PhysicalDevice) -> bool {
+        if physical_device.api_version() < self.min_api_version() {
+            return false;
+        }
+        let (_, _, capabilities) = self.device_setup(physical_device);
+        let required = self.required();
+        (!required.dynamic_rendering || capabilities.dynamic_rendering)
+            && (!required.timeline_semaphore || capabilities.timeline_semaphore)
+            && (!required.synchronization2 || capabilities.synchronization2)
+    }
+
+    /// Extensions and features to enable on `physical_device`, plus what they provide.
+    ///
+    /// Functionality is taken from core where the device's version has it and from the
+    /// corresponding extension otherwise, as long as the extension's own dependencies are core
+    /// at that version.
+    pub fn device_setup(
+        self,
+        physical_device: &PhysicalDevice,
+    ) -> (DeviceExtensions, Features, DeviceCapabilities) {
+        let version = physical_device.api_version();
+        let supported = physical_device.supported_extensions();
+        let available = physical_device.supported_features();
+        let mut extensions = DeviceExtensions::empty();
+        let mut features = Features::empty();
+
+        let dynamic_rendering = available.dynamic_rendering
+            && (version >= Version::V1_3
+                || version >= Version::V1_2 && supported.khr_dynamic_rendering);
+        if dynamic_rendering {
+            extensions.khr_dynamic_rendering = version < Version::V1_3;
+            features.dynamic_rendering = true;
+        }
+
+        let timeline_semaphore = available.timeline_semaphore
+            && (version >= Version::V1_2
+                || version >= Version::V1_1 && supported.khr_timeline_semaphore);
+        if timeline_semaphore {
+            extensions.khr_timeline_semaphore = version < Version::V1_2;
+            features.timeline_semaphore = true;
+        }
+
+        let synchronization2 = available.synchronization2
+            && (version >= Version::V1_3
+                || version >= Version::V1_1 && supported.khr_synchronization2);
+        if synchronization2 {
+            extensions.khr_synchronization2 = version < Version::V1_3;
+            features.synchronization2 = true;
+        }
+
+        let capabilities = DeviceCapabilities {
+            dynamic_rendering,
+            timeline_semaphore,
+            synchronization2,
+        };
+        (extensions, features, capabilities)
+    }
+
+    fn required(self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            dynamic_rendering: self >= Self::V1_3,
+            timeline_semaphore: self >= Self::V1_2,
+            synchronization2: self >= Self::V1_3,
+        }
+    }
+}
+
+/// Picks a device that supports `feature_set` and `device_extensions` and has a graphics queue
+/// family, which must also be able to present to `surface` if one is given.
+///
+/// Software and virtual devices are preferred when the `CI` environment variable is set,
+/// hardware ones otherwise. Portability subset devices such as MoltenVK are included when the
+/// instance lists them, see
+/// [`InstanceBuilder::with_portability`](crate::instance::InstanceBuilder::with_portability).
+pub fn select_physical_device(
+    instance: &Arc<Instance>,
+    surface: Option<&Arc<Surface>>,
+    device_extensions: &DeviceExtensions,
+    feature_set: FeatureSet,
+) -> Result<(Arc<PhysicalDevice>, u32), ThorusError> {
+    let ci = env::var_os("CI").is_some();
+    instance
+        .enumerate_physical_devices()
+        .context("could not enumerate physical devices")?
+        .filter(|d| feature_set.supports(d))
+        .filter(|d| d.supported_extensions().contains(device_extensions))
+        .filter_map(|d| {
+            d.queue_family_properties()
+                .iter()
+                .enumerate()
+                .filter(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+                .map(|(i, _)| i as u32)
+                .find(|&i| surface.is_none_or(|s| d.surface_support(i, s).unwrap_or(false)))
+                .map(|i| (d, i))
+        })
+        .min_by_key(|(d, _)| device_rank(d.properties().device_type, ci))
+        .ok_or(ThorusError::Missing("physical device"))
+}
+
+/// Lower is better, see [`select_physical_device`].
+fn device_rank(device_type: PhysicalDeviceType, ci: bool) -> u32 {
+    match (device_type, ci) {
+        (PhysicalDeviceType::VirtualGpu | PhysicalDeviceType::Cpu, true) => 0,
+        (PhysicalDeviceType::DiscreteGpu, _) => 1,
+        (PhysicalDeviceType::IntegratedGpu, _) => 2,
+        (PhysicalDeviceType::VirtualGpu, _) => 3,
+        (PhysicalDeviceType::Cpu, _) => 4,
+        _ => 5,
+    }
+}
+
+/// Whether `VulkanError::DeviceLost` is anywhere in the source chain of `error`.
+pub fn is_device_lost(error: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(VulkanError::DeviceLost) = error.downcast_ref::<VulkanError>() {
+            return true;
+        }
+        current = error.source();
+    }
+    false
+}
+
+/// Rebuilds everything that depends on the device after `VulkanError::DeviceLost`.
+///
+/// Recoveries are counted until [`reset`](Self::reset) is called after a frame that worked, so
+/// a device that keeps getting lost ends in an error instead of an endless loop.
+#[derive(Debug)]
+pub struct DeviceLostRecovery {
+    max_attempts: u32,
+    attempts: u32,
+}
+
+impl DeviceLostRecovery {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            attempts: 0,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Forgets previous recoveries; call after every successful frame.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Recovers from `error` if it is a lost device, otherwise returns it unchanged.
+    ///
+    /// Waits for `device` to become idle, which usually fails again on a lost device and is
+    /// only logged, and then calls `reinitialize` until it succeeds, fails with another error,
+    /// or the attempts are used up. `reinitialize` is expected to create a new device and
+    /// everything on it; objects of the lost device must be dropped before it is called if
+    /// they hold exclusive resources such as the surface's swapchain.
+    pub fn recover<T>(
+        &mut self,
+        error: ThorusError,
+        device: &Device,
+        reinitialize: impl FnMut() -> Result<T, ThorusError>,
+    ) -> Result<T, ThorusError> {
+        // Safety: nothing else submits to the device's queues while the renderer recovers.
+        self.recover_with(error, || unsafe { device.wait_idle() }, reinitialize)
+    }
+
+    fn recover_with<T>(
+        &mut self,
+        error: ThorusError,
+        wait_idle: impl FnOnce() -> Result<(), VulkanError>,
+        mut reinitialize: impl FnMut() -> Result<T, ThorusError>,
+    ) -> Result<T, ThorusError> {
+        if !is_device_lost(&error) {
+            return Err(error);
+        }
+        error!("device lost: {error}");
+        if let Err(e) = wait_idle() {
+            warn!("failed to wait for the lost device: {e}");
+        }
+
+        let mut error = error;
+        while self.attempts < self.max_attempts {
+            self.attempts += 1;
+            warn!(
+                "reinitializing the device (attempt {}/{})",
+                self.attempts, self.max_attempts
+            );
+            match reinitialize() {
+                Ok(value) => return Ok(value),
+                Err(e) if is_device_lost(&e) => {
+                    error!("device lost again while reinitializing: {e}");
+                    error = e;
+                }
+                Err(e) => return Err(e.context("failed to reinitialize after device loss")),
+            }
+        }
+        Err(error.context(&format!(
+            "device still lost after {} recoveries",
+            self.max_attempts
+        )))
+    }
+}
+
+/// Reports `VulkanError::DeviceLost` once at a chosen frame, to exercise
+/// [`DeviceLostRecovery`] without a misbehaving driver.
+#[derive(Debug)]
+pub struct DeviceLostSimulator {
+    frame: u64,
+    lose_at_frame: u64,
+}
+
+impl DeviceLostSimulator {
+    pub fn new(lose_at_frame: u64) -> Self {
+        Self {
+            frame: 0,
+            lose_at_frame,
+        }
+    }
+
+    /// Counts a frame and fails if it is the chosen one.
+    pub fn next_frame(&mut self) -> Result<(), VulkanError> {
+        self.frame += 1;
+        if self.frame == self.lose_at_frame {
+            Err(VulkanError::DeviceLost)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::InstanceBuilder;
+    use crate::shader::load_vertex;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use vulkano::device::{DeviceCreateInfo, QueueCreateInfo};
+    use vulkano::{Validated, VulkanLibrary};
+
+    /// Stands in for a device and everything on it. Like a swapchain, it holds the window
+    /// exclusively while it lives.
+    struct MockRenderer {
+        simulator: DeviceLostSimulator,
+        _window: Rc<()>,
+    }
+
+    impl MockRenderer {
+        fn new(window: &Rc<()>, lose_at_frame: u64) -> Result<Self, ThorusError> {
+            if Rc::strong_count(window) > 1 {
+                return Err(VulkanError::NativeWindowInUse.into());
+            }
+            Ok(Self {
+                simulator: DeviceLostSimulator::new(lose_at_frame),
+                _window: window.clone(),
+            })
+        }
+
+        fn draw_frame(&mut self) -> Result<(), ThorusError> {
+            Ok(self.simulator.next_frame()?)
+        }
+    }
+
+    fn lost() -> ThorusError {
+        ThorusError::from(VulkanError::DeviceLost).context("failed to submit frame")
+    }
+
+    #[test]
+    fn simulator_loses_the_device_once_at_the_chosen_frame() {
+        let mut simulator = DeviceLostSimulator::new(3);
+        let frames: Vec<_> = (0..5).map(|_| simulator.next_frame()).collect();
+        assert_eq!(
+            frames,
+            [Ok(()), Ok(()), Err(VulkanError::DeviceLost), Ok(()), Ok(())]
+        );
+    }
+
+    #[test]
+    fn device_lost_is_found_in_the_source_chain() {
+        assert!(is_device_lost(&lost()));
+        assert!(is_device_lost(&ThorusError::from(Validated::Error(
+            VulkanError::DeviceLost
+        ))));
+        assert!(!is_device_lost(&ThorusError::from(VulkanError::OutOfDate)));
+        assert!(!is_device_lost(&ThorusError::Missing("physical device")));
+    }
+
+    #[test]
+    fn renderer_is_recreated_after_a_simulated_device_loss() {
+        let mut recovery = DeviceLostRecovery::new(3);
+        let window = Rc::new(());
+        let mut renderer = MockRenderer::new(&window, 2).unwrap();
+        let created = Cell::new(0);
+        let waited = Cell::new(false);
+
+        renderer.draw_frame().unwrap();
+        recovery.reset();
+        let error = renderer.draw_frame().unwrap_err();
+        drop(renderer);
+        let mut renderer = recovery
+            .recover_with(
+                error,
+                || {
+                    waited.set(true);
+                    Err(VulkanError::DeviceLost)
+                },
+                || {
+                    created.set(created.get() + 1);
+                    MockRenderer::new(&window, 0)
+                },
+            )
+            .unwrap();
+        assert!(waited.get());
+        assert_eq!(created.get(), 1);
+        assert_eq!(recovery.attempts(), 1);
+
+        renderer.draw_frame().unwrap();
+        recovery.reset();
+        assert_eq!(recovery.attempts(), 0);
+    }
+
+    #[test]
+    fn reinitializing_is_retried_while_the_device_stays_lost() {
+        let mut recovery = DeviceLostRecovery::new(3);
+        let mut failures = 2;
+        let value = recovery
+            .recover_with(
+                lost(),
+                || Ok(()),
+                || {
+                    if failures > 0 {
+                        failures -= 1;
+                        Err(lost())
+                    } else {
+                        Ok(42)
+                    }
+                },
+            )
+            .unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(recovery.attempts(), 3);
+    }
+
+    #[test]
+    fn recovery_gives_up_after_the_attempts() {
+        let mut recovery = DeviceLostRecovery::new(2);
+        let error = recovery
+            .recover_with(lost(), || Ok(()), || Err::<(), _>(lost()))
+            .unwrap_err();
+        assert!(is_device_lost(&error));
+        assert!(error.to_string().contains("after 2 recoveries"));
+        assert_eq!(recovery.attempts(), 2);
+    }
+
+    #[test]
+    fn other_errors_are_not_recovered() {
+        let mut recovery = DeviceLostRecovery::new(3);
+        let error = recovery
+            .recover_with(
+                ThorusError::from(VulkanError::OutOfHostMemory),
+                || panic!("waited for a device that is not lost"),
+                || -> Result<(), _> { panic!("reinitialized a device that is not lost") },
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ThorusError::Vulkan(VulkanError::OutOfHostMemory)
+        ));
+    }
+
+    #[test]
+    fn recovery_fails_while_the_lost_renderer_holds_the_window() {
+        let mut recovery = DeviceLostRecovery::new(3);
+        let window = Rc::new(());
+        let mut renderer = MockRenderer::new(&window, 1).unwrap();
+        let error = renderer.draw_frame().unwrap_err();
+        let error = recovery
+            .recover_with(error, || Ok(()), || MockRenderer::new(&window, 0))
+            .err()
+            .unwrap();
+        assert!(!is_device_lost(&error));
+        assert_eq!(recovery.attempts(), 1);
+    }
+
+    #[test]
+    fn newer_sets_require_more() {
+        assert_eq!(FeatureSet::default(), FeatureSet::V1_3);
+        assert_eq!(
+            FeatureSet::Minimum.required(),
+            DeviceCapabilities::default()
+        );
+        assert!(FeatureSet::V1_2.required().timeline_semaphore);
+        assert!(!FeatureSet::V1_2.required().dynamic_rendering);
+        assert_eq!(
+            FeatureSet::V1_3.required(),
+            DeviceCapabilities {
+                dynamic_rendering: true,
+                timeline_semaphore: true,
+                synchronization2: true,
+            }
+        );
+        let versions = [
+            FeatureSet::Minimum,
+            FeatureSet::V1_1,
+            FeatureSet::V1_2,
+            FeatureSet::V1_3,
+        ]
+        .map(FeatureSet::min_api_version);
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn minimum_selects_a_device_on_a_vulkan_1_1_instance() {
+        let instance = InstanceBuilder::new(VulkanLibrary::new().unwrap())
+            .max_api_version(Version::V1_1)
+            .build()
+            .unwrap();
+        let (physical_device, queue_family_index) = select_physical_device(
+            &instance,
+            None,
+            &DeviceExtensions::empty(),
+            FeatureSet::Minimum,
+        )
+        .unwrap();
+        assert!(physical_device.api_version() <= Version::V1_1);
+        assert!(!FeatureSet::V1_2.supports(&physical_device));
+
+        let (extensions, features, _) = FeatureSet::Minimum.device_setup(&physical_device);
+        let (device, _) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..QueueCreateInfo::default()
+                }],
+                enabled_extensions: extensions
+                    .union(&InstanceBuilder::device_extensions(&physical_device)),
+                enabled_features: features,
+                ..DeviceCreateInfo::default()
+            },
+        )
+        .unwrap();
+        load_vertex(device).unwrap();
+    }
+}
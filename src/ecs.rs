@@ -0,0 +1,242 @@
+use crate::light::{LightBuffer, PointLight};
+use crate::math::{Mat4, Quat};
+use crate::renderer::{DrawEntry, IndirectRenderer};
+use hecs::{Entity, World};
+use vulkano::sync::HostAccessError;
+
+/// Placement of an entity in world space.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation: Quat,
+    pub scale: [f32; 3],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: Quat::identity(),
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    /// Column-major matrix scaling, then rotating, then translating.
+    pub fn matrix(&self) -> [[f32; 4]; 4] {
+        (Mat4::translate(self.translation)
+            * Mat4::from_quat(self.rotation)
+            * Mat4::scale(self.scale))
+        .into()
+    }
+}
+
+/// Index of a mesh added to the [`IndirectRenderer`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MeshHandle(pub usize);
+
+/// Index of the material an entity is drawn with.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialHandle(pub usize);
+
+/// Whether the systems pick up an entity.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    Hidden,
+}
+
+impl Visibility {
+    pub fn is_visible(self) -> bool {
+        self == Self::Visible
+    }
+}
+
+/// Entities of a scene, stored by component in a [`hecs::World`].
+///
+/// Entities are drawn by [`render_system`] when they have a [`Transform`], a [`MeshHandle`]
+/// and a visible [`Visibility`], and lit by the ones [`light_gather_system`] finds with a
+/// [`Transform`] and a [`PointLight`].
+#[derive(Default)]
+pub struct ThorusWorld {
+    world: World,
+}
+
+impl ThorusWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Spawns a visible entity drawing `mesh` with `material`.
+    pub fn spawn_mesh(
+        &mut self,
+        transform: Transform,
+        mesh: MeshHandle,
+        material: MaterialHandle,
+    ) -> Entity {
+        self.world
+            .spawn((transform, mesh, material, Visibility::Visible))
+    }
+
+    pub fn spawn_light(&mut self, transform: Transform, light: PointLight) -> Entity {
+        self.world.spawn((transform, light))
+    }
+
+    pub fn len(&self) -> u32 {
+        self.world.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+}
+
+/// Queues a draw on `renderer` for every visible entity with a mesh, after the draws already
+/// queued, until the renderer is full. Returns how many draws were queued; the renderer is
+/// usually cleared first.
+///
+/// # Panics
+///
+/// - Panics if an entity refers to a mesh the renderer does not have.
+pub fn render_system(world: &ThorusWorld, renderer: &mut IndirectRenderer) -> usize {
+    let mut query = world.world.query::<(
+        &Transform,
+        &MeshHandle,
+        &Visibility,
+        Option<&MaterialHandle>,
+    )>();
+    let mut count = 0;
+    for (_, (transform, mesh, visibility, material)) in query.iter() {
+        if !visibility.is_visible() {
+            continue;
+        }
+        if renderer.is_full() {
+            break;
+        }
+        renderer.push(DrawEntry {
+            mesh: mesh.0,
+            material: material.map(|material| material.0),
+            transform: transform.matrix(),
+        });
+        count += 1;
+    }
+    count
+}
+
+/// Writes every point light not hidden by a [`Visibility`] into `light_buffer`, after the
+/// lights already in it, until the buffer is full. Returns how many lights were written.
+pub fn light_gather_system(
+    world: &ThorusWorld,
+    light_buffer: &mut LightBuffer,
+) -> Result<usize, HostAccessError> {
+    let mut query = world
+        .world
+        .query::<(&Transform, &PointLight, Option<&Visibility>)>();
+    let mut count = 0;
+    for (_, (transform, light, visibility)) in query.iter() {
+        if visibility.is_some_and(|visibility| !visibility.is_visible()) {
+            continue;
+        }
+        if !light_buffer.push(transform.translation, light)? {
+            break;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::IndirectMesh;
+    use crate::testing::TestContext;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn renderer(capacity: u32) -> IndirectRenderer {
+        let context = TestContext::new();
+        let mut renderer =
+            IndirectRenderer::new(context.memory_allocator.clone(), capacity).unwrap();
+        renderer.add_mesh(IndirectMesh {
+            first_index: 0,
+            index_count: 3,
+            vertex_offset: 0,
+        });
+        renderer
+    }
+
+    fn spawn(world: &mut ThorusWorld, count: usize) {
+        for i in 0..count {
+            let entity = world.spawn_mesh(
+                Transform::from_translation([i as f32, 0.0, 0.0]),
+                MeshHandle(0),
+                MaterialHandle(i % 3),
+            );
+            world
+                .world_mut()
+                .insert_one(entity, PointLight::default())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn matrix_scales_rotates_and_translates() {
+        let transform = Transform {
+            translation: [1.0, 2.0, 3.0],
+            rotation: Quat::from_axis_angle([0.0, 0.0, 1.0], FRAC_PI_2),
+            scale: [2.0; 3],
+        };
+        let [x, y, z, _] = Mat4::from(transform.matrix()).mul_vec4([1.0, 0.0, 0.0, 1.0]);
+        assert!((x - 1.0).abs() < 1e-6, "{x}");
+        assert!((y - 4.0).abs() < 1e-6, "{y}");
+        assert!((z - 3.0).abs() < 1e-6, "{z}");
+        assert_eq!(
+            Transform::default().matrix(),
+            <[[f32; 4]; 4]>::from(Mat4::identity())
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn hundred_entities_make_hundred_draws() {
+        let mut world = ThorusWorld::new();
+        spawn(&mut world, 100);
+        let hidden = world.spawn_mesh(Transform::default(), MeshHandle(0), MaterialHandle(0));
+        *world.world_mut().get::<&mut Visibility>(hidden).unwrap() = Visibility::Hidden;
+
+        let mut renderer = renderer(128);
+        assert_eq!(render_system(&world, &mut renderer), 100);
+        assert_eq!(renderer.draws().len(), 100);
+        assert!(renderer
+            .draws()
+            .iter()
+            .all(|draw| draw.mesh == 0 && draw.material.is_some()));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn render_system_stops_at_capacity() {
+        let mut world = ThorusWorld::new();
+        spawn(&mut world, 100);
+        let mut renderer = renderer(64);
+        assert_eq!(render_system(&world, &mut renderer), 64);
+        assert!(renderer.is_full());
+        assert_eq!(render_system(&world, &mut renderer), 0);
+    }
+}
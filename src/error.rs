@@ -0,0 +1,272 @@
+use crate::config::ConfigError;
+use crate::pipeline::PipelineError;
+use crate::raytracing::RayTracingError;
+use crate::shader::{CompileError, ShaderError};
+use crate::texture::TextureError;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use vulkano::buffer::AllocateBufferError;
+use vulkano::command_buffer::CommandBufferExecError;
+use vulkano::image::AllocateImageError;
+use vulkano::memory::allocator::MemoryAllocatorError;
+use vulkano::sync::HostAccessError;
+use vulkano::{LoadingError, Validated, ValidationError, VulkanError};
+use winit::error::OsError;
+
+/// Application-level error returned from `main`.
+#[derive(Debug)]
+pub enum ThorusError {
+    Config(ConfigError),
+    Loading(LoadingError),
+    Vulkan(VulkanError),
+    Validation(Box<ValidationError>),
+    Allocation(AllocateBufferError),
+    ImageAllocation(AllocateImageError),
+    MemoryAllocation(MemoryAllocatorError),
+    Texture(TextureError),
+    ShaderLoad(ShaderError),
+    ShaderCompile(CompileError),
+    Swapchain(Validated<VulkanError>),
+    Pipeline(PipelineError),
+    RayTracing(RayTracingError),
+    CommandBufferExec(CommandBufferExecError),
+    HostAccess(HostAccessError),
+    Window(OsError),
+    Io(io::Error),
+    /// Nothing suitable was found, e.g. a physical device or a surface format.
+    Missing(&'static str),
+    /// Another error with a message describing what was being done.
+    Context {
+        message: String,
+        source: Box<ThorusError>,
+    },
+}
+
+impl ThorusError {
+    /// Wraps the error with a message describing what was being done.
+    pub fn context(self, message: &str) -> Self {
+        Self::Context {
+            message: message.to_owned(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl Display for ThorusError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "{e}"),
+            Self::Loading(e) => write!(f, "failed to load the Vulkan library: {e}"),
+            Self::Vulkan(e) => write!(f, "Vulkan call failed: {e}"),
+            Self::Validation(e) => write!(f, "validation failed: {e}"),
+            Self::Allocation(e) => write!(f, "failed to allocate buffer: {e}"),
+            Self::ImageAllocation(e) => write!(f, "failed to allocate image: {e}"),
+            Self::MemoryAllocation(e) => write!(f, "failed to allocate memory: {e}"),
+            Self::Texture(e) => write!(f, "{e}"),
+            Self::ShaderLoad(e) => write!(f, "{e}"),
+            Self::ShaderCompile(e) => write!(f, "{e}"),
+            Self::Swapchain(e) => write!(f, "swapchain operation failed: {e}"),
+            Self::Pipeline(e) => write!(f, "{e}"),
+            Self::RayTracing(e) => write!(f, "{e}"),
+            Self::CommandBufferExec(e) => write!(f, "failed to execute command buffer: {e}"),
+            Self::HostAccess(e) => write!(f, "failed to access buffer from the host: {e}"),
+            Self::Window(e) => write!(f, "failed to create window: {e}"),
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::Missing(what) => write!(f, "no {what} available"),
+            Self::Context { message, source } => write!(f, "{message}: {source}"),
+        }
+    }
+}
+
+impl Error for ThorusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Config(e) => Some(e),
+            Self::Loading(e) => Some(e),
+            Self::Vulkan(e) => Some(e),
+            Self::Validation(e) => Some(e.as_ref()),
+            Self::Allocation(e) => Some(e),
+            Self::ImageAllocation(e) => Some(e),
+            Self::MemoryAllocation(e) => Some(e),
+            Self::Texture(e) => Some(e),
+            Self::ShaderLoad(e) => Some(e),
+            Self::ShaderCompile(e) => Some(e),
+            Self::Swapchain(e) => Some(e),
+            Self::Pipeline(e) => Some(e),
+            Self::RayTracing(e) => Some(e),
+            Self::CommandBufferExec(e) => Some(e),
+            Self::HostAccess(e) => Some(e),
+            Self::Window(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Context { source, .. } => Some(source.as_ref()),
+            Self::Missing(_) => None,
+        }
+    }
+}
+
+impl From<ConfigError> for ThorusError {
+    fn from(e: ConfigError) -> Self {
+        Self::Config(e)
+    }
+}
+
+impl From<LoadingError> for ThorusError {
+    fn from(e: LoadingError) -> Self {
+        Self::Loading(e)
+    }
+}
+
+impl From<VulkanError> for ThorusError {
+    fn from(e: VulkanError) -> Self {
+        Self::Vulkan(e)
+    }
+}
+
+impl From<Box<ValidationError>> for ThorusError {
+    fn from(e: Box<ValidationError>) -> Self {
+        Self::Validation(e)
+    }
+}
+
+impl From<AllocateBufferError> for ThorusError {
+    fn from(e: AllocateBufferError) -> Self {
+        Self::Allocation(e)
+    }
+}
+
+impl From<AllocateImageError> for ThorusError {
+    fn from(e: AllocateImageError) -> Self {
+        Self::ImageAllocation(e)
+    }
+}
+
+impl From<MemoryAllocatorError> for ThorusError {
+    fn from(e: MemoryAllocatorError) -> Self {
+        Self::MemoryAllocation(e)
+    }
+}
+
+impl From<TextureError> for ThorusError {
+    fn from(e: TextureError) -> Self {
+        Self::Texture(e)
+    }
+}
+
+impl From<ShaderError> for ThorusError {
+    fn from(e: ShaderError) -> Self {
+        Self::ShaderLoad(e)
+    }
+}
+
+impl From<CompileError> for ThorusError {
+    fn from(e: CompileError) -> Self {
+        Self::ShaderCompile(e)
+    }
+}
+
+impl<E> From<Validated<E>> for ThorusError
+where
+    Self: From<E>,
+{
+    fn from(e: Validated<E>) -> Self {
+        match e {
+            Validated::Error(e) => e.into(),
+            Validated::ValidationError(e) => e.into(),
+        }
+    }
+}
+
+impl From<PipelineError> for ThorusError {
+    fn from(e: PipelineError) -> Self {
+        Self::Pipeline(e)
+    }
+}
+
+impl From<RayTracingError> for ThorusError {
+    fn from(e: RayTracingError) -> Self {
+        Self::RayTracing(e)
+    }
+}
+
+impl From<CommandBufferExecError> for ThorusError {
+    fn from(e: CommandBufferExecError) -> Self {
+        Self::CommandBufferExec(e)
+    }
+}
+
+impl From<HostAccessError> for ThorusError {
+    fn from(e: HostAccessError) -> Self {
+        Self::HostAccess(e)
+    }
+}
+
+impl From<OsError> for ThorusError {
+    fn from(e: OsError) -> Self {
+        Self::Window(e)
+    }
+}
+
+impl From<io::Error> for ThorusError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Adds [`ThorusError::context`] to any result whose error converts into [`ThorusError`].
+pub trait Context<T> {
+    fn context(self, message: &str) -> Result<T, ThorusError>;
+}
+
+impl<T, E: Into<ThorusError>> Context<T> for Result<T, E> {
+    fn context(self, message: &str) -> Result<T, ThorusError> {
+        self.map_err(|e| e.into().context(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every variant but `Window`, whose `OsError` cannot be built outside winit.
+    fn one_of_each() -> Vec<ThorusError> {
+        vec![
+            ConfigError::Io(io::Error::other("unreadable")).into(),
+            LoadingError::VulkanError(VulkanError::InitializationFailed).into(),
+            VulkanError::DeviceLost.into(),
+            Box::<ValidationError>::default().into(),
+            AllocateBufferError::CreateBuffer(VulkanError::OutOfDeviceMemory).into(),
+            AllocateImageError::CreateImage(VulkanError::OutOfDeviceMemory).into(),
+            MemoryAllocatorError::FindMemoryType.into(),
+            TextureError::FeatureNotEnabled("sampler_anisotropy").into(),
+            ShaderError::Io(io::Error::other("missing")).into(),
+            CompileError::Unavailable.into(),
+            ThorusError::Swapchain(Validated::Error(VulkanError::OutOfDate)),
+            PipelineError::Missing("vertex shader").into(),
+            RayTracingError::ExtensionNotEnabled.into(),
+            CommandBufferExecError::OneTimeSubmitAlreadySubmitted.into(),
+            HostAccessError::NotHostMapped.into(),
+            io::Error::other("disk full").into(),
+            ThorusError::Missing("physical device"),
+            ThorusError::Missing("surface format").context("creating the swapchain"),
+        ]
+    }
+
+    #[test]
+    fn every_variant_has_a_message() {
+        for error in one_of_each() {
+            assert!(!error.to_string().is_empty(), "{error:?} has no message");
+        }
+    }
+
+    #[test]
+    fn context_keeps_the_source() {
+        let result: Result<(), _> = Err(VulkanError::DeviceLost);
+        let error = result.context("waiting for the frame").unwrap_err();
+        assert!(error.to_string().starts_with("waiting for the frame"));
+        assert!(matches!(
+            error.source().and_then(|e| e.downcast_ref::<ThorusError>()),
+            Some(ThorusError::Vulkan(VulkanError::DeviceLost))
+        ));
+    }
+}
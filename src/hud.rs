@@ -0,0 +1,256 @@
+use crate::debug_draw::DebugDraw;
+use std::collections::VecDeque;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+/// Frame times kept for the histogram.
+pub const HISTORY_LEN: usize = 128;
+
+/// Frame time, in seconds, drawn as a full-height histogram bar.
+const HISTOGRAM_SCALE: f32 = 1.0 / 20.0;
+const HISTOGRAM_ORIGIN: [f32; 2] = [-0.98, 0.98];
+const HISTOGRAM_SIZE: [f32; 2] = [0.5, 0.3];
+const GOOD_COLOR: [f32; 4] = [0.2, 0.9, 0.2, 1.0];
+const SLOW_COLOR: [f32; 4] = [0.9, 0.8, 0.2, 1.0];
+const BAD_COLOR: [f32; 4] = [0.9, 0.2, 0.2, 1.0];
+const TARGET_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.5];
+const TARGET_FRAME_TIME: f32 = 1.0 / 60.0;
+
+/// On-screen performance metrics: frame rate, frame time history, GPU time and VRAM usage.
+///
+/// Hidden until toggled with `F3`.
+#[derive(Clone, Debug)]
+pub struct PerformanceHud {
+    visible: bool,
+    history: VecDeque<f32>,
+    /// Frame times of roughly the last second, oldest first.
+    window: VecDeque<f32>,
+    window_time: f32,
+    gpu_time_ns: u64,
+    vram_used: u64,
+    vram_budget: u64,
+}
+
+impl Default for PerformanceHud {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            window: VecDeque::new(),
+            window_time: 0.0,
+            gpu_time_ns: 0,
+            vram_used: 0,
+            vram_budget: 0,
+        }
+    }
+}
+
+impl PerformanceHud {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Toggles the HUD when `F3` is pressed; returns whether the event was consumed.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::F3),
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.toggle();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Records a frame that took `dt` seconds on the CPU and `gpu_time_ns` on the GPU, with
+    /// `vram_used` of `vram_budget` bytes of device-local memory in use, see
+    /// [`MemoryBudget::device_local`](crate::memory::MemoryBudget::device_local).
+    pub fn update(&mut self, dt: f32, gpu_time_ns: u64, vram_used: u64, vram_budget: u64) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(dt);
+
+        self.window.push_back(dt);
+        self.window_time += dt;
+        while self.window.len() > 1 && self.window_time - self.window[0] >= 1.0 {
+            self.window_time -= self.window.pop_front().unwrap_or_default();
+        }
+
+        self.gpu_time_ns = gpu_time_ns;
+        self.vram_used = vram_used;
+        self.vram_budget = vram_budget;
+    }
+
+    /// Frame times of the last [`HISTORY_LEN`] frames, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = f32> + '_ {
+        self.history.iter().copied()
+    }
+
+    /// Average frame rate over the last second.
+    pub fn fps(&self) -> f32 {
+        if self.window_time > 0.0 {
+            self.window.len() as f32 / self.window_time
+        } else {
+            0.0
+        }
+    }
+
+    /// Lowest and highest frame rate of a single frame within the last second.
+    pub fn fps_range(&self) -> (f32, f32) {
+        let (shortest, longest) = self
+            .window
+            .iter()
+            .fold((f32::INFINITY, 0.0f32), |(shortest, longest), &dt| {
+                (shortest.min(dt), longest.max(dt))
+            });
+        if longest > 0.0 {
+            (1.0 / longest, 1.0 / shortest.max(f32::EPSILON))
+        } else {
+            (0.0, 0.0)
+        }
+    }
+
+    pub fn overlay_text(&self) -> String {
+        let (min_fps, max_fps) = self.fps_range();
+        format!(
+            "fps: {:.1} (min {:.1}, max {:.1})\n\
+             frame: {:.2} ms\n\
+             gpu: {:.2} ms\n\
+             vram: {} / {} MiB",
+            self.fps(),
+            min_fps,
+            max_fps,
+            self.history.back().map_or(0.0, |dt| dt * 1e3),
+            self.gpu_time_ns as f64 / 1e6,
+            self.vram_used >> 20,
+            self.vram_budget >> 20,
+        )
+    }
+
+    /// Queues the frame time histogram into `debug_draw` for one frame when visible.
+    ///
+    /// Positions are in clip space, so `debug_draw` has to be recorded with an identity
+    /// view-projection matrix. Bars are green up to 60 fps, yellow up to 30 fps and red
+    /// beyond, with a line marking 60 fps.
+    pub fn draw(&self, debug_draw: &mut DebugDraw) {
+        if !self.visible {
+            return;
+        }
+        let [left, bottom] = HISTOGRAM_ORIGIN;
+        let [width, height] = HISTOGRAM_SIZE;
+        let bar_width = width / HISTORY_LEN as f32;
+        // Clip space y points down, so bars grow towards negative y.
+        let bar_top = |dt: f32| bottom - (dt / HISTOGRAM_SCALE).min(1.0) * height;
+        for (i, dt) in self.history().enumerate() {
+            let x = left + (i as f32 + 0.5) * bar_width;
+            let color = if dt <= TARGET_FRAME_TIME {
+                GOOD_COLOR
+            } else if dt <= 2.0 * TARGET_FRAME_TIME {
+                SLOW_COLOR
+            } else {
+                BAD_COLOR
+            };
+            debug_draw.line([x, bottom, 0.0], [x, bar_top(dt), 0.0], color, 0.0);
+        }
+        let target = bar_top(TARGET_FRAME_TIME);
+        debug_draw.line(
+            [left, target, 0.0],
+            [left + width, target, 0.0],
+            TARGET_COLOR,
+            0.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::DeviceId;
+
+    fn assert_within_one_percent(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= expected * 0.01,
+            "{actual} is not within 1% of {expected}"
+        );
+    }
+
+    #[test]
+    fn fps_of_a_steady_frame_rate() {
+        let mut hud = PerformanceHud::new();
+        for _ in 0..180 {
+            hud.update(1.0 / 60.0, 0, 0, 0);
+        }
+        assert_within_one_percent(hud.fps(), 60.0);
+        let (min, max) = hud.fps_range();
+        assert_within_one_percent(min, 60.0);
+        assert_within_one_percent(max, 60.0);
+    }
+
+    #[test]
+    fn fps_averages_uneven_frames_over_the_last_second() {
+        let mut hud = PerformanceHud::new();
+        // a slow second first, which must drop out of the average
+        for _ in 0..10 {
+            hud.update(0.1, 0, 0, 0);
+        }
+        for dt in [0.01, 0.03].repeat(50) {
+            hud.update(dt, 0, 0, 0);
+        }
+        assert_within_one_percent(hud.fps(), 50.0);
+        let (min, max) = hud.fps_range();
+        assert_within_one_percent(min, 1.0 / 0.03);
+        assert_within_one_percent(max, 100.0);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_frames() {
+        let mut hud = PerformanceHud::new();
+        for frame in 0..200 {
+            hud.update(frame as f32, 0, 0, 0);
+        }
+        let history: Vec<_> = hud.history().collect();
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history[0], (200 - HISTORY_LEN) as f32);
+        assert_eq!(history[HISTORY_LEN - 1], 199.0);
+    }
+
+    #[test]
+    fn f3_toggles_visibility() {
+        let key = |virtual_keycode, state| WindowEvent::KeyboardInput {
+            // SAFETY: the id is only compared, never passed to the platform.
+            device_id: unsafe { DeviceId::dummy() },
+            #[allow(deprecated)]
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(virtual_keycode),
+                modifiers: Default::default(),
+            },
+            is_synthetic: false,
+        };
+        let mut hud = PerformanceHud::new();
+        assert!(!hud.is_visible());
+        assert!(!hud.handle_event(&key(VirtualKeyCode::F2, ElementState::Pressed)));
+        assert!(!hud.handle_event(&key(VirtualKeyCode::F3, ElementState::Released)));
+        assert!(!hud.is_visible());
+        assert!(hud.handle_event(&key(VirtualKeyCode::F3, ElementState::Pressed)));
+        assert!(hud.is_visible());
+        assert!(hud.handle_event(&key(VirtualKeyCode::F3, ElementState::Pressed)));
+        assert!(!hud.is_visible());
+    }
+}
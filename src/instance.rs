@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use tracing::{debug, warn};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::DeviceExtensions;
+use vulkano::instance::{Instance, InstanceCreateFlags, InstanceCreateInfo, InstanceExtensions};
+use vulkano::{Validated, Version, VulkanError, VulkanLibrary};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "macos")] {
+        /// Vulkan on macOS is only available through MoltenVK, a portability implementation.
+        const PORTABILITY_BY_DEFAULT: bool = true;
+    } else {
+        const PORTABILITY_BY_DEFAULT: bool = false;
+    }
+}
+
+/// Creates the Vulkan instance, optionally listing portability subset devices.
+pub struct InstanceBuilder {
+    library: Arc<VulkanLibrary>,
+    enabled_layers: Vec<String>,
+    enabled_extensions: InstanceExtensions,
+    max_api_version: Option<Version>,
+    portability: bool,
+}
+
+impl InstanceBuilder {
+    /// Portability is enabled by default on macOS only.
+    pub fn new(library: Arc<VulkanLibrary>) -> Self {
+        Self {
+            library,
+            enabled_layers: vec![],
+            enabled_extensions: InstanceExtensions::empty(),
+            max_api_version: None,
+            portability: PORTABILITY_BY_DEFAULT,
+        }
+    }
+
+    pub fn enabled_layers(mut self, layers: impl IntoIterator<Item = String>) -> Self {
+        self.enabled_layers.extend(layers);
+        self
+    }
+
+    pub fn enabled_extensions(mut self, extensions: InstanceExtensions) -> Self {
+        self.enabled_extensions = self.enabled_extensions.union(&extensions);
+        self
+    }
+
+    /// Caps the Vulkan version used with every device, which defaults to the version of the
+    /// library; devices then report the lower of their own and this one.
+    pub fn max_api_version(mut self, version: Version) -> Self {
+        self.max_api_version = Some(version);
+        self
+    }
+
+    /// Lists portability subset devices, enabling `VK_KHR_portability_enumeration` and
+    /// `VK_INSTANCE_CREATE_ENUMERATE_PORTABILITY_BIT_KHR`; devices created on them then need
+    /// [`device_extensions`](Self::device_extensions).
+    ///
+    /// On macOS the only devices are MoltenVK ones, which translate Vulkan to Metal and are
+    /// hidden by the loader unless this is enabled. MoltenVK does not implement all of
+    /// Vulkan: among others, triangle fans, point polygon mode, separate stencil reference
+    /// values per face, events and some sampler and image view swizzles are missing, and
+    /// geometry and tessellation shaders are limited or absent. Optional features queried at
+    /// runtime, such as in [`FeatureSet::device_setup`](crate::device::FeatureSet), keep
+    /// working; the gaps of core Vulkan are reported by `VK_KHR_portability_subset`.
+    pub fn with_portability(mut self, enabled: bool) -> Self {
+        self.portability = enabled;
+        self
+    }
+
+    pub fn portability(&self) -> bool {
+        self.portability
+    }
+
+    /// Device extensions to enable on `physical_device`: `VK_KHR_portability_subset` if it is
+    /// a portability subset device, which the specification requires to be enabled.
+    pub fn device_extensions(physical_device: &PhysicalDevice) -> DeviceExtensions {
+        DeviceExtensions {
+            khr_portability_subset: physical_device
+                .supported_extensions()
+                .khr_portability_subset,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    pub fn build(self) -> Result<Arc<Instance>, Validated<VulkanError>> {
+        let mut enabled_extensions = self.enabled_extensions;
+        let mut flags = InstanceCreateFlags::empty();
+        if self.portability {
+            if self
+                .library
+                .supported_extensions()
+                .khr_portability_enumeration
+            {
+                enabled_extensions.khr_portability_enumeration = true;
+                flags |= InstanceCreateFlags::ENUMERATE_PORTABILITY;
+            } else {
+                warn!(
+                    "VK_KHR_portability_enumeration is not supported, skipping portability devices"
+                );
+            }
+        }
+        let instance = Instance::new(
+            self.library,
+            InstanceCreateInfo {
+                flags,
+                enabled_layers: self.enabled_layers,
+                enabled_extensions,
+                max_api_version: self.max_api_version,
+                ..InstanceCreateInfo::default()
+            },
+        )?;
+        debug!("instance flags: {flags:?}");
+        Ok(instance)
+    }
+}
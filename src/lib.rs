@@ -1,2 +1,62 @@
-pub mod shader;
-pub mod vertex;
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+pub mod bvh;
+pub mod culling;
+pub mod math;
+pub mod resources;
+#[cfg(all(target_arch = "wasm32", feature = "web"))]
+pub mod web;
+
+// vulkano, ash and shaderc do not build for wasm32, and neither does anything that touches them.
+cfg_if::cfg_if! {
+    if #[cfg(not(target_arch = "wasm32"))] {
+        pub mod animation;
+        pub mod assets;
+        pub mod buffer;
+        pub mod cli;
+        pub mod command;
+        pub mod compute;
+        pub mod config;
+        pub mod debug_draw;
+        pub mod descriptor;
+        pub mod device;
+        pub mod ecs;
+        pub mod error;
+        pub mod hud;
+        pub mod instance;
+        pub mod light;
+        pub mod lod;
+        pub mod material;
+        pub mod memory;
+        pub mod mesh;
+        pub mod mesh_gen;
+        pub mod noise;
+        pub mod offscreen;
+        pub mod physics_debug;
+        pub mod pipeline;
+        pub mod postprocess;
+        pub mod query;
+        pub mod raytracing;
+        pub mod renderer;
+        pub mod scene;
+        pub mod scheduler;
+        pub mod shader;
+        pub mod sprite;
+        pub mod ssr;
+        pub mod stats;
+        pub mod streaming;
+        pub mod surface;
+        pub mod swapchain;
+        pub mod terrain;
+        #[cfg(test)]
+        mod testing;
+        pub mod text;
+        pub mod texture;
+        pub mod tilemap;
+        pub mod ui;
+        pub mod vertex;
+        pub mod volume;
+        pub mod voxel;
+        pub mod window;
+    }
+}
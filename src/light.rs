@@ -0,0 +1,115 @@
+use crate::buffer::UPLOAD_MEMORY;
+use std::sync::Arc;
+use vulkano::buffer::{
+    AllocateBufferError, Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer,
+};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::sync::HostAccessError;
+use vulkano::{DeviceSize, Validated};
+
+/// Light shining in every direction from a point, fading out at `radius`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PointLight {
+    /// Linear RGB.
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 3],
+            intensity: 1.0,
+            radius: 10.0,
+        }
+    }
+}
+
+/// Element of the storage buffer written by a [`LightBuffer`], read by shaders as
+/// `vec4 position_radius; vec4 color_intensity;`.
+#[derive(BufferContents, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct GpuPointLight {
+    /// World-space position in `xyz`, radius in `w`.
+    pub position_radius: [f32; 4],
+    /// Linear color in `rgb`, intensity in `a`.
+    pub color_intensity: [f32; 4],
+}
+
+impl GpuPointLight {
+    pub fn new(position: [f32; 3], light: &PointLight) -> Self {
+        let [x, y, z] = position;
+        let [r, g, b] = light.color;
+        Self {
+            position_radius: [x, y, z, light.radius],
+            color_intensity: [r, g, b, light.intensity],
+        }
+    }
+}
+
+/// Host-visible storage buffer of the point lights of a frame, filled from the start every
+/// frame.
+pub struct LightBuffer {
+    buffer: Subbuffer<[GpuPointLight]>,
+    count: u32,
+}
+
+impl LightBuffer {
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        capacity: u32,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let buffer = Buffer::new_slice(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            capacity.max(1) as DeviceSize,
+        )?;
+        Ok(Self { buffer, count: 0 })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.buffer.len() as u32
+    }
+
+    /// Lights written since the last [`clear`](Self::clear).
+    pub fn len(&self) -> u32 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    /// Appends `light` at `position`. Returns `Ok(false)` without writing if the buffer is
+    /// full.
+    pub fn push(
+        &mut self,
+        position: [f32; 3],
+        light: &PointLight,
+    ) -> Result<bool, HostAccessError> {
+        if self.count >= self.capacity() {
+            return Ok(false);
+        }
+        let index = self.count as DeviceSize;
+        *self.buffer.clone().index(index).write()? = GpuPointLight::new(position, light);
+        self.count += 1;
+        Ok(true)
+    }
+
+    /// The whole buffer; only the first [`len`](Self::len) lights are current.
+    pub fn buffer(&self) -> &Subbuffer<[GpuPointLight]> {
+        &self.buffer
+    }
+}
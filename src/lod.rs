@@ -0,0 +1,154 @@
+use crate::mesh::Mesh;
+
+/// Mesh with several levels of detail.
+///
+/// Each level is paired with the distance from which it is used; levels are sorted by that
+/// threshold ascending, and the first level is also used below its threshold. One `LodMesh`
+/// can be shared by many objects; with [`hysteresis`](Self::hysteresis), each keeps its
+/// selected level in its own [`LodState`].
+#[derive(Debug)]
+pub struct LodMesh {
+    levels: Vec<(f32, Mesh)>,
+    hysteresis: f32,
+    bias: f32,
+}
+
+/// Level of detail last selected for one object drawn with a [`LodMesh`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LodState {
+    level: usize,
+}
+
+impl LodState {
+    pub fn level(&self) -> usize {
+        self.level
+    }
+}
+
+impl LodMesh {
+    pub fn new(mut levels: Vec<(f32, Mesh)>) -> Self {
+        assert!(!levels.is_empty(), "at least one level of detail expected");
+        levels.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Self {
+            levels,
+            hysteresis: 0.0,
+            bias: 1.0,
+        }
+    }
+
+    /// Widens every threshold into a band of `±factor` relative size, inside which the
+    /// previously selected level is kept. Prevents popping when an object hovers at a boundary.
+    pub fn hysteresis(mut self, factor: f32) -> Self {
+        self.hysteresis = factor.clamp(0.0, 0.99);
+        self
+    }
+
+    /// Scales the camera distance before selection; above one switches to coarser levels
+    /// sooner, below one keeps finer levels longer.
+    pub fn bias(mut self, bias: f32) -> Self {
+        self.bias = bias.max(0.0);
+        self
+    }
+
+    pub fn levels(&self) -> &[(f32, Mesh)] {
+        &self.levels
+    }
+
+    /// Picks the level for an object at `object_pos`: the one before the first level whose
+    /// threshold is greater than the distance.
+    pub fn select(&self, camera_pos: [f32; 3], object_pos: [f32; 3]) -> &Mesh {
+        let distance = distance(camera_pos, object_pos) * self.bias;
+        &self.levels[self.level_for(distance)].1
+    }
+
+    /// Like [`select`](Self::select), but keeps the level in `state` while the distance is
+    /// within the hysteresis band around its threshold.
+    pub fn select_with_hysteresis(
+        &self,
+        state: &mut LodState,
+        camera_pos: [f32; 3],
+        object_pos: [f32; 3],
+    ) -> &Mesh {
+        let distance = distance(camera_pos, object_pos) * self.bias;
+        let finest = self.level_for(distance / (1.0 + self.hysteresis));
+        let coarsest = self.level_for(distance / (1.0 - self.hysteresis));
+        state.level = state.level.clamp(finest, coarsest);
+        &self.levels[state.level].1
+    }
+
+    fn level_for(&self, distance: f32) -> usize {
+        self.levels
+            .iter()
+            .position(|&(threshold, _)| threshold > distance)
+            .map_or(self.levels.len() - 1, |next| next.saturating_sub(1))
+    }
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Levels from 0, 10 and 50 on, told apart by their triangle count.
+    fn three_levels() -> LodMesh {
+        let mesh = |triangles: usize| Mesh::new(vec![], vec![0; triangles * 3]);
+        LodMesh::new(vec![(10.0, mesh(3)), (0.0, mesh(6)), (50.0, mesh(1))])
+    }
+
+    fn selected_level(lod: &LodMesh, distance: f32) -> usize {
+        let mesh = lod.select([0.0; 3], [distance, 0.0, 0.0]);
+        lod.levels()
+            .iter()
+            .position(|(_, level)| std::ptr::eq(level, mesh))
+            .unwrap()
+    }
+
+    fn level_at(lod: &LodMesh, state: &mut LodState, distance: f32) -> usize {
+        lod.select_with_hysteresis(state, [0.0; 3], [distance, 0.0, 0.0]);
+        state.level()
+    }
+
+    #[test]
+    fn selects_level_by_distance() {
+        let lod = three_levels();
+        assert_eq!(lod.levels()[0].1.triangle_count(), 6);
+        assert_eq!(selected_level(&lod, 5.0), 0);
+        assert_eq!(selected_level(&lod, 15.0), 1);
+        assert_eq!(selected_level(&lod, 100.0), 2);
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_level_near_a_threshold() {
+        let lod = three_levels().hysteresis(0.1);
+        let mut state = LodState::default();
+        assert_eq!(level_at(&lod, &mut state, 10.5), 0);
+        assert_eq!(level_at(&lod, &mut state, 11.5), 1);
+        assert_eq!(level_at(&lod, &mut state, 9.5), 1);
+        assert_eq!(level_at(&lod, &mut state, 8.5), 0);
+        // without hysteresis the threshold alone decides
+        assert_eq!(selected_level(&lod, 10.5), 1);
+    }
+
+    #[test]
+    fn instances_keep_their_own_level() {
+        let lod = three_levels().hysteresis(0.1);
+        let mut near = LodState::default();
+        let mut far = LodState::default();
+        assert_eq!(level_at(&lod, &mut far, 15.0), 1);
+        assert_eq!(level_at(&lod, &mut near, 10.5), 0);
+        assert_eq!(level_at(&lod, &mut far, 10.5), 1);
+    }
+
+    #[test]
+    fn bias_scales_the_distance() {
+        let lod = three_levels().bias(2.0);
+        assert_eq!(selected_level(&lod, 6.0), 1);
+    }
+}
@@ -1,216 +1,149 @@
+// The browser build starts from `thorus::web::run` instead.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use clap::Parser;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::sync::Arc;
-use thorus::shader::{load_fragment, load_vertex};
+use thorus::cli::CliArgs;
+use thorus::config::RenderConfig;
+use thorus::device::{
+    is_device_lost, select_physical_device, DeviceLostRecovery, DeviceLostSimulator,
+};
+use thorus::error::{Context, ThorusError};
+use thorus::instance::InstanceBuilder;
+use thorus::mesh::Mesh;
+use thorus::pipeline::{
+    render_pass_mismatches, DepthPrepass, GraphicsPipelineBuilder, RenderPassBuilder,
+};
+use thorus::shader::{
+    load_fragment, load_spirv, load_vertex, spirv_module, AsyncShaderCompiler, ShaderError,
+    ShaderType,
+};
+use thorus::swapchain::{
+    AcquireResult, PresentResult, RebuildCommandBuffers, SwapchainConfig, SwapchainManager,
+};
 use thorus::vertex::MyVertex;
-use tracing::{debug, warn};
+use thorus::window::DpiAwareWindow;
+use tracing::{debug, error, info_span, instrument, warn};
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
 use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::command_buffer::allocator::{
     StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
 };
 use vulkano::command_buffer::{
-    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
-    SubpassBeginInfo, SubpassContents, SubpassEndInfo,
+    AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage,
+    PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassBeginInfo, SubpassContents,
+    SubpassEndInfo,
 };
-use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::device::{
-    Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::image::{ImageLayout, ImageUsage, SampleCount};
+use vulkano::instance::Instance;
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, GenericMemoryAllocatorCreateInfo, MemoryTypeFilter,
+    StandardMemoryAllocator,
 };
-use vulkano::image::view::ImageView;
-use vulkano::image::{Image, ImageUsage};
-use vulkano::instance::{Instance, InstanceCreateInfo};
-use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
-use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorBlendState};
-use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
-use vulkano::pipeline::graphics::multisample::MultisampleState;
-use vulkano::pipeline::graphics::rasterization::RasterizationState;
-use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexDefinition};
-use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
-use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
-use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
-use vulkano::pipeline::{GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
-use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::pipeline::graphics::depth_stencil::CompareOp;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{AttachmentLoadOp, AttachmentStoreOp, Framebuffer, RenderPass};
 use vulkano::shader::ShaderModule;
 use vulkano::swapchain::{
-    Surface, SurfaceInfo, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
+    PresentFuture, PresentMode, Surface, SurfaceInfo, Swapchain, SwapchainAcquireFuture,
+    SwapchainCreateInfo,
 };
-use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::future::{FenceSignalFuture, JoinFuture};
 use vulkano::sync::GpuFuture;
-use vulkano::{swapchain, sync, Validated, Version, VulkanError, VulkanLibrary};
+use vulkano::{sync, Validated, VulkanError, VulkanLibrary};
+use winit::dpi::PhysicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 
-#[allow(clippy::arc_with_non_send_sync)]
-fn main() {
-    tracing_subscriber::fmt::init();
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+/// Config file read from the working directory when `--config` is not given.
+const DEFAULT_CONFIG: &str = "thorus.toml";
+const MAX_DEVICE_RECOVERIES: u32 = 3;
+/// GLSL sources in `--shader-dir` that are compiled at startup instead of loading SPIR-V.
+const GLSL_SHADERS: [(&str, ShaderType); 2] = [
+    ("shader.vert", ShaderType::Vertex),
+    ("shader.frag", ShaderType::Fragment),
+];
+/// Subdirectory of `--shader-dir` caching the SPIR-V compiled from [`GLSL_SHADERS`].
+const SHADER_CACHE_DIR: &str = ".spv-cache";
+
+fn main() -> Result<(), ThorusError> {
+    let args = CliArgs::parse();
+    let mut trace_guard = init_tracing(args.trace);
+    debug!("arguments: {args:?}");
+
+    let mut render_config = match &args.config {
+        Some(path) => RenderConfig::load(path)?,
+        None if Path::new(DEFAULT_CONFIG).exists() => {
+            RenderConfig::load(Path::new(DEFAULT_CONFIG))?
+        }
+        None => RenderConfig::default(),
+    };
+    args.apply(&mut render_config);
+    debug!("render config: {render_config:?}");
 
     let event_loop = EventLoop::new();
     debug!("event loop created");
 
-    let library = VulkanLibrary::new().expect("no local Vulkan library");
+    let library = VulkanLibrary::new()?;
     debug!("initialized library: {library:?}");
 
     let required_extensions = Surface::required_extensions(&event_loop);
 
-    let instance = Instance::new(
-        library,
-        InstanceCreateInfo {
-            enabled_extensions: required_extensions,
-            ..InstanceCreateInfo::default()
-        },
-    )
-    .expect("failed to create instance");
-    debug!("initialized instance: {instance:?}");
-
-    let window = Arc::new(WindowBuilder::new().build(&event_loop).unwrap());
-    debug!("window created");
-
-    let surface = Surface::from_window(instance.clone(), window.clone()).unwrap();
-    debug!("surface created");
+    let mut enabled_layers = vec![];
+    if render_config.validation {
+        if library
+            .layer_properties()?
+            .any(|layer| layer.name() == VALIDATION_LAYER)
+        {
+            enabled_layers.push(VALIDATION_LAYER.to_owned());
+        } else {
+            warn!("{VALIDATION_LAYER} is not installed, continuing without validation");
+        }
+    }
 
-    let device_extensions = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::default()
-    };
+    let instance = InstanceBuilder::new(library)
+        .enabled_layers(enabled_layers)
+        .enabled_extensions(required_extensions)
+        .build()
+        .context("failed to create instance")?;
+    debug!("initialized instance: {instance:?}");
 
-    let (physical_device, queue_family_index) =
-        select_physical_device(&instance, &surface, &device_extensions);
-    debug!("chosen physical device: {physical_device:?}");
-    debug!("selected queue family index: {queue_family_index}");
-
-    let (device, mut queues) = Device::new(
-        physical_device.clone(),
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..QueueCreateInfo::default()
-            }],
-            enabled_extensions: device_extensions,
-            ..DeviceCreateInfo::default()
-        },
-    )
-    .expect("failed to create device");
-    debug!("created device: {device:?}");
-
-    let caps = physical_device
-        .surface_capabilities(&surface, SurfaceInfo::default())
-        .expect("failed to get surface capabilities");
-    debug!("surface capabilities: {caps:?}");
-
-    let dimensions = window.inner_size();
-    debug!("dimensions: {dimensions:?}");
-
-    let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
-    debug!("composite alpha: {composite_alpha:?}");
-
-    let image_format = physical_device
-        .surface_formats(&surface, SurfaceInfo::default())
-        .unwrap()
-        .first()
-        .unwrap()
-        .0;
-    debug!("image format: {image_format:?}");
-
-    let (mut swapchain, images) = Swapchain::new(
-        device.clone(),
-        surface.clone(),
-        SwapchainCreateInfo {
-            min_image_count: caps.min_image_count + 1,
-            image_format,
-            image_extent: dimensions.into(),
-            image_usage: ImageUsage::COLOR_ATTACHMENT,
-            composite_alpha,
-            ..SwapchainCreateInfo::default()
-        },
-    )
-    .unwrap();
-    debug!("swapchain: {swapchain:?}");
-    debug!("images: {images:?}");
-
-    let queue = queues
-        .next()
-        .expect("at least one queue expected for device");
-    debug!("device queue: {queue:?}");
-
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-    debug!("created memory allocator: {memory_allocator:?}");
-
-    let command_buffer_allocator = StandardCommandBufferAllocator::new(
-        device.clone(),
-        StandardCommandBufferAllocatorCreateInfo::default(),
+    let mut window_builder = WindowBuilder::new();
+    if let (Some(width), Some(height)) = (render_config.width, render_config.height) {
+        window_builder = window_builder.with_inner_size(PhysicalSize::new(width, height));
+    }
+    let mut window = DpiAwareWindow::new(Arc::new(window_builder.build(&event_loop)?));
+    debug!(
+        "window created: {:?} pixels, {:?} logical at scale {}",
+        window.physical_size(),
+        window.logical_size(),
+        window.scale_factor()
     );
-    debug!("create command buffer allocator: {command_buffer_allocator:?}");
-
-    let render_pass = get_render_pass(device.clone(), &swapchain);
-    debug!("render_pass: {render_pass:?}");
 
-    let framebuffers = get_framebuffers(&images, &render_pass);
-    debug!("framebuffers: {framebuffers:?}");
-
-    let vertex_buffer = Buffer::from_iter(
-        memory_allocator.clone(),
-        BufferCreateInfo {
-            usage: BufferUsage::VERTEX_BUFFER,
-            ..BufferCreateInfo::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-            ..AllocationCreateInfo::default()
-        },
-        [
-            MyVertex {
-                position: [-0.5, -0.5],
-            },
-            MyVertex {
-                position: [0.0, 0.5],
-            },
-            MyVertex {
-                position: [0.5, -0.25],
-            },
-        ],
-    )
-    .unwrap();
-    debug!("vertex buffer: {vertex_buffer:?}");
-
-    let vs = load_vertex(device.clone()).unwrap();
-    debug!("vertex shader: {vs:?}");
-
-    let fs = load_fragment(device.clone()).unwrap();
-    debug!("fragment shader: {fs:?}");
+    let surface = Surface::from_window(instance.clone(), window.window().clone())
+        .context("failed to create surface")?;
+    debug!("surface created");
 
-    let mut viewport = Viewport {
-        offset: [0.0, 0.0],
-        extent: window.inner_size().into(),
-        depth_range: 0.0..=1.0,
+    let mut shader_swap = match &render_config.shader_dir {
+        Some(dir) if has_glsl_shaders(dir) => Some(ShaderSwap::start(dir)?),
+        _ => None,
     };
-    debug!("viewport: {viewport:?}");
-
-    let pipeline = get_pipeline(
-        device.clone(),
-        vs.clone(),
-        fs.clone(),
-        render_pass.clone(),
-        viewport.clone(),
-    );
-    debug!("graphics pipeline: {pipeline:?}");
-
-    let mut command_buffers = get_command_buffers(
-        &command_buffer_allocator,
-        &queue,
-        &pipeline,
-        &framebuffers,
-        &vertex_buffer,
-    );
-    debug!("command buffers");
+    let mut renderer = Some(Renderer::new(&instance, surface, &window, &render_config)?);
+    let mut recovery = DeviceLostRecovery::new(MAX_DEVICE_RECOVERIES);
+    let mut device_lost_simulator = args.simulate_device_lost.map(DeviceLostSimulator::new);
 
     let mut window_resized = false;
-    let mut recreate_swapchain = false;
-
-    let frames_in_flight = images.len();
-    let mut fences: Vec<Option<Arc<FenceSignalFuture<_>>>> = vec![None; frames_in_flight];
-    let mut previous_fence_i = 0;
-
-    let mut cleanup_counter = vec![0; frames_in_flight];
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
@@ -220,242 +153,717 @@ fn main() {
             *control_flow = ControlFlow::Exit;
         }
         Event::WindowEvent {
-            event: WindowEvent::Resized(_),
+            event: WindowEvent::Resized(size),
             ..
         } => {
+            window.handle_resized(size);
+            window_resized = true;
+        }
+        Event::WindowEvent {
+            event:
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                },
+            ..
+        } => {
+            window.handle_scale_factor_changed(scale_factor);
+            window.handle_resized(*new_inner_size);
             window_resized = true;
         }
         Event::MainEventsCleared => {
-            if window_resized || recreate_swapchain {
-                recreate_swapchain = false;
-
-                let new_dimensions = window.inner_size();
-
-                let (new_swapchain, new_images) = swapchain
-                    .recreate(SwapchainCreateInfo {
-                        image_extent: new_dimensions.into(),
-                        ..swapchain.create_info()
-                    })
-                    .expect("failed to recreate swapchain");
-                swapchain = new_swapchain;
-                let new_framebuffers = get_framebuffers(&new_images, &render_pass);
-
-                if window_resized {
-                    window_resized = false;
-
-                    viewport.extent = new_dimensions.into();
-                    let new_pipeline = get_pipeline(
-                        device.clone(),
-                        vs.clone(),
-                        fs.clone(),
-                        render_pass.clone(),
-                        viewport.clone(),
-                    );
-                    command_buffers = get_command_buffers(
-                        &command_buffer_allocator,
-                        &queue,
-                        &new_pipeline,
-                        &new_framebuffers,
-                        &vertex_buffer,
-                    );
+            let Some(current) = &mut renderer else {
+                return;
+            };
+            if let Some(swap) = &mut shader_swap {
+                if let Err(e) = swap.poll_and_install(current) {
+                    error!("{e}");
+                    *control_flow = ControlFlow::Exit;
+                    return;
                 }
             }
-            let (image_i, suboptimal, acquire_future) =
-                match swapchain::acquire_next_image(swapchain.clone(), None)
-                    .map_err(Validated::unwrap)
-                {
-                    Ok(r) => r,
-                    Err(VulkanError::OutOfDate) => {
-                        recreate_swapchain = true;
-                        return;
-                    }
-                    Err(e) => panic!("failed to acquire next image: {e}", e = e),
-                };
-
-            if suboptimal {
-                recreate_swapchain = true;
-            }
-
-            if let Some(image_fence) = &fences[image_i as usize] {
-                image_fence.wait(None).unwrap();
+            let drawn = match &mut device_lost_simulator {
+                Some(simulator) => simulator.next_frame().map_err(ThorusError::from),
+                None => Ok(()),
             }
-
-            let previous_future = match fences[previous_fence_i as usize].clone() {
-                None => sync::now(device.clone()).boxed(),
-                Some(fence) => fence.boxed(),
+            .and_then(|()| current.draw_frame(&window, &mut window_resized));
+            let Err(e) = drawn else {
+                recovery.reset();
+                return;
             };
+            if !is_device_lost(&e) {
+                error!("{e}");
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
 
-            let future = previous_future
-                .join(acquire_future)
-                .then_execute(queue.clone(), command_buffers[image_i as usize].clone())
-                .unwrap()
-                .then_swapchain_present(
-                    queue.clone(),
-                    SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i),
-                )
-                .then_signal_fence_and_flush();
-
-            fences[image_i as usize] = match future.map_err(Validated::unwrap) {
-                Ok(mut value) => Some(Arc::new({
-                    let cleanup_counter = &mut cleanup_counter[image_i as usize];
-                    if *cleanup_counter >= 1_024 {
-                        value.cleanup_finished();
-                        *cleanup_counter = 0;
-                    } else {
-                        *cleanup_counter += 1;
-                    }
-                    value
-                })),
-                Err(VulkanError::OutOfDate) => {
-                    recreate_swapchain = true;
-                    None
+            let device = current.device.clone();
+            let surface = current.surface().clone();
+            if let Some(lost) = renderer.take() {
+                lost.abandon();
+            }
+            let recovered = recovery.recover(e, &device, || {
+                let mut renderer =
+                    Renderer::new(&instance, surface.clone(), &window, &render_config)?;
+                if let Some(swap) = &shader_swap {
+                    swap.install(&mut renderer)?;
+                }
+                Ok(renderer)
+            });
+            match recovered {
+                Ok(new_renderer) => {
+                    window_resized = false;
+                    renderer = Some(new_renderer);
                 }
                 Err(e) => {
-                    warn!("failed to flush future: {e}");
-                    None
+                    error!("{e}");
+                    *control_flow = ControlFlow::Exit;
                 }
-            };
-            previous_fence_i = image_i;
+            }
+        }
+        Event::LoopDestroyed => {
+            // The process exits right after this event, so the trace file has to be
+            // flushed here.
+            drop(trace_guard.take());
         }
         _ => (),
     });
 }
 
-fn select_physical_device(
-    instance: &Arc<Instance>,
-    surface: &Arc<Surface>,
-    device_extensions: &DeviceExtensions,
-) -> (Arc<PhysicalDevice>, u32) {
-    instance
-        .enumerate_physical_devices()
-        .expect("could not enumerate physical devices")
-        .filter(|d| d.api_version() >= Version::V1_3)
-        .filter(|d| d.supported_extensions().contains(device_extensions))
-        .filter_map(|d| {
-            d.queue_family_properties()
-                .iter()
-                .enumerate()
-                .filter_map(|(i, q)| {
-                    Some(i as u32).filter(|_| q.queue_flags.contains(QueueFlags::GRAPHICS))
-                })
-                .find(|&i| d.surface_support(i, surface).unwrap_or(false))
-                .map(|i| (d, i))
-        })
-        .max_by_key(|(d, _)| match d.properties().device_type {
-            PhysicalDeviceType::DiscreteGpu => 0,
-            PhysicalDeviceType::IntegratedGpu => 1,
-            PhysicalDeviceType::VirtualGpu => 2,
-            PhysicalDeviceType::Cpu => 3,
-            _ => 4,
+fn has_glsl_shaders(dir: &Path) -> bool {
+    GLSL_SHADERS
+        .iter()
+        .all(|(name, _)| dir.join(name).is_file())
+}
+
+/// Compiles the [`GLSL_SHADERS`] of a shader directory in the background and replaces the
+/// built-in shaders, which are drawn in the meantime, once all of them are ready.
+struct ShaderSwap {
+    compiler: AsyncShaderCompiler,
+    /// SPIR-V in the order of [`GLSL_SHADERS`], kept to reinstall after device loss.
+    spirv: [Option<Vec<u8>>; GLSL_SHADERS.len()],
+}
+
+impl ShaderSwap {
+    fn start(dir: &Path) -> Result<Self, ThorusError> {
+        let mut compiler =
+            AsyncShaderCompiler::new(GLSL_SHADERS.len(), Some(dir.join(SHADER_CACHE_DIR)))?;
+        for (name, ty) in GLSL_SHADERS {
+            let source = fs::read_to_string(dir.join(name))
+                .map_err(ThorusError::from)
+                .context(&format!("failed to read shader {name}"))?;
+            compiler.submit(name, source, ty);
+        }
+        Ok(Self {
+            compiler,
+            spirv: Default::default(),
         })
-        .expect("no devices available")
+    }
+
+    /// Collects finished shaders and installs them when the last of them has just finished.
+    ///
+    /// A shader that fails to compile is logged and the built-in shaders stay in use.
+    fn poll_and_install(&mut self, renderer: &mut Renderer) -> Result<(), ThorusError> {
+        if self.compiler.pending() == 0 {
+            return Ok(());
+        }
+        for shader in self.compiler.poll() {
+            match shader.spirv {
+                Ok(spirv) => {
+                    let i = GLSL_SHADERS
+                        .iter()
+                        .position(|(name, _)| *name == shader.name)
+                        .expect("only GLSL_SHADERS are submitted");
+                    self.spirv[i] = Some(spirv);
+                }
+                Err(e) => error!("{}: {e}", shader.name),
+            }
+        }
+        if self.compiler.pending() > 0 {
+            return Ok(());
+        }
+        self.install(renderer)
+    }
+
+    /// Switches `renderer` to the compiled shaders if all of them are ready.
+    fn install(&self, renderer: &mut Renderer) -> Result<(), ThorusError> {
+        match &self.spirv {
+            [Some(vs), Some(fs)] => renderer.install_shaders(vs, fs),
+            _ => Ok(()),
+        }
+    }
 }
 
-fn get_render_pass(device: Arc<Device>, swapchain: &Arc<Swapchain>) -> Arc<RenderPass> {
-    vulkano::single_pass_renderpass!(
-        device,
-        attachments: {
-            color: {
-                format: swapchain.image_format(),
-                samples: 1,
-                load_op: Clear,
-                store_op: Store,
-            },
-        },
-        pass: {
-            color: [color],
-            depth_stencil: {},
-        },
-    )
-    .unwrap()
+type FrameFuture =
+    PresentFuture<CommandBufferExecFuture<JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>>>;
+
+/// Everything created on the device, rebuilt from scratch when the device is lost.
+struct Renderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    swapchain_manager: SwapchainManager,
+    render_pass: Arc<RenderPass>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    viewport: Viewport,
+    vertex_buffer: Subbuffer<[MyVertex]>,
+    render_config: RenderConfig,
+    fences: Vec<Option<Arc<FenceSignalFuture<FrameFuture>>>>,
+    previous_fence_i: u32,
+    cleanup_counter: Vec<u32>,
+    recreate_swapchain: bool,
+    surface_lost: bool,
 }
 
-fn get_framebuffers(images: &[Arc<Image>], render_pass: &Arc<RenderPass>) -> Vec<Arc<Framebuffer>> {
-    images
-        .iter()
-        .map(|image| {
-            debug!("processing image: {image:?}");
-            let view = ImageView::new_default(image.clone()).unwrap();
-            debug!("image view: {view:?}");
-            Framebuffer::new(
-                render_pass.clone(),
-                FramebufferCreateInfo {
-                    attachments: vec![view],
-                    ..FramebufferCreateInfo::default()
+impl Renderer {
+    fn new(
+        instance: &Arc<Instance>,
+        surface: Arc<Surface>,
+        window: &DpiAwareWindow,
+        render_config: &RenderConfig,
+    ) -> Result<Self, ThorusError> {
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::default()
+        };
+
+        let feature_set = render_config.feature_set;
+        let (physical_device, queue_family_index) =
+            select_physical_device(instance, Some(&surface), &device_extensions, feature_set)?;
+        debug!("chosen physical device: {physical_device:?}");
+        debug!("selected queue family index: {queue_family_index}");
+
+        let (optional_extensions, enabled_features, capabilities) =
+            feature_set.device_setup(&physical_device);
+        debug!("device capabilities: {capabilities:?}");
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..QueueCreateInfo::default()
+                }],
+                enabled_extensions: device_extensions
+                    .union(&optional_extensions)
+                    .union(&InstanceBuilder::device_extensions(&physical_device)),
+                enabled_features,
+                ..DeviceCreateInfo::default()
+            },
+        )
+        .context("failed to create device")?;
+        debug!("created device: {device:?}");
+
+        let caps = physical_device
+            .surface_capabilities(&surface, SurfaceInfo::default())
+            .context("failed to get surface capabilities")?;
+        debug!("surface capabilities: {caps:?}");
+
+        let dimensions = window.physical_size();
+        debug!("dimensions: {dimensions:?}");
+
+        let composite_alpha = caps
+            .supported_composite_alpha
+            .into_iter()
+            .next()
+            .ok_or(ThorusError::Missing("composite alpha mode"))?;
+        debug!("composite alpha: {composite_alpha:?}");
+
+        let image_format = physical_device
+            .surface_formats(&surface, SurfaceInfo::default())
+            .context("failed to get surface formats")?
+            .first()
+            .ok_or(ThorusError::Missing("surface format"))?
+            .0;
+        debug!("image format: {image_format:?}");
+
+        let present_mode = if render_config.vsync {
+            PresentMode::Fifo
+        } else {
+            let supported: Vec<_> = physical_device
+                .surface_present_modes(&surface, SurfaceInfo::default())
+                .context("failed to get surface present modes")?
+                .collect();
+            [PresentMode::Mailbox, PresentMode::Immediate]
+                .into_iter()
+                .find(|mode| supported.contains(mode))
+                .unwrap_or(PresentMode::Fifo)
+        };
+        debug!("present mode: {present_mode:?}");
+
+        let (swapchain, images) = Swapchain::new(
+            device.clone(),
+            surface.clone(),
+            SwapchainCreateInfo {
+                min_image_count: SwapchainConfig {
+                    present_mode,
+                    max_frames_in_flight: render_config.max_frames_in_flight,
+                }
+                .min_image_count(&caps),
+                image_format,
+                image_extent: dimensions,
+                image_usage: ImageUsage::COLOR_ATTACHMENT,
+                composite_alpha,
+                present_mode,
+                ..SwapchainCreateInfo::default()
+            },
+        )
+        .map_err(ThorusError::Swapchain)?;
+        debug!("swapchain: {swapchain:?}");
+        debug!("images: {images:?}");
+
+        let queue = queues.next().ok_or(ThorusError::Missing("device queue"))?;
+        debug!("device queue: {queue:?}");
+
+        let memory_allocator = Arc::new(match render_config.memory_pool_size_mb {
+            Some(size_mb) => {
+                let block_sizes =
+                    vec![size_mb << 20; physical_device.memory_properties().memory_types.len()];
+                StandardMemoryAllocator::new(
+                    device.clone(),
+                    GenericMemoryAllocatorCreateInfo {
+                        block_sizes: &block_sizes,
+                        ..GenericMemoryAllocatorCreateInfo::default()
+                    },
+                )
+            }
+            None => StandardMemoryAllocator::new_default(device.clone()),
+        });
+        debug!("created memory allocator: {memory_allocator:?}");
+
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        );
+        debug!("create command buffer allocator: {command_buffer_allocator:?}");
+
+        let samples = SampleCount::try_from(render_config.msaa_samples)
+            .ok()
+            .filter(|&samples| {
+                physical_device
+                    .properties()
+                    .framebuffer_color_sample_counts
+                    .contains_enum(samples)
+            })
+            .unwrap_or_else(|| {
+                warn!(
+                    "{} samples are not supported, disabling MSAA",
+                    render_config.msaa_samples
+                );
+                SampleCount::Sample1
+            });
+        debug!("samples: {samples:?}");
+
+        let render_pass = get_render_pass(
+            device.clone(),
+            &swapchain,
+            samples,
+            render_config.depth_prepass.enabled,
+        )?;
+        debug!("render_pass: {render_pass:?}");
+
+        let frames_in_flight = images.len();
+
+        let mut swapchain_manager = SwapchainManager::new(
+            swapchain,
+            images,
+            render_pass.clone(),
+            memory_allocator.clone(),
+        )?;
+        debug!("framebuffers: {:?}", swapchain_manager.framebuffers());
+
+        let vertices = match &render_config.model_path {
+            Some(path) => model_vertices(path)?,
+            None => vec![
+                MyVertex {
+                    position: [-0.5, -0.5],
+                },
+                MyVertex {
+                    position: [0.0, 0.5],
+                },
+                MyVertex {
+                    position: [0.5, -0.25],
                 },
+            ],
+        };
+
+        let vertex_buffer = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            vertices,
+        )
+        .context("failed to create vertex buffer")?;
+        debug!("vertex buffer: {vertex_buffer:?}");
+
+        let (vs, fs) = match &render_config.shader_dir {
+            Some(dir) if !has_glsl_shaders(dir) => (
+                load_spirv(device.clone(), dir.join("shader.vert.spv"))?,
+                load_spirv(device.clone(), dir.join("shader.frag.spv"))?,
+            ),
+            _ => (
+                load_vertex(device.clone()).map_err(ShaderError::from)?,
+                load_fragment(device.clone()).map_err(ShaderError::from)?,
+            ),
+        };
+        debug!("vertex shader: {vs:?}");
+        debug!("fragment shader: {fs:?}");
+
+        let viewport = window.viewport().clone();
+        debug!("viewport: {viewport:?}");
+
+        let (pipeline, depth_prepass) = get_pipeline(
+            device.clone(),
+            vs.clone(),
+            fs.clone(),
+            render_pass.clone(),
+            viewport.clone(),
+            render_config.depth_prepass.enabled,
+        )?;
+        debug!("graphics pipeline: {pipeline:?}");
+
+        swapchain_manager.set_command_buffers(get_command_buffers(
+            &command_buffer_allocator,
+            &queue,
+            &pipeline,
+            depth_prepass.as_ref(),
+            swapchain_manager.framebuffers(),
+            &vertex_buffer,
+            render_config,
+        )?);
+        debug!("command buffers");
+
+        Ok(Self {
+            device,
+            queue,
+            command_buffer_allocator,
+            swapchain_manager,
+            render_pass,
+            vs,
+            fs,
+            viewport,
+            vertex_buffer,
+            render_config: render_config.clone(),
+            fences: vec![None; frames_in_flight],
+            previous_fence_i: 0,
+            cleanup_counter: vec![0; frames_in_flight],
+            recreate_swapchain: false,
+            surface_lost: false,
+        })
+    }
+
+    /// Rebuilds the pipeline and command buffers with shaders compiled at runtime.
+    fn install_shaders(&mut self, vs: &[u8], fs: &[u8]) -> Result<(), ThorusError> {
+        let vs = spirv_module(self.device.clone(), vs)?;
+        let fs = spirv_module(self.device.clone(), fs)?;
+        let (pipeline, depth_prepass) = get_pipeline(
+            self.device.clone(),
+            vs.clone(),
+            fs.clone(),
+            self.render_pass.clone(),
+            self.viewport.clone(),
+            self.render_config.depth_prepass.enabled,
+        )?;
+        self.swapchain_manager
+            .set_command_buffers(get_command_buffers(
+                &self.command_buffer_allocator,
+                &self.queue,
+                &pipeline,
+                depth_prepass.as_ref(),
+                self.swapchain_manager.framebuffers(),
+                &self.vertex_buffer,
+                &self.render_config,
+            )?);
+        debug!("installed compiled shaders");
+        self.vs = vs;
+        self.fs = fs;
+        Ok(())
+    }
+
+    fn surface(&self) -> &Arc<Surface> {
+        self.swapchain_manager.swapchain().surface()
+    }
+
+    /// Drops the renderer of a lost device after waiting for its frames in flight as far as
+    /// the device still allows.
+    ///
+    /// The frame fences hold the swapchain, which has to be destroyed before the surface can
+    /// get a new one. vulkano panics when dropping a frame whose queue cannot be waited for,
+    /// which happens once the device is lost, so such panics are caught and logged.
+    fn abandon(mut self) {
+        for fence in self.fences.drain(..).flatten() {
+            let waited = panic::catch_unwind(AssertUnwindSafe(|| {
+                let waited = fence.wait(None);
+                drop(fence);
+                waited
+            }));
+            match waited {
+                Ok(Ok(()) | Err(Validated::Error(VulkanError::DeviceLost))) => {}
+                Ok(Err(e)) => warn!("failed to wait for a frame of the lost device: {e}"),
+                Err(_) => warn!("failed to release a frame of the lost device"),
+            }
+        }
+    }
+
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn draw_frame(
+        &mut self,
+        window: &DpiAwareWindow,
+        window_resized: &mut bool,
+    ) -> Result<(), ThorusError> {
+        let _frame = info_span!("frame").entered();
+        let new_dimensions = window.physical_size();
+        let rebuild: &mut RebuildCommandBuffers = &mut |framebuffers| {
+            self.viewport = window.viewport().clone();
+            let (new_pipeline, depth_prepass) = get_pipeline(
+                self.device.clone(),
+                self.vs.clone(),
+                self.fs.clone(),
+                self.render_pass.clone(),
+                self.viewport.clone(),
+                self.render_config.depth_prepass.enabled,
+            )?;
+            get_command_buffers(
+                &self.command_buffer_allocator,
+                &self.queue,
+                &new_pipeline,
+                depth_prepass.as_ref(),
+                framebuffers,
+                &self.vertex_buffer,
+                &self.render_config,
             )
-            .unwrap()
+        };
+        if self.surface_lost {
+            let _recreate = info_span!("surface_recreate").entered();
+            self.surface_lost = false;
+            self.recreate_swapchain = false;
+            *window_resized = false;
+            self.swapchain_manager
+                .recover_surface(window.window().clone(), rebuild)?;
+        } else if *window_resized || self.recreate_swapchain {
+            let _recreate = info_span!("swapchain_recreate").entered();
+            self.recreate_swapchain = false;
+            self.swapchain_manager
+                .recreate(new_dimensions, window_resized.then_some(rebuild))
+                .context("failed to recreate swapchain")?;
+            *window_resized = false;
+        }
+        let acquired = info_span!("acquire_image").in_scope(|| {
+            self.swapchain_manager
+                .acquire_next_image()
+                .map_err(ThorusError::Swapchain)
+                .context("failed to acquire next image")
+        })?;
+        let (image_i, acquire_future) = match acquired {
+            AcquireResult::Ok(image_i, future) => (image_i, future),
+            AcquireResult::Suboptimal(image_i, future) => {
+                self.recreate_swapchain = true;
+                (image_i, future)
+            }
+            AcquireResult::OutOfDate => {
+                self.recreate_swapchain = true;
+                return Ok(());
+            }
+            AcquireResult::SurfaceLost => {
+                self.surface_lost = true;
+                return Ok(());
+            }
+        };
+
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None)?;
+        }
+
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => sync::now(self.device.clone()).boxed(),
+            Some(fence) => fence.boxed(),
+        };
+
+        let execute_future = info_span!("submit").in_scope(|| {
+            previous_future.join(acquire_future).then_execute(
+                self.queue.clone(),
+                self.swapchain_manager.command_buffer(image_i).clone(),
+            )
+        })?;
+
+        let presented = info_span!("present").in_scope(|| {
+            self.swapchain_manager
+                .present(execute_future, self.queue.clone(), image_i)
+        });
+
+        let mut keep_fence = |mut value: FenceSignalFuture<_>| {
+            let cleanup_counter = &mut self.cleanup_counter[image_i as usize];
+            if *cleanup_counter >= 1_024 {
+                value.cleanup_finished();
+                *cleanup_counter = 0;
+            } else {
+                *cleanup_counter += 1;
+            }
+            Some(Arc::new(value))
+        };
+        self.fences[image_i as usize] = match presented {
+            Ok(PresentResult::Ok(value)) => keep_fence(value),
+            Ok(PresentResult::Suboptimal(value)) => {
+                self.recreate_swapchain = true;
+                keep_fence(value)
+            }
+            Ok(PresentResult::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Ok(PresentResult::SurfaceLost) => {
+                self.surface_lost = true;
+                None
+            }
+            Err(e) => {
+                warn!("failed to flush future: {e}");
+                None
+            }
+        };
+        self.previous_fence_i = image_i;
+        Ok(())
+    }
+}
+
+/// Installs the log subscriber, plus a Chrome trace writer when `trace` is set.
+///
+/// The trace is written to `trace-<timestamp>.json` in the working directory and can be
+/// opened in Perfetto; it is complete once the returned guard is dropped.
+fn init_tracing(trace: bool) -> Option<FlushGuard> {
+    let (chrome_layer, guard) = if trace {
+        let (layer, guard) = ChromeLayerBuilder::new().include_args(true).build();
+        (Some(layer), Some(guard))
+    } else {
+        (None, None)
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+        .with(chrome_layer)
+        .init();
+    guard
+}
+
+/// Flattens the triangles of an OBJ model onto the XY plane of clip space.
+fn model_vertices(path: &Path) -> Result<Vec<MyVertex>, ThorusError> {
+    let mesh = Mesh::from_obj(path).context("failed to load model")?;
+    debug!("model triangles: {}", mesh.triangle_count());
+    Ok(mesh
+        .indices
+        .iter()
+        .map(|&index| {
+            let [x, y, _] = mesh.vertices[index as usize].position;
+            MyVertex { position: [x, y] }
         })
-        .collect()
+        .collect())
 }
 
+/// Renders into the swapchain image directly, or into a multisampled attachment resolved
+/// into it when `samples` is more than one.
+///
+/// With `depth_prepass` a depth attachment is added last, filled by a depth-only subpass
+/// before the color subpass, see [`DepthPrepass`].
+fn get_render_pass(
+    device: Arc<Device>,
+    swapchain: &Arc<Swapchain>,
+    samples: SampleCount,
+    depth_prepass: bool,
+) -> Result<Arc<RenderPass>, ThorusError> {
+    let builder = RenderPassBuilder::new(device).add_attachment(
+        swapchain.image_format(),
+        SampleCount::Sample1,
+        if samples == SampleCount::Sample1 {
+            AttachmentLoadOp::Clear
+        } else {
+            AttachmentLoadOp::DontCare
+        },
+        AttachmentStoreOp::Store,
+        ImageLayout::PresentSrc,
+    );
+    let (builder, color_attachment) = if samples == SampleCount::Sample1 {
+        (builder, 0)
+    } else {
+        let builder = builder.add_attachment(
+            swapchain.image_format(),
+            samples,
+            AttachmentLoadOp::Clear,
+            AttachmentStoreOp::DontCare,
+            ImageLayout::ColorAttachmentOptimal,
+        );
+        (builder, 1)
+    };
+    let builder = if depth_prepass {
+        let depth_attachment = color_attachment + 1;
+        let builder = builder.add_attachment(
+            DepthPrepass::DEPTH_FORMAT,
+            samples,
+            AttachmentLoadOp::Clear,
+            AttachmentStoreOp::DontCare,
+            ImageLayout::DepthStencilAttachmentOptimal,
+        );
+        DepthPrepass::add_subpasses(builder, &[color_attachment], depth_attachment)
+    } else {
+        builder.add_subpass(&[color_attachment], &[], None)
+    };
+    let builder = if samples == SampleCount::Sample1 {
+        builder
+    } else {
+        builder.resolve_into(&[0])
+    };
+    builder.build().context("failed to create render pass")
+}
+
+/// Creates the main pipeline, plus the depth prepass when enabled, in which case the main
+/// pipeline only shades fragments matching the depth the prepass wrote.
+#[instrument(skip_all)]
 fn get_pipeline(
     device: Arc<Device>,
     vs: Arc<ShaderModule>,
     fs: Arc<ShaderModule>,
     render_pass: Arc<RenderPass>,
     viewport: Viewport,
-) -> Arc<GraphicsPipeline> {
-    let vs = vs.entry_point("main").unwrap();
-    debug!("vertex shader entry point: {vs:?}");
-
-    let fs = fs.entry_point("main").unwrap();
-    debug!("fragment shader entry point: {fs:?}");
-
-    let vertex_input_state = MyVertex::per_vertex()
-        .definition(&vs.info().input_interface)
-        .unwrap();
-    debug!("vertex input state: {vertex_input_state:?}");
-
-    let stages = [
-        PipelineShaderStageCreateInfo::new(vs),
-        PipelineShaderStageCreateInfo::new(fs),
-    ];
-    debug!("stages: {stages:?}");
-
-    let layout = PipelineLayout::new(
-        device.clone(),
-        PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
-            .into_pipeline_layout_create_info(device.clone())
-            .unwrap(),
-    )
-    .unwrap();
-    debug!("pipeline layout: {layout:?}");
-
-    let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
-    debug!("subpass: {subpass:?}");
-
-    GraphicsPipeline::new(
-        device.clone(),
-        None,
-        GraphicsPipelineCreateInfo {
-            stages: stages.into_iter().collect(),
-            vertex_input_state: Some(vertex_input_state),
-            input_assembly_state: Some(InputAssemblyState::default()),
-            viewport_state: Some(ViewportState {
-                viewports: [viewport].into_iter().collect(),
-                ..ViewportState::default()
-            }),
-            rasterization_state: Some(RasterizationState::default()),
-            multisample_state: Some(MultisampleState::default()),
-            color_blend_state: Some(ColorBlendState::with_attachment_states(
-                subpass.num_color_attachments(),
-                ColorBlendAttachmentState::default(),
-            )),
-            subpass: Some(subpass.into()),
-            ..GraphicsPipelineCreateInfo::layout(layout)
-        },
-    )
-    .unwrap()
+    depth_prepass: bool,
+) -> Result<(Arc<GraphicsPipeline>, Option<DepthPrepass>), ThorusError> {
+    let builder = GraphicsPipelineBuilder::new(device.clone())
+        .vertex_shader(vs.clone())
+        .fragment_shader(fs)
+        .vertex_input(MyVertex::per_vertex())
+        .viewport(viewport.clone());
+    if !depth_prepass {
+        let pipeline = builder.render_pass(render_pass, 0).build()?;
+        return Ok((pipeline, None));
+    }
+    let prepass = DepthPrepass::new(
+        device,
+        vs,
+        MyVertex::per_vertex(),
+        render_pass.clone(),
+        viewport,
+    )?;
+    let pipeline = builder
+        .render_pass(render_pass, DepthPrepass::MAIN_SUBPASS)
+        .depth_test(true, false, CompareOp::Equal)
+        .build()?;
+    Ok((pipeline, Some(prepass)))
 }
 
+#[instrument(skip_all, fields(framebuffers = framebuffers.len()))]
 fn get_command_buffers(
     command_buffer_allocator: &StandardCommandBufferAllocator,
     queue: &Arc<Queue>,
     pipeline: &Arc<GraphicsPipeline>,
+    depth_prepass: Option<&DepthPrepass>,
     framebuffers: &[Arc<Framebuffer>],
     vertex_buffer: &Subbuffer<[MyVertex]>,
-) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
+    render_config: &RenderConfig,
+) -> Result<Vec<Arc<PrimaryAutoCommandBuffer>>, ThorusError> {
     framebuffers
         .iter()
         .map(|framebuffer| {
@@ -463,31 +871,194 @@ fn get_command_buffers(
                 command_buffer_allocator,
                 queue.queue_family_index(),
                 CommandBufferUsage::MultipleSubmit,
-            )
-            .unwrap();
+            )?;
+
+            if cfg!(debug_assertions) {
+                for mismatch in render_pass_mismatches(pipeline, framebuffer.render_pass()) {
+                    warn!("pipeline is incompatible with the render pass: {mismatch}");
+                }
+            }
 
+            builder.begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: render_config.clear_values(framebuffer.render_pass()),
+                    ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )?;
+            if let Some(depth_prepass) = depth_prepass {
+                depth_prepass.record(
+                    &mut builder,
+                    vertex_buffer.clone(),
+                    vertex_buffer.len() as u32,
+                )?;
+            }
             builder
-                .begin_render_pass(
-                    RenderPassBeginInfo {
-                        clear_values: vec![Some([0.3, 0.3, 0.3, 1.0].into())],
-                        ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                    },
-                    SubpassBeginInfo {
-                        contents: SubpassContents::Inline,
-                        ..SubpassBeginInfo::default()
-                    },
-                )
-                .unwrap()
-                .bind_pipeline_graphics(pipeline.clone())
-                .unwrap()
-                .bind_vertex_buffers(0, vertex_buffer.clone())
-                .unwrap()
-                .draw(vertex_buffer.len() as u32, 1, 0, 0)
-                .unwrap()
-                .end_render_pass(SubpassEndInfo::default())
-                .unwrap();
-
-            builder.build().unwrap()
+                .bind_pipeline_graphics(pipeline.clone())?
+                .bind_vertex_buffers(0, vertex_buffer.clone())?
+                .draw(vertex_buffer.len() as u32, 1, 0, 0)?
+                .end_render_pass(SubpassEndInfo::default())?;
+
+            Ok(builder.build()?)
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use thorus::device::FeatureSet;
+    use tracing::span::Id;
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{self, Layer};
+    use tracing_subscriber::registry::LookupSpan;
+    use vulkano::format::Format;
+    use vulkano::image::view::ImageView;
+    use vulkano::image::{Image, ImageCreateInfo};
+    use vulkano::render_pass::FramebufferCreateInfo;
+
+    /// Records every span entry and exit as `enter <name>` or `exit <name>`.
+    #[derive(Clone, Default)]
+    struct CallLog(Arc<Mutex<Vec<String>>>);
+
+    impl CallLog {
+        fn push<S>(&self, event: &str, id: &Id, ctx: layer::Context<'_, S>)
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+        {
+            let name = ctx.span(id).map_or("?", |span| span.name());
+            self.0.lock().unwrap().push(format!("{event} {name}"));
+        }
+    }
+
+    impl<S> Layer<S> for CallLog
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_enter(&self, id: &Id, ctx: layer::Context<'_, S>) {
+            self.push("enter", id, ctx);
+        }
+
+        fn on_exit(&self, id: &Id, ctx: layer::Context<'_, S>) {
+            self.push("exit", id, ctx);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn initialization_spans_are_entered_and_exited_in_order() {
+        let instance = InstanceBuilder::new(VulkanLibrary::new().unwrap())
+            .build()
+            .unwrap();
+        let (physical_device, queue_family_index) = select_physical_device(
+            &instance,
+            None,
+            &DeviceExtensions::empty(),
+            FeatureSet::Minimum,
+        )
+        .unwrap();
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..QueueCreateInfo::default()
+                }],
+                ..DeviceCreateInfo::default()
+            },
+        )
+        .unwrap();
+        let queue = queues.next().unwrap();
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        );
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], None)
+            .build()
+            .unwrap();
+        let image = Image::new(
+            memory_allocator.clone(),
+            ImageCreateInfo {
+                format: Format::R8G8B8A8_UNORM,
+                extent: [64, 64, 1],
+                usage: ImageUsage::COLOR_ATTACHMENT,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![ImageView::new_default(image).unwrap()],
+                ..FramebufferCreateInfo::default()
+            },
+        )
+        .unwrap();
+        let vertex_buffer = Buffer::from_iter(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            [[-0.5, -0.5], [0.0, 0.5], [0.5, -0.25]].map(|position| MyVertex { position }),
+        )
+        .unwrap();
+
+        let log = CallLog::default();
+        let subscriber = tracing_subscriber::registry().with(log.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let (pipeline, depth_prepass) = get_pipeline(
+                device.clone(),
+                load_vertex(device.clone()).unwrap(),
+                load_fragment(device.clone()).unwrap(),
+                render_pass,
+                Viewport {
+                    offset: [0.0, 0.0],
+                    extent: [64.0, 64.0],
+                    depth_range: 0.0..=1.0,
+                },
+                false,
+            )
+            .unwrap();
+            get_command_buffers(
+                &command_buffer_allocator,
+                &queue,
+                &pipeline,
+                depth_prepass.as_ref(),
+                &[framebuffer],
+                &vertex_buffer,
+                &RenderConfig::default(),
+            )
+            .unwrap();
+        });
+
+        assert_eq!(
+            *log.0.lock().unwrap(),
+            [
+                "enter get_pipeline",
+                "exit get_pipeline",
+                "enter get_command_buffers",
+                "exit get_command_buffers",
+            ]
+        );
+    }
+}
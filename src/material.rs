@@ -0,0 +1,307 @@
+use crate::vertex::MyVertex;
+use std::collections::HashMap;
+use std::ops::{BitOr, BitOrAssign};
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::device::Device;
+use vulkano::image::view::ImageView;
+use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorBlendState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::multisample::MultisampleState;
+use vulkano::pipeline::graphics::rasterization::RasterizationState;
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexDefinition};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::shader::{ShaderModule, SpecializationConstant};
+
+/// Shader permutation flags of a material.
+///
+/// Bit `i` is fed to the shaders as the boolean specialization constant with `constant_id = i`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialFlags(u32);
+
+impl MaterialFlags {
+    pub const NONE: Self = Self(0);
+    pub const HAS_BASE_COLOR_MAP: Self = Self(1 << 0);
+    pub const HAS_NORMAL_MAP: Self = Self(1 << 1);
+    pub const HAS_METALLIC_ROUGHNESS_MAP: Self = Self(1 << 2);
+    pub const HAS_AO: Self = Self(1 << 3);
+    pub const HAS_EMISSIVE: Self = Self(1 << 4);
+
+    const COUNT: u32 = 5;
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits & ((1 << Self::COUNT) - 1))
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    fn specialization_info(
+        self,
+        module: &ShaderModule,
+    ) -> impl Iterator<Item = (u32, SpecializationConstant)> + '_ {
+        let constants = module.specialization_constants();
+        (0..Self::COUNT)
+            .filter(|id| constants.contains_key(id))
+            .map(move |id| (id, SpecializationConstant::Bool(self.0 & (1 << id) != 0)))
+    }
+}
+
+impl BitOr for MaterialFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for MaterialFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: [f32; 3],
+    pub base_color_map: Option<Arc<ImageView>>,
+    pub normal_map: Option<Arc<ImageView>>,
+    pub metallic_roughness_map: Option<Arc<ImageView>>,
+    pub ao_map: Option<Arc<ImageView>>,
+    pub emissive_map: Option<Arc<ImageView>>,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: [0.0; 3],
+            base_color_map: None,
+            normal_map: None,
+            metallic_roughness_map: None,
+            ao_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+impl PbrMaterial {
+    pub fn flags(&self) -> MaterialFlags {
+        [
+            (&self.base_color_map, MaterialFlags::HAS_BASE_COLOR_MAP),
+            (&self.normal_map, MaterialFlags::HAS_NORMAL_MAP),
+            (
+                &self.metallic_roughness_map,
+                MaterialFlags::HAS_METALLIC_ROUGHNESS_MAP,
+            ),
+            (&self.ao_map, MaterialFlags::HAS_AO),
+            (&self.emissive_map, MaterialFlags::HAS_EMISSIVE),
+        ]
+        .into_iter()
+        .filter(|(map, _)| map.is_some())
+        .fold(MaterialFlags::NONE, |acc, (_, flag)| acc | flag)
+    }
+}
+
+/// Compiled graphics pipelines indexed by material permutation.
+///
+/// Unlike vulkano's `PipelineCache`, which caches driver binaries, this keeps the pipeline
+/// objects themselves, so materials sharing the same flags share one pipeline.
+///
+/// The cached pipelines are all for the render pass and viewport they were last requested
+/// with; asking for another render pass or viewport, e.g. after a resize, drops them.
+pub struct MaterialPipelineCache {
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    target: Option<(Arc<RenderPass>, Viewport)>,
+    pipelines: HashMap<MaterialFlags, Arc<GraphicsPipeline>>,
+}
+
+impl MaterialPipelineCache {
+    pub fn new(vs: Arc<ShaderModule>, fs: Arc<ShaderModule>) -> Self {
+        Self {
+            vs,
+            fs,
+            target: None,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn get_or_compile(
+        &mut self,
+        flags: MaterialFlags,
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        viewport: Viewport,
+    ) -> Arc<GraphicsPipeline> {
+        let same_target = self
+            .target
+            .as_ref()
+            .is_some_and(|(cached_pass, cached_viewport)| {
+                Arc::ptr_eq(cached_pass, &render_pass) && *cached_viewport == viewport
+            });
+        if !same_target {
+            if self.target.is_some() {
+                debug!("material pipeline target changed, dropping cached pipelines");
+            }
+            self.pipelines.clear();
+            self.target = Some((render_pass.clone(), viewport.clone()));
+        }
+        self.pipelines
+            .entry(flags)
+            .or_insert_with(|| {
+                debug!("compiling pipeline for material flags: {flags:?}");
+                compile(&self.vs, &self.fs, flags, device, render_pass, viewport)
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Drops every cached pipeline.
+    pub fn clear(&mut self) {
+        self.target = None;
+        self.pipelines.clear();
+    }
+}
+
+fn compile(
+    vs: &Arc<ShaderModule>,
+    fs: &Arc<ShaderModule>,
+    flags: MaterialFlags,
+    device: Arc<Device>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+) -> Arc<GraphicsPipeline> {
+    let vs = vs
+        .specialize(flags.specialization_info(vs).collect())
+        .unwrap()
+        .entry_point("main")
+        .unwrap();
+    let fs = fs
+        .specialize(flags.specialization_info(fs).collect())
+        .unwrap()
+        .entry_point("main")
+        .unwrap();
+
+    let vertex_input_state = MyVertex::per_vertex()
+        .definition(&vs.info().input_interface)
+        .unwrap();
+
+    let stages = [
+        PipelineShaderStageCreateInfo::new(vs),
+        PipelineShaderStageCreateInfo::new(fs),
+    ];
+
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+            .into_pipeline_layout_create_info(device.clone())
+            .unwrap(),
+    )
+    .unwrap();
+
+    let subpass = Subpass::from(render_pass, 0).unwrap();
+
+    GraphicsPipeline::new(
+        device,
+        None,
+        GraphicsPipelineCreateInfo {
+            stages: stages.into_iter().collect(),
+            vertex_input_state: Some(vertex_input_state),
+            input_assembly_state: Some(InputAssemblyState::default()),
+            viewport_state: Some(ViewportState {
+                viewports: [viewport].into_iter().collect(),
+                ..ViewportState::default()
+            }),
+            rasterization_state: Some(RasterizationState::default()),
+            multisample_state: Some(MultisampleState::default()),
+            color_blend_state: Some(ColorBlendState::with_attachment_states(
+                subpass.num_color_attachments(),
+                ColorBlendAttachmentState::default(),
+            )),
+            subpass: Some(subpass.into()),
+            ..GraphicsPipelineCreateInfo::layout(layout)
+        },
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::RenderPassBuilder;
+    use crate::shader::{load_fragment, load_vertex};
+    use crate::testing::TestContext;
+    use vulkano::format::Format;
+    use vulkano::image::{ImageLayout, SampleCount};
+    use vulkano::render_pass::{AttachmentLoadOp, AttachmentStoreOp};
+
+    #[test]
+    fn flags_combine_and_truncate() {
+        let flags = MaterialFlags::HAS_NORMAL_MAP | MaterialFlags::HAS_AO;
+        assert!(flags.contains(MaterialFlags::HAS_NORMAL_MAP));
+        assert!(!flags.contains(MaterialFlags::HAS_EMISSIVE));
+        assert_eq!(MaterialFlags::from_bits(u32::MAX).bits(), 0b1_1111);
+        assert_eq!(PbrMaterial::default().flags(), MaterialFlags::NONE);
+    }
+
+    fn viewport(size: f32) -> Viewport {
+        Viewport {
+            offset: [0.0, 0.0],
+            extent: [size, size],
+            depth_range: 0.0..=1.0,
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn identical_flags_share_a_pipeline() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], None)
+            .build()
+            .unwrap();
+        let mut cache = MaterialPipelineCache::new(
+            load_vertex(device.clone()).unwrap(),
+            load_fragment(device.clone()).unwrap(),
+        );
+        let mut get = |flags, size| {
+            cache.get_or_compile(flags, device.clone(), render_pass.clone(), viewport(size))
+        };
+
+        let first = get(MaterialFlags::HAS_AO, 64.0);
+        assert!(Arc::ptr_eq(&first, &get(MaterialFlags::HAS_AO, 64.0)));
+        assert!(!Arc::ptr_eq(&first, &get(MaterialFlags::NONE, 64.0)));
+        assert!(!Arc::ptr_eq(&first, &get(MaterialFlags::HAS_AO, 128.0)));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,778 @@
+use crate::bvh::Aabb;
+use std::iter;
+use std::ops::Mul;
+#[cfg(feature = "simd")]
+use std::simd::{f32x4, num::SimdFloat};
+
+/// Halton low-discrepancy sequence of a base, in `[0, 1)`: the digits of 1, 2, 3, … in
+/// `base` mirrored around the radix point. Coprime bases give well spread points in several
+/// dimensions, for sub-pixel jitter and sampling kernels.
+///
+/// The sequence starts at index 1, skipping the 0 every base begins with.
+#[derive(Clone, Debug)]
+pub struct HaltonSequence {
+    base: u32,
+    index: u64,
+}
+
+impl HaltonSequence {
+    /// # Panics
+    ///
+    /// Panics if `base` is less than 2.
+    pub fn new(base: u32) -> Self {
+        assert!(base >= 2, "Halton base must be at least 2, got {base}");
+        Self { base, index: 0 }
+    }
+
+    /// Points of the sequences of `base1` and `base2` side by side, e.g. 2 and 3 for 2D.
+    pub fn pair(base1: u32, base2: u32) -> impl Iterator<Item = [f32; 2]> {
+        iter::zip(Self::new(base1), Self::new(base2)).map(|(x, y)| [x, y])
+    }
+
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    /// Element `n` counted from the start of the sequence, mapped to `[-0.5, 0.5)`, e.g. as a
+    /// pixel offset.
+    pub fn nth_centered(&self, n: u64) -> f32 {
+        radical_inverse(n + 1, self.base) - 0.5
+    }
+}
+
+impl Iterator for HaltonSequence {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.index += 1;
+        Some(radical_inverse(self.index, self.base))
+    }
+}
+
+fn radical_inverse(mut index: u64, base: u32) -> f32 {
+    let base = u64::from(base);
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f64;
+        result += fraction * (index % base) as f64;
+        index /= base;
+    }
+    result as f32
+}
+
+/// Rotation quaternion stored as `[w, x, y, z]`; unit length unless built by hand.
+///
+/// Angles are in radians and rotations counter-clockwise looking down the axis, as in a
+/// right-handed coordinate system.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quat(pub [f32; 4]);
+
+impl Quat {
+    pub fn identity() -> Self {
+        Self([1.0, 0.0, 0.0, 0.0])
+    }
+
+    /// Rotation by `angle` around the unit vector `axis`.
+    pub fn from_axis_angle(axis: [f32; 3], angle: f32) -> Self {
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        let [x, y, z] = axis;
+        Self([cos, x * sin, y * sin, z * sin])
+    }
+
+    /// Rotation by `roll` around Z, then `pitch` around X, then `yaw` around Y, the order of a
+    /// camera looking around.
+    pub fn from_euler_yxz(yaw: f32, pitch: f32, roll: f32) -> Self {
+        Self::from_axis_angle([0.0, 1.0, 0.0], yaw)
+            * Self::from_axis_angle([1.0, 0.0, 0.0], pitch)
+            * Self::from_axis_angle([0.0, 0.0, 1.0], roll)
+    }
+
+    pub fn dot(&self, other: Self) -> f32 {
+        iter::zip(self.0, other.0).map(|(a, b)| a * b).sum()
+    }
+
+    pub fn normalize(&self) -> Self {
+        let length = self.dot(*self).sqrt();
+        Self(self.0.map(|component| component / length))
+    }
+
+    /// Inverse of a unit quaternion.
+    pub fn conjugate(&self) -> Self {
+        let [w, x, y, z] = self.0;
+        Self([w, -x, -y, -z])
+    }
+
+    /// Interpolates at constant angular speed from `self` at `t = 0` to `other` at `t = 1`,
+    /// taking the shorter way around.
+    pub fn slerp(&self, other: Self, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = other;
+        if cos < 0.0 {
+            // `q` and `-q` are the same rotation
+            cos = -cos;
+            other = Self(other.0.map(|component| -component));
+        }
+        let (from, to) = if cos > 0.9995 {
+            // nearly parallel, where the sine below vanishes
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self(std::array::from_fn(|i| from * self.0[i] + to * other.0[i])).normalize()
+    }
+
+    pub fn rotate_vec3(&self, v: [f32; 3]) -> [f32; 3] {
+        // v + 2w (q × v) + 2 q × (q × v), with q the vector part
+        let [w, x, y, z] = self.0;
+        let q = [x, y, z];
+        let t = cross(q, v).map(|component| 2.0 * component);
+        let qt = cross(q, t);
+        std::array::from_fn(|i| v[i] + w * t[i] + qt[i])
+    }
+
+    /// Column-major rotation matrix of a unit quaternion.
+    pub fn to_matrix(&self) -> [[f32; 4]; 4] {
+        Mat4::from_quat(*self).into()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// `self * other` rotates by `other` first, then by `self`.
+impl Mul for Quat {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let [w1, x1, y1, z1] = self.0;
+        let [w2, x2, y2, z2] = other.0;
+        Self([
+            w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+            w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+            w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+            w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+        ])
+    }
+}
+
+/// Column-major 4×4 matrix, `self.0[column][row]`, the layout of GLSL's `mat4`.
+///
+/// Projections follow Vulkan: a right-handed view space looking down -Z, Y pointing down in
+/// clip space and depth mapped to `0..=1`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub fn identity() -> Self {
+        Self::IDENTITY
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self(std::array::from_fn(|column| {
+            std::array::from_fn(|row| self.0[row][column])
+        }))
+    }
+
+    /// `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let m: [f32; 16] = std::array::from_fn(|i| self.0[i / 4][i % 4]);
+        // adjugate by cofactor expansion
+        let mut inv = [0.0; 16];
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if det == 0.0 {
+            return None;
+        }
+        Some(Self(std::array::from_fn(|column| {
+            std::array::from_fn(|row| inv[column * 4 + row] / det)
+        })))
+    }
+
+    /// Perspective projection with a vertical field of view of `fov_y` radians.
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let focal = 1.0 / (fov_y * 0.5).tan();
+        let depth = far / (near - far);
+        Self([
+            [focal / aspect, 0.0, 0.0, 0.0],
+            [0.0, -focal, 0.0, 0.0],
+            [0.0, 0.0, depth, -1.0],
+            [0.0, 0.0, near * depth, 0.0],
+        ])
+    }
+
+    /// View matrix of a camera at `eye` looking at `center`.
+    pub fn look_at(eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> Self {
+        let forward = normalize(std::array::from_fn(|i| center[i] - eye[i]));
+        let side = normalize(cross(forward, up));
+        let up = cross(side, forward);
+        Self([
+            [side[0], up[0], -forward[0], 0.0],
+            [side[1], up[1], -forward[1], 0.0],
+            [side[2], up[2], -forward[2], 0.0],
+            [-dot(side, eye), -dot(up, eye), dot(forward, eye), 1.0],
+        ])
+    }
+
+    pub fn translate(v: [f32; 3]) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix.0[3] = [v[0], v[1], v[2], 1.0];
+        matrix
+    }
+
+    pub fn scale(v: [f32; 3]) -> Self {
+        let mut matrix = Self::IDENTITY;
+        for (axis, factor) in v.into_iter().enumerate() {
+            matrix.0[axis][axis] = factor;
+        }
+        matrix
+    }
+
+    /// Rotation matrix of the unit quaternion `q`.
+    pub fn from_quat(q: Quat) -> Self {
+        let [w, x, y, z] = q.0;
+        Self([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y + z * w),
+                2.0 * (x * z - y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y - z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z + x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z + y * w),
+                2.0 * (y * z - x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn mul_vec4(&self, v: [f32; 4]) -> [f32; 4] {
+        std::array::from_fn(|row| (0..4).map(|column| self.0[column][row] * v[column]).sum())
+    }
+
+    /// `self * other` one element at a time.
+    pub fn mul_scalar(&self, other: Self) -> Self {
+        Self(other.0.map(|column| self.mul_vec4(column)))
+    }
+
+    /// `self * other` computing the four dot products of each output column at once, as the
+    /// columns of `self` weighted by the elements of the column of `other`.
+    #[cfg(feature = "simd")]
+    pub fn mul_simd(&self, other: Self) -> Self {
+        let columns = self.0.map(f32x4::from_array);
+        Self(other.0.map(|column| {
+            (0..4)
+                .map(|k| columns[k] * f32x4::splat(column[k]))
+                .fold(f32x4::splat(0.0), |sum, term| sum + term)
+                .to_array()
+        }))
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// `self * other` applies `other` first, then `self`; uses [`Mat4::mul_simd`] with the `simd`
+/// feature.
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "simd")] {
+                self.mul_simd(other)
+            } else {
+                self.mul_scalar(other)
+            }
+        }
+    }
+}
+
+impl From<[[f32; 4]; 4]> for Mat4 {
+    fn from(columns: [[f32; 4]; 4]) -> Self {
+        Self(columns)
+    }
+}
+
+impl From<Mat4> for [[f32; 4]; 4] {
+    fn from(matrix: Mat4) -> Self {
+        matrix.0
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Vec3(pub [f32; 3]);
+
+impl Vec3 {
+    pub fn dot(&self, other: Self) -> f32 {
+        dot(self.0, other.0)
+    }
+
+    /// [`dot`](Self::dot) in a single SIMD multiply, padding the fourth lane with zero.
+    #[cfg(feature = "simd")]
+    pub fn dot_simd(&self, other: Self) -> f32 {
+        let [ax, ay, az] = self.0;
+        let [bx, by, bz] = other.0;
+        (f32x4::from_array([ax, ay, az, 0.0]) * f32x4::from_array([bx, by, bz, 0.0])).reduce_sum()
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Vec4(pub [f32; 4]);
+
+impl Vec4 {
+    pub fn dot(&self, other: Self) -> f32 {
+        iter::zip(self.0, other.0).map(|(a, b)| a * b).sum()
+    }
+
+    #[cfg(feature = "simd")]
+    pub fn dot_simd(&self, other: Self) -> f32 {
+        (f32x4::from_array(self.0) * f32x4::from_array(other.0)).reduce_sum()
+    }
+}
+
+/// Half-line from `origin` along `direction`; distances are in units of `direction`'s length.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        Vec3(std::array::from_fn(|i| {
+            self.origin.0[i] + self.direction.0[i] * t
+        }))
+    }
+
+    /// Slab test; returns the entry distance, clamped to 0 when the origin is inside.
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        Aabb::new(min.0, max.0).intersect_ray(self.origin.0, self.direction.0.map(|c| 1.0 / c))
+    }
+
+    /// Distances at which the ray enters and leaves the sphere, the first negative when the
+    /// origin is inside; `None` if the sphere is missed or behind the origin.
+    pub fn intersect_sphere(&self, center: Vec3, radius: f32) -> Option<(f32, f32)> {
+        let offset = sub(self.origin.0, center.0);
+        let a = dot(self.direction.0, self.direction.0);
+        let half_b = dot(offset, self.direction.0);
+        let c = dot(offset, offset) - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 || a == 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (enter, leave) = ((-half_b - root) / a, (-half_b + root) / a);
+        (leave >= 0.0).then_some((enter, leave))
+    }
+
+    /// Distance to the triangle, hit from either side; see
+    /// [`intersect_triangle_barycentric`](Self::intersect_triangle_barycentric).
+    pub fn intersect_triangle(&self, v0: Vec3, v1: Vec3, v2: Vec3) -> Option<f32> {
+        self.intersect_triangle_barycentric(v0, v1, v2)
+            .map(|(t, _)| t)
+    }
+
+    /// Möller–Trumbore test, returning the distance and the barycentric coordinates `[u, v]`
+    /// of the hit, the weights of `v1` and `v2`; `v0` weighs `1 - u - v`.
+    pub fn intersect_triangle_barycentric(
+        &self,
+        v0: Vec3,
+        v1: Vec3,
+        v2: Vec3,
+    ) -> Option<(f32, [f32; 2])> {
+        let edge1 = sub(v1.0, v0.0);
+        let edge2 = sub(v2.0, v0.0);
+        let p = cross(self.direction.0, edge2);
+        let det = dot(edge1, p);
+        if det.abs() < f32::EPSILON {
+            // the ray is parallel to the triangle
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let offset = sub(self.origin.0, v0.0);
+        let u = dot(offset, p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = cross(offset, edge1);
+        let v = dot(self.direction.0, q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = dot(edge2, q) * inv_det;
+        (t > f32::EPSILON).then_some((t, [u, v]))
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    iter::zip(a, b).map(|(a, b)| a * b).sum()
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    v.map(|component| component / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{random_floats, random_signed_floats};
+
+    const SAMPLES: usize = 100;
+
+    /// Largest difference between the fraction of `samples` below `x` and `x` itself.
+    fn star_discrepancy(samples: &[f32]) -> f64 {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let n = sorted.len() as f64;
+        sorted
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (f64::from(x) - i as f64 / n).max((i + 1) as f64 / n - f64::from(x)))
+            .fold(0.0, f64::max)
+    }
+
+    /// [`star_discrepancy`] over the boxes from the origin to every corner built from sample
+    /// coordinates, which is where the largest difference lies.
+    fn star_discrepancy_2d(points: &[[f32; 2]]) -> f64 {
+        let n = points.len() as f64;
+        let corners = |axis: usize| points.iter().map(move |p| p[axis]).chain([1.0]);
+        let mut discrepancy = 0.0f64;
+        for a in corners(0) {
+            for b in corners(1) {
+                let area = f64::from(a) * f64::from(b);
+                let open = points.iter().filter(|p| p[0] < a && p[1] < b).count() as f64;
+                let closed = points.iter().filter(|p| p[0] <= a && p[1] <= b).count() as f64;
+                discrepancy = discrepancy
+                    .max((open / n - area).abs())
+                    .max((closed / n - area).abs());
+            }
+        }
+        discrepancy
+    }
+
+    #[test]
+    fn halton_values_are_in_unit_range_and_centered_ones_in_half_range() {
+        for base in [2, 3, 5, 7] {
+            let sequence = HaltonSequence::new(base);
+            for (n, value) in sequence.clone().take(1000).enumerate() {
+                assert!((0.0..1.0).contains(&value), "base {base}: {value}");
+                assert_eq!(sequence.nth_centered(n as u64), value - 0.5);
+            }
+        }
+        let base_2: Vec<_> = HaltonSequence::new(2).take(4).collect();
+        assert_eq!(base_2, [0.5, 0.25, 0.75, 0.125]);
+    }
+
+    #[test]
+    fn halton_is_less_discrepant_than_random_samples() {
+        let mut random = random_floats(0x2545_f491);
+        for base in [2, 3] {
+            let halton: Vec<_> = HaltonSequence::new(base).take(SAMPLES).collect();
+            let discrepancy = star_discrepancy(&halton);
+            // The van der Corput sequence of base b has N·D* ≤ log_b(N) + 1, while N uniform
+            // random samples expect about 0.87 / √N.
+            let bound = ((SAMPLES as f64).ln() / f64::from(base).ln() + 1.0) / SAMPLES as f64;
+            assert!(discrepancy <= bound, "base {base}: {discrepancy} > {bound}");
+            let uniform: Vec<_> = (0..SAMPLES).map(|_| random()).collect();
+            let random_discrepancy = star_discrepancy(&uniform);
+            assert!(
+                discrepancy < random_discrepancy,
+                "base {base}: {discrepancy} >= random {random_discrepancy}"
+            );
+        }
+
+        let halton: Vec<_> = HaltonSequence::pair(2, 3).take(SAMPLES).collect();
+        let uniform: Vec<_> = (0..SAMPLES).map(|_| [random(), random()]).collect();
+        let (discrepancy, random_discrepancy) =
+            (star_discrepancy_2d(&halton), star_discrepancy_2d(&uniform));
+        assert!(
+            discrepancy < random_discrepancy,
+            "2, 3: {discrepancy} >= random {random_discrepancy}"
+        );
+    }
+
+    fn assert_close<const N: usize>(actual: [f32; N], expected: [f32; N]) {
+        assert!(
+            iter::zip(actual, expected).all(|(a, e)| (a - e).abs() < 1e-5),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn slerp_ends_at_both_quaternions_and_halves_the_angle() {
+        let from = Quat::from_axis_angle([0.0, 0.0, 1.0], 0.2);
+        let to = Quat::from_axis_angle([0.0, 0.0, 1.0], 1.4);
+        assert_close(from.slerp(to, 0.0).0, from.0);
+        assert_close(from.slerp(to, 1.0).0, to.0);
+        assert_close(
+            from.slerp(to, 0.5).0,
+            Quat::from_axis_angle([0.0, 0.0, 1.0], 0.8).0,
+        );
+
+        // `-to` is the same rotation, reached the same way
+        let negated = Quat(to.0.map(|c| -c));
+        assert_close(
+            from.slerp(negated, 0.5).0,
+            Quat::from_axis_angle([0.0, 0.0, 1.0], 0.8).0,
+        );
+    }
+
+    #[test]
+    fn quarter_turns_rotate_the_principal_axes() {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let [x, y, z] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let around_x = Quat::from_axis_angle(x, quarter);
+        assert_close(around_x.rotate_vec3(x), x);
+        assert_close(around_x.rotate_vec3(y), z);
+        assert_close(around_x.rotate_vec3(z), [0.0, -1.0, 0.0]);
+        let around_y = Quat::from_axis_angle(y, quarter);
+        assert_close(around_y.rotate_vec3(z), x);
+        assert_close(around_y.rotate_vec3(x), [0.0, 0.0, -1.0]);
+        let around_z = Quat::from_axis_angle(z, quarter);
+        assert_close(around_z.rotate_vec3(x), y);
+        assert_close(around_z.rotate_vec3(y), [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn quaternion_matrix_and_product_agree_with_rotate_vec3() {
+        let a = Quat::from_euler_yxz(0.3, -1.1, 2.0);
+        let b = Quat::from_axis_angle(normalize([1.0, 2.0, -2.0]), 0.9);
+        let v = [0.5, -2.0, 3.0];
+        let matrix = Mat4(a.to_matrix()).mul_vec4([v[0], v[1], v[2], 1.0]);
+        assert_close([matrix[0], matrix[1], matrix[2]], a.rotate_vec3(v));
+        assert_close((a * b).rotate_vec3(v), a.rotate_vec3(b.rotate_vec3(v)));
+        assert_close(a.conjugate().rotate_vec3(a.rotate_vec3(v)), v);
+    }
+
+    #[test]
+    fn matrix_times_its_inverse_is_identity() {
+        let mut random = random_signed_floats(0x6a09_e667);
+        for _ in 0..100 {
+            // diagonally dominant, so the matrix is well conditioned
+            let m = Mat4(std::array::from_fn(|column| {
+                std::array::from_fn(|row| random() + if row == column { 4.0 } else { 0.0 })
+            }));
+            let inverse = m.inverse().unwrap();
+            for product in [m * inverse, inverse * m] {
+                for (column, identity) in iter::zip(product.0, Mat4::IDENTITY.0) {
+                    assert_close(column, identity);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert_eq!(Mat4::scale([1.0, 0.0, 1.0]).inverse(), None);
+    }
+
+    #[test]
+    fn transform_inverses_undo_the_transform() {
+        let transform = Mat4::translate([1.0, -2.0, 3.0])
+            * Mat4::from_quat(Quat::from_axis_angle([0.0, 1.0, 0.0], 0.5))
+            * Mat4::scale([2.0, 2.0, 0.5]);
+        let point = [0.25, 4.0, -1.0, 1.0];
+        let inverse = transform.inverse().unwrap();
+        assert_close(inverse.mul_vec4(transform.mul_vec4(point)), point);
+    }
+
+    #[test]
+    fn ray_hits_and_misses_a_box() {
+        let (min, max) = (Vec3([-1.0, -1.0, -1.0]), Vec3([1.0, 1.0, 1.0]));
+        let ray = Ray::new(Vec3([-5.0, 0.5, 0.0]), Vec3([1.0, 0.0, 0.0]));
+        assert_eq!(ray.intersect_aabb(min, max), Some(4.0));
+        let inside = Ray::new(Vec3([0.0, 0.0, 0.0]), Vec3([0.0, 0.0, 1.0]));
+        assert_eq!(inside.intersect_aabb(min, max), Some(0.0));
+        let above = Ray::new(Vec3([-5.0, 1.5, 0.0]), Vec3([1.0, 0.0, 0.0]));
+        assert_eq!(above.intersect_aabb(min, max), None);
+        let away = Ray::new(Vec3([-5.0, 0.5, 0.0]), Vec3([-1.0, 0.0, 0.0]));
+        assert_eq!(away.intersect_aabb(min, max), None);
+    }
+
+    #[test]
+    fn ray_hits_and_misses_a_sphere() {
+        let center = Vec3([0.0, 0.0, -10.0]);
+        let ray = Ray::new(Vec3::default(), Vec3([0.0, 0.0, -1.0]));
+        assert_eq!(ray.intersect_sphere(center, 2.0), Some((8.0, 12.0)));
+        let inside = Ray::new(center, Vec3([0.0, 0.0, -1.0]));
+        assert_eq!(inside.intersect_sphere(center, 2.0), Some((-2.0, 2.0)));
+        let beside = Ray::new(Vec3([3.0, 0.0, 0.0]), Vec3([0.0, 0.0, -1.0]));
+        assert_eq!(beside.intersect_sphere(center, 2.0), None);
+        let behind = Ray::new(Vec3::default(), Vec3([0.0, 0.0, 1.0]));
+        assert_eq!(behind.intersect_sphere(center, 2.0), None);
+    }
+
+    #[test]
+    fn ray_hits_a_triangle_at_the_barycentric_coordinates_of_the_hit() {
+        let (v0, v1, v2) = (
+            Vec3([0.0, 0.0, 0.0]),
+            Vec3([4.0, 0.0, 0.0]),
+            Vec3([0.0, 4.0, 0.0]),
+        );
+        let ray = Ray::new(Vec3([1.0, 2.0, 3.0]), Vec3([0.0, 0.0, -1.0]));
+        let (t, [u, v]) = ray.intersect_triangle_barycentric(v0, v1, v2).unwrap();
+        assert_close([t, u, v], [3.0, 0.25, 0.5]);
+        let hit: [f32; 3] =
+            std::array::from_fn(|i| (1.0 - u - v) * v0.0[i] + u * v1.0[i] + v * v2.0[i]);
+        assert_close(hit, ray.at(t).0);
+        // the back side is hit as well
+        let below = Ray::new(Vec3([1.0, 2.0, -3.0]), Vec3([0.0, 0.0, 1.0]));
+        assert_eq!(below.intersect_triangle(v0, v1, v2), Some(3.0));
+
+        let outside = Ray::new(Vec3([3.0, 3.0, 3.0]), Vec3([0.0, 0.0, -1.0]));
+        assert_eq!(outside.intersect_triangle(v0, v1, v2), None);
+        let parallel = Ray::new(Vec3([1.0, 1.0, 0.0]), Vec3([1.0, 0.0, 0.0]));
+        assert_eq!(parallel.intersect_triangle(v0, v1, v2), None);
+        let away = Ray::new(Vec3([1.0, 2.0, 3.0]), Vec3([0.0, 0.0, 1.0]));
+        assert_eq!(away.intersect_triangle(v0, v1, v2), None);
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod simd_tests {
+    use super::*;
+    use crate::testing::random_signed_floats;
+
+    fn assert_close(simd: f32, scalar: f32) {
+        assert!(
+            (simd - scalar).abs() <= f32::EPSILON * 4.0 * scalar.abs().max(1.0),
+            "simd {simd} != scalar {scalar}"
+        );
+    }
+
+    #[test]
+    fn mat4_mul_simd_matches_scalar() {
+        let mut random = random_signed_floats(0x9e37_79b9);
+        for _ in 0..1000 {
+            let a = Mat4(std::array::from_fn(|_| std::array::from_fn(|_| random())));
+            let b = Mat4(std::array::from_fn(|_| std::array::from_fn(|_| random())));
+            let simd = a.mul_simd(b);
+            let scalar = a.mul_scalar(b);
+            for (simd, scalar) in iter::zip(simd.0.as_flattened(), scalar.0.as_flattened()) {
+                assert_close(*simd, *scalar);
+            }
+        }
+    }
+
+    #[test]
+    fn dot_simd_matches_scalar() {
+        let mut random = random_signed_floats(0x85eb_ca6b);
+        for _ in 0..1000 {
+            let (a, b) = (
+                Vec3(std::array::from_fn(|_| random())),
+                Vec3(std::array::from_fn(|_| random())),
+            );
+            assert_close(a.dot_simd(b), a.dot(b));
+            let (a, b) = (
+                Vec4(std::array::from_fn(|_| random())),
+                Vec4(std::array::from_fn(|_| random())),
+            );
+            assert_close(a.dot_simd(b), a.dot(b));
+        }
+    }
+}
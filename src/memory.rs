@@ -0,0 +1,751 @@
+use ash::vk;
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::c_void;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::memory::allocator::suballocator::Suballocator;
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, AllocationType, BuddyAllocator, DeviceLayout, FreeListAllocator,
+    GenericMemoryAllocator, GenericMemoryAllocatorCreateInfo, MemoryAlloc,
+    MemoryAllocatePreference, MemoryAllocator, MemoryAllocatorError, MemoryTypeFilter,
+};
+use vulkano::memory::{
+    DedicatedAllocation, ExternalMemoryHandleTypes, MemoryHeapFlags, MemoryPropertyFlags,
+    MemoryRequirements,
+};
+use vulkano::{DeviceSize, Version, VulkanObject};
+
+const DEFAULT_WARN_THRESHOLD: f32 = 0.8;
+const PRESSURE_STEP_COUNT: DeviceSize = 64;
+const MIN_PRESSURE_STEP: DeviceSize = 1 << 20;
+const PRESSURE_ALIGNMENT: DeviceSize = 256;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct HeapBudget {
+    pub usage: u64,
+    pub budget: u64,
+}
+
+impl HeapBudget {
+    pub fn usage_ratio(&self) -> f32 {
+        if self.budget == 0 {
+            0.0
+        } else {
+            self.usage as f32 / self.budget as f32
+        }
+    }
+}
+
+struct HighUsageCallback {
+    threshold: f32,
+    callback: Box<dyn Fn(u32, f32) + Send + Sync>,
+    /// Heaps currently above the threshold, so the callback fires once per crossing.
+    triggered: Vec<bool>,
+}
+
+/// Per-heap memory usage and budget as reported by `VK_EXT_memory_budget`.
+///
+/// When the extension is not available, every heap reports zero usage and zero budget.
+pub struct MemoryBudget {
+    physical_device: Arc<PhysicalDevice>,
+    heaps: Vec<HeapBudget>,
+    callbacks: Vec<HighUsageCallback>,
+}
+
+impl MemoryBudget {
+    pub fn new(physical_device: Arc<PhysicalDevice>) -> Self {
+        let mut budget = Self {
+            heaps: vec![],
+            physical_device,
+            callbacks: vec![],
+        };
+        budget.on_high_usage(DEFAULT_WARN_THRESHOLD, |heap_index, ratio| {
+            warn!(
+                "memory heap {heap_index} is at {percent:.1}% of its budget",
+                percent = ratio * 100.0
+            )
+        });
+        budget.update();
+        budget
+    }
+
+    pub fn is_supported(physical_device: &PhysicalDevice) -> bool {
+        let instance = physical_device.instance();
+        physical_device.supported_extensions().ext_memory_budget
+            && (instance.api_version() >= Version::V1_1
+                || instance
+                    .enabled_extensions()
+                    .khr_get_physical_device_properties2)
+    }
+
+    /// Re-queries the budget and fires the callbacks of thresholds that have been crossed.
+    pub fn update(&mut self) {
+        self.heaps = heaps_or_zeros(
+            query(&self.physical_device),
+            self.physical_device.memory_properties().memory_heaps.len(),
+        );
+        debug!("memory budget: {heaps:?}", heaps = self.heaps);
+
+        for callback in &mut self.callbacks {
+            callback.notify(&self.heaps);
+        }
+    }
+
+    pub fn heaps(&self) -> &[HeapBudget] {
+        &self.heaps
+    }
+
+    /// Usage and budget summed over the device-local heaps.
+    pub fn device_local(&self) -> HeapBudget {
+        let heaps = &self.physical_device.memory_properties().memory_heaps;
+        self.heaps
+            .iter()
+            .zip(heaps)
+            .filter(|(_, heap)| heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL))
+            .fold(HeapBudget::default(), |total, (budget, _)| HeapBudget {
+                usage: total.usage + budget.usage,
+                budget: total.budget + budget.budget,
+            })
+    }
+
+    pub fn usage_ratio(&self, heap_index: u32) -> f32 {
+        self.heaps
+            .get(heap_index as usize)
+            .map_or(0.0, HeapBudget::usage_ratio)
+    }
+
+    /// Registers `callback(heap_index, usage_ratio)`, invoked on the update at which a heap's
+    /// usage ratio rises above `threshold`.
+    pub fn on_high_usage(
+        &mut self,
+        threshold: f32,
+        callback: impl Fn(u32, f32) + Send + Sync + 'static,
+    ) {
+        self.callbacks.push(HighUsageCallback {
+            threshold,
+            callback: Box::new(callback),
+            triggered: vec![],
+        });
+    }
+}
+
+impl HighUsageCallback {
+    fn notify(&mut self, heaps: &[HeapBudget]) {
+        self.triggered.resize(heaps.len(), false);
+        for (heap_index, heap) in heaps.iter().enumerate() {
+            let ratio = heap.usage_ratio();
+            let above = ratio > self.threshold;
+            if above && !self.triggered[heap_index] {
+                (self.callback)(heap_index as u32, ratio);
+            }
+            self.triggered[heap_index] = above;
+        }
+    }
+}
+
+fn heaps_or_zeros(queried: Option<Vec<HeapBudget>>, heap_count: usize) -> Vec<HeapBudget> {
+    queried.unwrap_or_else(|| vec![HeapBudget::default(); heap_count])
+}
+
+fn query(physical_device: &PhysicalDevice) -> Option<Vec<HeapBudget>> {
+    if !MemoryBudget::is_supported(physical_device) {
+        return None;
+    }
+
+    let instance = physical_device.instance();
+    let fns = instance.fns();
+    let mut budget = ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut properties = ash::vk::PhysicalDeviceMemoryProperties2 {
+        p_next: &mut budget as *mut _ as *mut c_void,
+        ..ash::vk::PhysicalDeviceMemoryProperties2::default()
+    };
+    unsafe {
+        if instance.api_version() >= Version::V1_1 {
+            (fns.v1_1.get_physical_device_memory_properties2)(
+                physical_device.handle(),
+                &mut properties,
+            );
+        } else {
+            (fns.khr_get_physical_device_properties2
+                .get_physical_device_memory_properties2_khr)(
+                physical_device.handle(),
+                &mut properties,
+            );
+        }
+    }
+
+    let heap_count = properties.memory_properties.memory_heap_count as usize;
+    Some(
+        budget.heap_usage[..heap_count]
+            .iter()
+            .zip(&budget.heap_budget[..heap_count])
+            .map(|(&usage, &budget)| HeapBudget { usage, budget })
+            .collect(),
+    )
+}
+
+#[derive(Debug)]
+pub struct MemoryPressureReport {
+    /// Bytes the run tried to reach.
+    pub target_bytes: DeviceSize,
+    /// Largest total that was allocated at the same time.
+    pub max_allocated_bytes: DeviceSize,
+    pub allocation_count: u32,
+    /// The error of the first failed allocation, if the target could not be reached.
+    pub failure: Option<MemoryAllocatorError>,
+}
+
+impl MemoryPressureReport {
+    pub fn reached_target(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Drives an allocator towards out-of-memory to exercise the fallback paths of the renderer.
+pub struct MemoryPressureTester;
+
+impl MemoryPressureTester {
+    /// Allocates memory from the largest device-local heap in increments until
+    /// `target_fraction` of its available memory is held or an allocation fails, then frees
+    /// everything again.
+    ///
+    /// Available memory is the heap budget when `VK_EXT_memory_budget` is supported and the
+    /// heap size otherwise.
+    pub fn run<A>(allocator: &A, target_fraction: f32) -> MemoryPressureReport
+    where
+        A: MemoryAllocator + ?Sized,
+    {
+        let (memory_type_bits, available) = largest_device_local_heap(allocator);
+        let target_bytes =
+            (available as f64 * target_fraction.clamp(0.0, 1.0) as f64) as DeviceSize;
+        let step = (target_bytes / PRESSURE_STEP_COUNT)
+            .max(MIN_PRESSURE_STEP)
+            .next_multiple_of(PRESSURE_ALIGNMENT);
+        debug!("memory pressure run: target {target_bytes} bytes in steps of {step}");
+
+        let mut allocations: Vec<MemoryAlloc> = vec![];
+        let mut allocated = 0;
+        let mut failure = None;
+        while allocated < target_bytes {
+            let size = step.min(target_bytes - allocated);
+            let requirements = MemoryRequirements {
+                layout: DeviceLayout::from_size_alignment(size, PRESSURE_ALIGNMENT).unwrap(),
+                memory_type_bits,
+                prefers_dedicated_allocation: false,
+                requires_dedicated_allocation: false,
+            };
+            match allocator.allocate(
+                requirements,
+                AllocationType::Linear,
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                    ..AllocationCreateInfo::default()
+                },
+                None,
+            ) {
+                Ok(allocation) => {
+                    allocations.push(allocation);
+                    allocated += size;
+                }
+                Err(e) => {
+                    warn!("memory pressure allocation failed after {allocated} bytes: {e}");
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let allocation_count = allocations.len() as u32;
+        for allocation in allocations {
+            // SAFETY: every allocation came from `allocator` and is freed exactly once.
+            unsafe { allocator.deallocate(allocation) };
+        }
+        MemoryPressureReport {
+            target_bytes,
+            max_allocated_bytes: allocated,
+            allocation_count,
+            failure,
+        }
+    }
+}
+
+/// Memory types of the largest device-local heap, as a `memory_type_bits` mask, and the bytes
+/// still available in it; zero bytes if the device has no device-local heap.
+fn largest_device_local_heap<A>(allocator: &A) -> (u32, DeviceSize)
+where
+    A: MemoryAllocator + ?Sized,
+{
+    let physical_device = allocator.device().physical_device();
+    let properties = physical_device.memory_properties();
+    let heaps = properties
+        .memory_heaps
+        .iter()
+        .map(|heap| (heap.size, heap.flags));
+    let Some((heap_index, available)) =
+        largest_device_local(heaps, query(physical_device).as_deref())
+    else {
+        return (0, 0);
+    };
+    let memory_type_bits = properties
+        .memory_types
+        .iter()
+        .enumerate()
+        .filter(|(_, memory_type)| memory_type.heap_index == heap_index)
+        .fold(0, |bits, (index, _)| bits | 1 << index);
+    (memory_type_bits, available)
+}
+
+/// Index of the largest of `heaps` that is device-local, with its budget minus its usage if
+/// `budgets` are known and its size otherwise.
+fn largest_device_local(
+    heaps: impl IntoIterator<Item = (DeviceSize, MemoryHeapFlags)>,
+    budgets: Option<&[HeapBudget]>,
+) -> Option<(u32, DeviceSize)> {
+    let (index, size) = heaps
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, flags))| flags.intersects(MemoryHeapFlags::DEVICE_LOCAL))
+        .max_by_key(|&(_, (size, _))| size)
+        .map(|(index, (size, _))| (index, size))?;
+    let available = budgets
+        .and_then(|budgets| budgets.get(index))
+        .map_or(size, |budget| budget.budget.saturating_sub(budget.usage));
+    Some((index as u32, available))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PoolConfigError {
+    /// The buddy allocator of the uniform pool splits blocks in halves down to single bytes.
+    BlockSizeNotPowerOfTwo(DeviceSize),
+}
+
+impl Display for PoolConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockSizeNotPowerOfTwo(size) => {
+                write!(f, "uniform pool block size {size} is not a power of two")
+            }
+        }
+    }
+}
+
+impl Error for PoolConfigError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PoolConfig {
+    /// Size of each `DeviceMemory` block the pool suballocates from; must be a power of two for
+    /// the uniform pool.
+    pub block_size_bytes: DeviceSize,
+    /// Blocks, including dedicated allocations, the pool may hold in one memory heap.
+    pub max_blocks_per_heap: u32,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            block_size_bytes: 64 * 1024 * 1024,
+            max_blocks_per_heap: 16,
+        }
+    }
+}
+
+/// A `GenericMemoryAllocator` that stops allocating new blocks once a heap holds
+/// `max_blocks_per_heap` of them.
+pub struct MemoryPool<S> {
+    inner: GenericMemoryAllocator<S>,
+    max_blocks_per_heap: u32,
+    /// Memory the inner allocator holds in each heap. Allocations that may create a block are
+    /// only counted when their memory is new, as the inner allocator can just as well
+    /// suballocate them from a block it already has.
+    blocks: Mutex<Vec<HashSet<vk::DeviceMemory>>>,
+}
+
+impl<S: Suballocator + Send + 'static> MemoryPool<S> {
+    fn new(device: Arc<Device>, config: PoolConfig) -> Self {
+        let memory_properties = device.physical_device().memory_properties();
+        let memory_types = &memory_properties.memory_types;
+        let block_sizes = vec![config.block_size_bytes; memory_types.len()];
+        // Same exclusions as `StandardMemoryAllocator::new_default`.
+        let memory_type_bits = memory_types
+            .iter()
+            .enumerate()
+            .filter(|(_, memory_type)| {
+                !memory_type.property_flags.intersects(
+                    MemoryPropertyFlags::LAZILY_ALLOCATED
+                        | MemoryPropertyFlags::PROTECTED
+                        | MemoryPropertyFlags::DEVICE_COHERENT
+                        | MemoryPropertyFlags::RDMA_CAPABLE,
+                )
+            })
+            .fold(0, |bits, (index, _)| bits | 1 << index);
+        let heap_count = memory_properties.memory_heaps.len();
+        let inner = GenericMemoryAllocator::new(
+            device,
+            GenericMemoryAllocatorCreateInfo {
+                block_sizes: &block_sizes,
+                memory_type_bits,
+                ..GenericMemoryAllocatorCreateInfo::default()
+            },
+        );
+        Self {
+            inner,
+            max_blocks_per_heap: config.max_blocks_per_heap,
+            blocks: Mutex::new(vec![HashSet::new(); heap_count]),
+        }
+    }
+
+    /// Blocks currently held in each memory heap.
+    pub fn block_counts(&self) -> Vec<u32> {
+        let blocks = self.blocks.lock().unwrap();
+        blocks.iter().map(|heap| heap.len() as u32).collect()
+    }
+
+    fn heap_index(&self, memory_type_index: u32) -> usize {
+        self.device()
+            .physical_device()
+            .memory_properties()
+            .memory_types[memory_type_index as usize]
+            .heap_index as usize
+    }
+
+    /// Runs `allocate` only if the heap of `memory_type_index` may hold one more block.
+    fn allocate_block(
+        &self,
+        memory_type_index: u32,
+        allocate: impl FnOnce() -> Result<MemoryAlloc, MemoryAllocatorError>,
+    ) -> Result<MemoryAlloc, MemoryAllocatorError> {
+        let mut blocks = self.blocks.lock().unwrap();
+        let heap_index = self.heap_index(memory_type_index);
+        if blocks[heap_index].len() as u32 >= self.max_blocks_per_heap {
+            return Err(MemoryAllocatorError::OutOfPoolMemory);
+        }
+        let allocation = allocate()?;
+        blocks[self.heap_index(allocation.device_memory.memory_type_index())]
+            .insert(allocation.device_memory.handle());
+        Ok(allocation)
+    }
+}
+
+unsafe impl<S: Suballocator + Send + 'static> MemoryAllocator for MemoryPool<S> {
+    fn find_memory_type_index(
+        &self,
+        memory_type_bits: u32,
+        filter: MemoryTypeFilter,
+    ) -> Option<u32> {
+        self.inner.find_memory_type_index(memory_type_bits, filter)
+    }
+
+    fn allocate_from_type(
+        &self,
+        memory_type_index: u32,
+        layout: DeviceLayout,
+        allocation_type: AllocationType,
+        never_allocate: bool,
+    ) -> Result<MemoryAlloc, MemoryAllocatorError> {
+        match self
+            .inner
+            .allocate_from_type(memory_type_index, layout, allocation_type, true)
+        {
+            Err(MemoryAllocatorError::OutOfPoolMemory) if !never_allocate => {
+                self.allocate_block(memory_type_index, || {
+                    self.inner
+                        .allocate_from_type(memory_type_index, layout, allocation_type, false)
+                })
+            }
+            result => result,
+        }
+    }
+
+    fn allocate(
+        &self,
+        requirements: MemoryRequirements,
+        allocation_type: AllocationType,
+        create_info: AllocationCreateInfo,
+        dedicated_allocation: Option<DedicatedAllocation<'_>>,
+    ) -> Result<MemoryAlloc, MemoryAllocatorError> {
+        let suballocated = self.inner.allocate(
+            requirements,
+            allocation_type,
+            AllocationCreateInfo {
+                allocate_preference: MemoryAllocatePreference::NeverAllocate,
+                ..create_info.clone()
+            },
+            dedicated_allocation,
+        );
+        match suballocated {
+            Err(
+                MemoryAllocatorError::OutOfPoolMemory
+                | MemoryAllocatorError::BlockSizeExceeded
+                | MemoryAllocatorError::DedicatedAllocationRequired,
+            ) if create_info.allocate_preference != MemoryAllocatePreference::NeverAllocate => {
+                let memory_type_index = self
+                    .find_memory_type_index(
+                        requirements.memory_type_bits & create_info.memory_type_bits,
+                        create_info.memory_type_filter,
+                    )
+                    .ok_or(MemoryAllocatorError::FindMemoryType)?;
+                self.allocate_block(memory_type_index, || {
+                    self.inner.allocate(
+                        requirements,
+                        allocation_type,
+                        create_info,
+                        dedicated_allocation,
+                    )
+                })
+            }
+            result => result,
+        }
+    }
+
+    fn allocate_dedicated(
+        &self,
+        memory_type_index: u32,
+        allocation_size: DeviceSize,
+        dedicated_allocation: Option<DedicatedAllocation<'_>>,
+        export_handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<MemoryAlloc, MemoryAllocatorError> {
+        self.allocate_block(memory_type_index, || {
+            self.inner.allocate_dedicated(
+                memory_type_index,
+                allocation_size,
+                dedicated_allocation,
+                export_handle_types,
+            )
+        })
+    }
+
+    unsafe fn deallocate(&self, allocation: MemoryAlloc) {
+        // Suballocated blocks are kept by the inner allocator; only dedicated memory goes away.
+        if allocation.suballocation.is_none() {
+            let heap_index = self.heap_index(allocation.device_memory.memory_type_index());
+            let mut blocks = self.blocks.lock().unwrap();
+            blocks[heap_index].remove(&allocation.device_memory.handle());
+        }
+        self.inner.deallocate(allocation);
+    }
+}
+
+unsafe impl<S> DeviceOwned for MemoryPool<S> {
+    fn device(&self) -> &Arc<Device> {
+        self.inner.device()
+    }
+}
+
+/// Separate memory pools for vertex buffers, uniform buffers and textures.
+///
+/// Keeping allocations of similar size and lifetime together reduces fragmentation compared to
+/// a single `StandardMemoryAllocator`. Uniforms, which are mostly small and equally sized, use a
+/// buddy allocator; the other pools use free lists.
+pub struct PooledMemoryAllocator {
+    vertices: Arc<MemoryPool<FreeListAllocator>>,
+    uniforms: Arc<MemoryPool<BuddyAllocator>>,
+    textures: Arc<MemoryPool<FreeListAllocator>>,
+}
+
+impl PooledMemoryAllocator {
+    pub fn new(
+        device: Arc<Device>,
+        vertices: PoolConfig,
+        uniforms: PoolConfig,
+        textures: PoolConfig,
+    ) -> Result<Self, PoolConfigError> {
+        check_uniform_pool(&uniforms)?;
+        Ok(Self {
+            vertices: Arc::new(MemoryPool::new(device.clone(), vertices)),
+            uniforms: Arc::new(MemoryPool::new(device.clone(), uniforms)),
+            textures: Arc::new(MemoryPool::new(device, textures)),
+        })
+    }
+
+    pub fn new_default(device: Arc<Device>) -> Self {
+        let uniforms = PoolConfig {
+            block_size_bytes: 4 * 1024 * 1024,
+            ..PoolConfig::default()
+        };
+        Self::new(
+            device,
+            PoolConfig::default(),
+            uniforms,
+            PoolConfig::default(),
+        )
+        .expect("default uniform block size is a power of two")
+    }
+
+    pub fn vertices(&self) -> &Arc<MemoryPool<FreeListAllocator>> {
+        &self.vertices
+    }
+
+    pub fn uniforms(&self) -> &Arc<MemoryPool<BuddyAllocator>> {
+        &self.uniforms
+    }
+
+    pub fn textures(&self) -> &Arc<MemoryPool<FreeListAllocator>> {
+        &self.textures
+    }
+}
+
+fn check_uniform_pool(config: &PoolConfig) -> Result<(), PoolConfigError> {
+    if config.block_size_bytes.is_power_of_two() {
+        Ok(())
+    } else {
+        Err(PoolConfigError::BlockSizeNotPowerOfTwo(
+            config.block_size_bytes,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+
+    #[test]
+    fn largest_device_local_heap_is_targeted() {
+        let heaps = [
+            (256 << 20, MemoryHeapFlags::DEVICE_LOCAL),
+            (16 << 30, MemoryHeapFlags::empty()),
+            (8 << 30, MemoryHeapFlags::DEVICE_LOCAL),
+        ];
+        assert_eq!(largest_device_local(heaps, None), Some((2, 8 << 30)));
+
+        let budgets = [
+            HeapBudget {
+                usage: 0,
+                budget: 256 << 20,
+            },
+            HeapBudget {
+                usage: 0,
+                budget: 12 << 30,
+            },
+            HeapBudget {
+                usage: 1 << 30,
+                budget: 6 << 30,
+            },
+        ];
+        assert_eq!(
+            largest_device_local(heaps, Some(&budgets)),
+            Some((2, 5 << 30))
+        );
+        assert_eq!(
+            largest_device_local([(1 << 30, MemoryHeapFlags::empty())], None),
+            None
+        );
+    }
+
+    #[test]
+    fn uniform_block_size_must_be_a_power_of_two() {
+        let config = |block_size_bytes| PoolConfig {
+            block_size_bytes,
+            ..PoolConfig::default()
+        };
+        assert_eq!(check_uniform_pool(&config(4 << 20)), Ok(()));
+        assert_eq!(check_uniform_pool(&PoolConfig::default()), Ok(()));
+        assert_eq!(
+            check_uniform_pool(&config(3 << 20)),
+            Err(PoolConfigError::BlockSizeNotPowerOfTwo(3 << 20))
+        );
+        assert_eq!(
+            check_uniform_pool(&config(0)),
+            Err(PoolConfigError::BlockSizeNotPowerOfTwo(0))
+        );
+    }
+
+    #[test]
+    fn missing_budget_falls_back_to_zeros() {
+        let heaps = heaps_or_zeros(None, 3);
+        assert_eq!(heaps, vec![HeapBudget::default(); 3]);
+        assert!(heaps.iter().all(|heap| heap.usage_ratio() == 0.0));
+
+        let queried = vec![HeapBudget {
+            usage: 1,
+            budget: 4,
+        }];
+        assert_eq!(heaps_or_zeros(Some(queried.clone()), 1), queried);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn budget_constructs_without_the_extension() {
+        let context = TestContext::new();
+        let physical_device = context.queue.device().physical_device().clone();
+        let budget = MemoryBudget::new(physical_device.clone());
+        assert_eq!(
+            budget.heaps().len(),
+            physical_device.memory_properties().memory_heaps.len()
+        );
+        if !MemoryBudget::is_supported(&physical_device) {
+            assert!(budget
+                .heaps()
+                .iter()
+                .all(|heap| *heap == HeapBudget::default()));
+            assert_eq!(budget.device_local(), HeapBudget::default());
+            assert_eq!(budget.usage_ratio(0), 0.0);
+        }
+    }
+
+    #[test]
+    fn high_usage_callback_fires_only_when_crossing_the_threshold() {
+        let fired = Arc::new(Mutex::new(vec![]));
+        let mut callback = HighUsageCallback {
+            threshold: 0.5,
+            callback: Box::new({
+                let fired = fired.clone();
+                move |heap_index, _| fired.lock().unwrap().push(heap_index)
+            }),
+            triggered: vec![],
+        };
+        let heaps = |usages: [u64; 2]| usages.map(|usage| HeapBudget { usage, budget: 100 });
+
+        callback.notify(&heaps([10, 40]));
+        assert!(fired.lock().unwrap().is_empty());
+        callback.notify(&heaps([60, 40]));
+        assert_eq!(*fired.lock().unwrap(), [0]);
+        callback.notify(&heaps([70, 50]));
+        assert_eq!(*fired.lock().unwrap(), [0]);
+        callback.notify(&heaps([30, 90]));
+        assert_eq!(*fired.lock().unwrap(), [0, 1]);
+        callback.notify(&heaps([80, 90]));
+        assert_eq!(*fired.lock().unwrap(), [0, 1, 0]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn suballocations_share_a_block() {
+        let context = TestContext::new();
+        let pooled = PooledMemoryAllocator::new_default(context.queue.device().clone());
+        let uniforms: Vec<_> = (0..1000)
+            .map(|_| {
+                Buffer::new_sized::<[u8; 64]>(
+                    pooled.uniforms().clone(),
+                    BufferCreateInfo {
+                        usage: BufferUsage::UNIFORM_BUFFER,
+                        ..BufferCreateInfo::default()
+                    },
+                    AllocationCreateInfo {
+                        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                            | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                        ..AllocationCreateInfo::default()
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(pooled.uniforms().block_counts().iter().sum::<u32>(), 1);
+        drop(uniforms);
+        assert_eq!(pooled.uniforms().block_counts().iter().sum::<u32>(), 1);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn pressure_run_completes() {
+        let context = TestContext::new();
+        let report = MemoryPressureTester::run(context.memory_allocator.as_ref(), 0.01);
+        assert!(report.reached_target(), "{:?}", report.failure);
+        assert!(report.max_allocated_bytes >= report.target_bytes);
+    }
+}
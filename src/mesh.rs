@@ -0,0 +1,985 @@
+use crate::vertex::Vertex3D;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Indexed triangle list kept on the host.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex3D>,
+    pub indices: Vec<u32>,
+}
+
+/// Parameters of [`Mesh::simplify_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct SimplificationConfig {
+    /// Fraction of the triangles to keep.
+    pub target_ratio: f32,
+    /// Never moves or removes vertices on open edges, so holes and outlines keep their shape.
+    pub preserve_boundary: bool,
+    /// Stops early once the cheapest collapse would cost more than this quadric error.
+    pub max_error: f32,
+}
+
+impl Default for SimplificationConfig {
+    fn default() -> Self {
+        Self {
+            target_ratio: 0.5,
+            preserve_boundary: true,
+            max_error: f32::INFINITY,
+        }
+    }
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex3D>, indices: Vec<u32>) -> Self {
+        Self { vertices, indices }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Reduces the mesh to about `target_triangle_ratio` of its triangles with the default
+    /// [`SimplificationConfig`].
+    pub fn simplify(&self, target_triangle_ratio: f32) -> Mesh {
+        self.simplify_with(&SimplificationConfig {
+            target_ratio: target_triangle_ratio,
+            ..SimplificationConfig::default()
+        })
+    }
+
+    /// Quadric error metric simplification (Garland and Heckbert).
+    ///
+    /// Vertices sharing a position are welded first, so seams of normals or texture
+    /// coordinates do not split the surface. Edges are then collapsed cheapest first, each
+    /// into the position that minimizes the summed squared distance to the planes of the
+    /// faces around both of its ends. Collapses that would make the surface non-manifold or
+    /// flip a face are skipped. Surviving vertices keep their normal and texture coordinates.
+    pub fn simplify_with(&self, config: &SimplificationConfig) -> Mesh {
+        Simplifier::new(self).run(config)
+    }
+
+    /// Sets UVs to the positions projected onto the plane orthogonal to `axis`, scaled so the
+    /// projected bounding rectangle spans `[0, 1]` in both directions.
+    pub fn project_uvs_planar(vertices: &mut [Vertex3D], axis: [f32; 3]) {
+        let Some(frame) = Projection::new(vertices, axis) else {
+            return;
+        };
+        for vertex in vertices {
+            let [u, v, _] = frame.local(vertex.position);
+            vertex.uv = [frame.fraction(0, u), frame.fraction(1, v)];
+        }
+    }
+
+    /// Sets UVs from a cylinder around `axis` through the center of the bounds: `u` is the
+    /// angle around the axis and `v` the height along it, both mapped to `[0, 1]`.
+    pub fn project_uvs_cylindrical(vertices: &mut [Vertex3D], axis: [f32; 3]) {
+        let Some(frame) = Projection::new(vertices, axis) else {
+            return;
+        };
+        for vertex in vertices {
+            let [x, y, height] = frame.local(vertex.position);
+            vertex.uv = [angle_fraction(x, y), frame.fraction(2, height)];
+        }
+    }
+
+    /// Sets UVs from a sphere around the center of the bounds with its poles on `axis`: `u`
+    /// is the longitude and `v` the angle from the pole `axis` points to, both mapped to
+    /// `[0, 1]`.
+    pub fn project_uvs_spherical(vertices: &mut [Vertex3D], axis: [f32; 3]) {
+        let Some(frame) = Projection::new(vertices, axis) else {
+            return;
+        };
+        for vertex in vertices {
+            let [x, y, z] = frame.local(vertex.position);
+            let latitude = normalize([x, y, z]).map_or(0.5, |[_, _, z]| {
+                z.clamp(-1.0, 1.0).acos() / std::f64::consts::PI
+            });
+            vertex.uv = [angle_fraction(x, y), latitude as f32];
+        }
+    }
+
+    /// Loads the `v`, `vt`, `vn` and `f` statements of a Wavefront OBJ file; polygons are
+    /// triangulated as fans and everything else is ignored.
+    pub fn from_obj(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse_obj(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse_obj(source: &str) -> io::Result<Self> {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut uvs: Vec<[f32; 2]> = vec![];
+        let mut normals: Vec<[f32; 3]> = vec![];
+        let mut mesh = Self::default();
+        let mut unique: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {message}", line_number + 1),
+                )
+            };
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => positions.push(floats(tokens).ok_or_else(|| invalid("bad vertex"))?),
+                Some("vt") => uvs.push(floats(tokens).ok_or_else(|| invalid("bad texcoord"))?),
+                Some("vn") => normals.push(floats(tokens).ok_or_else(|| invalid("bad normal"))?),
+                Some("f") => {
+                    let corners = tokens
+                        .map(|corner| {
+                            let mut refs = corner.split('/');
+                            let position = resolve(refs.next(), positions.len())
+                                .ok_or_else(|| invalid("bad face position index"))?;
+                            let uv = resolve(refs.next(), uvs.len());
+                            let normal = resolve(refs.next(), normals.len());
+                            let key = (position, uv, normal);
+                            let index = *unique.entry(key).or_insert_with(|| {
+                                mesh.vertices.push(Vertex3D {
+                                    position: positions[position],
+                                    normal: normal.map_or([0.0; 3], |i| normals[i]),
+                                    uv: uv.map_or([0.0; 2], |i| uvs[i]),
+                                    tangent: [0.0; 4],
+                                });
+                                mesh.vertices.len() as u32 - 1
+                            });
+                            Ok(index)
+                        })
+                        .collect::<io::Result<Vec<_>>>()?;
+                    if corners.len() < 3 {
+                        return Err(invalid("face has fewer than 3 corners"));
+                    }
+                    for i in 1..corners.len() - 1 {
+                        mesh.indices
+                            .extend([corners[0], corners[i], corners[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if normals.is_empty() {
+            Self::generate_smooth_normals(&mut mesh.vertices, &mesh.indices);
+        }
+        Self::compute_tangents(&mut mesh.vertices, &mesh.indices);
+        Ok(mesh)
+    }
+
+    /// Sets every vertex normal to the area-weighted average of the normals of the faces
+    /// using it. Vertices not used by any non-degenerate face keep their normal.
+    pub fn generate_smooth_normals(vertices: &mut [Vertex3D], indices: &[u32]) {
+        let mut sums = vec![[0.0; 3]; vertices.len()];
+        for triangle in indices.chunks_exact(3) {
+            let normal = face_normal(
+                [0, 1, 2].map(|i| vertices[triangle[i] as usize].position.map(f64::from)),
+            );
+            for &index in triangle {
+                let sum = &mut sums[index as usize];
+                for i in 0..3 {
+                    sum[i] += normal[i];
+                }
+            }
+        }
+        for (vertex, sum) in vertices.iter_mut().zip(sums) {
+            if let Some(normal) = normalize(sum) {
+                vertex.normal = normal.map(|c| c as f32);
+            }
+        }
+    }
+
+    /// Gives every triangle its face normal, duplicating vertices shared by faces that point
+    /// in different directions. Coplanar triangles sharing a vertex still share it.
+    pub fn generate_flat_normals(
+        vertices: &[Vertex3D],
+        indices: &[u32],
+    ) -> (Vec<Vertex3D>, Vec<u32>) {
+        let mut flat_vertices = vec![];
+        let mut flat_indices = Vec::with_capacity(indices.len());
+        let mut unique: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            let normal = normalize(face_normal(
+                [0, 1, 2].map(|i| vertices[triangle[i] as usize].position.map(f64::from)),
+            ))
+            // `+ 0.0` turns negative zeros positive, so coplanar faces share the key.
+            .map_or([0.0; 3], |normal| normal.map(|c| c as f32 + 0.0));
+            for &index in triangle {
+                let flat_index = *unique
+                    .entry((index, normal.map(f32::to_bits)))
+                    .or_insert_with(|| {
+                        flat_vertices.push(Vertex3D {
+                            normal,
+                            ..vertices[index as usize]
+                        });
+                        flat_vertices.len() as u32 - 1
+                    });
+                flat_indices.push(flat_index);
+            }
+        }
+        (flat_vertices, flat_indices)
+    }
+
+    /// Fills in [`Vertex3D::tangent`] from positions, normals and texture coordinates.
+    ///
+    /// Every triangle contributes the directions in which its `u` and `v` increase to its
+    /// corners. Per vertex, the summed tangent is made orthogonal to the normal and
+    /// normalized, and `w` is set to -1 where the summed bitangent points against
+    /// `cross(normal, tangent)`, i.e. where the UVs are mirrored. Vertices only touched by
+    /// triangles with degenerate UVs get an arbitrary, deterministic tangent orthogonal to
+    /// their normal.
+    pub fn compute_tangents(vertices: &mut [Vertex3D], indices: &[u32]) {
+        let mut tangents = vec![[0.0; 3]; vertices.len()];
+        let mut bitangents = vec![[0.0; 3]; vertices.len()];
+        for triangle in indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+            let [p0, p1, p2] = corners.map(|v| v.position.map(f64::from));
+            let [uv0, uv1, uv2] = corners.map(|v| v.uv.map(f64::from));
+            let (e1, e2) = (sub(p1, p0), sub(p2, p0));
+            let (du1, dv1) = (uv1[0] - uv0[0], uv1[1] - uv0[1]);
+            let (du2, dv2) = (uv2[0] - uv0[0], uv2[1] - uv0[1]);
+            let determinant = du1 * dv2 - du2 * dv1;
+            if determinant.abs() < 1e-12 {
+                continue;
+            }
+            let tangent = [0, 1, 2].map(|i| (e1[i] * dv2 - e2[i] * dv1) / determinant);
+            let bitangent = [0, 1, 2].map(|i| (e2[i] * du1 - e1[i] * du2) / determinant);
+            for &index in triangle {
+                let index = index as usize;
+                for i in 0..3 {
+                    tangents[index][i] += tangent[i];
+                    bitangents[index][i] += bitangent[i];
+                }
+            }
+        }
+
+        for ((vertex, tangent), bitangent) in vertices.iter_mut().zip(tangents).zip(bitangents) {
+            let normal = normalize(vertex.normal.map(f64::from)).unwrap_or([0.0, 0.0, 1.0]);
+            let along_normal = dot(normal, tangent);
+            let tangent = normalize([0, 1, 2].map(|i| tangent[i] - normal[i] * along_normal))
+                .unwrap_or_else(|| arbitrary_tangent(normal));
+            let w = if dot(cross(normal, tangent), bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.tangent = [tangent[0] as f32, tangent[1] as f32, tangent[2] as f32, w];
+        }
+    }
+}
+
+fn floats<'a, const N: usize>(mut tokens: impl Iterator<Item = &'a str>) -> Option<[f32; N]> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        *value = tokens.next()?.parse().ok()?;
+    }
+    Some(values)
+}
+
+/// Turns a one-based, possibly negative (relative to the end) OBJ index into a zero-based one;
+/// `None` for absent or out-of-range references.
+fn resolve(token: Option<&str>, len: usize) -> Option<usize> {
+    let index: isize = token.filter(|token| !token.is_empty())?.parse().ok()?;
+    let index = match index {
+        1.. => index as usize - 1,
+        ..=-1 => len.checked_sub(index.unsigned_abs())?,
+        0 => return None,
+    };
+    Some(index).filter(|&index| index < len)
+}
+
+/// Orthonormal frame with `axis` as third direction, centered on the bounds of some vertices
+/// in that frame.
+struct Projection {
+    basis: [[f64; 3]; 3],
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+impl Projection {
+    /// `None` for a zero `axis` or no vertices.
+    fn new(vertices: &[Vertex3D], axis: [f32; 3]) -> Option<Self> {
+        let axis = normalize(axis.map(f64::from))?;
+        let tangent = arbitrary_tangent(axis);
+        let mut frame = Self {
+            basis: [tangent, cross(axis, tangent), axis],
+            min: [0.0; 3],
+            max: [0.0; 3],
+        };
+        let mut points = vertices.iter().map(|vertex| frame.raw(vertex.position));
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), p| {
+            (
+                [0, 1, 2].map(|i| min[i].min(p[i])),
+                [0, 1, 2].map(|i| max[i].max(p[i])),
+            )
+        });
+        frame.min = min;
+        frame.max = max;
+        Some(frame)
+    }
+
+    fn raw(&self, position: [f32; 3]) -> [f64; 3] {
+        let position = position.map(f64::from);
+        self.basis.map(|direction| dot(direction, position))
+    }
+
+    /// Coordinates relative to the center of the bounds.
+    fn local(&self, position: [f32; 3]) -> [f64; 3] {
+        let raw = self.raw(position);
+        [0, 1, 2].map(|i| raw[i] - (self.min[i] + self.max[i]) / 2.0)
+    }
+
+    /// Where the local coordinate `value` lies between the bounds along direction `i`.
+    fn fraction(&self, i: usize, value: f64) -> f32 {
+        let extent = self.max[i] - self.min[i];
+        if extent > 1e-12 {
+            (value / extent + 0.5).clamp(0.0, 1.0) as f32
+        } else {
+            0.5
+        }
+    }
+}
+
+/// Angle of `(x, y)` around the origin mapped from `[-π, π]` to `[0, 1]`.
+fn angle_fraction(x: f64, y: f64) -> f32 {
+    (y.atan2(x) / std::f64::consts::TAU + 0.5).clamp(0.0, 1.0) as f32
+}
+
+/// Symmetric 4x4 matrix `Σ p pᵀ` over planes `p = [a, b, c, d]`, upper triangle row by row.
+#[derive(Clone, Copy, Default, Debug)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane([a, b, c]: [f64; 3], d: f64, weight: f64) -> Self {
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|q| q * weight),
+        )
+    }
+
+    fn add(self, other: Self) -> Self {
+        let mut sum = self.0;
+        for (s, o) in sum.iter_mut().zip(other.0) {
+            *s += o;
+        }
+        Self(sum)
+    }
+
+    fn error(&self, [x, y, z]: [f64; 3]) -> f64 {
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    /// Position of minimum error, if the quadric is not degenerate.
+    fn minimum(&self) -> Option<[f64; 3]> {
+        let q = &self.0;
+        let m = [[q[0], q[1], q[2]], [q[1], q[4], q[5]], [q[2], q[5], q[7]]];
+        let rhs = [-q[3], -q[6], -q[8]];
+        let det = dot(m[0], cross(m[1], m[2]));
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        // Cramer's rule on the symmetric system.
+        let column = |i: usize| [m[0][i], m[1][i], m[2][i]];
+        let solve = |replaced: usize| {
+            let mut columns = [column(0), column(1), column(2)];
+            columns[replaced] = rhs;
+            dot(columns[0], cross(columns[1], columns[2])) / det
+        };
+        Some([solve(0), solve(1), solve(2)])
+    }
+}
+
+/// Candidate collapse of node `b` into node `a`, ordered by cost.
+#[derive(Debug)]
+struct Collapse {
+    cost: f64,
+    a: usize,
+    b: usize,
+    versions: (u32, u32),
+    position: [f64; 3],
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.total_cmp(&other.cost)
+    }
+}
+
+/// Edge collapse state; nodes are welded vertex positions.
+struct Simplifier<'a> {
+    mesh: &'a Mesh,
+    /// Node of every mesh vertex, before any collapse.
+    vertex_nodes: Vec<usize>,
+    /// Node each collapsed node was merged into; `parent[n] == n` for live nodes.
+    parent: Vec<usize>,
+    positions: Vec<[f64; 3]>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    locked: Vec<bool>,
+    /// Live faces around every node.
+    node_faces: Vec<Vec<usize>>,
+    faces: Vec<[usize; 3]>,
+    live_faces: Vec<bool>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Self {
+        let mut welded: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions = vec![];
+        let vertex_nodes = mesh
+            .vertices
+            .iter()
+            .map(|vertex| {
+                *welded
+                    .entry(vertex.position.map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(vertex.position.map(f64::from));
+                        positions.len() - 1
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        let node_count = positions.len();
+        let mut quadrics = vec![Quadric::default(); node_count];
+        let mut node_faces = vec![vec![]; node_count];
+        let mut faces = vec![];
+        let mut edge_faces: HashMap<(usize, usize), u32> = HashMap::new();
+        for triangle in mesh.indices.chunks_exact(3) {
+            let face = [0, 1, 2].map(|i| vertex_nodes[triangle[i] as usize]);
+            if face[0] == face[1] || face[1] == face[2] || face[2] == face[0] {
+                continue;
+            }
+            let [p0, p1, p2] = face.map(|n| positions[n]);
+            let normal = cross(sub(p1, p0), sub(p2, p0));
+            let length = dot(normal, normal).sqrt();
+            if length > 0.0 {
+                let normal = normal.map(|c| c / length);
+                let plane = Quadric::plane(normal, -dot(normal, p0), length / 2.0);
+                for n in face {
+                    quadrics[n] = quadrics[n].add(plane);
+                }
+            }
+            for i in 0..3 {
+                node_faces[face[i]].push(faces.len());
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                *edge_faces.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+            faces.push(face);
+        }
+
+        let mut locked = vec![false; node_count];
+        for (&(a, b), &count) in &edge_faces {
+            if count != 2 {
+                locked[a] = true;
+                locked[b] = true;
+            }
+        }
+
+        Self {
+            mesh,
+            vertex_nodes,
+            parent: (0..node_count).collect(),
+            positions,
+            quadrics,
+            versions: vec![0; node_count],
+            locked,
+            node_faces,
+            live_faces: vec![true; faces.len()],
+            faces,
+        }
+    }
+
+    fn run(mut self, config: &SimplificationConfig) -> Mesh {
+        let target = (self.faces.len() as f64 * f64::from(config.target_ratio.clamp(0.0, 1.0)))
+            .ceil() as usize;
+        let mut remaining = self.faces.len();
+        let mut heap = BinaryHeap::new();
+        for a in 0..self.positions.len() {
+            for b in self.neighbours(a) {
+                if a < b {
+                    heap.extend(self.candidate(a, b, config).map(Reverse));
+                }
+            }
+        }
+
+        while remaining > target {
+            let Some(Reverse(collapse)) = heap.pop() else {
+                break;
+            };
+            let Collapse { a, b, .. } = collapse;
+            if self.parent[a] != a
+                || self.parent[b] != b
+                || collapse.versions != (self.versions[a], self.versions[b])
+            {
+                continue;
+            }
+            if collapse.cost > f64::from(config.max_error) {
+                break;
+            }
+            if !self.can_collapse(a, b, collapse.position) {
+                continue;
+            }
+            remaining -= self.collapse(a, b, collapse.position);
+            for n in self.neighbours(a) {
+                heap.extend(self.candidate(a, n, config).map(Reverse));
+            }
+        }
+        self.build()
+    }
+
+    fn neighbours(&self, node: usize) -> HashSet<usize> {
+        self.node_faces[node]
+            .iter()
+            .flat_map(|&f| self.faces[f])
+            .filter(|&n| n != node)
+            .collect()
+    }
+
+    fn candidate(&self, a: usize, b: usize, config: &SimplificationConfig) -> Option<Collapse> {
+        let (a, b, position) = if config.preserve_boundary {
+            match (self.locked[a], self.locked[b]) {
+                (true, true) => return None,
+                (true, false) => (a, b, self.positions[a]),
+                (false, true) => (b, a, self.positions[b]),
+                (false, false) => (a, b, self.optimal_position(a, b)),
+            }
+        } else {
+            (a, b, self.optimal_position(a, b))
+        };
+        Some(Collapse {
+            cost: self.quadrics[a].add(self.quadrics[b]).error(position),
+            a,
+            b,
+            versions: (self.versions[a], self.versions[b]),
+            position,
+        })
+    }
+
+    fn optimal_position(&self, a: usize, b: usize) -> [f64; 3] {
+        let quadric = self.quadrics[a].add(self.quadrics[b]);
+        quadric.minimum().unwrap_or_else(|| {
+            let (pa, pb) = (self.positions[a], self.positions[b]);
+            let midpoint = [0, 1, 2].map(|i| (pa[i] + pb[i]) / 2.0);
+            [pa, pb, midpoint]
+                .into_iter()
+                .min_by(|p, q| quadric.error(*p).total_cmp(&quadric.error(*q)))
+                .unwrap_or(midpoint)
+        })
+    }
+
+    /// Whether collapsing `b` into `a` at `position` keeps the surface manifold and no face
+    /// flips over.
+    fn can_collapse(&self, a: usize, b: usize, position: [f64; 3]) -> bool {
+        // Link condition: the only neighbours the ends share are the opposite corners of the
+        // faces on the edge.
+        let shared_faces = self.node_faces[a]
+            .iter()
+            .filter(|&&f| self.faces[f].contains(&b))
+            .count();
+        let shared_neighbours = self.neighbours(a).intersection(&self.neighbours(b)).count();
+        if shared_faces == 0 || shared_neighbours != shared_faces {
+            return false;
+        }
+
+        [a, b].into_iter().all(|moved| {
+            self.node_faces[moved]
+                .iter()
+                .filter(|&&f| !(self.faces[f].contains(&a) && self.faces[f].contains(&b)))
+                .all(|&f| {
+                    let corners = self.faces[f].map(|n| self.positions[n]);
+                    let moved_corners = self.faces[f].map(|n| {
+                        if n == moved {
+                            position
+                        } else {
+                            self.positions[n]
+                        }
+                    });
+                    dot(face_normal(corners), face_normal(moved_corners)) > 0.0
+                })
+        })
+    }
+
+    /// Merges `b` into `a` at `position` and returns the number of faces that disappeared.
+    fn collapse(&mut self, a: usize, b: usize, position: [f64; 3]) -> usize {
+        let mut removed = 0;
+        for f in std::mem::take(&mut self.node_faces[b]) {
+            if self.faces[f].contains(&a) {
+                self.live_faces[f] = false;
+                removed += 1;
+                for n in self.faces[f] {
+                    self.node_faces[n].retain(|&g| g != f);
+                }
+            } else {
+                for n in &mut self.faces[f] {
+                    if *n == b {
+                        *n = a;
+                    }
+                }
+                self.node_faces[a].push(f);
+            }
+        }
+        self.parent[b] = a;
+        self.positions[a] = position;
+        self.quadrics[a] = self.quadrics[a].add(self.quadrics[b]);
+        self.locked[a] |= self.locked[b];
+        self.versions[a] += 1;
+        removed
+    }
+
+    fn root(&self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            node = self.parent[node];
+        }
+        node
+    }
+
+    /// Emits the live faces with their original vertices, moved to their node's position and
+    /// compacted.
+    fn build(&self) -> Mesh {
+        let mut mesh = Mesh::default();
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+        let old = &self.mesh.indices;
+        let mut face = 0;
+        for triangle in old.chunks_exact(3) {
+            let nodes = [0, 1, 2].map(|i| self.vertex_nodes[triangle[i] as usize]);
+            if nodes[0] == nodes[1] || nodes[1] == nodes[2] || nodes[2] == nodes[0] {
+                continue;
+            }
+            let live = self.live_faces[face];
+            face += 1;
+            if !live {
+                continue;
+            }
+            for &index in triangle {
+                let new_index = *remap.entry(index).or_insert_with(|| {
+                    let mut vertex = self.mesh.vertices[index as usize];
+                    let node = self.root(self.vertex_nodes[index as usize]);
+                    vertex.position = self.positions[node].map(|c| c as f32);
+                    mesh.vertices.push(vertex);
+                    mesh.vertices.len() as u32 - 1
+                });
+                mesh.indices.push(new_index);
+            }
+        }
+        mesh
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(v, v).sqrt();
+    (length > 1e-12).then(|| v.map(|c| c / length))
+}
+
+/// Unit vector orthogonal to the unit vector `normal`, built from the axis least aligned
+/// with it.
+fn arbitrary_tangent(normal: [f64; 3]) -> [f64; 3] {
+    let axis = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let along_normal = dot(normal, axis);
+    normalize([0, 1, 2].map(|i| axis[i] - normal[i] * along_normal)).unwrap_or(axis)
+}
+
+fn face_normal([p0, p1, p2]: [[f64; 3]; 3]) -> [f64; 3] {
+    cross(sub(p1, p0), sub(p2, p0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_gen::MeshGen;
+
+    /// UV sphere with the seam and pole duplicates snapped onto each other; `sin(π)` and
+    /// `sin(2π)` are not exactly zero in `f32`.
+    fn closed_sphere(rings: u32, sectors: u32) -> Mesh {
+        let (mut vertices, indices) = MeshGen::sphere(1.0, rings, sectors);
+        for vertex in &mut vertices {
+            vertex.position = vertex.position.map(|c| (c * 1e5).round() / 1e5 + 0.0);
+        }
+        Mesh::new(vertices, indices)
+    }
+
+    /// Whether every edge, with vertices welded by position, is used once in each direction,
+    /// and the surface has the Euler characteristic of a sphere.
+    fn is_closed_manifold(mesh: &Mesh) -> bool {
+        let mut welded: HashMap<[u32; 3], usize> = HashMap::new();
+        let nodes: Vec<usize> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let next = welded.len();
+                *welded
+                    .entry(vertex.position.map(f32::to_bits))
+                    .or_insert(next)
+            })
+            .collect();
+        let mut edges = HashSet::new();
+        for triangle in mesh.indices.chunks_exact(3) {
+            let face = [0, 1, 2].map(|i| nodes[triangle[i] as usize]);
+            for i in 0..3 {
+                let edge = (face[i], face[(i + 1) % 3]);
+                if edge.0 == edge.1 || !edges.insert(edge) {
+                    return false;
+                }
+            }
+        }
+        let all_paired = edges.iter().all(|&(a, b)| edges.contains(&(b, a)));
+        let euler = welded.len() as i64 - edges.len() as i64 / 2 + mesh.triangle_count() as i64;
+        all_paired && euler == 2
+    }
+
+    #[test]
+    fn simplified_sphere_stays_closed() {
+        let sphere = closed_sphere(16, 32);
+        assert!(is_closed_manifold(&sphere));
+        assert!(sphere.triangle_count() >= 900);
+
+        let simplified = sphere.simplify(0.5);
+        assert!(simplified.triangle_count() <= sphere.triangle_count() / 2);
+        assert!(simplified.triangle_count() > 0);
+        assert!(is_closed_manifold(&simplified));
+    }
+
+    #[test]
+    fn max_error_stops_simplification() {
+        let sphere = closed_sphere(16, 32);
+        let simplified = sphere.simplify_with(&SimplificationConfig {
+            target_ratio: 0.0,
+            max_error: 0.0,
+            ..SimplificationConfig::default()
+        });
+        assert_eq!(simplified.triangle_count(), sphere.triangle_count());
+    }
+
+    fn assert_orthonormal_tbn(vertices: &[Vertex3D]) {
+        for vertex in vertices {
+            let normal = vertex.normal.map(f64::from);
+            let [x, y, z, w] = vertex.tangent;
+            let tangent = [x, y, z].map(f64::from);
+            assert!((dot(tangent, tangent) - 1.0).abs() < 1e-5, "{vertex:?}");
+            assert!(dot(normal, tangent).abs() < 1e-5, "{vertex:?}");
+            assert!(w == 1.0 || w == -1.0, "{vertex:?}");
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_normals() {
+        for segments in 3..12 {
+            let size = segments as f32 * 0.37;
+            for (vertices, _) in [
+                MeshGen::sphere(size, segments, segments + 1),
+                MeshGen::cuboid([size, 1.0, 2.0 * size]),
+                MeshGen::cylinder(size, 2.0, segments),
+                MeshGen::torus(2.0 * size, size, segments + 2, segments),
+            ] {
+                assert_orthonormal_tbn(&vertices);
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_the_bitangent_sign() {
+        let (mut vertices, indices) = MeshGen::cuboid([1.0; 3]);
+        let signs: Vec<_> = vertices.iter().map(|vertex| vertex.tangent[3]).collect();
+        for vertex in &mut vertices {
+            vertex.uv[0] = 1.0 - vertex.uv[0];
+        }
+        Mesh::compute_tangents(&mut vertices, &indices);
+        assert_orthonormal_tbn(&vertices);
+        for (vertex, sign) in vertices.iter().zip(signs) {
+            assert_eq!(vertex.tangent[3], -sign);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_get_a_deterministic_tangent() {
+        let (mut vertices, indices) = MeshGen::sphere(1.0, 6, 8);
+        for vertex in &mut vertices {
+            vertex.uv = [0.5; 2];
+        }
+        Mesh::compute_tangents(&mut vertices, &indices);
+        assert_orthonormal_tbn(&vertices);
+        let first: Vec<_> = vertices.iter().map(|vertex| vertex.tangent).collect();
+        Mesh::compute_tangents(&mut vertices, &indices);
+        let second = vertices.iter().map(|vertex| vertex.tangent);
+        assert!(first.into_iter().eq(second));
+    }
+
+    /// The cube of [`MeshGen::cuboid`] with its 24 vertices welded into 8 corners.
+    fn welded_cube() -> (Vec<Vertex3D>, Vec<u32>) {
+        let (vertices, indices) = MeshGen::cuboid([1.0; 3]);
+        let mut corners: Vec<Vertex3D> = vec![];
+        let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+        let indices = indices
+            .iter()
+            .map(|&index| {
+                let vertex = vertices[index as usize];
+                *welded
+                    .entry(vertex.position.map(f32::to_bits))
+                    .or_insert_with(|| {
+                        corners.push(vertex);
+                        corners.len() as u32 - 1
+                    })
+            })
+            .collect();
+        (corners, indices)
+    }
+
+    fn distinct_normals(vertices: &[Vertex3D]) -> HashSet<[u32; 3]> {
+        vertices
+            .iter()
+            .map(|vertex| vertex.normal.map(f32::to_bits))
+            .collect()
+    }
+
+    #[test]
+    fn smooth_normals_of_a_cube_with_split_faces_follow_the_faces() {
+        let (mut vertices, indices) = MeshGen::cuboid([1.0; 3]);
+        for vertex in &mut vertices {
+            vertex.normal = [0.0; 3];
+        }
+        Mesh::generate_smooth_normals(&mut vertices, &indices);
+        assert_eq!(distinct_normals(&vertices).len(), 6);
+        for vertex in &vertices {
+            let [x, y, z] = vertex.normal;
+            assert_eq!(x.abs() + y.abs() + z.abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn smooth_normals_of_a_welded_cube_point_away_from_the_corners() {
+        let (mut vertices, indices) = welded_cube();
+        assert_eq!(vertices.len(), 8);
+        Mesh::generate_smooth_normals(&mut vertices, &indices);
+        for vertex in &vertices {
+            let normal = vertex.normal.map(f64::from);
+            assert!((dot(normal, normal) - 1.0).abs() < 1e-6);
+            // area weighted, so only the octant is exact: the two triangles of a face do not
+            // both touch every corner
+            for (n, p) in normal.iter().zip(vertex.position) {
+                assert_eq!(n.signum(), f64::from(p).signum());
+            }
+        }
+    }
+
+    #[test]
+    fn flat_normals_split_a_welded_cube_into_faces() {
+        let (vertices, indices) = welded_cube();
+        let (flat_vertices, flat_indices) = Mesh::generate_flat_normals(&vertices, &indices);
+        assert_eq!(flat_vertices.len(), 24);
+        assert_eq!(flat_indices.len(), indices.len());
+        assert_eq!(distinct_normals(&flat_vertices).len(), 6);
+        for triangle in flat_indices.chunks_exact(3) {
+            let normal = |i: usize| flat_vertices[triangle[i] as usize].normal;
+            assert!(normal(0) == normal(1) && normal(1) == normal(2));
+        }
+    }
+
+    #[test]
+    fn projected_uvs_of_a_unit_sphere_are_in_unit_range() {
+        let (mut vertices, _) = MeshGen::sphere(1.0, 16, 16);
+        for project in [
+            Mesh::project_uvs_planar as fn(&mut [Vertex3D], [f32; 3]),
+            Mesh::project_uvs_cylindrical,
+            Mesh::project_uvs_spherical,
+        ] {
+            for axis in [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.3, -0.5, 0.8]] {
+                for vertex in &mut vertices {
+                    vertex.uv = [-1.0; 2];
+                }
+                project(&mut vertices, axis);
+                for vertex in &vertices {
+                    assert!(
+                        vertex.uv.iter().all(|c| (0.0..=1.0).contains(c)),
+                        "{:?} along {axis:?}",
+                        vertex.uv
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn planar_uvs_span_the_bounds() {
+        let (mut vertices, _) = MeshGen::cuboid([2.0, 1.0, 3.0]);
+        Mesh::project_uvs_planar(&mut vertices, [0.0, 0.0, 1.0]);
+        for axis in 0..2 {
+            let (min, max) = vertices
+                .iter()
+                .fold((1.0_f32, 0.0_f32), |(min, max), vertex| {
+                    (min.min(vertex.uv[axis]), max.max(vertex.uv[axis]))
+                });
+            assert_eq!((min, max), (0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn spherical_v_runs_from_the_pole_the_axis_points_to() {
+        let mut vertices = [
+            [0.0, 1.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [-1.0, 0.0, 0.0],
+        ]
+        .map(|position| Vertex3D {
+            position,
+            ..Vertex3D::default()
+        });
+        Mesh::project_uvs_spherical(&mut vertices, [0.0, 1.0, 0.0]);
+        assert_eq!(vertices.map(|vertex| vertex.uv[1]), [0.0, 1.0, 0.5, 0.5]);
+    }
+}
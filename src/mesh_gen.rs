@@ -0,0 +1,506 @@
+use crate::mesh::Mesh;
+use crate::vertex::Vertex3D;
+use std::f32::consts::{PI, TAU};
+
+/// Generators of indexed primitive meshes with normals, UVs and tangents.
+///
+/// All shapes are centered on the origin with `y` up and counter-clockwise front faces. Round
+/// shapes repeat the first column of vertices at `u = 1` so textures wrap without a seam.
+pub struct MeshGen;
+
+impl MeshGen {
+    /// UV sphere with `(rings + 1) * (sectors + 1)` vertices; `u` runs around `y` and `v`
+    /// from the top pole to the bottom one.
+    pub fn sphere(radius: f32, rings: u32, sectors: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+        assert!(
+            rings >= 2 && sectors >= 3,
+            "sphere needs at least 2 rings and 3 sectors"
+        );
+        let rows = (0..=rings)
+            .map(|ring| {
+                let fraction = ring as f32 / rings as f32;
+                (fraction * PI, 0.0, fraction)
+            })
+            .collect::<Vec<_>>();
+        finish(lathe(&rows, radius, sectors))
+    }
+
+    /// Box with one quad of 4 vertices per face, each face fully covering the texture.
+    pub fn cuboid(half_extents: [f32; 3]) -> (Vec<Vertex3D>, Vec<u32>) {
+        // Face normal, then the directions of increasing `u` and decreasing `v`.
+        const FACES: [[[f32; 3]; 3]; 6] = [
+            [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+            [[-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+            [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+            [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+            [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            [[0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        ];
+        const CORNERS: [[f32; 2]; 4] = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for [normal, u, v] in FACES {
+            let base = vertices.len() as u32;
+            vertices.extend(CORNERS.map(|[s, t]| Vertex3D {
+                position: [0, 1, 2].map(|i| (normal[i] + s * u[i] + t * v[i]) * half_extents[i]),
+                normal,
+                uv: [(s + 1.0) / 2.0, (1.0 - t) / 2.0],
+                ..Vertex3D::default()
+            }));
+            indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+        }
+        finish((vertices, indices))
+    }
+
+    /// Capped cylinder along `y`. The side wraps the texture once around with `v` from top
+    /// to bottom; each cap is a disc inscribed in the texture.
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+        assert!(segments >= 3, "cylinder needs at least 3 segments");
+        let half_height = height / 2.0;
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for (y, v) in [(half_height, 0.0), (-half_height, 1.0)] {
+            vertices.extend((0..=segments).map(|segment| {
+                let u = segment as f32 / segments as f32;
+                let (sin, cos) = (u * TAU).sin_cos();
+                Vertex3D {
+                    position: [cos * radius, y, sin * radius],
+                    normal: [cos, 0.0, sin],
+                    uv: [u, v],
+                    ..Vertex3D::default()
+                }
+            }));
+        }
+        let columns = segments + 1;
+        for segment in 0..segments {
+            let (top, bottom) = (segment, segment + columns);
+            indices.extend([top, top + 1, bottom, top + 1, bottom + 1, bottom]);
+        }
+
+        cap(
+            &mut vertices,
+            &mut indices,
+            radius,
+            half_height,
+            segments,
+            true,
+        );
+        cap(
+            &mut vertices,
+            &mut indices,
+            radius,
+            -half_height,
+            segments,
+            false,
+        );
+        finish((vertices, indices))
+    }
+
+    /// Cone along `y` with its apex at the top and a capped base. The side wraps the texture
+    /// once around with `v` from the apex to the base; the base is a disc inscribed in the
+    /// texture.
+    pub fn cone(base_radius: f32, height: f32, segments: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+        assert!(segments >= 3, "cone needs at least 3 segments");
+        let half_height = height / 2.0;
+        let slant = (base_radius * base_radius + height * height).sqrt();
+        let side_normal = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            [
+                cos * height / slant,
+                base_radius / slant,
+                sin * height / slant,
+            ]
+        };
+        // Every sector gets its own apex vertex with the normal of the middle of the sector.
+        let mut vertices = (0..segments)
+            .map(|segment| Vertex3D {
+                position: [0.0, half_height, 0.0],
+                normal: side_normal((segment as f32 + 0.5) / segments as f32 * TAU),
+                uv: [(segment as f32 + 0.5) / segments as f32, 0.0],
+                ..Vertex3D::default()
+            })
+            .collect::<Vec<_>>();
+        vertices.extend((0..=segments).map(|segment| {
+            let u = segment as f32 / segments as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            Vertex3D {
+                position: [cos * base_radius, -half_height, sin * base_radius],
+                normal: side_normal(u * TAU),
+                uv: [u, 1.0],
+                ..Vertex3D::default()
+            }
+        }));
+        let mut indices = vec![];
+        for segment in 0..segments {
+            let base = segments + segment;
+            indices.extend([segment, base + 1, base]);
+        }
+        cap(
+            &mut vertices,
+            &mut indices,
+            base_radius,
+            -half_height,
+            segments,
+            false,
+        );
+        finish((vertices, indices))
+    }
+
+    /// Torus around `y`: a tube of radius `minor_radius` whose center circle has radius
+    /// `major_radius`. `u` runs around `y` and `v` around the tube, starting on the outside.
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> (Vec<Vertex3D>, Vec<u32>) {
+        assert!(
+            major_segments >= 3 && minor_segments >= 3,
+            "torus needs at least 3 segments in both directions"
+        );
+        let mut vertices = vec![];
+        for minor in 0..=minor_segments {
+            let v = minor as f32 / minor_segments as f32;
+            let (minor_sin, minor_cos) = (v * TAU).sin_cos();
+            vertices.extend((0..=major_segments).map(|major| {
+                let u = major as f32 / major_segments as f32;
+                let (major_sin, major_cos) = (u * TAU).sin_cos();
+                let normal = [minor_cos * major_cos, minor_sin, minor_cos * major_sin];
+                Vertex3D {
+                    position: [
+                        major_radius * major_cos + minor_radius * normal[0],
+                        minor_radius * normal[1],
+                        major_radius * major_sin + minor_radius * normal[2],
+                    ],
+                    normal,
+                    uv: [u, v],
+                    ..Vertex3D::default()
+                }
+            }));
+        }
+        let indices = grid_indices(minor_segments, major_segments);
+        finish((vertices, indices))
+    }
+
+    /// Möbius strip of the given `width` around a center circle of radius 1 in the `xz`
+    /// plane, making half a turn around its center line per loop.
+    ///
+    /// `u` runs along the loop and `v` across the strip. Being one-sided, the strip meets
+    /// itself mirrored: the vertices at `u = 1` coincide with those at `u = 0` with `v`
+    /// flipped and opposite normals, so textures only continue seamlessly if they are
+    /// symmetric in `v`.
+    pub fn mobius_strip(width: f32, segments: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+        assert!(segments >= 3, "Möbius strip needs at least 3 segments");
+        let half_width = width / 2.0;
+        let mut vertices = vec![];
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let angle = u * TAU;
+            let (sin, cos) = angle.sin_cos();
+            let (half_sin, half_cos) = (angle / 2.0).sin_cos();
+            // Direction across the strip and its derivative by `angle`.
+            let across = [half_cos * cos, half_sin, half_cos * sin];
+            let across_derivative = [
+                -half_sin * cos / 2.0 - half_cos * sin,
+                half_cos / 2.0,
+                -half_sin * sin / 2.0 + half_cos * cos,
+            ];
+            vertices.extend([-1.0f32, 1.0].map(|offset| {
+                let along = [
+                    -sin + offset * half_width * across_derivative[0],
+                    offset * half_width * across_derivative[1],
+                    cos + offset * half_width * across_derivative[2],
+                ];
+                let normal = [
+                    along[1] * across[2] - along[2] * across[1],
+                    along[2] * across[0] - along[0] * across[2],
+                    along[0] * across[1] - along[1] * across[0],
+                ];
+                let length = normal.iter().map(|c| c * c).sum::<f32>().sqrt();
+                Vertex3D {
+                    position: [
+                        cos + offset * half_width * across[0],
+                        offset * half_width * across[1],
+                        sin + offset * half_width * across[2],
+                    ],
+                    normal: normal.map(|c| c / length),
+                    uv: [u, (offset + 1.0) / 2.0],
+                    ..Vertex3D::default()
+                }
+            }));
+        }
+        let indices = grid_indices(segments, 1);
+        finish((vertices, indices))
+    }
+
+    /// Cylinder of length `2 * half_height` along `y` with hemispherical ends of `rings` rings
+    /// each. `v` runs from the top pole to the bottom one proportionally to the distance along
+    /// the surface.
+    pub fn capsule(
+        radius: f32,
+        half_height: f32,
+        rings: u32,
+        sectors: u32,
+    ) -> (Vec<Vertex3D>, Vec<u32>) {
+        assert!(
+            rings >= 1 && sectors >= 3,
+            "capsule needs at least 1 ring and 3 sectors"
+        );
+        let arc = radius * PI / 2.0;
+        let length = 2.0 * arc + 2.0 * half_height;
+        let hemisphere = |offset: f32, first_angle: f32, first_distance: f32| {
+            (0..=rings).map(move |ring| {
+                let fraction = ring as f32 / rings as f32;
+                let angle = first_angle + fraction * PI / 2.0;
+                let distance = first_distance + fraction * arc;
+                (angle, offset, distance / length)
+            })
+        };
+        let rows = hemisphere(half_height, 0.0, 0.0)
+            .chain(hemisphere(-half_height, PI / 2.0, arc + 2.0 * half_height))
+            .collect::<Vec<_>>();
+        finish(lathe(&rows, radius, sectors))
+    }
+}
+
+/// Revolves rows of `(polar angle from +y, y offset, v)` around `y`, connecting consecutive
+/// rows. Triangles collapsed onto a pole are left out.
+fn lathe(rows: &[(f32, f32, f32)], radius: f32, sectors: u32) -> (Vec<Vertex3D>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(rows.len() * (sectors as usize + 1));
+    for &(angle, offset, v) in rows {
+        let (ring_sin, ring_cos) = angle.sin_cos();
+        vertices.extend((0..=sectors).map(|sector| {
+            let u = sector as f32 / sectors as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            let normal = [ring_sin * cos, ring_cos, ring_sin * sin];
+            Vertex3D {
+                position: [
+                    normal[0] * radius,
+                    normal[1] * radius + offset,
+                    normal[2] * radius,
+                ],
+                normal,
+                uv: [u, v],
+                ..Vertex3D::default()
+            }
+        }));
+    }
+
+    let columns = sectors + 1;
+    let mut indices = vec![];
+    for row in 0..rows.len() as u32 - 1 {
+        let top_is_pole = rows[row as usize].0.sin().abs() < 1e-6;
+        let bottom_is_pole = rows[row as usize + 1].0.sin().abs() < 1e-6;
+        for sector in 0..sectors {
+            let top = row * columns + sector;
+            let bottom = top + columns;
+            if !top_is_pole {
+                indices.extend([top, top + 1, bottom]);
+            }
+            if !bottom_is_pole {
+                indices.extend([top + 1, bottom + 1, bottom]);
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// Triangles over a grid of `(rows + 1) * (columns + 1)` vertices stored row by row, facing
+/// the direction of `cross(next row - vertex, next column - vertex)`.
+fn grid_indices(rows: u32, columns: u32) -> Vec<u32> {
+    let stride = columns + 1;
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| row * stride + column))
+        .flat_map(|vertex| {
+            let next_row = vertex + stride;
+            [
+                vertex,
+                next_row,
+                vertex + 1,
+                vertex + 1,
+                next_row,
+                next_row + 1,
+            ]
+        })
+        .collect()
+}
+
+/// Appends a disc of radius `radius` at height `y` facing up or down, inscribed in the
+/// texture.
+fn cap(
+    vertices: &mut Vec<Vertex3D>,
+    indices: &mut Vec<u32>,
+    radius: f32,
+    y: f32,
+    segments: u32,
+    up: bool,
+) {
+    let normal_y = if up { 1.0 } else { -1.0 };
+    let disc_uv = |cos: f32, sin: f32| [0.5 + cos / 2.0, 0.5 - sin * normal_y / 2.0];
+    let center = vertices.len() as u32;
+    vertices.push(Vertex3D {
+        position: [0.0, y, 0.0],
+        normal: [0.0, normal_y, 0.0],
+        uv: disc_uv(0.0, 0.0),
+        ..Vertex3D::default()
+    });
+    vertices.extend((0..segments).map(|segment| {
+        let (sin, cos) = (segment as f32 / segments as f32 * TAU).sin_cos();
+        Vertex3D {
+            position: [cos * radius, y, sin * radius],
+            normal: [0.0, normal_y, 0.0],
+            uv: disc_uv(cos, sin),
+            ..Vertex3D::default()
+        }
+    }));
+    for segment in 0..segments {
+        let (current, next) = (center + 1 + segment, center + 1 + (segment + 1) % segments);
+        if up {
+            indices.extend([center, next, current]);
+        } else {
+            indices.extend([center, current, next]);
+        }
+    }
+}
+
+fn finish((mut vertices, indices): (Vec<Vertex3D>, Vec<u32>)) -> (Vec<Vertex3D>, Vec<u32>) {
+    Mesh::compute_tangents(&mut vertices, &indices);
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+    }
+
+    fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+
+    fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    /// Checks indices, unit normals, UVs in `[0, 1]`, and that every face is counter-clockwise
+    /// seen from the side its vertex normals point to.
+    fn assert_well_formed((vertices, indices): &(Vec<Vertex3D>, Vec<u32>)) {
+        assert_eq!(indices.len() % 3, 0);
+        assert!(indices
+            .iter()
+            .all(|&index| (index as usize) < vertices.len()));
+        for vertex in vertices {
+            assert!(
+                (dot(vertex.normal, vertex.normal) - 1.0).abs() < 1e-5,
+                "{vertex:?}"
+            );
+            assert!(
+                vertex.uv.iter().all(|c| (0.0..=1.0).contains(c)),
+                "{vertex:?}"
+            );
+        }
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+            let face = cross(sub(b.position, a.position), sub(c.position, a.position));
+            let normals = [0, 1, 2].map(|i| a.normal[i] + b.normal[i] + c.normal[i]);
+            assert!(dot(face, normals) > 0.0, "{triangle:?} faces inwards");
+        }
+    }
+
+    #[test]
+    fn sphere_has_a_vertex_per_ring_and_sector_crossing() {
+        let sphere = MeshGen::sphere(2.0, 16, 16);
+        assert_eq!(sphere.0.len(), 17 * 17);
+        assert_eq!(sphere.1.len() / 3, 2 * 16 * 16 - 2 * 16);
+        assert_well_formed(&sphere);
+        for vertex in &sphere.0 {
+            for (position, normal) in vertex.position.iter().zip(vertex.normal) {
+                assert!((position - 2.0 * normal).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn cuboid_has_four_vertices_per_face() {
+        let cuboid = MeshGen::cuboid([1.0, 2.0, 3.0]);
+        assert_eq!(cuboid.0.len(), 24);
+        assert_eq!(cuboid.1.len(), 36);
+        assert_well_formed(&cuboid);
+        for vertex in &cuboid.0 {
+            assert_eq!(vertex.position.map(f32::abs), [1.0, 2.0, 3.0]);
+        }
+    }
+
+    #[test]
+    fn cylinder_and_capsule_are_well_formed() {
+        let cylinder = MeshGen::cylinder(1.0, 3.0, 12);
+        assert_well_formed(&cylinder);
+        assert!(cylinder
+            .0
+            .iter()
+            .all(|vertex| vertex.position[1].abs() == 1.5));
+
+        let capsule = MeshGen::capsule(0.5, 1.0, 4, 12);
+        assert_well_formed(&capsule);
+        for vertex in &capsule.0 {
+            let [x, y, z] = vertex.position;
+            let axis_y = y.clamp(-1.0, 1.0);
+            let distance = dot([x, y - axis_y, z], [x, y - axis_y, z]).sqrt();
+            assert!((distance - 0.5).abs() < 1e-5, "{vertex:?}");
+        }
+    }
+
+    #[test]
+    fn cone_is_well_formed() {
+        let cone = MeshGen::cone(1.0, 2.0, 16);
+        assert_well_formed(&cone);
+        let apex_count = cone
+            .0
+            .iter()
+            .filter(|vertex| vertex.position == [0.0, 1.0, 0.0])
+            .count();
+        assert_eq!(apex_count, 16);
+    }
+
+    #[test]
+    fn torus_vertices_match_the_analytic_surface() {
+        let (major, minor) = (3.0, 1.0);
+        let torus = MeshGen::torus(major, minor, 24, 12);
+        assert_eq!(torus.0.len(), 25 * 13);
+        assert_well_formed(&torus);
+        for vertex in &torus.0 {
+            let [u, v] = vertex.uv.map(|c| c * TAU);
+            let ring = major + minor * v.cos();
+            let expected = [ring * u.cos(), minor * v.sin(), ring * u.sin()];
+            for (position, expected) in vertex.position.iter().zip(expected) {
+                assert!((position - expected).abs() < 1e-5, "{vertex:?}");
+            }
+        }
+        // the outer equator at `v = 0` and the inner one at `v = 0.5`
+        let radius = |vertex: &Vertex3D| vertex.position[0].hypot(vertex.position[2]);
+        let outer = &torus.0[..25];
+        assert!(outer.iter().all(|v| (radius(v) - 4.0).abs() < 1e-5));
+        let inner = &torus.0[6 * 25..7 * 25];
+        assert!(inner.iter().all(|v| (radius(v) - 2.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn mobius_strip_normal_reverses_over_one_loop() {
+        let strip = MeshGen::mobius_strip(0.5, 32);
+        assert_well_formed(&strip);
+        let (first, last) = (&strip.0[..2], &strip.0[strip.0.len() - 2..]);
+        for (start, end) in first.iter().zip(last.iter().rev()) {
+            for i in 0..3 {
+                assert!((start.position[i] - end.position[i]).abs() < 1e-5);
+                assert!((start.normal[i] + end.normal[i]).abs() < 1e-5);
+            }
+        }
+    }
+}
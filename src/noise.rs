@@ -0,0 +1,176 @@
+use crate::compute::ComputePass;
+use crate::error::ThorusError;
+use crate::shader::load_noise;
+use std::sync::Arc;
+use vulkano::buffer::BufferContents;
+use vulkano::command_buffer::allocator::{
+    StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract,
+};
+use vulkano::descriptor_set::WriteDescriptorSet;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::sync::GpuFuture;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Mirrors the push constant block of `shader/noise.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct NoiseParams {
+    pub size: u32,
+    pub octaves: u32,
+    pub persistence: f32,
+    pub lacunarity: f32,
+    pub seed: u32,
+    pub frequency: f32,
+}
+
+/// Generates tileable fractal Perlin noise textures on the GPU.
+pub struct NoiseGenerator {
+    device: Arc<Device>,
+    pass: ComputePass,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+}
+
+impl NoiseGenerator {
+    pub fn new(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let pass = ComputePass::new(device.clone(), load_noise(device.clone())?)?;
+        Ok(Self {
+            command_buffer_allocator: StandardCommandBufferAllocator::new(
+                device.clone(),
+                StandardCommandBufferAllocatorCreateInfo::default(),
+            ),
+            device,
+            pass,
+        })
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    /// Renders a `size`×`size` `R32_SFLOAT` image of fractal Brownian motion in `[0, 1]`.
+    ///
+    /// The first octave has a period of one tile; each following one has its frequency
+    /// multiplied by `lacunarity`, rounded to whole periods to stay tileable, and its amplitude
+    /// multiplied by `persistence`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_2d(
+        &self,
+        queue: Arc<Queue>,
+        allocator: Arc<dyn MemoryAllocator>,
+        size: u32,
+        octaves: u32,
+        persistence: f32,
+        lacunarity: f32,
+        seed: u32,
+    ) -> Result<Arc<ImageView>, ThorusError> {
+        let image = Image::new(
+            allocator,
+            ImageCreateInfo {
+                format: Format::R32_SFLOAT,
+                extent: [size, size, 1],
+                usage: ImageUsage::STORAGE | ImageUsage::SAMPLED | ImageUsage::TRANSFER_SRC,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        let view = ImageView::new_default(image)?;
+
+        let params = NoiseParams {
+            size,
+            octaves,
+            persistence,
+            lacunarity,
+            seed,
+            frequency: 1.0,
+        };
+        let group_count = size.div_ceil(WORKGROUP_SIZE);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        self.pass.bind(
+            &mut builder,
+            [WriteDescriptorSet::image_view(0, view.clone())],
+        )?;
+        self.pass.push_constants(&mut builder, params)?;
+        self.pass
+            .dispatch(&mut builder, [group_count, group_count, 1])?;
+
+        builder
+            .build()?
+            .execute(queue)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        Ok(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::CopyImageToBufferInfo;
+    use vulkano::memory::allocator::MemoryTypeFilter;
+
+    const SIZE: u32 = 64;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn values_are_in_unit_range() {
+        let context = TestContext::new();
+        let generator = NoiseGenerator::new(context.queue.device().clone()).unwrap();
+        let view = generator
+            .generate_2d(
+                context.queue.clone(),
+                context.memory_allocator.clone(),
+                SIZE,
+                5,
+                0.5,
+                2.0,
+                7,
+            )
+            .unwrap();
+
+        let readback = Buffer::new_slice::<f32>(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..AllocationCreateInfo::default()
+            },
+            (SIZE * SIZE) as u64,
+        )
+        .unwrap();
+        let mut builder = context.command_buffer();
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                view.image().clone(),
+                readback.clone(),
+            ))
+            .unwrap();
+        context.submit(builder);
+
+        let values = readback.read().unwrap();
+        let (min, max) = values.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+        assert!(min >= 0.0 && max <= 1.0, "noise spans {min}..{max}");
+        assert!(max - min > 0.1, "noise is nearly constant: {min}..{max}");
+    }
+}
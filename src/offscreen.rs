@@ -0,0 +1,179 @@
+use crate::error::ThorusError;
+use std::sync::{Arc, Mutex, Weak};
+use tracing::debug;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+
+#[derive(Default)]
+struct PoolState {
+    frame: u64,
+    /// Released targets with the frame they were released in.
+    free: Vec<(Arc<ImageView>, u64)>,
+}
+
+/// Recycles the temporary images of post-processing chains: a target released by one pass is
+/// handed to the next pass asking for the same format and extent instead of allocating a new
+/// image.
+///
+/// A target may be released as soon as the commands using it are recorded. Reusing it in a
+/// later pass of the same or a following submission is ordered by vulkano's resource tracking
+/// like any other image access.
+pub struct RenderTargetPool {
+    allocator: Arc<dyn MemoryAllocator>,
+    usage: ImageUsage,
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl RenderTargetPool {
+    /// Creates a pool of images with `usage`, e.g. `COLOR_ATTACHMENT | SAMPLED`.
+    pub fn new(allocator: Arc<dyn MemoryAllocator>, usage: ImageUsage) -> Self {
+        Self {
+            allocator,
+            usage,
+            state: Arc::default(),
+        }
+    }
+
+    /// A free target of `format` and `extent`, or a new one if there is none.
+    pub fn acquire(
+        &self,
+        format: Format,
+        extent: [u32; 2],
+    ) -> Result<PooledRenderTarget, ThorusError> {
+        let reused = {
+            let mut state = self.state.lock().unwrap();
+            state
+                .free
+                .iter()
+                .position(|(view, _)| {
+                    let image = view.image();
+                    image.format() == format && image.extent() == [extent[0], extent[1], 1]
+                })
+                .map(|index| state.free.swap_remove(index).0)
+        };
+        let view = match reused {
+            Some(view) => view,
+            None => {
+                let image = Image::new(
+                    self.allocator.clone(),
+                    ImageCreateInfo {
+                        image_type: ImageType::Dim2d,
+                        format,
+                        extent: [extent[0], extent[1], 1],
+                        usage: self.usage,
+                        ..ImageCreateInfo::default()
+                    },
+                    AllocationCreateInfo::default(),
+                )?;
+                debug!("new render target: {format:?} {extent:?}");
+                ImageView::new_default(image)?
+            }
+        };
+        Ok(PooledRenderTarget {
+            view: Some(view),
+            pool: Arc::downgrade(&self.state),
+        })
+    }
+
+    /// Starts a new frame, for [`gc`](Self::gc) to count the frames targets stay unused.
+    pub fn next_frame(&self) {
+        self.state.lock().unwrap().frame += 1;
+    }
+
+    /// Frees the targets that have not been acquired for `min_free_frames` frames or more and
+    /// returns how many.
+    pub fn gc(&self, min_free_frames: u64) -> usize {
+        let freed: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            let frame = state.frame;
+            let (freed, kept) = state
+                .free
+                .drain(..)
+                .partition(|&(_, released)| frame - released >= min_free_frames);
+            state.free = kept;
+            freed
+        };
+        let count = freed.len();
+        if count > 0 {
+            debug!("freed {count} unused render targets");
+        }
+        count
+    }
+
+    /// Targets waiting in the pool to be acquired again.
+    pub fn free_count(&self) -> usize {
+        self.state.lock().unwrap().free.len()
+    }
+}
+
+/// Render target on loan from a [`RenderTargetPool`], returned to it when dropped.
+pub struct PooledRenderTarget {
+    view: Option<Arc<ImageView>>,
+    pool: Weak<Mutex<PoolState>>,
+}
+
+impl PooledRenderTarget {
+    pub fn view(&self) -> &Arc<ImageView> {
+        self.view.as_ref().unwrap()
+    }
+
+    pub fn image(&self) -> &Arc<Image> {
+        self.view().image()
+    }
+}
+
+impl Drop for PooledRenderTarget {
+    fn drop(&mut self) {
+        // The image is simply freed if the pool is already gone.
+        if let (Some(view), Some(pool)) = (self.view.take(), self.pool.upgrade()) {
+            let mut state = pool.lock().unwrap();
+            let frame = state.frame;
+            state.free.push((view, frame));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn released_targets_are_reused_for_the_same_format_and_extent() {
+        let context = TestContext::new();
+        let pool = RenderTargetPool::new(
+            context.memory_allocator.clone(),
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+        );
+
+        let first = pool.acquire(Format::R8G8B8A8_UNORM, [64, 32]).unwrap();
+        let image = first.image().clone();
+        drop(first);
+        assert_eq!(pool.free_count(), 1);
+        let second = pool.acquire(Format::R8G8B8A8_UNORM, [64, 32]).unwrap();
+        assert!(Arc::ptr_eq(second.image(), &image));
+        assert_eq!(pool.free_count(), 0);
+
+        // a different extent or format needs an image of its own
+        let other = pool.acquire(Format::R8G8B8A8_UNORM, [32, 32]).unwrap();
+        assert!(!Arc::ptr_eq(other.image(), &image));
+        let other = pool.acquire(Format::R16G16B16A16_SFLOAT, [64, 32]).unwrap();
+        assert!(!Arc::ptr_eq(other.image(), &image));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn gc_frees_targets_unused_for_long_enough() {
+        let context = TestContext::new();
+        let pool = RenderTargetPool::new(context.memory_allocator.clone(), ImageUsage::SAMPLED);
+        drop(pool.acquire(Format::R8G8B8A8_UNORM, [16, 16]).unwrap());
+        pool.next_frame();
+        assert_eq!(pool.gc(2), 0);
+        pool.next_frame();
+        assert_eq!(pool.gc(2), 1);
+        assert_eq!(pool.free_count(), 0);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::debug_draw::DebugDraw;
+use crate::error::ThorusError;
+use rapier2d::dynamics::{ImpulseJointSet, MultibodyJointSet, RigidBodyHandle, RigidBodySet};
+use rapier2d::geometry::{ColliderSet, NarrowPhase};
+use rapier2d::math::{Point, Real};
+use rapier2d::pipeline::{DebugRenderBackend, DebugRenderObject, DebugRenderPipeline};
+use std::collections::HashSet;
+use std::sync::Arc;
+use vulkano::buffer::allocator::SubbufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::pipeline::GraphicsPipeline;
+
+pub const SLEEPING_COLOR: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+pub const AWAKE_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+pub const SENSOR_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+
+/// Collects rapier's debug lines into a [`DebugDraw`] on the `z = 0` plane.
+///
+/// Colliders are recolored by state: sensors yellow, colliders of sleeping bodies red and
+/// everything else green. Joints and contacts keep rapier's colors.
+#[derive(Default)]
+pub struct PhysicsDebugDraw {
+    draw: DebugDraw,
+    sleeping: HashSet<RigidBodyHandle>,
+}
+
+impl PhysicsDebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `pipeline` over the whole physics scene with `self` as the backend.
+    pub fn render(
+        &mut self,
+        pipeline: &mut DebugRenderPipeline,
+        bodies: &RigidBodySet,
+        colliders: &ColliderSet,
+        impulse_joints: &ImpulseJointSet,
+        multibody_joints: &MultibodyJointSet,
+        narrow_phase: &NarrowPhase,
+    ) {
+        self.sleeping = bodies
+            .iter()
+            .filter(|(_, body)| body.is_sleeping())
+            .map(|(handle, _)| handle)
+            .collect();
+        pipeline.render(
+            self,
+            bodies,
+            colliders,
+            impulse_joints,
+            multibody_joints,
+            narrow_phase,
+        );
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.draw.line_count()
+    }
+
+    /// Draws every line accumulated since the last call, then forgets them.
+    pub fn record(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: Arc<GraphicsPipeline>,
+        buffer_allocator: &SubbufferAllocator,
+        view_proj: [[f32; 4]; 4],
+    ) -> Result<(), ThorusError> {
+        let result = self
+            .draw
+            .record(builder, pipeline, buffer_allocator, view_proj);
+        self.draw.clear();
+        result
+    }
+
+    fn body_color(&self, handle: Option<RigidBodyHandle>) -> [f32; 4] {
+        match handle {
+            Some(handle) if self.sleeping.contains(&handle) => SLEEPING_COLOR,
+            _ => AWAKE_COLOR,
+        }
+    }
+}
+
+impl DebugRenderBackend for PhysicsDebugDraw {
+    fn draw_line(
+        &mut self,
+        object: DebugRenderObject,
+        a: Point<Real>,
+        b: Point<Real>,
+        color: [f32; 4],
+    ) {
+        let color = match object {
+            DebugRenderObject::Collider(_, collider) if collider.is_sensor() => SENSOR_COLOR,
+            DebugRenderObject::Collider(_, collider) => self.body_color(collider.parent()),
+            DebugRenderObject::RigidBody(handle, _) => self.body_color(Some(handle)),
+            _ => color,
+        };
+        self.draw.line([a.x, a.y, 0.0], [b.x, b.y, 0.0], color, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rapier2d::prelude::*;
+
+    #[test]
+    fn three_bodies_produce_colored_lines() {
+        let mut bodies = RigidBodySet::new();
+        let mut colliders = ColliderSet::new();
+        for (i, sensor) in [false, false, true].into_iter().enumerate() {
+            let body = bodies.insert(
+                RigidBodyBuilder::dynamic()
+                    .translation(vector![i as f32 * 3.0, 5.0])
+                    .build(),
+            );
+            colliders.insert_with_parent(
+                ColliderBuilder::cuboid(0.5, 0.5).sensor(sensor).build(),
+                body,
+                &mut bodies,
+            );
+        }
+        let mut islands = IslandManager::new();
+        let mut broad_phase = DefaultBroadPhase::new();
+        let mut narrow_phase = NarrowPhase::new();
+        let mut impulse_joints = ImpulseJointSet::new();
+        let mut multibody_joints = MultibodyJointSet::new();
+        let mut physics = PhysicsPipeline::new();
+        for _ in 0..10 {
+            physics.step(
+                &vector![0.0, -9.81],
+                &IntegrationParameters::default(),
+                &mut islands,
+                &mut broad_phase,
+                &mut narrow_phase,
+                &mut bodies,
+                &mut colliders,
+                &mut impulse_joints,
+                &mut multibody_joints,
+                &mut CCDSolver::new(),
+                None,
+                &(),
+                &(),
+            );
+        }
+
+        let mut debug_draw = PhysicsDebugDraw::new();
+        debug_draw.render(
+            &mut DebugRenderPipeline::default(),
+            &bodies,
+            &colliders,
+            &impulse_joints,
+            &multibody_joints,
+            &narrow_phase,
+        );
+        assert!(debug_draw.line_count() > 0);
+        let colors: Vec<_> = debug_draw.draw.vertices().map(|v| v.color).collect();
+        assert!(colors.contains(&AWAKE_COLOR));
+        assert!(colors.contains(&SENSOR_COLOR));
+        assert!(!colors.contains(&SLEEPING_COLOR));
+    }
+}
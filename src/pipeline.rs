@@ -0,0 +1,2619 @@
+use crate::shader::{
+    load_capture_vertex, load_fullscreen, load_input_tint, load_outline_fragment,
+    load_outline_vertex, load_shadow_vertex, load_stereo_fragment, load_stereo_vertex,
+};
+use crate::vertex::{CapturedVertex, MyVertex, Vertex3D};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+use std::{mem, ptr};
+use tracing::debug;
+use vulkano::buffer::sys::RawBuffer;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::sys::UnsafeCommandBufferBuilder;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassBeginInfo,
+    SubpassContents, SubpassEndInfo,
+};
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceExtensions, DeviceOwned};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageAspects, ImageCreateInfo, ImageLayout, ImageUsage, SampleCount};
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, AllocationType, MemoryAllocator, MemoryTypeFilter,
+};
+use vulkano::memory::{DedicatedAllocation, ResourceMemory};
+use vulkano::pipeline::graphics::color_blend::{
+    AttachmentBlend, BlendFactor, BlendOp, ColorBlendAttachmentState, ColorBlendState,
+};
+use vulkano::pipeline::graphics::depth_stencil::{
+    CompareOp, DepthState, DepthStencilState, StencilOp, StencilOpState, StencilOps, StencilState,
+};
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::multisample::MultisampleState;
+use vulkano::pipeline::graphics::rasterization::{
+    self, DepthBiasState, FrontFace, RasterizationState,
+};
+use vulkano::pipeline::graphics::subpass::PipelineSubpassType;
+use vulkano::pipeline::graphics::vertex_input::{
+    Vertex, VertexBufferDescription, VertexDefinition, VertexInputState,
+};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::DynamicState;
+use vulkano::pipeline::{
+    GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+};
+use vulkano::render_pass::{
+    AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, Framebuffer,
+    FramebufferCreateInfo, RenderPass, RenderPassCreateInfo, ResolveMode, Subpass,
+    SubpassDependency, SubpassDescription,
+};
+use vulkano::shader::ShaderModule;
+use vulkano::swapchain::Swapchain;
+use vulkano::sync::{
+    AccessFlags, BufferMemoryBarrier, DependencyFlags, DependencyInfo, HostAccessError,
+    PipelineStages,
+};
+use vulkano::{DeviceSize, Validated, ValidationError, Version, VulkanError, VulkanObject};
+
+/// Whether `pipeline` may be bound while its subpass of `render_pass` is active.
+pub fn compatible_with_render_pass(
+    pipeline: &Arc<GraphicsPipeline>,
+    render_pass: &Arc<RenderPass>,
+) -> bool {
+    render_pass_mismatches(pipeline, render_pass).is_empty()
+}
+
+/// Describes every attachment format and sample count that differs between the render pass
+/// `pipeline` was built for and `render_pass`.
+pub fn render_pass_mismatches(
+    pipeline: &GraphicsPipeline,
+    render_pass: &Arc<RenderPass>,
+) -> Vec<String> {
+    let subpass = match pipeline.subpass() {
+        PipelineSubpassType::BeginRenderPass(subpass) => subpass,
+        PipelineSubpassType::BeginRendering(_) => {
+            return vec!["pipeline was created for dynamic rendering".to_owned()];
+        }
+    };
+    let expected = subpass.render_pass();
+    let mut mismatches = vec![];
+
+    let (expected_attachments, actual_attachments) =
+        (expected.attachments(), render_pass.attachments());
+    if expected_attachments.len() != actual_attachments.len() {
+        mismatches.push(format!(
+            "pipeline expects {} attachments, render pass has {}",
+            expected_attachments.len(),
+            actual_attachments.len(),
+        ));
+    }
+    for (index, (expected, actual)) in expected_attachments
+        .iter()
+        .zip(actual_attachments)
+        .enumerate()
+    {
+        if expected.format != actual.format {
+            mismatches.push(format!(
+                "attachment {index}: pipeline expects format {:?}, render pass has {:?}",
+                expected.format, actual.format,
+            ));
+        }
+        if expected.samples != actual.samples {
+            mismatches.push(format!(
+                "attachment {index}: pipeline expects {:?}, render pass has {:?}",
+                expected.samples, actual.samples,
+            ));
+        }
+    }
+
+    match Subpass::from(render_pass.clone(), subpass.index()) {
+        Some(actual) => {
+            let rasterization_samples = pipeline
+                .multisample_state()
+                .map(|state| state.rasterization_samples);
+            if let (Some(pipeline_samples), Some(subpass_samples)) =
+                (rasterization_samples, actual.num_samples())
+            {
+                if pipeline_samples != subpass_samples {
+                    mismatches.push(format!(
+                        "subpass {}: pipeline rasterizes with {pipeline_samples:?}, \
+                         attachments have {subpass_samples:?}",
+                        subpass.index(),
+                    ));
+                }
+            }
+        }
+        None => mismatches.push(format!("render pass has no subpass {}", subpass.index())),
+    }
+
+    mismatches
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    /// A required builder option was not set.
+    Missing(&'static str),
+    NoEntryPoint(&'static str),
+    NoSubpass(u32),
+    /// A builder option needs a device feature that is not enabled.
+    FeatureNotEnabled(&'static str),
+    Vulkan(Box<dyn Error + Send + Sync>),
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(option) => write!(f, "pipeline {option} was not set"),
+            Self::NoEntryPoint(stage) => write!(f, "{stage} shader has no main entry point"),
+            Self::NoSubpass(index) => write!(f, "render pass has no subpass {index}"),
+            Self::FeatureNotEnabled(feature) => {
+                write!(f, "the {feature} device feature is not enabled")
+            }
+            Self::Vulkan(e) => write!(f, "failed to create pipeline: {e}"),
+        }
+    }
+}
+
+impl Error for PipelineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Vulkan(e) => Some(e.as_ref()),
+            Self::Missing(_)
+            | Self::NoEntryPoint(_)
+            | Self::NoSubpass(_)
+            | Self::FeatureNotEnabled(_) => None,
+        }
+    }
+}
+
+fn vulkan_error(e: impl Error + Send + Sync + 'static) -> PipelineError {
+    PipelineError::Vulkan(Box::new(e))
+}
+
+/// How fragment colors are combined with what is already in the color attachments.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    /// Straight alpha: colors are stored independently of their coverage.
+    ///
+    /// Filtering mixes the color of transparent texels into their visible neighbours, so edges
+    /// of cut-outs pick up fringes of whatever color the transparent area happens to have.
+    Alpha,
+    /// Colors are already multiplied by their alpha, so transparent texels are black and
+    /// filtering cannot bleed them into visible ones.
+    ///
+    /// Preferred for textures with soft edges and for layers composited on top of each other.
+    /// Images exported from design tools are usually straight alpha and have to be converted
+    /// first, see [`Texture::premultiply_alpha`](crate::texture::Texture::premultiply_alpha).
+    PremultipliedAlpha,
+    Additive,
+}
+
+impl BlendMode {
+    fn attachment_blend(self) -> Option<AttachmentBlend> {
+        match self {
+            Self::Opaque => None,
+            Self::Alpha => Some(AttachmentBlend::alpha()),
+            Self::PremultipliedAlpha => Some(AttachmentBlend {
+                src_color_blend_factor: BlendFactor::One,
+                dst_color_blend_factor: BlendFactor::OneMinusSrcAlpha,
+                color_blend_op: BlendOp::Add,
+                src_alpha_blend_factor: BlendFactor::One,
+                dst_alpha_blend_factor: BlendFactor::OneMinusSrcAlpha,
+                alpha_blend_op: BlendOp::Add,
+            }),
+            Self::Additive => Some(AttachmentBlend::additive()),
+        }
+    }
+}
+
+/// Which faces are discarded before rasterization; faces are told apart by the winding set
+/// with [`GraphicsPipelineBuilder::culling`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum CullMode {
+    /// Draws both sides, for double-sided materials such as foliage or cloth.
+    None,
+    /// Draws only back faces, for geometry seen from inside such as sky domes.
+    Front,
+    #[default]
+    Back,
+    /// Draws no triangles at all; points and lines are unaffected.
+    FrontAndBack,
+}
+
+impl From<CullMode> for rasterization::CullMode {
+    fn from(mode: CullMode) -> Self {
+        match mode {
+            CullMode::None => Self::None,
+            CullMode::Front => Self::Front,
+            CullMode::Back => Self::Back,
+            CullMode::FrontAndBack => Self::FrontAndBack,
+        }
+    }
+}
+
+/// Stencil test and update applied to both front and back faces.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StencilConfig {
+    pub reference: u32,
+    pub compare_op: CompareOp,
+    /// Applied when both the stencil and the depth test pass.
+    pub pass_op: StencilOp,
+    pub fail_op: StencilOp,
+    /// Applied when the stencil test passes but the depth test fails.
+    pub depth_fail_op: StencilOp,
+    pub compare_mask: u32,
+    pub write_mask: u32,
+}
+
+impl StencilConfig {
+    /// Always passes and writes `reference` wherever something is drawn.
+    pub fn write(reference: u32) -> Self {
+        Self {
+            reference,
+            compare_op: CompareOp::Always,
+            pass_op: StencilOp::Replace,
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            compare_mask: u32::MAX,
+            write_mask: u32::MAX,
+        }
+    }
+
+    /// Passes where the stored value compares to `reference` with `compare_op`, leaving the
+    /// stencil buffer unchanged.
+    pub fn test(compare_op: CompareOp, reference: u32) -> Self {
+        Self {
+            reference,
+            compare_op,
+            pass_op: StencilOp::Keep,
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            compare_mask: u32::MAX,
+            write_mask: 0,
+        }
+    }
+
+    fn state(self) -> StencilState {
+        let face = StencilOpState {
+            ops: StencilOps {
+                fail_op: self.fail_op,
+                pass_op: self.pass_op,
+                depth_fail_op: self.depth_fail_op,
+                compare_op: self.compare_op,
+            },
+            compare_mask: self.compare_mask,
+            write_mask: self.write_mask,
+            reference: self.reference,
+        };
+        StencilState {
+            front: face,
+            back: face,
+        }
+    }
+}
+
+/// Fluent alternative to filling `GraphicsPipelineCreateInfo` by hand.
+///
+/// Only the shaders, the render pass and the viewport are required; the rest defaults to an
+/// opaque, single-sampled triangle list culling back faces, without depth testing, also in
+/// subpasses with a depth attachment. The fragment shader may be left out for subpasses
+/// without color attachments or with rasterizer discard.
+pub struct GraphicsPipelineBuilder {
+    device: Arc<Device>,
+    vertex_shader: Option<Arc<ShaderModule>>,
+    fragment_shader: Option<Arc<ShaderModule>>,
+    vertex_input: Option<VertexBufferDescription>,
+    subpass: Option<(Arc<RenderPass>, u32)>,
+    viewport: Option<Viewport>,
+    state: FixedFunctionState,
+}
+
+/// Settings of a [`GraphicsPipelineBuilder`] that do not depend on the device, turned into
+/// the create info states of the pipeline.
+#[derive(Clone, Debug)]
+struct FixedFunctionState {
+    topology: PrimitiveTopology,
+    blend_mode: BlendMode,
+    depth: Option<DepthState>,
+    stencil: Option<StencilConfig>,
+    depth_bias: Option<DepthBiasState>,
+    depth_bias_clamp: f32,
+    dynamic_depth_bias: bool,
+    dynamic_line_width: bool,
+    dynamic_scissor: bool,
+    cull_mode: CullMode,
+    front_face: FrontFace,
+    rasterizer_discard: bool,
+}
+
+impl Default for FixedFunctionState {
+    fn default() -> Self {
+        Self {
+            topology: PrimitiveTopology::TriangleList,
+            blend_mode: BlendMode::default(),
+            depth: None,
+            stencil: None,
+            depth_bias: None,
+            depth_bias_clamp: 0.0,
+            dynamic_depth_bias: false,
+            dynamic_line_width: false,
+            dynamic_scissor: false,
+            cull_mode: CullMode::default(),
+            front_face: FrontFace::CounterClockwise,
+            rasterizer_discard: false,
+        }
+    }
+}
+
+impl FixedFunctionState {
+    fn input_assembly_state(&self) -> InputAssemblyState {
+        InputAssemblyState {
+            topology: self.topology,
+            ..InputAssemblyState::default()
+        }
+    }
+
+    fn rasterization_state(&self) -> RasterizationState {
+        RasterizationState {
+            rasterizer_discard_enable: self.rasterizer_discard,
+            cull_mode: self.cull_mode.into(),
+            front_face: self.front_face,
+            depth_bias: if self.dynamic_depth_bias {
+                Some(DepthBiasState::default())
+            } else {
+                self.depth_bias.map(|depth_bias| DepthBiasState {
+                    clamp: self.depth_bias_clamp,
+                    ..depth_bias
+                })
+            },
+            ..RasterizationState::default()
+        }
+    }
+
+    /// `None` for subpasses without color attachments.
+    fn color_blend_state(&self, num_color_attachments: u32) -> Option<ColorBlendState> {
+        (num_color_attachments > 0).then(|| {
+            ColorBlendState::with_attachment_states(
+                num_color_attachments,
+                ColorBlendAttachmentState {
+                    blend: self.blend_mode.attachment_blend(),
+                    ..ColorBlendAttachmentState::default()
+                },
+            )
+        })
+    }
+
+    fn dynamic_state(&self) -> impl Iterator<Item = DynamicState> {
+        [
+            (self.dynamic_depth_bias, DynamicState::DepthBias),
+            (self.dynamic_line_width, DynamicState::LineWidth),
+            (self.dynamic_scissor, DynamicState::Scissor),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, state)| enabled.then_some(state))
+    }
+}
+
+impl GraphicsPipelineBuilder {
+    pub fn new(device: Arc<Device>) -> Self {
+        Self {
+            device,
+            vertex_shader: None,
+            fragment_shader: None,
+            vertex_input: None,
+            subpass: None,
+            viewport: None,
+            state: FixedFunctionState::default(),
+        }
+    }
+
+    pub fn vertex_shader(mut self, vs: Arc<ShaderModule>) -> Self {
+        self.vertex_shader = Some(vs);
+        self
+    }
+
+    pub fn fragment_shader(mut self, fs: Arc<ShaderModule>) -> Self {
+        self.fragment_shader = Some(fs);
+        self
+    }
+
+    /// Per-vertex buffer layout, e.g. `MyVertex::per_vertex()`; without one the vertex shader
+    /// must not have inputs.
+    pub fn vertex_input(mut self, description: VertexBufferDescription) -> Self {
+        self.vertex_input = Some(description);
+        self
+    }
+
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.state.topology = topology;
+        self
+    }
+
+    pub fn render_pass(mut self, render_pass: Arc<RenderPass>, subpass: u32) -> Self {
+        self.subpass = Some((render_pass, subpass));
+        self
+    }
+
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.state.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn depth_test(mut self, enabled: bool, write: bool, compare_op: CompareOp) -> Self {
+        self.state.depth = enabled.then_some(DepthState {
+            write_enable: write,
+            compare_op,
+        });
+        self
+    }
+
+    /// Enables the stencil test; the subpass needs an attachment with a stencil aspect.
+    pub fn stencil(mut self, config: StencilConfig) -> Self {
+        self.state.stencil = Some(config);
+        self
+    }
+
+    /// Offsets the depth of every fragment by `units` times the smallest resolvable depth
+    /// difference plus `factor` times the polygon's depth slope.
+    ///
+    /// Typically only used by the shadow-map pipeline, where it keeps surfaces from shadowing
+    /// themselves ("shadow acne").
+    pub fn polygon_offset(mut self, factor: f32, units: f32) -> Self {
+        self.state.depth_bias = Some(DepthBiasState {
+            constant_factor: units,
+            slope_factor: factor,
+            clamp: 0.0,
+        });
+        self
+    }
+
+    /// Limits the magnitude of the [`polygon_offset`](Self::polygon_offset); zero means no
+    /// limit. Anything else needs the `depth_bias_clamp` device feature.
+    pub fn polygon_offset_clamp(mut self, clamp: f32) -> Self {
+        self.state.depth_bias_clamp = clamp;
+        self
+    }
+
+    /// Leaves the depth bias to be set with `set_depth_bias` while recording, so it can change
+    /// between draws; any [`polygon_offset`](Self::polygon_offset) is ignored.
+    pub fn with_dynamic_depth_bias(mut self) -> Self {
+        self.state.dynamic_depth_bias = true;
+        self
+    }
+
+    /// Leaves the width of lines to be set with `set_line_width` while recording, see
+    /// [`DebugDraw::set_line_width`](crate::debug_draw::DebugDraw::set_line_width).
+    pub fn with_dynamic_line_width(mut self) -> Self {
+        self.state.dynamic_line_width = true;
+        self
+    }
+
+    /// Leaves the scissor rectangle to be set with `set_scissor` while recording, e.g. to clip
+    /// UI elements to their panel, see [`SpriteRenderer::begin_clip`](crate::ui::SpriteRenderer::begin_clip).
+    pub fn with_dynamic_scissor(mut self) -> Self {
+        self.state.dynamic_scissor = true;
+        self
+    }
+
+    /// Culls the faces selected by `cull_mode`, with `front_face` being the winding of front
+    /// faces in framebuffer coordinates.
+    pub fn culling(mut self, cull_mode: CullMode, front_face: FrontFace) -> Self {
+        self.state.cull_mode = cull_mode;
+        self.state.front_face = front_face;
+        self
+    }
+
+    /// Drops primitives right after the vertex stage, for pipelines that only capture
+    /// vertices with transform feedback; no fragment shader is needed then.
+    pub fn rasterizer_discard(mut self) -> Self {
+        self.state.rasterizer_discard = true;
+        self
+    }
+
+    pub fn build(self) -> Result<Arc<GraphicsPipeline>, PipelineError> {
+        let vs = self
+            .vertex_shader
+            .ok_or(PipelineError::Missing("vertex shader"))?
+            .entry_point("main")
+            .ok_or(PipelineError::NoEntryPoint("vertex"))?;
+        let (render_pass, subpass_index) =
+            self.subpass.ok_or(PipelineError::Missing("render pass"))?;
+        let subpass = Subpass::from(render_pass, subpass_index)
+            .ok_or(PipelineError::NoSubpass(subpass_index))?;
+        let fs = match self.fragment_shader {
+            Some(fs) => Some(
+                fs.entry_point("main")
+                    .ok_or(PipelineError::NoEntryPoint("fragment"))?,
+            ),
+            None if self.state.rasterizer_discard || subpass.num_color_attachments() == 0 => None,
+            None => return Err(PipelineError::Missing("fragment shader")),
+        };
+        let viewport = self.viewport.ok_or(PipelineError::Missing("viewport"))?;
+        if self.state.depth_bias_clamp != 0.0 && !self.device.enabled_features().depth_bias_clamp {
+            return Err(PipelineError::FeatureNotEnabled("depth_bias_clamp"));
+        }
+
+        let vertex_input_state = match &self.vertex_input {
+            Some(description) => description
+                .definition(&vs.info().input_interface)
+                .map_err(vulkan_error)?,
+            None => VertexInputState::default(),
+        };
+        debug!("vertex input state: {vertex_input_state:?}");
+
+        let stages: Vec<_> = [Some(vs), fs]
+            .into_iter()
+            .flatten()
+            .map(PipelineShaderStageCreateInfo::new)
+            .collect();
+
+        let layout = PipelineLayout::new(
+            self.device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+                .into_pipeline_layout_create_info(self.device.clone())
+                .map_err(|e| vulkan_error(e.error))?,
+        )
+        .map_err(vulkan_error)?;
+        debug!("pipeline layout: {layout:?}");
+
+        let pipeline = GraphicsPipeline::new(
+            self.device,
+            None,
+            GraphicsPipelineCreateInfo {
+                stages: stages.into_iter().collect(),
+                vertex_input_state: Some(vertex_input_state),
+                input_assembly_state: Some(self.state.input_assembly_state()),
+                viewport_state: Some(ViewportState {
+                    viewports: [viewport].into_iter().collect(),
+                    ..ViewportState::default()
+                }),
+                rasterization_state: Some(self.state.rasterization_state()),
+                multisample_state: Some(MultisampleState {
+                    rasterization_samples: subpass.num_samples().unwrap_or(SampleCount::Sample1),
+                    ..MultisampleState::default()
+                }),
+                depth_stencil_state: depth_stencil_state(
+                    subpass.subpass_desc().depth_stencil_attachment.is_some(),
+                    self.state.depth,
+                    self.state.stencil,
+                ),
+                color_blend_state: self
+                    .state
+                    .color_blend_state(subpass.num_color_attachments()),
+                dynamic_state: self.state.dynamic_state().collect(),
+                subpass: Some(subpass.into()),
+                ..GraphicsPipelineCreateInfo::layout(layout)
+            },
+        )
+        .map_err(vulkan_error)?;
+        debug!("graphics pipeline: {pipeline:?}");
+        Ok(pipeline)
+    }
+}
+
+/// Depth and stencil tests of a pipeline. Subpasses with a depth/stencil attachment always get
+/// a state, with both tests disabled if neither is configured.
+fn depth_stencil_state(
+    has_depth_stencil_attachment: bool,
+    depth: Option<DepthState>,
+    stencil: Option<StencilConfig>,
+) -> Option<DepthStencilState> {
+    (has_depth_stencil_attachment || depth.is_some() || stencil.is_some()).then(|| {
+        DepthStencilState {
+            depth,
+            stencil: stencil.map(StencilConfig::state),
+            ..DepthStencilState::default()
+        }
+    })
+}
+
+/// Whether the scene is drawn into the depth buffer alone before it is shaded.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct DepthPrepassConfig {
+    pub enabled: bool,
+}
+
+/// Depth-only pass that lays down the depth of the scene so that the main pass, testing with
+/// `CompareOp::Equal` and without depth writes, runs its fragment shader once per pixel.
+///
+/// The render pass is expected to start with a subpass that has only the depth attachment,
+/// see [`DepthPrepass::add_subpasses`], and the main pass to be in the subpass after it.
+pub struct DepthPrepass {
+    pipeline: Arc<GraphicsPipeline>,
+}
+
+impl DepthPrepass {
+    /// Mandatory on every device as a depth attachment.
+    pub const DEPTH_FORMAT: Format = Format::D16_UNORM;
+    pub const SUBPASS: u32 = 0;
+    pub const MAIN_SUBPASS: u32 = 1;
+
+    /// Creates a pipeline for [`Self::SUBPASS`] of `render_pass` that runs `vs` and writes
+    /// depth only; `vs` has to be the vertex shader of the main pass so that both produce
+    /// the same depth.
+    pub fn new(
+        device: Arc<Device>,
+        vs: Arc<ShaderModule>,
+        vertex_input: VertexBufferDescription,
+        render_pass: Arc<RenderPass>,
+        viewport: Viewport,
+    ) -> Result<Self, PipelineError> {
+        let pipeline = GraphicsPipelineBuilder::new(device)
+            .vertex_shader(vs)
+            .vertex_input(vertex_input)
+            .render_pass(render_pass, Self::SUBPASS)
+            .viewport(viewport)
+            .depth_test(true, true, CompareOp::Less)
+            .build()?;
+        Ok(Self { pipeline })
+    }
+
+    /// Adds the depth-only subpass and the main subpass writing `color_attachments` with
+    /// `depth_attachment` as read-only depth, plus the dependency between them.
+    pub fn add_subpasses(
+        builder: RenderPassBuilder,
+        color_attachments: &[u32],
+        depth_attachment: u32,
+    ) -> RenderPassBuilder {
+        builder
+            .add_subpass(&[], &[], Some(depth_attachment))
+            .add_subpass(color_attachments, &[], Some(depth_attachment))
+            .add_dependency(
+                Some(Self::SUBPASS),
+                Some(Self::MAIN_SUBPASS),
+                PipelineStages::LATE_FRAGMENT_TESTS,
+                PipelineStages::EARLY_FRAGMENT_TESTS,
+                AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            )
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    /// Draws `vertex_count` vertices of `vertex_buffer` into the depth buffer and moves on to
+    /// the main subpass; to be called right after the render pass begins.
+    pub fn record<V: ?Sized>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        vertex_buffer: Subbuffer<V>,
+        vertex_count: u32,
+    ) -> Result<(), Box<ValidationError>> {
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_vertex_buffers(0, vertex_buffer)?
+            .draw(vertex_count, 1, 0, 0)?
+            .next_subpass(
+                SubpassEndInfo::default(),
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )?;
+        Ok(())
+    }
+}
+
+/// Mirrors the push constant block of `shader/outline.vert` and `shader/outline.frag`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct OutlineParams {
+    pub color: [f32; 4],
+    /// Point in clip space the outline is scaled around, usually the object's center.
+    pub center: [f32; 2],
+    /// How much larger than the object the outline is drawn; above one.
+    pub scale: f32,
+}
+
+/// Outlines an object in two draws sharing one subpass with a stencil attachment.
+///
+/// The first draw renders the object with its own shaders and marks its pixels with
+/// [`Self::STENCIL_REFERENCE`]; the second renders it again scaled up in a solid color where
+/// the stencil is still zero, which leaves only the rim around the object.
+pub struct OutlineEffect {
+    mask_pipeline: Arc<GraphicsPipeline>,
+    outline_pipeline: Arc<GraphicsPipeline>,
+}
+
+impl OutlineEffect {
+    pub const STENCIL_REFERENCE: u32 = 1;
+
+    /// Creates both pipelines for `subpass` of `render_pass`, drawing [`MyVertex`] triangles
+    /// with `vs` and `fs` in the first pass.
+    pub fn new(
+        device: Arc<Device>,
+        vs: Arc<ShaderModule>,
+        fs: Arc<ShaderModule>,
+        render_pass: Arc<RenderPass>,
+        subpass: u32,
+        viewport: Viewport,
+    ) -> Result<Self, PipelineError> {
+        let mask_pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(vs)
+            .fragment_shader(fs)
+            .vertex_input(MyVertex::per_vertex())
+            .render_pass(render_pass.clone(), subpass)
+            .viewport(viewport.clone())
+            .stencil(StencilConfig::write(Self::STENCIL_REFERENCE))
+            .build()?;
+        let outline_pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_outline_vertex(device.clone()).map_err(vulkan_error)?)
+            .fragment_shader(load_outline_fragment(device).map_err(vulkan_error)?)
+            .vertex_input(MyVertex::per_vertex())
+            .render_pass(render_pass, subpass)
+            .viewport(viewport)
+            .stencil(StencilConfig::test(CompareOp::Equal, 0))
+            .build()?;
+        Ok(Self {
+            mask_pipeline,
+            outline_pipeline,
+        })
+    }
+
+    pub fn mask_pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.mask_pipeline
+    }
+
+    pub fn outline_pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.outline_pipeline
+    }
+
+    /// Draws `vertex_buffer` and its outline. The stencil attachment must have been cleared
+    /// to zero.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        vertex_buffer: Subbuffer<[MyVertex]>,
+        params: OutlineParams,
+    ) -> Result<(), Box<ValidationError>> {
+        let vertex_count = vertex_buffer.len() as u32;
+        builder
+            .bind_pipeline_graphics(self.mask_pipeline.clone())?
+            .bind_vertex_buffers(0, vertex_buffer)?
+            .draw(vertex_count, 1, 0, 0)?
+            .bind_pipeline_graphics(self.outline_pipeline.clone())?
+            .push_constants(self.outline_pipeline.layout().clone(), 0, params)?
+            .draw(vertex_count, 1, 0, 0)?;
+        Ok(())
+    }
+}
+
+/// Mirrors the push constant block of `shader/input_tint.frag`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct TintParams {
+    /// Multiplied with the scene color; `w` is how strongly, from zero to one.
+    pub tint: [f32; 4],
+}
+
+/// Tints the scene in a second subpass that reads the color of the first one as an input
+/// attachment, so the effect reads only the pixel it writes, straight from tile memory where
+/// the hardware keeps it there, instead of sampling a texture.
+///
+/// The scene attachment needs `COLOR_ATTACHMENT` and `INPUT_ATTACHMENT` usage and can be
+/// transient, as nothing outside the render pass reads it.
+pub struct InputAttachmentPipeline {
+    pipeline: Arc<GraphicsPipeline>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+}
+
+impl InputAttachmentPipeline {
+    pub const SCENE_SUBPASS: u32 = 0;
+    pub const TINT_SUBPASS: u32 = 1;
+
+    /// Creates the pipeline for [`Self::TINT_SUBPASS`] of a render pass made with
+    /// [`add_subpasses`](Self::add_subpasses).
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        viewport: Viewport,
+    ) -> Result<Self, PipelineError> {
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_fullscreen(device.clone()).map_err(vulkan_error)?)
+            .fragment_shader(load_input_tint(device.clone()).map_err(vulkan_error)?)
+            .render_pass(render_pass, Self::TINT_SUBPASS)
+            .viewport(viewport)
+            .culling(CullMode::None, FrontFace::CounterClockwise)
+            .build()?;
+        Ok(Self {
+            pipeline,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+        })
+    }
+
+    /// Adds the scene subpass, drawing into `scene_attachment`, and the tint subpass, reading
+    /// it and writing `output_attachment`.
+    pub fn add_subpasses(
+        builder: RenderPassBuilder,
+        scene_attachment: u32,
+        output_attachment: u32,
+    ) -> RenderPassBuilder {
+        builder
+            .add_subpass(&[scene_attachment], &[], None)
+            .add_subpass(&[output_attachment], &[scene_attachment], None)
+            .add_input_dependency(Self::SCENE_SUBPASS, Self::TINT_SUBPASS)
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    /// Binds `scene` as the input attachment; one set is needed per framebuffer.
+    pub fn descriptor_set(
+        &self,
+        scene: Arc<ImageView>,
+    ) -> Result<Arc<PersistentDescriptorSet>, PipelineError> {
+        PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            self.pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view(0, scene)],
+            [],
+        )
+        .map_err(vulkan_error)
+    }
+
+    /// Moves on to the tint subpass and draws it; to be called once the scene is drawn.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        descriptor_set: Arc<PersistentDescriptorSet>,
+        params: TintParams,
+    ) -> Result<(), Box<ValidationError>> {
+        let layout = self.pipeline.layout().clone();
+        builder
+            .next_subpass(
+                SubpassEndInfo::default(),
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )?
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )?
+            .push_constants(layout, 0, params)?
+            .draw(3, 1, 0, 0)?;
+        Ok(())
+    }
+}
+
+/// Mirrors the push constant block of `shader/shadow.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ShadowParams {
+    pub light_view_proj: [[f32; 4]; 4],
+}
+
+/// Renders the depth of [`Vertex3D`] meshes as seen from a light into a square shadow map.
+///
+/// Casters are drawn with a depth bias against shadow acne. With a dynamic depth bias it can
+/// be changed through [`set_depth_bias`](Self::set_depth_bias) from one
+/// [`record`](Self::record) to the next; otherwise [`Self::DEFAULT_DEPTH_BIAS`] is baked into
+/// the pipeline.
+pub struct ShadowMapPass {
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    shadow_map: Arc<ImageView>,
+    framebuffer: Arc<Framebuffer>,
+    depth_bias_constant: f32,
+    depth_bias_slope: f32,
+}
+
+impl ShadowMapPass {
+    pub const FORMAT: Format = Format::D16_UNORM;
+    /// Constant and slope factor the bias starts out with.
+    pub const DEFAULT_DEPTH_BIAS: (f32, f32) = (1.25, 1.75);
+
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        size: u32,
+        dynamic_depth_bias: bool,
+    ) -> Result<Self, PipelineError> {
+        let device = allocator.device().clone();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Self::FORMAT,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::DepthStencilReadOnlyOptimal,
+            )
+            .add_subpass(&[], &[], Some(0))
+            .build()
+            .map_err(vulkan_error)?;
+
+        let image = Image::new(
+            allocator,
+            ImageCreateInfo {
+                format: Self::FORMAT,
+                extent: [size, size, 1],
+                usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::SAMPLED,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .map_err(vulkan_error)?;
+        let shadow_map = ImageView::new_default(image).map_err(vulkan_error)?;
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![shadow_map.clone()],
+                ..FramebufferCreateInfo::default()
+            },
+        )
+        .map_err(vulkan_error)?;
+
+        let (depth_bias_constant, depth_bias_slope) = Self::DEFAULT_DEPTH_BIAS;
+        let builder = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_shadow_vertex(device).map_err(vulkan_error)?)
+            .vertex_input(Vertex3D::per_vertex())
+            .render_pass(render_pass.clone(), 0)
+            .viewport(Viewport {
+                offset: [0.0, 0.0],
+                extent: [size as f32; 2],
+                depth_range: 0.0..=1.0,
+            })
+            .depth_test(true, true, CompareOp::Less)
+            .polygon_offset(depth_bias_slope, depth_bias_constant);
+        let builder = if dynamic_depth_bias {
+            builder.with_dynamic_depth_bias()
+        } else {
+            builder
+        };
+        let pipeline = builder.build()?;
+        debug!("shadow map pipeline: {pipeline:?}");
+
+        Ok(Self {
+            render_pass,
+            pipeline,
+            shadow_map,
+            framebuffer,
+            depth_bias_constant,
+            depth_bias_slope,
+        })
+    }
+
+    pub fn render_pass(&self) -> &Arc<RenderPass> {
+        &self.render_pass
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    /// Depth as seen from the light, in `DepthStencilReadOnlyOptimal` layout after
+    /// [`record`](Self::record).
+    pub fn shadow_map(&self) -> &Arc<ImageView> {
+        &self.shadow_map
+    }
+
+    pub fn has_dynamic_depth_bias(&self) -> bool {
+        self.pipeline
+            .dynamic_state()
+            .contains(&DynamicState::DepthBias)
+    }
+
+    /// Bias applied by the following [`record`](Self::record) calls; has no effect without a
+    /// dynamic depth bias.
+    pub fn set_depth_bias(&mut self, constant: f32, slope: f32) {
+        self.depth_bias_constant = constant;
+        self.depth_bias_slope = slope;
+    }
+
+    /// Constant and slope factor [`record`](Self::record) sets, if the bias is dynamic.
+    fn dynamic_depth_bias(&self) -> Option<(f32, f32)> {
+        self.has_dynamic_depth_bias()
+            .then_some((self.depth_bias_constant, self.depth_bias_slope))
+    }
+
+    /// Clears the shadow map and draws the indexed triangles of `vertex_buffer` into it.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        vertex_buffer: Subbuffer<[Vertex3D]>,
+        index_buffer: Subbuffer<[u32]>,
+        params: ShadowParams,
+    ) -> Result<(), Box<ValidationError>> {
+        let index_count = index_buffer.len() as u32;
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(ClearValue::Depth(1.0))],
+                    ..RenderPassBeginInfo::framebuffer(self.framebuffer.clone())
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )?
+            .bind_pipeline_graphics(self.pipeline.clone())?;
+        if let Some((constant, slope)) = self.dynamic_depth_bias() {
+            builder.set_depth_bias(constant, 0.0, slope)?;
+        }
+        builder
+            .push_constants(self.pipeline.layout().clone(), 0, params)?
+            .bind_vertex_buffers(0, vertex_buffer)?
+            .bind_index_buffer(index_buffer)?
+            .draw_indexed(index_count, 1, 0, 0, 0)?
+            .end_render_pass(SubpassEndInfo::default())?;
+        Ok(())
+    }
+}
+
+/// Mirrors the uniform block of `shader/stereo.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct EyeMatrices {
+    /// View-projection of the left eye, then of the right one, picked by `gl_ViewIndex`.
+    pub view_proj: [[[f32; 4]; 4]; 2],
+}
+
+/// Renders [`Vertex3D`] meshes for both eyes of a headset at once with multiview: every draw
+/// is broadcast to the two layers of the color and depth targets, layer 0 for the left eye
+/// and layer 1 for the right one.
+///
+/// The targets match the extent and format of the swapchain, so each eye can be blitted into
+/// it or handed on to a VR runtime.
+pub struct StereoRenderPass {
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffer: Arc<Framebuffer>,
+    color: Arc<Image>,
+    eye_matrices: Subbuffer<EyeMatrices>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+}
+
+impl StereoRenderPass {
+    /// One bit per eye.
+    pub const VIEW_MASK: u32 = 0b11;
+    pub const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
+    /// Requires the `multiview` feature to be enabled on the device of `allocator`.
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        swapchain: &Swapchain,
+        left_vp: [[f32; 4]; 4],
+        right_vp: [[f32; 4]; 4],
+    ) -> Result<Self, PipelineError> {
+        Self::with_target(
+            allocator,
+            swapchain.image_format(),
+            swapchain.image_extent(),
+            left_vp,
+            right_vp,
+        )
+    }
+
+    /// Like [`new`](Self::new), with targets of `format` and `extent` instead of the
+    /// swapchain's, e.g. for a VR runtime or headless rendering.
+    pub fn with_target(
+        allocator: Arc<dyn MemoryAllocator>,
+        format: Format,
+        [width, height]: [u32; 2],
+        left_vp: [[f32; 4]; 4],
+        right_vp: [[f32; 4]; 4],
+    ) -> Result<Self, PipelineError> {
+        let device = allocator.device().clone();
+        if !device.enabled_features().multiview {
+            return Err(PipelineError::FeatureNotEnabled("multiview"));
+        }
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                format,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::TransferSrcOptimal,
+            )
+            .add_attachment(
+                Self::DEPTH_FORMAT,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::DontCare,
+                ImageLayout::DepthStencilAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], Some(1))
+            .multiview(Self::VIEW_MASK)
+            .build()
+            .map_err(vulkan_error)?;
+
+        let layered_image = |format, usage| {
+            Image::new(
+                allocator.clone(),
+                ImageCreateInfo {
+                    format,
+                    extent: [width, height, 1],
+                    array_layers: Self::VIEW_MASK.count_ones(),
+                    usage,
+                    ..ImageCreateInfo::default()
+                },
+                AllocationCreateInfo::default(),
+            )
+            .map_err(vulkan_error)
+        };
+        let color = layered_image(
+            format,
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC | ImageUsage::SAMPLED,
+        )?;
+        let depth = layered_image(Self::DEPTH_FORMAT, ImageUsage::DEPTH_STENCIL_ATTACHMENT)?;
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![
+                    ImageView::new_default(color.clone()).map_err(vulkan_error)?,
+                    ImageView::new_default(depth).map_err(vulkan_error)?,
+                ],
+                ..FramebufferCreateInfo::default()
+            },
+        )
+        .map_err(vulkan_error)?;
+
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_stereo_vertex(device.clone()).map_err(vulkan_error)?)
+            .fragment_shader(load_stereo_fragment(device.clone()).map_err(vulkan_error)?)
+            .vertex_input(Vertex3D::per_vertex())
+            .render_pass(render_pass.clone(), 0)
+            .viewport(Viewport {
+                offset: [0.0, 0.0],
+                extent: [width as f32, height as f32],
+                depth_range: 0.0..=1.0,
+            })
+            .depth_test(true, true, CompareOp::Less)
+            .build()?;
+        debug!("stereo pipeline: {pipeline:?}");
+
+        let eye_matrices = Buffer::from_data(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::UNIFORM_BUFFER | BufferUsage::TRANSFER_DST,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            EyeMatrices {
+                view_proj: [left_vp, right_vp],
+            },
+        )
+        .map_err(vulkan_error)?;
+        let descriptor_set = PersistentDescriptorSet::new(
+            &StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+            pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::buffer(0, eye_matrices.clone())],
+            [],
+        )
+        .map_err(vulkan_error)?;
+
+        Ok(Self {
+            render_pass,
+            pipeline,
+            framebuffer,
+            color,
+            eye_matrices,
+            descriptor_set,
+        })
+    }
+
+    pub fn render_pass(&self) -> &Arc<RenderPass> {
+        &self.render_pass
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    /// Both eyes as array layers, in `TransferSrcOptimal` layout after [`record`](Self::record).
+    pub fn color_image(&self) -> &Arc<Image> {
+        &self.color
+    }
+
+    /// Replaces the view-projection matrices for the following draws; must be recorded
+    /// outside the render pass.
+    pub fn update_eye_matrices(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        left: [[f32; 4]; 4],
+        right: [[f32; 4]; 4],
+    ) -> Result<(), Box<ValidationError>> {
+        builder.update_buffer(
+            self.eye_matrices.clone(),
+            Box::new(EyeMatrices {
+                view_proj: [left, right],
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Clears both eyes and draws the indexed triangles of `vertex_buffer` into them.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        vertex_buffer: Subbuffer<[Vertex3D]>,
+        index_buffer: Subbuffer<[u32]>,
+    ) -> Result<(), Box<ValidationError>> {
+        let index_count = index_buffer.len() as u32;
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![
+                        Some(ClearValue::Float([0.0, 0.0, 0.0, 1.0])),
+                        Some(ClearValue::Depth(1.0)),
+                    ],
+                    ..RenderPassBeginInfo::framebuffer(self.framebuffer.clone())
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )?
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.descriptor_set.clone(),
+            )?
+            .bind_vertex_buffers(0, vertex_buffer)?
+            .bind_index_buffer(index_buffer)?
+            .draw_indexed(index_count, 1, 0, 0, 0)?
+            .end_render_pass(SubpassEndInfo::default())?;
+        Ok(())
+    }
+}
+
+/// Mirrors the push constant block of `shader/capture.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct CaptureParams {
+    pub transform: [[f32; 4]; 4],
+}
+
+/// Transforms the vertices of [`Vertex3D`] meshes and writes them out as [`CapturedVertex`]es
+/// instead of rasterizing them, one per vertex drawn.
+///
+/// The pipeline runs in a render pass of its own without attachments. Transform feedback
+/// itself is not supported by vulkano yet, so it is recorded by [`TransformFeedbackPass`]
+/// through the raw `VK_EXT_transform_feedback` functions.
+pub struct TransformFeedbackPipeline {
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffer: Arc<Framebuffer>,
+}
+
+impl TransformFeedbackPipeline {
+    /// Whether the device can be created with [`Self::required_extensions`] and the
+    /// `transform_feedback` feature.
+    pub fn is_supported(physical_device: &PhysicalDevice) -> bool {
+        physical_device
+            .supported_extensions()
+            .contains(&Self::required_extensions())
+            && physical_device.supported_features().transform_feedback
+    }
+
+    pub fn required_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            ext_transform_feedback: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    /// Requires the `transform_feedback` feature to be enabled on `device`.
+    pub fn new(device: Arc<Device>) -> Result<Self, PipelineError> {
+        if !device.enabled_features().transform_feedback {
+            return Err(PipelineError::FeatureNotEnabled("transform_feedback"));
+        }
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_subpass(&[], &[], None)
+            .build()
+            .map_err(vulkan_error)?;
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                extent: [1, 1],
+                layers: 1,
+                ..FramebufferCreateInfo::default()
+            },
+        )
+        .map_err(vulkan_error)?;
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_capture_vertex(device).map_err(vulkan_error)?)
+            .vertex_input(Vertex3D::per_vertex())
+            .topology(PrimitiveTopology::PointList)
+            .render_pass(render_pass, 0)
+            .viewport(Viewport {
+                offset: [0.0, 0.0],
+                extent: [1.0, 1.0],
+                depth_range: 0.0..=1.0,
+            })
+            .rasterizer_discard()
+            .build()?;
+        debug!("transform feedback pipeline: {pipeline:?}");
+        Ok(Self {
+            pipeline,
+            framebuffer,
+        })
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    pub fn render_pass(&self) -> &Arc<RenderPass> {
+        self.framebuffer.render_pass()
+    }
+}
+
+/// Captures the output of a [`TransformFeedbackPipeline`] into a buffer that can be drawn from
+/// afterwards, counting the vertices written in a host-visible counter buffer.
+///
+/// Commands are recorded into an [`UnsafeCommandBufferBuilder`], as vulkano would neither
+/// track nor order the raw transform feedback commands between its own.
+pub struct TransformFeedbackPass {
+    pipeline: TransformFeedbackPipeline,
+    capture: Subbuffer<[CapturedVertex]>,
+    counter: Subbuffer<[u32]>,
+}
+
+impl TransformFeedbackPass {
+    /// Allocates room for `capacity` vertices; whatever is drawn beyond it is not captured.
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        pipeline: TransformFeedbackPipeline,
+        capacity: u32,
+    ) -> Result<Self, PipelineError> {
+        let capture = transform_feedback_buffer(
+            allocator.clone(),
+            capacity.max(1) as DeviceSize * mem::size_of::<CapturedVertex>() as DeviceSize,
+            ash::vk::BufferUsageFlags::TRANSFORM_FEEDBACK_BUFFER_EXT,
+            BufferUsage::VERTEX_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE,
+        )?
+        .reinterpret();
+        let counter = transform_feedback_buffer(
+            allocator,
+            mem::size_of::<u32>() as DeviceSize,
+            ash::vk::BufferUsageFlags::TRANSFORM_FEEDBACK_COUNTER_BUFFER_EXT,
+            BufferUsage::empty(),
+            MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+        )?
+        .reinterpret();
+        Ok(Self {
+            pipeline,
+            capture,
+            counter,
+        })
+    }
+
+    pub fn pipeline(&self) -> &TransformFeedbackPipeline {
+        &self.pipeline
+    }
+
+    /// The captured vertices, ready to be read as a vertex buffer after [`end`](Self::end).
+    pub fn capture_buffer(&self) -> &Subbuffer<[CapturedVertex]> {
+        &self.capture
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capture.len() as u32
+    }
+
+    /// Begins the render pass of the pipeline, binds it and starts capturing at the start of
+    /// the capture buffer. Draw [`Vertex3D`] vertex buffers next, then [`end`](Self::end).
+    ///
+    /// # Safety
+    ///
+    /// The buffers must not be in use by commands that are still pending.
+    pub unsafe fn begin<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+        params: CaptureParams,
+    ) -> Result<(), Box<ValidationError>> {
+        let pipeline = &self.pipeline.pipeline;
+        builder
+            .begin_render_pass(
+                &RenderPassBeginInfo::framebuffer(self.pipeline.framebuffer.clone()),
+                &SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )?
+            .bind_pipeline_graphics(pipeline)?
+            .push_constants(pipeline.layout(), 0, &params)?;
+        let fns = &pipeline.device().fns().ext_transform_feedback;
+        (fns.cmd_bind_transform_feedback_buffers_ext)(
+            builder.handle(),
+            0,
+            1,
+            &self.capture.buffer().handle(),
+            &self.capture.offset(),
+            &self.capture.size(),
+        );
+        (fns.cmd_begin_transform_feedback_ext)(builder.handle(), 0, 0, ptr::null(), ptr::null());
+        Ok(())
+    }
+
+    /// Stops capturing, storing the number of bytes written in the counter buffer, ends the
+    /// render pass and makes the captured vertices visible to vertex input and the counter to
+    /// the host.
+    ///
+    /// # Safety
+    ///
+    /// Must follow a [`begin`](Self::begin) in the same command buffer, with only draws in
+    /// between.
+    pub unsafe fn end<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+    ) -> Result<(), Box<ValidationError>> {
+        (self
+            .pipeline
+            .pipeline
+            .device()
+            .fns()
+            .ext_transform_feedback
+            .cmd_end_transform_feedback_ext)(
+            builder.handle(),
+            0,
+            1,
+            &self.counter.buffer().handle(),
+            &self.counter.offset(),
+        );
+        builder
+            .end_render_pass(&SubpassEndInfo::default())?
+            .pipeline_barrier(&DependencyInfo {
+                buffer_memory_barriers: [
+                    BufferMemoryBarrier {
+                        src_stages: PipelineStages::TRANSFORM_FEEDBACK,
+                        src_access: AccessFlags::TRANSFORM_FEEDBACK_WRITE,
+                        dst_stages: PipelineStages::VERTEX_ATTRIBUTE_INPUT,
+                        dst_access: AccessFlags::VERTEX_ATTRIBUTE_READ,
+                        range: self.capture.offset()..self.capture.offset() + self.capture.size(),
+                        ..BufferMemoryBarrier::buffer(self.capture.buffer().clone())
+                    },
+                    BufferMemoryBarrier {
+                        src_stages: PipelineStages::TRANSFORM_FEEDBACK,
+                        src_access: AccessFlags::TRANSFORM_FEEDBACK_COUNTER_WRITE,
+                        dst_stages: PipelineStages::HOST,
+                        dst_access: AccessFlags::HOST_READ,
+                        range: self.counter.offset()..self.counter.offset() + self.counter.size(),
+                        ..BufferMemoryBarrier::buffer(self.counter.buffer().clone())
+                    },
+                ]
+                .into_iter()
+                .collect(),
+                ..DependencyInfo::default()
+            })?;
+        Ok(())
+    }
+
+    /// Number of vertices captured between the last [`begin`](Self::begin) and
+    /// [`end`](Self::end); only meaningful once the command buffer has completed.
+    pub fn captured_vertex_count(&self) -> Result<u32, HostAccessError> {
+        let bytes = self.counter.read()?[0];
+        Ok((bytes / mem::size_of::<CapturedVertex>() as u32).min(self.capacity()))
+    }
+}
+
+/// Buffer for transform feedback `usage`, plus the `vulkano_usage` vulkano validates its own
+/// commands against.
+///
+/// vulkano does not expose transform feedback buffer usages yet, so the buffer is created
+/// directly and only its memory is bound through vulkano.
+fn transform_feedback_buffer(
+    allocator: Arc<dyn MemoryAllocator>,
+    size: DeviceSize,
+    usage: ash::vk::BufferUsageFlags,
+    vulkano_usage: BufferUsage,
+    memory_type_filter: MemoryTypeFilter,
+) -> Result<Subbuffer<[u8]>, PipelineError> {
+    let device = allocator.device().clone();
+    let create_info = ash::vk::BufferCreateInfo {
+        size,
+        usage: usage | vulkano_usage.into(),
+        sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let mut handle = ash::vk::Buffer::null();
+    unsafe {
+        (device.fns().v1_0.create_buffer)(device.handle(), &create_info, ptr::null(), &mut handle)
+    }
+    .result()
+    .map_err(|e| vulkan_error(VulkanError::from(e)))?;
+    // Takes ownership of `handle`, destroying it on drop.
+    let raw_buffer = unsafe {
+        RawBuffer::from_handle(
+            device,
+            handle,
+            BufferCreateInfo {
+                size,
+                usage: vulkano_usage,
+                ..BufferCreateInfo::default()
+            },
+        )
+    };
+    let allocation = allocator
+        .allocate(
+            *raw_buffer.memory_requirements(),
+            AllocationType::Linear,
+            AllocationCreateInfo {
+                memory_type_filter,
+                ..AllocationCreateInfo::default()
+            },
+            Some(DedicatedAllocation::Buffer(&raw_buffer)),
+        )
+        .map_err(vulkan_error)?;
+    let allocation = unsafe { ResourceMemory::from_allocation(allocator, allocation) };
+    let buffer = raw_buffer
+        .bind_memory(allocation)
+        .map_err(|(e, _, _)| vulkan_error(e))?;
+    debug!("transform feedback buffer: {buffer:?}");
+    Ok(Subbuffer::new(Arc::new(buffer)))
+}
+
+/// How the samples of a multisampled depth attachment are combined when resolved.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DepthResolveMode {
+    /// Closest depth with a `LESS` depth test.
+    Min,
+    /// Farthest depth with a `LESS` depth test.
+    Max,
+    Average,
+    /// The depth of sample 0, the only mode every implementation supports.
+    SampleZero,
+}
+
+impl From<DepthResolveMode> for ResolveMode {
+    fn from(mode: DepthResolveMode) -> Self {
+        match mode {
+            DepthResolveMode::Min => ResolveMode::Min,
+            DepthResolveMode::Max => ResolveMode::Max,
+            DepthResolveMode::Average => ResolveMode::Average,
+            DepthResolveMode::SampleZero => ResolveMode::SampleZero,
+        }
+    }
+}
+
+/// Render pass drawing into multisampled color and depth attachments and resolving both into
+/// single-sample images at the end of its subpass, so that later passes can sample the depth.
+///
+/// Depth resolve is core in Vulkan 1.2 and needs [`Self::required_extensions`] before.
+pub struct DepthResolvePass {
+    render_pass: Arc<RenderPass>,
+    mode: DepthResolveMode,
+}
+
+impl DepthResolvePass {
+    pub const COLOR_ATTACHMENT: u32 = 0;
+    pub const RESOLVED_COLOR_ATTACHMENT: u32 = 1;
+    pub const DEPTH_ATTACHMENT: u32 = 2;
+    pub const RESOLVED_DEPTH_ATTACHMENT: u32 = 3;
+
+    /// Whether `physical_device` supports depth resolve with `mode`.
+    pub fn is_supported(physical_device: &PhysicalDevice, mode: DepthResolveMode) -> bool {
+        (physical_device.api_version() >= Version::V1_2
+            || physical_device
+                .supported_extensions()
+                .contains(&Self::required_extensions()))
+            && Self::supports_mode(physical_device, mode)
+    }
+
+    /// Extensions to enable on devices older than Vulkan 1.2.
+    pub fn required_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            khr_depth_stencil_resolve: true,
+            khr_create_renderpass2: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    /// Requires depth stencil resolve to be enabled on `device` and `mode` to be supported.
+    pub fn new(
+        device: Arc<Device>,
+        color_format: Format,
+        depth_format: Format,
+        samples: SampleCount,
+        mode: DepthResolveMode,
+    ) -> Result<Self, PipelineError> {
+        let enabled = device.api_version() >= Version::V1_2
+            || device
+                .enabled_extensions()
+                .contains(&Self::required_extensions());
+        if !enabled || !Self::supports_mode(device.physical_device(), mode) {
+            return Err(PipelineError::FeatureNotEnabled("depth_stencil_resolve"));
+        }
+        let render_pass = RenderPassBuilder::new(device)
+            .add_attachment(
+                color_format,
+                samples,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::DontCare,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_attachment(
+                color_format,
+                SampleCount::Sample1,
+                AttachmentLoadOp::DontCare,
+                AttachmentStoreOp::Store,
+                ImageLayout::ShaderReadOnlyOptimal,
+            )
+            .add_attachment(
+                depth_format,
+                samples,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::DontCare,
+                ImageLayout::DepthStencilAttachmentOptimal,
+            )
+            .add_attachment(
+                depth_format,
+                SampleCount::Sample1,
+                AttachmentLoadOp::DontCare,
+                AttachmentStoreOp::Store,
+                ImageLayout::DepthStencilReadOnlyOptimal,
+            )
+            .add_subpass(&[Self::COLOR_ATTACHMENT], &[], Some(Self::DEPTH_ATTACHMENT))
+            .resolve_into(&[Self::RESOLVED_COLOR_ATTACHMENT])
+            .resolve_depth_into(Self::RESOLVED_DEPTH_ATTACHMENT, mode)
+            .build()
+            .map_err(vulkan_error)?;
+        Ok(Self { render_pass, mode })
+    }
+
+    pub fn render_pass(&self) -> &Arc<RenderPass> {
+        &self.render_pass
+    }
+
+    pub fn mode(&self) -> DepthResolveMode {
+        self.mode
+    }
+
+    fn supports_mode(physical_device: &PhysicalDevice, mode: DepthResolveMode) -> bool {
+        physical_device
+            .properties()
+            .supported_depth_resolve_modes
+            .unwrap_or_default()
+            .contains_enum(mode.into())
+    }
+}
+
+/// Builds render passes with any number of subpasses sharing the same attachments.
+///
+/// Attachments and subpasses are numbered in the order they are added. Attachment layouts
+/// inside subpasses are derived from how each subpass uses them.
+pub struct RenderPassBuilder {
+    device: Arc<Device>,
+    attachments: Vec<AttachmentDescription>,
+    subpasses: Vec<SubpassDescription>,
+    dependencies: Vec<SubpassDependency>,
+    correlated_view_mask: u32,
+}
+
+impl RenderPassBuilder {
+    pub fn new(device: Arc<Device>) -> Self {
+        Self {
+            device,
+            attachments: vec![],
+            subpasses: vec![],
+            dependencies: vec![],
+            correlated_view_mask: 0,
+        }
+    }
+
+    pub fn add_attachment(
+        mut self,
+        format: Format,
+        samples: SampleCount,
+        load_op: AttachmentLoadOp,
+        store_op: AttachmentStoreOp,
+        final_layout: ImageLayout,
+    ) -> Self {
+        let depth_stencil = is_depth_stencil(format);
+        self.attachments.push(AttachmentDescription {
+            format,
+            samples,
+            load_op,
+            store_op,
+            stencil_load_op: depth_stencil.then_some(load_op),
+            stencil_store_op: depth_stencil.then_some(store_op),
+            final_layout,
+            ..AttachmentDescription::default()
+        });
+        self
+    }
+
+    /// Adds a subpass writing `color_attachments` and reading `input_attachments` through
+    /// `subpassInput`s; all values are attachment indices.
+    pub fn add_subpass(
+        mut self,
+        color_attachments: &[u32],
+        input_attachments: &[u32],
+        depth_attachment: Option<u32>,
+    ) -> Self {
+        let reference = |attachment, layout| {
+            Some(AttachmentReference {
+                attachment,
+                layout,
+                ..AttachmentReference::default()
+            })
+        };
+        let input_attachments = input_attachments
+            .iter()
+            .map(|&attachment| {
+                let layout = match self.attachments.get(attachment as usize) {
+                    Some(description) if is_depth_stencil(description.format) => {
+                        ImageLayout::DepthStencilReadOnlyOptimal
+                    }
+                    _ => ImageLayout::ShaderReadOnlyOptimal,
+                };
+                reference(attachment, layout)
+            })
+            .collect();
+        self.subpasses.push(SubpassDescription {
+            color_attachments: color_attachments
+                .iter()
+                .map(|&attachment| reference(attachment, ImageLayout::ColorAttachmentOptimal))
+                .collect(),
+            input_attachments,
+            depth_stencil_attachment: depth_attachment.and_then(|attachment| {
+                reference(attachment, ImageLayout::DepthStencilAttachmentOptimal)
+            }),
+            ..SubpassDescription::default()
+        });
+        self
+    }
+
+    /// Resolves the multisampled color attachments of the last subpass into
+    /// `resolve_attachments`, one per color attachment.
+    pub fn resolve_into(mut self, resolve_attachments: &[u32]) -> Self {
+        if let Some(subpass) = self.subpasses.last_mut() {
+            subpass.color_resolve_attachments = resolve_attachments
+                .iter()
+                .map(|&attachment| {
+                    Some(AttachmentReference {
+                        attachment,
+                        layout: ImageLayout::ColorAttachmentOptimal,
+                        ..AttachmentReference::default()
+                    })
+                })
+                .collect();
+        }
+        self
+    }
+
+    /// Resolves the multisampled depth attachment of the last subpass into
+    /// `resolve_attachment` with `mode`. The render pass is then built without vulkano's
+    /// validation, so the device must be checked like [`DepthResolvePass::new`] does.
+    pub fn resolve_depth_into(mut self, resolve_attachment: u32, mode: DepthResolveMode) -> Self {
+        if let Some(subpass) = self.subpasses.last_mut() {
+            subpass.depth_stencil_resolve_attachment = Some(AttachmentReference {
+                attachment: resolve_attachment,
+                layout: ImageLayout::DepthStencilAttachmentOptimal,
+                ..AttachmentReference::default()
+            });
+            subpass.depth_resolve_mode = Some(mode.into());
+        }
+        self
+    }
+
+    /// Makes the last subpass render once per bit set in `view_mask`, into the array layer of
+    /// that index, with `gl_ViewIndex` telling the shaders which one. The views are marked as
+    /// correlated, i.e. as seeing mostly the same things, as the eyes of a stereo pair do.
+    ///
+    /// Requires the `multiview` feature; either all subpasses have a view mask or none does.
+    pub fn multiview(mut self, view_mask: u32) -> Self {
+        if let Some(subpass) = self.subpasses.last_mut() {
+            subpass.view_mask = view_mask;
+            self.correlated_view_mask |= view_mask;
+        }
+        self
+    }
+
+    /// Adds an execution and memory dependency; `None` stands for commands outside the
+    /// render pass. Dependencies between two subpasses are made framebuffer-local.
+    pub fn add_dependency(
+        mut self,
+        src_subpass: Option<u32>,
+        dst_subpass: Option<u32>,
+        src_stages: PipelineStages,
+        dst_stages: PipelineStages,
+        src_access: AccessFlags,
+        dst_access: AccessFlags,
+    ) -> Self {
+        let dependency_flags = if src_subpass.is_some() && dst_subpass.is_some() {
+            DependencyFlags::BY_REGION
+        } else {
+            DependencyFlags::empty()
+        };
+        self.dependencies.push(SubpassDependency {
+            src_subpass,
+            dst_subpass,
+            src_stages,
+            dst_stages,
+            src_access,
+            dst_access,
+            dependency_flags,
+            ..SubpassDependency::default()
+        });
+        self
+    }
+
+    /// Makes color and depth writes of `src_subpass` visible to input attachment reads in the
+    /// fragment shaders of `dst_subpass`.
+    pub fn add_input_dependency(self, src_subpass: u32, dst_subpass: u32) -> Self {
+        self.add_dependency(
+            Some(src_subpass),
+            Some(dst_subpass),
+            PipelineStages::COLOR_ATTACHMENT_OUTPUT | PipelineStages::LATE_FRAGMENT_TESTS,
+            PipelineStages::FRAGMENT_SHADER,
+            AccessFlags::COLOR_ATTACHMENT_WRITE | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            AccessFlags::INPUT_ATTACHMENT_READ,
+        )
+    }
+
+    pub fn build(self) -> Result<Arc<RenderPass>, Validated<VulkanError>> {
+        let depth_resolve = self
+            .subpasses
+            .iter()
+            .any(|subpass| subpass.depth_resolve_mode.is_some());
+        let create_info = RenderPassCreateInfo {
+            attachments: self.attachments,
+            subpasses: self.subpasses,
+            dependencies: self.dependencies,
+            correlated_view_masks: (self.correlated_view_mask != 0)
+                .then_some(self.correlated_view_mask)
+                .into_iter()
+                .collect(),
+            ..RenderPassCreateInfo::default()
+        };
+        let render_pass = if depth_resolve {
+            // vulkano 0.34 rejects every subpass with both a depth resolve mode and a depth
+            // resolve attachment, its check being inverted, so validation is skipped.
+            // SAFETY: `resolve_depth_into` leaves checking the device supports depth resolve
+            // and the mode to the caller, as `DepthResolvePass::new` does.
+            unsafe { RenderPass::new_unchecked(self.device, create_info) }?
+        } else {
+            RenderPass::new(self.device, create_info)?
+        };
+        debug!("render pass: {render_pass:?}");
+        Ok(render_pass)
+    }
+}
+
+fn is_depth_stencil(format: Format) -> bool {
+    format
+        .aspects()
+        .intersects(ImageAspects::DEPTH | ImageAspects::STENCIL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Mat4;
+    use crate::testing::TestContext;
+    use vulkano::device::Features;
+    use vulkano::format::FormatFeatures;
+
+    fn record_shadow_pass(context: &TestContext, pass: &ShadowMapPass) {
+        let vertices = Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            [[-1.0, -1.0, 0.5], [3.0, -1.0, 0.5], [-1.0, 3.0, 0.5]].map(|position| Vertex3D {
+                position,
+                ..Vertex3D::default()
+            }),
+        )
+        .unwrap();
+        let indices = Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            [0u32, 1, 2],
+        )
+        .unwrap();
+        let mut builder = context.command_buffer();
+        pass.record(
+            &mut builder,
+            vertices,
+            indices,
+            ShadowParams {
+                light_view_proj: Mat4::IDENTITY.0,
+            },
+        )
+        .unwrap();
+        context.submit(builder);
+    }
+
+    fn test_viewport() -> Viewport {
+        Viewport {
+            offset: [0.0, 0.0],
+            extent: [64.0, 64.0],
+            depth_range: 0.0..=1.0,
+        }
+    }
+
+    #[test]
+    fn cull_modes_map_to_vulkano() {
+        for (mode, expected) in [
+            (CullMode::None, rasterization::CullMode::None),
+            (CullMode::Front, rasterization::CullMode::Front),
+            (CullMode::Back, rasterization::CullMode::Back),
+            (
+                CullMode::FrontAndBack,
+                rasterization::CullMode::FrontAndBack,
+            ),
+        ] {
+            assert_eq!(rasterization::CullMode::from(mode), expected);
+            let state = FixedFunctionState {
+                cull_mode: mode,
+                front_face: FrontFace::Clockwise,
+                ..FixedFunctionState::default()
+            }
+            .rasterization_state();
+            assert_eq!(state.cull_mode, expected);
+            assert_eq!(state.front_face, FrontFace::Clockwise);
+        }
+    }
+
+    #[test]
+    fn back_faces_are_culled_by_default() {
+        let state = FixedFunctionState::default().rasterization_state();
+        assert_eq!(state.cull_mode, rasterization::CullMode::Back);
+        assert_eq!(state.front_face, FrontFace::CounterClockwise);
+        assert!(!state.rasterizer_discard_enable);
+    }
+
+    #[test]
+    fn depth_stencil_state_follows_the_subpass() {
+        assert!(depth_stencil_state(false, None, None).is_none());
+
+        let state = depth_stencil_state(true, None, None).unwrap();
+        assert!(state.depth.is_none());
+        assert!(state.stencil.is_none());
+
+        let depth = DepthState {
+            write_enable: true,
+            compare_op: CompareOp::Less,
+        };
+        let state = depth_stencil_state(false, Some(depth), None).unwrap();
+        let state_depth = state.depth.unwrap();
+        assert!(state_depth.write_enable);
+        assert_eq!(state_depth.compare_op, CompareOp::Less);
+    }
+
+    #[test]
+    fn blend_modes_set_the_attachment_blend() {
+        let blend = |blend_mode| {
+            let state = FixedFunctionState {
+                blend_mode,
+                ..FixedFunctionState::default()
+            }
+            .color_blend_state(2)
+            .unwrap();
+            assert_eq!(state.attachments.len(), 2);
+            state.attachments[0].blend
+        };
+        assert!(blend(BlendMode::Opaque).is_none());
+        let alpha = blend(BlendMode::Alpha).unwrap();
+        assert_eq!(alpha.src_color_blend_factor, BlendFactor::SrcAlpha);
+        assert_eq!(alpha.dst_color_blend_factor, BlendFactor::OneMinusSrcAlpha);
+        let additive = blend(BlendMode::Additive).unwrap();
+        assert_eq!(additive.dst_color_blend_factor, BlendFactor::One);
+        assert!(FixedFunctionState::default().color_blend_state(0).is_none());
+    }
+
+    #[test]
+    fn premultiplied_alpha_adds_the_source_unscaled() {
+        let blend = BlendMode::PremultipliedAlpha.attachment_blend().unwrap();
+        assert_eq!(blend.src_color_blend_factor, BlendFactor::One);
+        assert_eq!(blend.dst_color_blend_factor, BlendFactor::OneMinusSrcAlpha);
+        assert_eq!(blend.color_blend_op, BlendOp::Add);
+        assert_eq!(blend.src_alpha_blend_factor, BlendFactor::One);
+        assert_eq!(blend.dst_alpha_blend_factor, BlendFactor::OneMinusSrcAlpha);
+        assert_eq!(blend.alpha_blend_op, BlendOp::Add);
+    }
+
+    #[test]
+    fn depth_test_and_write_reach_the_depth_state() {
+        let state = FixedFunctionState {
+            depth: Some(DepthState {
+                write_enable: false,
+                compare_op: CompareOp::LessOrEqual,
+            }),
+            ..FixedFunctionState::default()
+        };
+        let depth = depth_stencil_state(false, state.depth, state.stencil)
+            .unwrap()
+            .depth
+            .unwrap();
+        assert!(!depth.write_enable);
+        assert_eq!(depth.compare_op, CompareOp::LessOrEqual);
+    }
+
+    #[test]
+    fn topology_and_dynamic_states_are_applied() {
+        let default = FixedFunctionState::default();
+        assert_eq!(
+            default.input_assembly_state().topology,
+            PrimitiveTopology::TriangleList
+        );
+        assert_eq!(default.dynamic_state().count(), 0);
+
+        let state = FixedFunctionState {
+            topology: PrimitiveTopology::LineStrip,
+            dynamic_line_width: true,
+            dynamic_scissor: true,
+            ..FixedFunctionState::default()
+        };
+        assert_eq!(
+            state.input_assembly_state().topology,
+            PrimitiveTopology::LineStrip
+        );
+        assert_eq!(
+            state.dynamic_state().collect::<Vec<_>>(),
+            [DynamicState::LineWidth, DynamicState::Scissor]
+        );
+    }
+
+    #[test]
+    fn polygon_offset_is_clamped_unless_dynamic() {
+        let depth_bias = DepthBiasState {
+            constant_factor: 1.25,
+            slope_factor: 1.75,
+            clamp: 0.0,
+        };
+        let state = FixedFunctionState {
+            depth_bias: Some(depth_bias),
+            depth_bias_clamp: 0.5,
+            ..FixedFunctionState::default()
+        };
+        let bias = state.rasterization_state().depth_bias.unwrap();
+        assert_eq!(
+            (bias.constant_factor, bias.slope_factor, bias.clamp),
+            (1.25, 1.75, 0.5)
+        );
+        let dynamic = FixedFunctionState {
+            dynamic_depth_bias: true,
+            ..state
+        };
+        let bias = dynamic.rasterization_state().depth_bias.unwrap();
+        assert_eq!(
+            bias.constant_factor,
+            DepthBiasState::default().constant_factor
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn missing_options_are_reported() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], None)
+            .build()
+            .unwrap();
+        let viewport = Viewport {
+            offset: [0.0, 0.0],
+            extent: [64.0, 64.0],
+            depth_range: 0.0..=1.0,
+        };
+        let vertex_shader = load_fullscreen(device.clone()).unwrap();
+        let missing = |builder: GraphicsPipelineBuilder| match builder.build() {
+            Err(PipelineError::Missing(option)) => option,
+            other => panic!("expected a missing option, got {other:?}"),
+        };
+
+        assert_eq!(
+            missing(
+                GraphicsPipelineBuilder::new(device.clone())
+                    .render_pass(render_pass.clone(), 0)
+                    .viewport(viewport.clone())
+            ),
+            "vertex shader"
+        );
+        assert_eq!(
+            missing(
+                GraphicsPipelineBuilder::new(device.clone())
+                    .vertex_shader(vertex_shader.clone())
+                    .viewport(viewport.clone())
+            ),
+            "render pass"
+        );
+        assert_eq!(
+            missing(
+                GraphicsPipelineBuilder::new(device.clone())
+                    .vertex_shader(vertex_shader.clone())
+                    .render_pass(render_pass.clone(), 0)
+                    .viewport(viewport.clone())
+            ),
+            "fragment shader"
+        );
+        assert!(matches!(
+            GraphicsPipelineBuilder::new(device)
+                .vertex_shader(vertex_shader)
+                .render_pass(render_pass, 1)
+                .build(),
+            Err(PipelineError::NoSubpass(1))
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn two_subpass_deferred_render_pass_builds() {
+        let context = TestContext::new();
+        let attachment = |builder: RenderPassBuilder, format, store_op| {
+            builder.add_attachment(
+                format,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                store_op,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+        };
+        let mut builder = RenderPassBuilder::new(context.queue.device().clone());
+        for (format, store_op) in [
+            (Format::R8G8B8A8_UNORM, AttachmentStoreOp::Store),
+            (Format::R8G8B8A8_UNORM, AttachmentStoreOp::DontCare),
+            (Format::R16G16B16A16_SFLOAT, AttachmentStoreOp::DontCare),
+        ] {
+            builder = attachment(builder, format, store_op);
+        }
+        let render_pass = builder
+            .add_attachment(
+                Format::D16_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::DontCare,
+                ImageLayout::DepthStencilAttachmentOptimal,
+            )
+            // geometry into albedo and normals, then lighting reading them back
+            .add_subpass(&[1, 2], &[], Some(3))
+            .add_subpass(&[0], &[1, 2, 3], None)
+            .add_input_dependency(0, 1)
+            .build()
+            .unwrap();
+
+        let subpasses = render_pass.subpasses();
+        assert_eq!(subpasses.len(), 2);
+        let layouts: Vec<_> = subpasses[1]
+            .input_attachments
+            .iter()
+            .map(|reference| reference.as_ref().unwrap().layout)
+            .collect();
+        assert_eq!(
+            layouts,
+            [
+                ImageLayout::ShaderReadOnlyOptimal,
+                ImageLayout::ShaderReadOnlyOptimal,
+                ImageLayout::DepthStencilReadOnlyOptimal,
+            ]
+        );
+        let dependency = &render_pass.dependencies()[0];
+        assert_eq!(
+            (dependency.src_subpass, dependency.dst_subpass),
+            (Some(0), Some(1))
+        );
+        assert_eq!(dependency.dependency_flags, DependencyFlags::BY_REGION);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn msaa_pipeline_is_incompatible_with_a_single_sampled_render_pass() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let render_pass = |samples| {
+            RenderPassBuilder::new(device.clone())
+                .add_attachment(
+                    Format::R8G8B8A8_UNORM,
+                    samples,
+                    AttachmentLoadOp::Clear,
+                    AttachmentStoreOp::Store,
+                    ImageLayout::ColorAttachmentOptimal,
+                )
+                .add_subpass(&[0], &[], None)
+                .build()
+                .unwrap()
+        };
+        let msaa = render_pass(SampleCount::Sample4);
+        let single = render_pass(SampleCount::Sample1);
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_outline_vertex(device.clone()).unwrap())
+            .fragment_shader(load_outline_fragment(device).unwrap())
+            .vertex_input(MyVertex::per_vertex())
+            .render_pass(msaa.clone(), 0)
+            .viewport(Viewport {
+                offset: [0.0, 0.0],
+                extent: [64.0, 64.0],
+                depth_range: 0.0..=1.0,
+            })
+            .build()
+            .unwrap();
+
+        assert!(compatible_with_render_pass(&pipeline, &msaa));
+        assert!(!compatible_with_render_pass(&pipeline, &single));
+        let mismatches = render_pass_mismatches(&pipeline, &single);
+        assert_eq!(mismatches.len(), 2, "{mismatches:?}");
+        assert!(mismatches[0].starts_with("attachment 0"));
+        assert!(mismatches[1].starts_with("subpass 0"));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn depth_bias_is_set_only_when_dynamic() {
+        let context = TestContext::new();
+
+        let mut fixed = ShadowMapPass::new(context.memory_allocator.clone(), 64, false).unwrap();
+        fixed.set_depth_bias(2.0, 3.0);
+        assert!(!fixed.has_dynamic_depth_bias());
+        assert_eq!(fixed.dynamic_depth_bias(), None);
+        record_shadow_pass(&context, &fixed);
+
+        let mut dynamic = ShadowMapPass::new(context.memory_allocator.clone(), 64, true).unwrap();
+        assert!(dynamic.has_dynamic_depth_bias());
+        assert_eq!(
+            dynamic.dynamic_depth_bias(),
+            Some(ShadowMapPass::DEFAULT_DEPTH_BIAS)
+        );
+        dynamic.set_depth_bias(2.0, 3.0);
+        assert_eq!(dynamic.dynamic_depth_bias(), Some((2.0, 3.0)));
+        record_shadow_pass(&context, &dynamic);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn depth_prepass_pipeline_has_no_color_attachments() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let builder = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_attachment(
+                DepthPrepass::DEPTH_FORMAT,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::DontCare,
+                ImageLayout::DepthStencilAttachmentOptimal,
+            );
+        let render_pass = DepthPrepass::add_subpasses(builder, &[0], 1)
+            .build()
+            .unwrap();
+        let prepass = DepthPrepass::new(
+            device.clone(),
+            load_outline_vertex(device).unwrap(),
+            MyVertex::per_vertex(),
+            render_pass.clone(),
+            test_viewport(),
+        )
+        .unwrap();
+
+        let subpass = &render_pass.subpasses()[DepthPrepass::SUBPASS as usize];
+        assert!(subpass.color_attachments.is_empty());
+        let pipeline = prepass.pipeline();
+        assert!(pipeline.color_blend_state().is_none());
+        let depth = pipeline.depth_stencil_state().unwrap().depth.unwrap();
+        assert!(depth.write_enable);
+        assert_eq!(depth.compare_op, CompareOp::Less);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn outline_pipelines_write_and_test_the_stencil() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        // one of the two is supported by every device
+        let stencil_format = [Format::D24_UNORM_S8_UINT, Format::D32_SFLOAT_S8_UINT]
+            .into_iter()
+            .find(|&format| {
+                device
+                    .physical_device()
+                    .format_properties(format)
+                    .unwrap()
+                    .optimal_tiling_features
+                    .intersects(FormatFeatures::DEPTH_STENCIL_ATTACHMENT)
+            })
+            .unwrap();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_attachment(
+                stencil_format,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::DontCare,
+                ImageLayout::DepthStencilAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], Some(1))
+            .build()
+            .unwrap();
+        let outline = OutlineEffect::new(
+            device.clone(),
+            load_outline_vertex(device.clone()).unwrap(),
+            load_outline_fragment(device).unwrap(),
+            render_pass,
+            0,
+            test_viewport(),
+        )
+        .unwrap();
+
+        let stencil = |pipeline: &GraphicsPipeline| {
+            pipeline
+                .depth_stencil_state()
+                .unwrap()
+                .stencil
+                .clone()
+                .unwrap()
+                .front
+        };
+        let mask = stencil(outline.mask_pipeline());
+        assert_eq!(mask.ops.pass_op, StencilOp::Replace);
+        assert_eq!(mask.reference, OutlineEffect::STENCIL_REFERENCE);
+        let rim = stencil(outline.outline_pipeline());
+        assert_eq!(rim.ops.compare_op, CompareOp::Equal);
+        assert_eq!((rim.reference, rim.write_mask), (0, 0));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn pipeline_with_polygon_offset_builds() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                ShadowMapPass::FORMAT,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::DepthStencilReadOnlyOptimal,
+            )
+            .add_subpass(&[], &[], Some(0))
+            .build()
+            .unwrap();
+        let builder = || {
+            GraphicsPipelineBuilder::new(device.clone())
+                .vertex_shader(load_shadow_vertex(device.clone()).unwrap())
+                .vertex_input(Vertex3D::per_vertex())
+                .render_pass(render_pass.clone(), 0)
+                .viewport(test_viewport())
+                .depth_test(true, true, CompareOp::Less)
+                .polygon_offset(2.0, 1.0)
+        };
+
+        let pipeline = builder().build().unwrap();
+        let bias = pipeline.rasterization_state().depth_bias.unwrap();
+        assert_eq!((bias.constant_factor, bias.slope_factor), (1.0, 2.0));
+        if !device.enabled_features().depth_bias_clamp {
+            assert!(matches!(
+                builder().polygon_offset_clamp(0.5).build(),
+                Err(PipelineError::FeatureNotEnabled("depth_bias_clamp"))
+            ));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device with transform feedback"]
+    fn transform_feedback_pipeline_compiles_and_counts_vertices() {
+        let context = TestContext::with_extensions(
+            TransformFeedbackPipeline::required_extensions(),
+            Features {
+                transform_feedback: true,
+                ..Features::empty()
+            },
+        );
+        let pipeline = TransformFeedbackPipeline::new(context.queue.device().clone()).unwrap();
+        assert!(
+            pipeline
+                .pipeline()
+                .rasterization_state()
+                .rasterizer_discard_enable
+        );
+        let pass =
+            TransformFeedbackPass::new(context.memory_allocator.clone(), pipeline, 16).unwrap();
+        assert_eq!(pass.capacity(), 16);
+
+        let vertex_size = mem::size_of::<CapturedVertex>() as u32;
+        pass.counter.write().unwrap()[0] = 3 * vertex_size;
+        assert_eq!(pass.captured_vertex_count().unwrap(), 3);
+        // whatever was drawn beyond the capture buffer was not captured
+        pass.counter.write().unwrap()[0] = 40 * vertex_size;
+        assert_eq!(pass.captured_vertex_count().unwrap(), 16);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn transform_feedback_needs_the_feature() {
+        let context = TestContext::new();
+        if !context.queue.device().enabled_features().transform_feedback {
+            assert!(matches!(
+                TransformFeedbackPipeline::new(context.queue.device().clone()),
+                Err(PipelineError::FeatureNotEnabled("transform_feedback"))
+            ));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn tint_subpass_reads_the_scene_as_an_input_attachment() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let mut builder = RenderPassBuilder::new(device.clone());
+        for _ in 0..2 {
+            builder = builder.add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            );
+        }
+        let render_pass = InputAttachmentPipeline::add_subpasses(builder, 0, 1)
+            .build()
+            .unwrap();
+
+        let tint = &render_pass.subpasses()[InputAttachmentPipeline::TINT_SUBPASS as usize];
+        let input = tint.input_attachments[0].as_ref().unwrap();
+        assert_eq!(
+            (input.attachment, input.layout),
+            (0, ImageLayout::ShaderReadOnlyOptimal)
+        );
+        assert_eq!(tint.color_attachments[0].as_ref().unwrap().attachment, 1);
+
+        let pipeline = InputAttachmentPipeline::new(device, render_pass, test_viewport()).unwrap();
+        let scene = Image::new(
+            context.memory_allocator.clone(),
+            ImageCreateInfo {
+                format: Format::R8G8B8A8_UNORM,
+                extent: [64, 64, 1],
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::INPUT_ATTACHMENT,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+        pipeline
+            .descriptor_set(ImageView::new_default(scene).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn stereo_render_pass_constructs_with_two_views() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions::empty(),
+            Features {
+                multiview: true,
+                ..Features::empty()
+            },
+        );
+        let stereo = StereoRenderPass::with_target(
+            context.memory_allocator.clone(),
+            Format::R8G8B8A8_UNORM,
+            [64, 64],
+            Mat4::IDENTITY.0,
+            Mat4::IDENTITY.0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            stereo.render_pass().subpasses()[0].view_mask,
+            StereoRenderPass::VIEW_MASK
+        );
+        assert_eq!(stereo.color_image().array_layers(), 2);
+        let mut builder = context.command_buffer();
+        stereo
+            .update_eye_matrices(&mut builder, Mat4::IDENTITY.0, Mat4::IDENTITY.0)
+            .unwrap();
+        context.submit(builder);
+    }
+
+    #[test]
+    fn depth_resolve_modes_map_to_vulkano() {
+        assert_eq!(ResolveMode::from(DepthResolveMode::Min), ResolveMode::Min);
+        assert_eq!(ResolveMode::from(DepthResolveMode::Max), ResolveMode::Max);
+        assert_eq!(
+            ResolveMode::from(DepthResolveMode::Average),
+            ResolveMode::Average
+        );
+        assert_eq!(
+            ResolveMode::from(DepthResolveMode::SampleZero),
+            ResolveMode::SampleZero
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn depth_resolve_render_pass_creates() {
+        let context = TestContext::new();
+        let context = if context.queue.device().api_version() >= Version::V1_2 {
+            context
+        } else {
+            TestContext::with_extensions(DepthResolvePass::required_extensions(), Features::empty())
+        };
+        let pass = DepthResolvePass::new(
+            context.queue.device().clone(),
+            Format::R8G8B8A8_UNORM,
+            Format::D16_UNORM,
+            SampleCount::Sample4,
+            DepthResolveMode::SampleZero,
+        )
+        .unwrap();
+
+        let subpass = &pass.render_pass().subpasses()[0];
+        assert_eq!(
+            subpass
+                .depth_stencil_resolve_attachment
+                .as_ref()
+                .unwrap()
+                .attachment,
+            DepthResolvePass::RESOLVED_DEPTH_ATTACHMENT
+        );
+        assert_eq!(subpass.depth_resolve_mode, Some(ResolveMode::SampleZero));
+        let attachments = pass.render_pass().attachments();
+        assert_eq!(
+            attachments[DepthResolvePass::DEPTH_ATTACHMENT as usize].samples,
+            SampleCount::Sample4
+        );
+        assert_eq!(
+            attachments[DepthResolvePass::RESOLVED_DEPTH_ATTACHMENT as usize].samples,
+            SampleCount::Sample1
+        );
+    }
+}
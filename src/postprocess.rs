@@ -0,0 +1,644 @@
+use crate::compute::ComputePass;
+use crate::error::ThorusError;
+use crate::math::HaltonSequence;
+use crate::shader::{load_dof, load_fullscreen, load_motion_blur, load_taa};
+use std::mem::size_of;
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::buffer::BufferContents;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::sampler::{Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorBlendState};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::multisample::MultisampleState;
+use vulkano::pipeline::graphics::rasterization::RasterizationState;
+use vulkano::pipeline::graphics::vertex_input::VertexInputState;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{
+    GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+};
+use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::shader::ShaderModule;
+
+/// Mirrors the push constant block of `shader/dof.frag`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DofConfig {
+    /// View distance that stays perfectly sharp.
+    pub focus_distance: f32,
+    /// Distance from the focus plane at which the blur reaches `bokeh_radius`; zero disables it.
+    pub focus_range: f32,
+    /// Largest circle of confusion, in pixels.
+    pub bokeh_radius: f32,
+    /// Poisson disk taps, at most 16.
+    pub sample_count: u32,
+    /// Clip planes used to linearize the depth buffer.
+    pub near: f32,
+    pub far: f32,
+}
+
+impl DofConfig {
+    /// Circle of confusion radius in pixels for a view distance, as computed by the shader.
+    pub fn circle_of_confusion(&self, linear_depth: f32) -> f32 {
+        if self.focus_range <= 0.0 {
+            return 0.0;
+        }
+        let defocus = (linear_depth - self.focus_distance).abs() / self.focus_range;
+        defocus.clamp(0.0, 1.0) * self.bokeh_radius
+    }
+}
+
+impl Default for DofConfig {
+    fn default() -> Self {
+        Self {
+            focus_distance: 10.0,
+            focus_range: 5.0,
+            bokeh_radius: 8.0,
+            sample_count: 16,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+/// Depth of field with a circular bokeh, drawn as a fullscreen triangle into subpass 0.
+pub struct DepthOfFieldPass {
+    pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+}
+
+impl DepthOfFieldPass {
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        viewport: Viewport,
+    ) -> Result<Self, ThorusError> {
+        let fs = load_dof(device.clone())?;
+        let pipeline = fullscreen_pipeline(device.clone(), fs, render_pass, viewport)?;
+        debug!("depth of field pipeline: {pipeline:?}");
+
+        Ok(Self {
+            pipeline,
+            sampler: clamped_sampler(device.clone())?,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+        })
+    }
+
+    /// Records the pass; must be called inside the render pass the pipeline was created for.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        color: Arc<ImageView>,
+        depth: Arc<ImageView>,
+        config: DofConfig,
+    ) -> Result<(), ThorusError> {
+        let layout = self.pipeline.layout();
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(0, color, self.sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(1, depth, self.sampler.clone()),
+            ],
+            [],
+        )?;
+
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )?
+            .push_constants(layout.clone(), 0, config)?
+            .draw(3, 1, 0, 0)?;
+        Ok(())
+    }
+}
+
+/// Mirrors the push constant block of `shader/gbuffer.vert`, whose fragment stage writes
+/// `current_ndc - prev_ndc` to its second color attachment for [`MotionBlurPass`].
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct GBufferParams {
+    pub mvp: [[f32; 4]; 4],
+    /// Last frame's model-view-projection of the same object.
+    pub prev_mvp: [[f32; 4]; 4],
+}
+
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct MotionBlurConfig {
+    /// Fraction of the frame the virtual shutter stays open.
+    pub shutter_speed: f32,
+    pub max_blur_pixels: u32,
+}
+
+impl Default for MotionBlurConfig {
+    fn default() -> Self {
+        Self {
+            shutter_speed: 0.5,
+            max_blur_pixels: 32,
+        }
+    }
+}
+
+/// Mirrors the push constant block of `shader/motion_blur.frag`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+struct MotionBlurParams {
+    reprojection: [[f32; 4]; 4],
+    config: MotionBlurConfig,
+    use_velocity: u32,
+}
+
+/// Blurs along per-pixel screen-space motion with 8 taps, drawn as a fullscreen triangle into
+/// subpass 0.
+pub struct MotionBlurPass {
+    pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+}
+
+impl MotionBlurPass {
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        viewport: Viewport,
+    ) -> Result<Self, ThorusError> {
+        let fs = load_motion_blur(device.clone())?;
+        let pipeline = fullscreen_pipeline(device.clone(), fs, render_pass, viewport)?;
+        debug!("motion blur pipeline: {pipeline:?}");
+
+        Ok(Self {
+            pipeline,
+            sampler: clamped_sampler(device.clone())?,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+        })
+    }
+
+    /// Records the pass; must be called inside the render pass the pipeline was created for.
+    ///
+    /// `velocity` is an `R16G16_SFLOAT` image of `current_ndc - prev_ndc`. Without it, motion
+    /// is reconstructed from `depth` and `reprojection`, the previous view-projection multiplied
+    /// by the inverse of the current one, which only captures camera movement.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        color: Arc<ImageView>,
+        depth: Arc<ImageView>,
+        velocity: Option<Arc<ImageView>>,
+        reprojection: [[f32; 4]; 4],
+        config: MotionBlurConfig,
+    ) -> Result<(), ThorusError> {
+        let params = MotionBlurParams {
+            reprojection,
+            config,
+            use_velocity: velocity.is_some() as u32,
+        };
+        // the binding must stay valid even when the shader never reads it
+        let velocity = velocity.unwrap_or_else(|| depth.clone());
+
+        let layout = self.pipeline.layout();
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(0, color, self.sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(1, depth, self.sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(2, velocity, self.sampler.clone()),
+            ],
+            [],
+        )?;
+
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )?
+            .push_constants(layout.clone(), 0, params)?
+            .draw(3, 1, 0, 0)?;
+        Ok(())
+    }
+}
+
+const TAA_WORKGROUP_SIZE: u32 = 8;
+
+/// Frames after which the jitter pattern repeats.
+pub const TAA_JITTER_PHASES: u64 = 8;
+
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct TaaConfig {
+    /// Weight of the current frame; lower values smooth more but take longer to converge.
+    pub blend_factor: f32,
+    /// Standard deviations of the neighbourhood color the history may stray from its mean
+    /// before being clipped; lower values ghost less but flicker more.
+    pub variance_gamma: f32,
+}
+
+impl Default for TaaConfig {
+    fn default() -> Self {
+        Self {
+            blend_factor: 0.1,
+            variance_gamma: 1.0,
+        }
+    }
+}
+
+/// Mirrors the push constant block of `shader/taa.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+struct TaaParams {
+    config: TaaConfig,
+    has_history: u32,
+}
+
+// std430 packs the three scalars without padding.
+const _: () = assert!(size_of::<TaaParams>() == 12);
+
+/// Temporal anti-aliasing: the projection is jittered by a sub-pixel offset every frame and
+/// each frame is blended into the history of the previous ones, reprojected along the motion
+/// vectors. The history is clipped to the variance of the current pixel's neighbourhood so
+/// that disoccluded and moving surfaces do not leave ghosts behind.
+///
+/// Two `R16G16B16A16_SFLOAT` history images of the output extent are used in turn, one read
+/// while the other is written.
+pub struct TaaPass {
+    pass: ComputePass,
+    sampler: Arc<Sampler>,
+    history: [Arc<ImageView>; 2],
+    /// Index of the history written last.
+    current_history: usize,
+    has_history: bool,
+    jitter: [f32; 2],
+    config: TaaConfig,
+}
+
+impl TaaPass {
+    pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<dyn MemoryAllocator>,
+        extent: [u32; 2],
+    ) -> Result<Self, ThorusError> {
+        Ok(Self {
+            pass: ComputePass::new(device.clone(), load_taa(device.clone())?)?,
+            sampler: clamped_sampler(device)?,
+            history: [
+                Self::output_image(allocator.clone(), extent)?,
+                Self::output_image(allocator, extent)?,
+            ],
+            current_history: 0,
+            has_history: false,
+            jitter: [0.0; 2],
+            config: TaaConfig::default(),
+        })
+    }
+
+    /// Creates an image suitable for [`TaaPass::record`]'s output.
+    pub fn output_image(
+        allocator: Arc<dyn MemoryAllocator>,
+        extent: [u32; 2],
+    ) -> Result<Arc<ImageView>, ThorusError> {
+        let image = Image::new(
+            allocator,
+            ImageCreateInfo {
+                format: Format::R16G16B16A16_SFLOAT,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        ImageView::new_default(image).map_err(ThorusError::from)
+    }
+
+    /// Recreates the history for a new swapchain extent.
+    pub fn resize(
+        &mut self,
+        allocator: Arc<dyn MemoryAllocator>,
+        extent: [u32; 2],
+    ) -> Result<(), ThorusError> {
+        self.history = [
+            Self::output_image(allocator.clone(), extent)?,
+            Self::output_image(allocator, extent)?,
+        ];
+        self.reset_history();
+        Ok(())
+    }
+
+    /// Drops the accumulated frames, e.g. on a camera cut, so that the next frame is output
+    /// as is.
+    pub fn reset_history(&mut self) {
+        self.has_history = false;
+    }
+
+    /// The anti-aliased result of the last recorded frame.
+    pub fn history(&self) -> &Arc<ImageView> {
+        &self.history[self.current_history]
+    }
+
+    pub fn config(&self) -> TaaConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: TaaConfig) {
+        self.config = config;
+    }
+
+    /// Picks the sub-pixel jitter of `frame_index` from the Halton (2, 3) sequence and returns
+    /// it in NDC units; apply it with [`jitter_projection`](Self::jitter_projection).
+    pub fn update_jitter(&mut self, frame_index: u64) -> [f32; 2] {
+        let [width, height, _] = self.history[0].image().extent();
+        let phase = frame_index % TAA_JITTER_PHASES;
+        // pixel offsets, NDC spanning two units across the screen
+        self.jitter = [
+            HaltonSequence::new(2).nth_centered(phase) * 2.0 / width as f32,
+            HaltonSequence::new(3).nth_centered(phase) * 2.0 / height as f32,
+        ];
+        self.jitter
+    }
+
+    /// Jitter set by the last [`update_jitter`](Self::update_jitter), in NDC units.
+    pub fn jitter(&self) -> [f32; 2] {
+        self.jitter
+    }
+
+    /// Offsets the output of the column-major `projection` by the current jitter, for
+    /// perspective and orthographic projections alike.
+    pub fn jitter_projection(&self, mut projection: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+        let [x, y] = self.jitter;
+        for column in &mut projection {
+            column[0] += x * column[3];
+            column[1] += y * column[3];
+        }
+        projection
+    }
+
+    /// Records the pass, blending `current` into the history and writing the result to
+    /// `output`, an image made by [`output_image`](Self::output_image) of the history's
+    /// extent.
+    ///
+    /// `motion_vectors` is an `R16G16_SFLOAT` image of `current_ndc - prev_ndc`, as written by
+    /// `shader/gbuffer.frag`.
+    pub fn record(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        current: Arc<ImageView>,
+        motion_vectors: Arc<ImageView>,
+        output: Arc<ImageView>,
+    ) -> Result<(), ThorusError> {
+        let params = TaaParams {
+            config: self.config,
+            has_history: self.has_history as u32,
+        };
+        let read = self.history[self.current_history].clone();
+        let write = self.history[1 - self.current_history].clone();
+        let [width, height, _] = output.image().extent();
+
+        self.pass.bind(
+            builder,
+            [
+                WriteDescriptorSet::image_view_sampler(0, current, self.sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(1, motion_vectors, self.sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(2, read, self.sampler.clone()),
+                WriteDescriptorSet::image_view(3, output),
+                WriteDescriptorSet::image_view(4, write),
+            ],
+        )?;
+        self.pass.push_constants(builder, params)?;
+        self.pass.dispatch(
+            builder,
+            [
+                width.div_ceil(TAA_WORKGROUP_SIZE),
+                height.div_ceil(TAA_WORKGROUP_SIZE),
+                1,
+            ],
+        )?;
+
+        self.current_history = 1 - self.current_history;
+        self.has_history = true;
+        Ok(())
+    }
+}
+
+fn clamped_sampler(device: Arc<Device>) -> Result<Arc<Sampler>, ThorusError> {
+    Sampler::new(
+        device,
+        SamplerCreateInfo {
+            address_mode: [SamplerAddressMode::ClampToEdge; 3],
+            ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
+        },
+    )
+    .map_err(ThorusError::from)
+}
+
+/// Pipeline drawing `shader/fullscreen.vert`'s single triangle with the given fragment shader.
+fn fullscreen_pipeline(
+    device: Arc<Device>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+) -> Result<Arc<GraphicsPipeline>, ThorusError> {
+    let vs = load_fullscreen(device.clone())?
+        .entry_point("main")
+        .expect("fullscreen shader has no main entry point");
+    let fs = fs
+        .entry_point("main")
+        .expect("post-process shader has no main entry point");
+
+    let stages = [
+        PipelineShaderStageCreateInfo::new(vs),
+        PipelineShaderStageCreateInfo::new(fs),
+    ];
+
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|e| e.error)?,
+    )?;
+
+    let subpass = Subpass::from(render_pass, 0).expect("render pass has no subpass 0");
+
+    GraphicsPipeline::new(
+        device,
+        None,
+        GraphicsPipelineCreateInfo {
+            stages: stages.into_iter().collect(),
+            vertex_input_state: Some(VertexInputState::default()),
+            input_assembly_state: Some(InputAssemblyState::default()),
+            viewport_state: Some(ViewportState {
+                viewports: [viewport].into_iter().collect(),
+                ..ViewportState::default()
+            }),
+            rasterization_state: Some(RasterizationState::default()),
+            multisample_state: Some(MultisampleState::default()),
+            color_blend_state: Some(ColorBlendState::with_attachment_states(
+                subpass.num_color_attachments(),
+                ColorBlendAttachmentState::default(),
+            )),
+            subpass: Some(subpass.into()),
+            ..GraphicsPipelineCreateInfo::layout(layout)
+        },
+    )
+    .map_err(ThorusError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::RenderPassBuilder;
+    use crate::testing::TestContext;
+    use vulkano::command_buffer::{
+        ClearColorImageInfo, ClearDepthStencilImageInfo, RenderPassBeginInfo, SubpassBeginInfo,
+        SubpassContents, SubpassEndInfo,
+    };
+    use vulkano::format::ClearValue;
+    use vulkano::image::{ImageLayout, SampleCount};
+    use vulkano::render_pass::{
+        AttachmentLoadOp, AttachmentStoreOp, Framebuffer, FramebufferCreateInfo,
+    };
+
+    #[test]
+    fn zero_focus_range_never_blurs() {
+        let config = DofConfig {
+            focus_range: 0.0,
+            ..DofConfig::default()
+        };
+        for depth in [0.1, 1.0, config.focus_distance, 100.0, 1000.0] {
+            assert_eq!(config.circle_of_confusion(depth), 0.0);
+        }
+    }
+
+    #[test]
+    fn circle_of_confusion_grows_away_from_focus_up_to_bokeh_radius() {
+        let config = DofConfig::default();
+        assert_eq!(config.circle_of_confusion(config.focus_distance), 0.0);
+        let half = config.circle_of_confusion(config.focus_distance + config.focus_range / 2.0);
+        assert_eq!(half, config.bokeh_radius / 2.0);
+        assert_eq!(
+            config.circle_of_confusion(config.focus_distance - config.focus_range / 2.0),
+            half
+        );
+        assert_eq!(config.circle_of_confusion(1000.0), config.bokeh_radius);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn motion_blur_records_with_and_without_velocity() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], None)
+            .build()
+            .unwrap();
+        let image = |format, usage| {
+            Image::new(
+                context.memory_allocator.clone(),
+                ImageCreateInfo {
+                    format,
+                    extent: [64, 64, 1],
+                    usage,
+                    ..ImageCreateInfo::default()
+                },
+                AllocationCreateInfo::default(),
+            )
+            .unwrap()
+        };
+        let sampled = ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST;
+        let target = image(Format::R8G8B8A8_UNORM, ImageUsage::COLOR_ATTACHMENT);
+        let color = image(Format::R8G8B8A8_UNORM, sampled);
+        let depth = image(Format::D16_UNORM, sampled);
+        let velocity = image(Format::R16G16_SFLOAT, sampled);
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![ImageView::new_default(target).unwrap()],
+                ..FramebufferCreateInfo::default()
+            },
+        )
+        .unwrap();
+        let pass = MotionBlurPass::new(
+            device,
+            render_pass,
+            Viewport {
+                offset: [0.0, 0.0],
+                extent: [64.0, 64.0],
+                depth_range: 0.0..=1.0,
+            },
+        )
+        .unwrap();
+
+        let mut builder = context.command_buffer();
+        for image in [color.clone(), velocity.clone()] {
+            builder
+                .clear_color_image(ClearColorImageInfo::image(image))
+                .unwrap();
+        }
+        builder
+            .clear_depth_stencil_image(ClearDepthStencilImageInfo::image(depth.clone()))
+            .unwrap()
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(ClearValue::Float([0.0; 4]))],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )
+            .unwrap();
+        let identity = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let [color, depth, velocity] =
+            [color, depth, velocity].map(|image| ImageView::new_default(image).unwrap());
+        for velocity in [Some(velocity), None] {
+            pass.record(
+                &mut builder,
+                color.clone(),
+                depth.clone(),
+                velocity,
+                identity,
+                MotionBlurConfig::default(),
+            )
+            .unwrap();
+        }
+        builder.end_render_pass(SubpassEndInfo::default()).unwrap();
+        context.submit(builder);
+    }
+}
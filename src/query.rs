@@ -0,0 +1,148 @@
+use crate::error::ThorusError;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::query::{
+    QueryControlFlags, QueryPipelineStatisticFlags, QueryPool, QueryPoolCreateInfo,
+    QueryResultFlags, QueryType,
+};
+use vulkano::{Validated, ValidationError, VulkanError};
+
+/// Counters read back by a [`PipelineStatisticsQuery`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PipelineStatisticsResult {
+    /// Vertices fetched by the input assembly.
+    pub input_assembly_vertices: u64,
+    /// Primitives that reached the clipping stage.
+    pub clipping_invocations: u64,
+    /// Primitives that came out of clipping, counting the pieces clipped ones are split into.
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+impl PipelineStatisticsResult {
+    /// Primitives output by clipping per primitive entering it; below one when primitives
+    /// are culled outside the view volume. Zero if nothing was drawn.
+    pub fn clipping_ratio(&self) -> f32 {
+        if self.clipping_invocations == 0 {
+            return 0.0;
+        }
+        self.clipping_primitives as f32 / self.clipping_invocations as f32
+    }
+}
+
+/// Pipeline statistics of the draws recorded between [`begin`](Self::begin) and
+/// [`end`](Self::end).
+///
+/// Needs the `pipeline_statistics_query` feature.
+pub struct PipelineStatisticsQuery {
+    pool: Arc<QueryPool>,
+}
+
+impl PipelineStatisticsQuery {
+    /// Statistics collected, in the order Vulkan writes them: by increasing bit.
+    const FLAGS: QueryPipelineStatisticFlags = QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES
+        .union(QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS)
+        .union(QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES)
+        .union(QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS);
+
+    pub fn new(device: Arc<Device>) -> Result<Self, ThorusError> {
+        if !device.enabled_features().pipeline_statistics_query {
+            return Err(ThorusError::Missing("pipeline statistics query support"));
+        }
+        let pool = QueryPool::new(
+            device,
+            QueryPoolCreateInfo {
+                query_count: 1,
+                ..QueryPoolCreateInfo::query_type(QueryType::PipelineStatistics(Self::FLAGS))
+            },
+        )?;
+        Ok(Self { pool })
+    }
+
+    pub fn pool(&self) -> &Arc<QueryPool> {
+        &self.pool
+    }
+
+    /// Resets the query and starts counting; must be recorded outside a render pass.
+    pub fn begin(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<ValidationError>> {
+        // SAFETY: the pool is only used by this query, and the reset makes the query
+        // unavailable before it begins.
+        unsafe {
+            builder
+                .reset_query_pool(self.pool.clone(), 0..1)?
+                .begin_query(self.pool.clone(), 0, QueryControlFlags::empty())?;
+        }
+        Ok(())
+    }
+
+    /// Stops counting; must be recorded in the same render pass instance as
+    /// [`begin`](Self::begin), or outside of any if it was.
+    pub fn end(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<ValidationError>> {
+        builder.end_query(self.pool.clone(), 0)?;
+        Ok(())
+    }
+
+    /// The statistics, `None` until the commands between `begin` and `end` have executed.
+    pub fn results(&self) -> Result<Option<PipelineStatisticsResult>, Validated<VulkanError>> {
+        let mut counters = [0u64; 4];
+        let available = self
+            .pool
+            .get_results(0..1, &mut counters, QueryResultFlags::empty())?;
+        let [input_assembly_vertices, clipping_invocations, clipping_primitives, fragment_shader_invocations] =
+            counters;
+        Ok(available.then_some(PipelineStatisticsResult {
+            input_assembly_vertices,
+            clipping_invocations,
+            clipping_primitives,
+            fragment_shader_invocations,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::device::{DeviceExtensions, Features};
+
+    #[test]
+    fn clipping_ratio_is_zero_without_primitives() {
+        assert_eq!(PipelineStatisticsResult::default().clipping_ratio(), 0.0);
+        let result = PipelineStatisticsResult {
+            clipping_invocations: 4,
+            clipping_primitives: 3,
+            ..PipelineStatisticsResult::default()
+        };
+        assert_eq!(result.clipping_ratio(), 0.75);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn begin_and_end_record() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions::empty(),
+            Features {
+                pipeline_statistics_query: true,
+                ..Features::empty()
+            },
+        );
+        let query = PipelineStatisticsQuery::new(context.queue.device().clone()).unwrap();
+        let mut builder = context.command_buffer();
+        query.begin(&mut builder).unwrap();
+        query.end(&mut builder).unwrap();
+        context.submit(builder);
+
+        // nothing was drawn in between
+        assert_eq!(
+            query.results().unwrap(),
+            Some(PipelineStatisticsResult::default())
+        );
+    }
+}
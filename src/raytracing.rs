@@ -0,0 +1,820 @@
+use crate::buffer::UPLOAD_MEMORY;
+use crate::error::ThorusError;
+use crate::shader::{load_raytrace_closest_hit, load_raytrace_miss, load_raytrace_raygen};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::mem::size_of_val;
+use std::sync::Arc;
+use std::{ptr, slice};
+use tracing::debug;
+use vulkano::acceleration_structure::{
+    AccelerationStructure, AccelerationStructureBuildGeometryInfo,
+    AccelerationStructureBuildRangeInfo, AccelerationStructureBuildType,
+    AccelerationStructureCreateInfo, AccelerationStructureGeometries,
+    AccelerationStructureGeometryInstancesData, AccelerationStructureGeometryTrianglesData,
+    AccelerationStructureInstance, AccelerationStructureType, BuildAccelerationStructureFlags,
+    GeometryFlags, GeometryInstanceFlags, TransformMatrix,
+};
+use vulkano::buffer::sys::RawBuffer;
+use vulkano::buffer::{
+    Buffer, BufferContents, BufferCreateInfo, BufferUsage, IndexBuffer, Subbuffer,
+};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::sys::UnsafeCommandBufferBuilder;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract,
+};
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::image::view::ImageView;
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, AllocationType, DeviceLayout, MemoryAllocator, MemoryTypeFilter,
+};
+use vulkano::memory::{DedicatedAllocation, DeviceAlignment, ResourceMemory};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::GpuFuture;
+use vulkano::{DeviceSize, Packed24_8, VulkanError, VulkanObject};
+
+/// Alignment of instance data required by `VkAccelerationStructureGeometryInstancesDataKHR`.
+const INSTANCE_ALIGNMENT: DeviceSize = 16;
+
+#[derive(Debug)]
+pub enum RayTracingError {
+    /// `VK_KHR_acceleration_structure` or `VK_KHR_ray_tracing_pipeline` is not enabled.
+    ExtensionNotEnabled,
+    /// The vertex type has no `position` member.
+    NoPosition,
+    /// A shader of the pipeline has no `main` entry point; holds the stage.
+    NoEntryPoint(&'static str),
+}
+
+impl Display for RayTracingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ExtensionNotEnabled => write!(
+                f,
+                "ray tracing needs the VK_KHR_acceleration_structure and \
+                 VK_KHR_ray_tracing_pipeline device extensions"
+            ),
+            Self::NoPosition => write!(f, "vertex type has no position member"),
+            Self::NoEntryPoint(stage) => write!(f, "{stage} shader has no main entry point"),
+        }
+    }
+}
+
+impl Error for RayTracingError {}
+
+/// Whether the device was created with everything acceleration structures and ray tracing
+/// pipelines need.
+pub fn is_enabled(device: &Device) -> bool {
+    let extensions = device.enabled_extensions();
+    extensions.khr_acceleration_structure
+        && extensions.khr_ray_tracing_pipeline
+        && device.enabled_features().acceleration_structure
+}
+
+/// Bottom-level acceleration structure over the triangles of one mesh.
+#[derive(Clone, Debug)]
+pub struct Blas {
+    acceleration_structure: Arc<AccelerationStructure>,
+}
+
+impl Blas {
+    /// Builds the structure from an indexed triangle list and waits for the build to finish.
+    ///
+    /// Both buffers need `ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY` and
+    /// `SHADER_DEVICE_ADDRESS` usage. Positions are read from the `position` member of `V`.
+    pub fn new<V: Vertex>(
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+        vertex_buffer: Subbuffer<[V]>,
+        index_buffer: Subbuffer<[u32]>,
+    ) -> Result<Self, ThorusError> {
+        let description = V::per_vertex();
+        let position = description
+            .members
+            .get("position")
+            .ok_or(RayTracingError::NoPosition)?;
+        let max_vertex = vertex_buffer.len().saturating_sub(1) as u32;
+        let primitive_count = (index_buffer.len() / 3) as u32;
+
+        let geometries = AccelerationStructureGeometries::Triangles(vec![
+            AccelerationStructureGeometryTrianglesData {
+                flags: GeometryFlags::OPAQUE,
+                vertex_data: Some(
+                    vertex_buffer
+                        .into_bytes()
+                        .slice(position.offset as DeviceSize..),
+                ),
+                vertex_stride: description.stride,
+                max_vertex,
+                index_data: Some(IndexBuffer::U32(index_buffer)),
+                ..AccelerationStructureGeometryTrianglesData::new(position.format)
+            },
+        ]);
+        let acceleration_structure = build(
+            allocator,
+            cmd_allocator,
+            queue,
+            AccelerationStructureType::BottomLevel,
+            geometries,
+            primitive_count,
+        )?;
+        Ok(Self {
+            acceleration_structure,
+        })
+    }
+
+    pub fn acceleration_structure(&self) -> &Arc<AccelerationStructure> {
+        &self.acceleration_structure
+    }
+}
+
+/// Top-level acceleration structure over transformed [`Blas`] instances.
+///
+/// The instance index is exposed to shaders as `gl_InstanceCustomIndexEXT`.
+#[derive(Clone, Debug)]
+pub struct Tlas {
+    acceleration_structure: Arc<AccelerationStructure>,
+    /// Keeps the referenced bottom-level structures alive.
+    instances: Vec<Blas>,
+}
+
+impl Tlas {
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+        instances: &[(Blas, TransformMatrix)],
+    ) -> Result<Self, ThorusError> {
+        if !is_enabled(queue.device()) {
+            return Err(RayTracingError::ExtensionNotEnabled.into());
+        }
+        let buffer = Buffer::new(
+            allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY
+                    | BufferUsage::SHADER_DEVICE_ADDRESS,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            DeviceLayout::from_size_alignment(
+                (instances.len().max(1) * size_of::<AccelerationStructureInstance>()) as DeviceSize,
+                INSTANCE_ALIGNMENT,
+            )
+            .unwrap(),
+        )?;
+        // An empty scene still gets one slot, since subbuffers cannot be empty.
+        let instance_buffer = Subbuffer::new(buffer)
+            .reinterpret::<[AccelerationStructureInstance]>()
+            .slice(..instances.len().max(1) as DeviceSize);
+        {
+            let mut data = instance_buffer.write()?;
+            for (index, ((blas, transform), instance)) in
+                instances.iter().zip(data.iter_mut()).enumerate()
+            {
+                *instance = AccelerationStructureInstance {
+                    transform: *transform,
+                    instance_custom_index_and_mask: Packed24_8::new(index as u32, 0xff),
+                    instance_shader_binding_table_record_offset_and_flags: Packed24_8::new(
+                        0,
+                        GeometryInstanceFlags::TRIANGLE_FACING_CULL_DISABLE.into(),
+                    ),
+                    acceleration_structure_reference: blas
+                        .acceleration_structure
+                        .device_address()
+                        .get(),
+                };
+            }
+        }
+
+        let geometries = AccelerationStructureGeometries::Instances(
+            AccelerationStructureGeometryInstancesData::new(instance_buffer.into()),
+        );
+        let acceleration_structure = build(
+            allocator,
+            cmd_allocator,
+            queue,
+            AccelerationStructureType::TopLevel,
+            geometries,
+            instances.len() as u32,
+        )?;
+        Ok(Self {
+            acceleration_structure,
+            instances: instances.iter().map(|(blas, _)| blas.clone()).collect(),
+        })
+    }
+
+    pub fn acceleration_structure(&self) -> &Arc<AccelerationStructure> {
+        &self.acceleration_structure
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instances.len()
+    }
+}
+
+fn build(
+    allocator: Arc<dyn MemoryAllocator>,
+    cmd_allocator: &StandardCommandBufferAllocator,
+    queue: Arc<Queue>,
+    ty: AccelerationStructureType,
+    geometries: AccelerationStructureGeometries,
+    primitive_count: u32,
+) -> Result<Arc<AccelerationStructure>, ThorusError> {
+    let device = queue.device().clone();
+    if !is_enabled(&device) {
+        return Err(RayTracingError::ExtensionNotEnabled.into());
+    }
+
+    let mut build_info = AccelerationStructureBuildGeometryInfo {
+        flags: BuildAccelerationStructureFlags::PREFER_FAST_TRACE,
+        ..AccelerationStructureBuildGeometryInfo::new(geometries)
+    };
+    let sizes = device.acceleration_structure_build_sizes(
+        AccelerationStructureBuildType::Device,
+        &build_info,
+        &[primitive_count],
+    )?;
+    debug!("{ty:?} acceleration structure sizes: {sizes:?}");
+
+    let storage = device_buffer(
+        allocator.clone(),
+        BufferUsage::ACCELERATION_STRUCTURE_STORAGE | BufferUsage::SHADER_DEVICE_ADDRESS,
+        sizes.acceleration_structure_size,
+        1,
+    )?;
+    let acceleration_structure = unsafe {
+        AccelerationStructure::new(
+            device.clone(),
+            AccelerationStructureCreateInfo {
+                ty,
+                ..AccelerationStructureCreateInfo::new(storage)
+            },
+        )
+    }?;
+
+    let scratch_alignment = device
+        .physical_device()
+        .properties()
+        .min_acceleration_structure_scratch_offset_alignment
+        .unwrap_or(1) as DeviceSize;
+    build_info.dst_acceleration_structure = Some(acceleration_structure.clone());
+    build_info.scratch_data = Some(device_buffer(
+        allocator,
+        BufferUsage::STORAGE_BUFFER | BufferUsage::SHADER_DEVICE_ADDRESS,
+        sizes.build_scratch_size,
+        scratch_alignment,
+    )?);
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        cmd_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )?;
+    unsafe {
+        builder.build_acceleration_structure(
+            build_info,
+            [AccelerationStructureBuildRangeInfo {
+                primitive_count,
+                ..AccelerationStructureBuildRangeInfo::default()
+            }]
+            .into_iter()
+            .collect(),
+        )
+    }?;
+    builder
+        .build()?
+        .execute(queue)?
+        .then_signal_fence_and_flush()?
+        .wait(None)?;
+    debug!("built acceleration structure: {acceleration_structure:?}");
+    Ok(acceleration_structure)
+}
+
+fn device_buffer(
+    allocator: Arc<dyn MemoryAllocator>,
+    usage: BufferUsage,
+    size: DeviceSize,
+    alignment: DeviceSize,
+) -> Result<Subbuffer<[u8]>, ThorusError> {
+    let buffer = Buffer::new(
+        allocator,
+        BufferCreateInfo {
+            usage,
+            ..BufferCreateInfo::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+            ..AllocationCreateInfo::default()
+        },
+        DeviceLayout::from_size_alignment(size.max(1), alignment).unwrap(),
+    )?;
+    Ok(Subbuffer::new(buffer))
+}
+
+/// Mirrors the push constant block of `shader/raytrace.rgen` and `shader/raytrace.rchit`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct RayParams {
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub camera_position: [f32; 4],
+    /// Direction towards the light in `xyz`.
+    pub light_direction: [f32; 4],
+}
+
+/// Device addresses of the [`Vertex3D`](crate::vertex::Vertex3D) and `u32` index buffers of
+/// one TLAS instance, read by `shader/raytrace.rchit` at `gl_InstanceCustomIndexEXT`.
+#[derive(BufferContents, Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct RayInstanceGeometry {
+    pub vertices: u64,
+    pub indices: u64,
+}
+
+/// Shader group handles of a [`RayTracingPipeline`], one record per group.
+pub struct ShaderBindingTable {
+    buffer: Subbuffer<[u8]>,
+    raygen: ash::vk::StridedDeviceAddressRegionKHR,
+    miss: ash::vk::StridedDeviceAddressRegionKHR,
+    hit: ash::vk::StridedDeviceAddressRegionKHR,
+}
+
+impl ShaderBindingTable {
+    /// Raygen, miss and hit group, in this order.
+    const GROUP_COUNT: u32 = 3;
+
+    fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        device: &Device,
+        pipeline: ash::vk::Pipeline,
+    ) -> Result<Self, ThorusError> {
+        let properties = device.physical_device().properties();
+        let handle_size = properties.shader_group_handle_size.unwrap_or(32) as DeviceSize;
+        let handle_alignment = properties.shader_group_handle_alignment.unwrap_or(32) as DeviceSize;
+        let base_alignment = properties.shader_group_base_alignment.unwrap_or(64) as DeviceSize;
+        let handle_stride = handle_size.next_multiple_of(handle_alignment);
+        let region_size = handle_stride.next_multiple_of(base_alignment);
+
+        let mut handles = vec![0u8; (handle_size * Self::GROUP_COUNT as DeviceSize) as usize];
+        unsafe {
+            (device
+                .fns()
+                .khr_ray_tracing_pipeline
+                .get_ray_tracing_shader_group_handles_khr)(
+                device.handle(),
+                pipeline,
+                0,
+                Self::GROUP_COUNT,
+                handles.len(),
+                handles.as_mut_ptr().cast(),
+            )
+        }
+        .result()
+        .map_err(VulkanError::from)?;
+
+        let buffer = sbt_buffer(
+            allocator,
+            region_size * Self::GROUP_COUNT as DeviceSize,
+            base_alignment,
+        )?;
+        {
+            let mut data = buffer.write()?;
+            for (group, handle) in handles.chunks_exact(handle_size as usize).enumerate() {
+                let start = group * region_size as usize;
+                data[start..start + handle.len()].copy_from_slice(handle);
+            }
+        }
+
+        let address = buffer.device_address()?.get();
+        let region = |group: DeviceSize| ash::vk::StridedDeviceAddressRegionKHR {
+            device_address: address + group * region_size,
+            stride: handle_stride,
+            size: handle_stride,
+        };
+        Ok(Self {
+            raygen: region(0),
+            miss: region(1),
+            hit: region(2),
+            buffer,
+        })
+    }
+
+    pub fn buffer(&self) -> &Subbuffer<[u8]> {
+        &self.buffer
+    }
+}
+
+/// Host-visible buffer usable as a shader binding table.
+///
+/// vulkano does not expose `SHADER_BINDING_TABLE` buffer usage yet, so the buffer is created
+/// directly and only its memory is bound through vulkano.
+fn sbt_buffer(
+    allocator: Arc<dyn MemoryAllocator>,
+    size: DeviceSize,
+    alignment: DeviceSize,
+) -> Result<Subbuffer<[u8]>, ThorusError> {
+    let device = allocator.device().clone();
+    let create_info = ash::vk::BufferCreateInfo {
+        size,
+        usage: ash::vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR
+            | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let mut handle = ash::vk::Buffer::null();
+    unsafe {
+        (device.fns().v1_0.create_buffer)(device.handle(), &create_info, ptr::null(), &mut handle)
+    }
+    .result()
+    .map_err(VulkanError::from)?;
+    // Takes ownership of `handle`, destroying it on drop.
+    let raw_buffer = unsafe {
+        RawBuffer::from_handle(
+            device,
+            handle,
+            BufferCreateInfo {
+                size,
+                usage: BufferUsage::SHADER_DEVICE_ADDRESS,
+                ..BufferCreateInfo::default()
+            },
+        )
+    };
+    let mut requirements = *raw_buffer.memory_requirements();
+    requirements.layout = requirements
+        .layout
+        .align_to(DeviceAlignment::new(alignment).unwrap())
+        .unwrap();
+    let allocation = allocator.allocate(
+        requirements,
+        AllocationType::Linear,
+        AllocationCreateInfo {
+            memory_type_filter: UPLOAD_MEMORY,
+            ..AllocationCreateInfo::default()
+        },
+        Some(DedicatedAllocation::Buffer(&raw_buffer)),
+    )?;
+    let allocation = unsafe { ResourceMemory::from_allocation(allocator, allocation) };
+    let buffer = raw_buffer.bind_memory(allocation).map_err(|(e, _, _)| e)?;
+    debug!("shader binding table buffer: {buffer:?}");
+    Ok(Subbuffer::new(Arc::new(buffer)))
+}
+
+/// Ray tracing pipeline with one ray generation, one miss and one triangle hit group.
+///
+/// vulkano has no ray tracing pipeline object yet, so the pipeline is created and recorded
+/// through the raw `VK_KHR_ray_tracing_pipeline` functions.
+pub struct RayTracingPipeline {
+    device: Arc<Device>,
+    handle: ash::vk::Pipeline,
+    layout: Arc<PipelineLayout>,
+    shader_binding_table: ShaderBindingTable,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+}
+
+impl RayTracingPipeline {
+    pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<dyn MemoryAllocator>,
+        raygen_shader: Arc<ShaderModule>,
+        miss_shader: Arc<ShaderModule>,
+        closest_hit_shader: Arc<ShaderModule>,
+    ) -> Result<Self, ThorusError> {
+        if !is_enabled(&device) {
+            return Err(RayTracingError::ExtensionNotEnabled.into());
+        }
+        let entry_point = |module: &Arc<ShaderModule>, stage| {
+            module
+                .entry_point("main")
+                .map(PipelineShaderStageCreateInfo::new)
+                .ok_or(RayTracingError::NoEntryPoint(stage))
+        };
+        let stages = [
+            entry_point(&raygen_shader, "ray generation")?,
+            entry_point(&miss_shader, "miss")?,
+            entry_point(&closest_hit_shader, "closest hit")?,
+        ];
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+                .into_pipeline_layout_create_info(device.clone())
+                .map_err(|e| e.error)?,
+        )?;
+
+        let raw_stages = [
+            (ash::vk::ShaderStageFlags::RAYGEN_KHR, &raygen_shader),
+            (ash::vk::ShaderStageFlags::MISS_KHR, &miss_shader),
+            (
+                ash::vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+                &closest_hit_shader,
+            ),
+        ]
+        .map(|(stage, module)| ash::vk::PipelineShaderStageCreateInfo {
+            stage,
+            module: module.handle(),
+            p_name: c"main".as_ptr(),
+            ..Default::default()
+        });
+        let general = |shader| ash::vk::RayTracingShaderGroupCreateInfoKHR {
+            ty: ash::vk::RayTracingShaderGroupTypeKHR::GENERAL,
+            general_shader: shader,
+            closest_hit_shader: ash::vk::SHADER_UNUSED_KHR,
+            any_hit_shader: ash::vk::SHADER_UNUSED_KHR,
+            intersection_shader: ash::vk::SHADER_UNUSED_KHR,
+            ..Default::default()
+        };
+        let groups = [
+            general(0),
+            general(1),
+            ash::vk::RayTracingShaderGroupCreateInfoKHR {
+                ty: ash::vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP,
+                general_shader: ash::vk::SHADER_UNUSED_KHR,
+                closest_hit_shader: 2,
+                any_hit_shader: ash::vk::SHADER_UNUSED_KHR,
+                intersection_shader: ash::vk::SHADER_UNUSED_KHR,
+                ..Default::default()
+            },
+        ];
+        let create_info = ash::vk::RayTracingPipelineCreateInfoKHR {
+            stage_count: raw_stages.len() as u32,
+            p_stages: raw_stages.as_ptr(),
+            group_count: groups.len() as u32,
+            p_groups: groups.as_ptr(),
+            max_pipeline_ray_recursion_depth: 1,
+            layout: layout.handle(),
+            ..Default::default()
+        };
+        let mut handle = ash::vk::Pipeline::null();
+        unsafe {
+            (device
+                .fns()
+                .khr_ray_tracing_pipeline
+                .create_ray_tracing_pipelines_khr)(
+                device.handle(),
+                ash::vk::DeferredOperationKHR::null(),
+                ash::vk::PipelineCache::null(),
+                1,
+                &create_info,
+                ptr::null(),
+                &mut handle,
+            )
+        }
+        .result()
+        .map_err(VulkanError::from)?;
+        debug!("ray tracing pipeline: {handle:?}");
+
+        let shader_binding_table = match ShaderBindingTable::new(allocator, &device, handle) {
+            Ok(shader_binding_table) => shader_binding_table,
+            Err(e) => {
+                unsafe {
+                    (device.fns().v1_0.destroy_pipeline)(device.handle(), handle, ptr::null())
+                };
+                return Err(e);
+            }
+        };
+        Ok(Self {
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device.clone(),
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+            device,
+            handle,
+            layout,
+            shader_binding_table,
+        })
+    }
+
+    /// Pipeline made of `shader/raytrace.rgen`, `shader/raytrace.rmiss` and
+    /// `shader/raytrace.rchit`.
+    pub fn primary_rays(
+        device: Arc<Device>,
+        allocator: Arc<dyn MemoryAllocator>,
+    ) -> Result<Self, ThorusError> {
+        let raygen = load_raytrace_raygen(device.clone())?;
+        let miss = load_raytrace_miss(device.clone())?;
+        let closest_hit = load_raytrace_closest_hit(device.clone())?;
+        Self::new(device, allocator, raygen, miss, closest_hit)
+    }
+
+    pub fn layout(&self) -> &Arc<PipelineLayout> {
+        &self.layout
+    }
+
+    pub fn shader_binding_table(&self) -> &ShaderBindingTable {
+        &self.shader_binding_table
+    }
+
+    /// Binds the pipeline with `tlas`, `geometries` and `output_image` (`R8G8B8A8_UNORM`) and
+    /// traces one primary ray per pixel.
+    ///
+    /// Returns the descriptor set, which has to outlive the execution of `cmd`.
+    ///
+    /// # Safety
+    ///
+    /// `cmd` bypasses vulkano's synchronization: `output_image` must be in the `General` layout
+    /// and the caller must insert the barriers around the trace and keep every resource alive
+    /// until the command buffer has completed.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn dispatch(
+        &self,
+        cmd: &mut UnsafeCommandBufferBuilder,
+        tlas: &Tlas,
+        geometries: Subbuffer<[RayInstanceGeometry]>,
+        output_image: Arc<ImageView>,
+        width: u32,
+        height: u32,
+        params: &RayParams,
+    ) -> Result<Arc<PersistentDescriptorSet>, ThorusError> {
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            self.layout.set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::acceleration_structure(0, tlas.acceleration_structure.clone()),
+                WriteDescriptorSet::image_view(1, output_image),
+                WriteDescriptorSet::buffer(2, geometries),
+            ],
+            [],
+        )?;
+
+        let fns = self.device.fns();
+        let bind_point = ash::vk::PipelineBindPoint::RAY_TRACING_KHR;
+        (fns.v1_0.cmd_bind_pipeline)(cmd.handle(), bind_point, self.handle);
+        (fns.v1_0.cmd_bind_descriptor_sets)(
+            cmd.handle(),
+            bind_point,
+            self.layout.handle(),
+            0,
+            1,
+            &descriptor_set.handle(),
+            0,
+            ptr::null(),
+        );
+        // SAFETY: `RayParams` is `repr(C)` and made of `f32`s only, so it has no padding.
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                (params as *const RayParams).cast::<u8>(),
+                size_of_val(params),
+            )
+        };
+        for range in self.layout.push_constant_ranges() {
+            let Some(range_bytes) = push_constant_bytes(bytes, range.offset, range.size) else {
+                continue;
+            };
+            (fns.v1_0.cmd_push_constants)(
+                cmd.handle(),
+                self.layout.handle(),
+                range.stages.into(),
+                range.offset,
+                range_bytes.len() as u32,
+                range_bytes.as_ptr().cast(),
+            );
+        }
+        let table = &self.shader_binding_table;
+        (fns.khr_ray_tracing_pipeline.cmd_trace_rays_khr)(
+            cmd.handle(),
+            &table.raygen,
+            &table.miss,
+            &table.hit,
+            &ash::vk::StridedDeviceAddressRegionKHR::default(),
+            width,
+            height,
+            1,
+        );
+        Ok(descriptor_set)
+    }
+}
+
+/// The part of `bytes` covered by the push constant range at `offset` with `size` bytes, or
+/// `None` if the range lies beyond them.
+fn push_constant_bytes(bytes: &[u8], offset: u32, size: u32) -> Option<&[u8]> {
+    let start = offset as usize;
+    let end = (start + size as usize).min(bytes.len());
+    (start < end).then(|| &bytes[start..end])
+}
+
+impl Drop for RayTracingPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            (self.device.fns().v1_0.destroy_pipeline)(
+                self.device.handle(),
+                self.handle,
+                ptr::null(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use crate::vertex::MyVertex;
+    use vulkano::buffer::Buffer;
+    use vulkano::device::{DeviceExtensions, Features};
+
+    fn ray_tracing_context() -> TestContext {
+        TestContext::with_extensions(
+            DeviceExtensions {
+                khr_acceleration_structure: true,
+                khr_ray_tracing_pipeline: true,
+                khr_deferred_host_operations: true,
+                khr_spirv_1_4: true,
+                ..DeviceExtensions::empty()
+            },
+            Features {
+                acceleration_structure: true,
+                ray_tracing_pipeline: true,
+                buffer_device_address: true,
+                ..Features::empty()
+            },
+        )
+    }
+
+    #[test]
+    fn push_constant_ranges_are_sliced() {
+        let bytes: Vec<u8> = (0..16).collect();
+        assert_eq!(push_constant_bytes(&bytes, 0, 16), Some(&bytes[..]));
+        assert_eq!(push_constant_bytes(&bytes, 4, 8), Some(&bytes[4..12]));
+        assert_eq!(push_constant_bytes(&bytes, 12, 16), Some(&bytes[12..]));
+        assert_eq!(push_constant_bytes(&bytes, 16, 4), None);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device with ray tracing"]
+    fn primary_ray_pipeline_builds() {
+        let context = ray_tracing_context();
+        let pipeline = RayTracingPipeline::primary_rays(
+            context.queue.device().clone(),
+            context.memory_allocator.clone(),
+        )
+        .unwrap();
+        assert!(!pipeline.layout().push_constant_ranges().is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device with ray tracing"]
+    fn triangle_blas_and_tlas_build() {
+        let context = ray_tracing_context();
+        let usage = BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY
+            | BufferUsage::SHADER_DEVICE_ADDRESS;
+        let allocation = || AllocationCreateInfo {
+            memory_type_filter: UPLOAD_MEMORY,
+            ..AllocationCreateInfo::default()
+        };
+        // the triangle main.rs draws without a model
+        let vertices = Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage,
+                ..BufferCreateInfo::default()
+            },
+            allocation(),
+            [[-0.5, -0.5], [0.0, 0.5], [0.5, -0.25]].map(|position| MyVertex { position }),
+        )
+        .unwrap();
+        let indices = Buffer::from_iter(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage,
+                ..BufferCreateInfo::default()
+            },
+            allocation(),
+            [0u32, 1, 2],
+        )
+        .unwrap();
+
+        let blas = Blas::new(
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+            vertices,
+            indices,
+        )
+        .unwrap();
+        assert_ne!(blas.acceleration_structure().device_address().get(), 0);
+
+        let identity = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ];
+        let tlas = Tlas::new(
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+            &[(blas, identity)],
+        )
+        .unwrap();
+        assert_eq!(tlas.instance_count(), 1);
+    }
+}
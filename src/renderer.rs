@@ -0,0 +1,726 @@
+use crate::buffer::UPLOAD_MEMORY;
+use crate::config::RenderConfig;
+use crate::device::select_physical_device;
+use crate::error::{Context, ThorusError};
+use crate::instance::InstanceBuilder;
+use crate::pipeline::{GraphicsPipelineBuilder, RenderPassBuilder};
+use crate::shader::{load_fragment, load_vertex, ShaderError};
+use crate::vertex::MyVertex;
+use std::sync::Arc;
+use std::{mem, ptr};
+use tracing::debug;
+use vulkano::buffer::sys::RawBuffer;
+use vulkano::buffer::{AllocateBufferError, Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::{
+    CommandBufferAllocator, StandardCommandBufferAllocator,
+    StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::command_buffer::sys::UnsafeCommandBufferBuilder;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
+    DrawIndexedIndirectCommand, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract,
+    RenderPassBeginInfo, SubpassBeginInfo, SubpassContents, SubpassEndInfo,
+};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, DeviceOwned, Queue, QueueCreateInfo,
+};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageLayout, ImageUsage, SampleCount};
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, AllocationType, MemoryAllocator, MemoryTypeFilter,
+    StandardMemoryAllocator,
+};
+use vulkano::memory::{DedicatedAllocation, ResourceMemory};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::query::{
+    QueryControlFlags, QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType,
+};
+use vulkano::render_pass::{
+    AttachmentLoadOp, AttachmentStoreOp, Framebuffer, FramebufferCreateInfo, RenderPass,
+};
+use vulkano::sync::{
+    AccessFlags, BufferMemoryBarrier, DependencyInfo, GpuFuture, HostAccessError, PipelineStages,
+};
+use vulkano::{DeviceSize, Validated, VulkanError, VulkanLibrary, VulkanObject};
+
+/// Color image rendered into without a swapchain, plus a host-visible copy of its pixels.
+pub struct OffscreenTarget {
+    image: Arc<Image>,
+    framebuffer: Arc<Framebuffer>,
+    readback: Subbuffer<[u8]>,
+}
+
+impl OffscreenTarget {
+    pub const FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        render_pass: Arc<RenderPass>,
+        extent: [u32; 2],
+    ) -> Result<Self, ThorusError> {
+        let image = Image::new(
+            allocator.clone(),
+            ImageCreateInfo {
+                format: Self::FORMAT,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        debug!("offscreen image: {image:?}");
+        let framebuffer = Framebuffer::new(
+            render_pass,
+            FramebufferCreateInfo {
+                attachments: vec![ImageView::new_default(image.clone())?],
+                ..FramebufferCreateInfo::default()
+            },
+        )?;
+        let readback = Buffer::new_slice(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..AllocationCreateInfo::default()
+            },
+            extent[0] as u64 * extent[1] as u64 * 4,
+        )?;
+        Ok(Self {
+            image,
+            framebuffer,
+            readback,
+        })
+    }
+
+    pub fn image(&self) -> &Arc<Image> {
+        &self.image
+    }
+
+    pub fn extent(&self) -> [u32; 2] {
+        let [width, height, _] = self.image.extent();
+        [width, height]
+    }
+}
+
+/// Renders triangle scenes into an [`OffscreenTarget`] without a window or swapchain.
+pub struct HeadlessRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    render_pass: Arc<RenderPass>,
+    pipeline: Arc<GraphicsPipeline>,
+    target: OffscreenTarget,
+    config: RenderConfig,
+}
+
+impl HeadlessRenderer {
+    pub fn new(extent: [u32; 2], config: RenderConfig) -> Result<Self, ThorusError> {
+        let library = VulkanLibrary::new()?;
+        let instance = InstanceBuilder::new(library)
+            .build()
+            .context("failed to create instance")?;
+        debug!("headless instance: {instance:?}");
+
+        let (physical_device, queue_family_index) = select_physical_device(
+            &instance,
+            None,
+            &DeviceExtensions::empty(),
+            config.feature_set,
+        )?;
+        debug!("headless physical device: {physical_device:?}");
+
+        let (enabled_extensions, enabled_features, _) =
+            config.feature_set.device_setup(&physical_device);
+        let enabled_extensions =
+            enabled_extensions.union(&InstanceBuilder::device_extensions(&physical_device));
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..QueueCreateInfo::default()
+                }],
+                enabled_extensions,
+                enabled_features,
+                ..DeviceCreateInfo::default()
+            },
+        )
+        .context("failed to create device")?;
+        let queue = queues.next().ok_or(ThorusError::Missing("device queue"))?;
+
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            device.clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        );
+
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                OffscreenTarget::FORMAT,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::TransferSrcOptimal,
+            )
+            .add_subpass(&[0], &[], None)
+            .build()
+            .context("failed to create render pass")?;
+        let target = OffscreenTarget::new(memory_allocator.clone(), render_pass.clone(), extent)?;
+
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_vertex(device.clone()).map_err(ShaderError::from)?)
+            .fragment_shader(load_fragment(device.clone()).map_err(ShaderError::from)?)
+            .vertex_input(MyVertex::per_vertex())
+            .render_pass(render_pass.clone(), 0)
+            .viewport(Viewport {
+                offset: [0.0, 0.0],
+                extent: extent.map(|dimension| dimension as f32),
+                depth_range: 0.0..=1.0,
+            })
+            .build()?;
+
+        Ok(Self {
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            render_pass,
+            pipeline,
+            target,
+            config,
+        })
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    pub fn target(&self) -> &OffscreenTarget {
+        &self.target
+    }
+
+    /// Draws `scene`, a triangle list in clip space, and returns the tightly packed RGBA8 rows
+    /// of the result.
+    pub fn render_frame(&mut self, scene: &[MyVertex]) -> Result<Vec<u8>, ThorusError> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        builder.begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: self.config.clear_values(&self.render_pass),
+                ..RenderPassBeginInfo::framebuffer(self.target.framebuffer.clone())
+            },
+            SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..SubpassBeginInfo::default()
+            },
+        )?;
+        if !scene.is_empty() {
+            let vertex_buffer = Buffer::from_iter(
+                self.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::VERTEX_BUFFER,
+                    ..BufferCreateInfo::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..AllocationCreateInfo::default()
+                },
+                scene.iter().copied(),
+            )?;
+            builder
+                .bind_pipeline_graphics(self.pipeline.clone())?
+                .bind_vertex_buffers(0, vertex_buffer)?
+                .draw(scene.len() as u32, 1, 0, 0)?;
+        }
+        builder
+            .end_render_pass(SubpassEndInfo::default())?
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                self.target.image.clone(),
+                self.target.readback.clone(),
+            ))?;
+
+        builder
+            .build()?
+            .execute(self.queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+        let pixels = self.target.readback.read()?.to_vec();
+        Ok(pixels)
+    }
+}
+
+/// Skips draws of objects whose occlusion query found no visible samples, using
+/// `VK_EXT_conditional_rendering`.
+///
+/// Every object gets one occlusion query, typically around a draw of its bounding box, whose
+/// result is copied into a predicate buffer by [`resolve`](Self::resolve). The real draws are
+/// then wrapped in [`for_object`](Self::for_object) and [`end`](Self::end), so the GPU discards
+/// them without a round trip to the host.
+///
+/// vulkano has no conditional rendering commands and its automatic command buffers reorder
+/// recording, so everything here is recorded into an `UnsafeCommandBufferBuilder`.
+pub struct ConditionalRender {
+    device: Arc<Device>,
+    query_pool: Arc<QueryPool>,
+    predicates: Subbuffer<[u32]>,
+    object_count: u32,
+}
+
+impl ConditionalRender {
+    /// Whether the device can be created with [`Self::required_extensions`].
+    pub fn is_supported(physical_device: &PhysicalDevice) -> bool {
+        physical_device
+            .supported_extensions()
+            .contains(&Self::required_extensions())
+    }
+
+    pub fn required_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            ext_conditional_rendering: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    /// Creates the queries and predicates of `object_count` objects.
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        object_count: u32,
+    ) -> Result<Self, ThorusError> {
+        let device = allocator.device().clone();
+        if !device
+            .enabled_extensions()
+            .contains(&Self::required_extensions())
+        {
+            return Err(ThorusError::Missing("conditional rendering support"));
+        }
+        let query_pool = QueryPool::new(
+            device.clone(),
+            QueryPoolCreateInfo {
+                query_count: object_count,
+                ..QueryPoolCreateInfo::query_type(QueryType::Occlusion)
+            },
+        )?;
+        let predicates = predicate_buffer(allocator, object_count)?;
+        Ok(Self {
+            device,
+            query_pool,
+            predicates,
+            object_count,
+        })
+    }
+
+    pub fn object_count(&self) -> u32 {
+        self.object_count
+    }
+
+    pub fn query_pool(&self) -> &Arc<QueryPool> {
+        &self.query_pool
+    }
+
+    /// One `u32` per object, non-zero if any of its samples passed.
+    pub fn predicates(&self) -> &Subbuffer<[u32]> {
+        &self.predicates
+    }
+
+    /// Resets every query; must be recorded outside a render pass before the queries begin.
+    ///
+    /// # Safety
+    ///
+    /// The queries must not be in use by commands that are still pending.
+    pub unsafe fn reset<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+    ) -> Result<(), ThorusError> {
+        builder.reset_query_pool(&self.query_pool, 0..self.object_count)?;
+        Ok(())
+    }
+
+    /// Starts counting the samples of `object_id` that pass the depth test.
+    ///
+    /// # Safety
+    ///
+    /// The query must have been reset since it was last used.
+    pub unsafe fn begin_query<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+        object_id: usize,
+    ) -> Result<(), ThorusError> {
+        builder.begin_query(
+            &self.query_pool,
+            object_id as u32,
+            QueryControlFlags::empty(),
+        )?;
+        Ok(())
+    }
+
+    /// # Safety
+    ///
+    /// The query of `object_id` must be active in `builder`.
+    pub unsafe fn end_query<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+        object_id: usize,
+    ) -> Result<(), ThorusError> {
+        builder.end_query(&self.query_pool, object_id as u32)?;
+        Ok(())
+    }
+
+    /// Copies the query results into the predicate buffer and makes them visible to
+    /// conditional rendering; must be recorded outside a render pass.
+    ///
+    /// # Safety
+    ///
+    /// Every query must have been written since the last [`reset`](Self::reset), and the
+    /// predicates must not be in use by commands that are still pending.
+    pub unsafe fn resolve<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+    ) -> Result<(), ThorusError> {
+        builder
+            .copy_query_pool_results(
+                &self.query_pool,
+                0..self.object_count,
+                &self.predicates,
+                QueryResultFlags::WAIT,
+            )?
+            .pipeline_barrier(&DependencyInfo {
+                buffer_memory_barriers: [BufferMemoryBarrier {
+                    src_stages: PipelineStages::COPY,
+                    src_access: AccessFlags::TRANSFER_WRITE,
+                    dst_stages: PipelineStages::CONDITIONAL_RENDERING,
+                    dst_access: AccessFlags::CONDITIONAL_RENDERING_READ,
+                    range: self.predicates.offset()
+                        ..self.predicates.offset() + self.predicates.size(),
+                    ..BufferMemoryBarrier::buffer(self.predicates.buffer().clone())
+                }]
+                .into_iter()
+                .collect(),
+                ..DependencyInfo::default()
+            })?;
+        Ok(())
+    }
+
+    /// Makes the draws up to the next [`end`](Self::end) depend on the predicate at byte
+    /// `offset` of the predicate buffer: they are skipped if it is zero, or if it is non-zero
+    /// and `inverted` is set.
+    ///
+    /// # Safety
+    ///
+    /// Conditional rendering must not already be active, `offset` must be a multiple of 4
+    /// inside the buffer, and [`resolve`](Self::resolve) must have been recorded before.
+    pub unsafe fn begin<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+        offset: DeviceSize,
+        inverted: bool,
+    ) {
+        let begin_info = ash::vk::ConditionalRenderingBeginInfoEXT {
+            buffer: self.predicates.buffer().handle(),
+            offset: self.predicates.offset() + offset,
+            flags: if inverted {
+                ash::vk::ConditionalRenderingFlagsEXT::INVERTED
+            } else {
+                ash::vk::ConditionalRenderingFlagsEXT::empty()
+            },
+            ..Default::default()
+        };
+        (self
+            .device
+            .fns()
+            .ext_conditional_rendering
+            .cmd_begin_conditional_rendering_ext)(builder.handle(), &begin_info);
+    }
+
+    /// Begins conditional rendering on the query result of `object_id`, so its draws are
+    /// skipped if none of its samples were visible.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`begin`](Self::begin); `object_id` must be below the object count.
+    pub unsafe fn for_object<A: CommandBufferAllocator>(
+        &self,
+        object_id: usize,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+    ) {
+        debug_assert!(object_id < self.object_count as usize);
+        let offset = (object_id * mem::size_of::<u32>()) as DeviceSize;
+        self.begin(builder, offset, false);
+    }
+
+    /// # Safety
+    ///
+    /// Conditional rendering must be active, begun in the same render pass instance or
+    /// outside of any.
+    pub unsafe fn end<A: CommandBufferAllocator>(
+        &self,
+        builder: &mut UnsafeCommandBufferBuilder<A>,
+    ) {
+        (self
+            .device
+            .fns()
+            .ext_conditional_rendering
+            .cmd_end_conditional_rendering_ext)(builder.handle());
+    }
+}
+
+/// Device-local buffer of one predicate per object.
+///
+/// vulkano does not expose `CONDITIONAL_RENDERING` buffer usage yet, so the buffer is created
+/// directly and only its memory is bound through vulkano.
+fn predicate_buffer(
+    allocator: Arc<dyn MemoryAllocator>,
+    object_count: u32,
+) -> Result<Subbuffer<[u32]>, ThorusError> {
+    let device = allocator.device().clone();
+    let size = object_count.max(1) as DeviceSize * mem::size_of::<u32>() as DeviceSize;
+    let create_info = ash::vk::BufferCreateInfo {
+        size,
+        usage: ash::vk::BufferUsageFlags::CONDITIONAL_RENDERING_EXT
+            | ash::vk::BufferUsageFlags::TRANSFER_DST,
+        sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let mut handle = ash::vk::Buffer::null();
+    unsafe {
+        (device.fns().v1_0.create_buffer)(device.handle(), &create_info, ptr::null(), &mut handle)
+    }
+    .result()
+    .map_err(|e| AllocateBufferError::CreateBuffer(VulkanError::from(e)))?;
+    // Takes ownership of `handle`, destroying it on drop.
+    let raw_buffer = unsafe {
+        RawBuffer::from_handle(
+            device,
+            handle,
+            BufferCreateInfo {
+                size,
+                usage: BufferUsage::TRANSFER_DST,
+                ..BufferCreateInfo::default()
+            },
+        )
+    };
+    let allocation = allocator
+        .allocate(
+            *raw_buffer.memory_requirements(),
+            AllocationType::Linear,
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..AllocationCreateInfo::default()
+            },
+            Some(DedicatedAllocation::Buffer(&raw_buffer)),
+        )
+        .map_err(AllocateBufferError::AllocateMemory)?;
+    let allocation = unsafe { ResourceMemory::from_allocation(allocator, allocation) };
+    let buffer = raw_buffer.bind_memory(allocation).map_err(|(e, _, _)| e)?;
+    debug!("conditional rendering predicate buffer: {buffer:?}");
+    Ok(Subbuffer::new(Arc::new(buffer)).reinterpret())
+}
+
+/// Where a mesh lies in the shared vertex and index buffers drawn by an [`IndirectRenderer`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct IndirectMesh {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// Added to every index before fetching the vertex.
+    pub vertex_offset: i32,
+}
+
+/// One instance of a mesh queued on an [`IndirectRenderer`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DrawEntry {
+    /// Index returned by [`IndirectRenderer::add_mesh`].
+    pub mesh: usize,
+    pub material: Option<usize>,
+    /// Column-major model matrix.
+    pub transform: [[f32; 4]; 4],
+}
+
+/// Draws every queued mesh instance with a single `vkCmdDrawIndexedIndirect`.
+///
+/// The meshes share the vertex and index buffers bound by the caller. Draw `i` uses
+/// `first_instance = i`, so shaders find its model matrix at `gl_InstanceIndex` in
+/// [`transforms`](Self::transforms).
+pub struct IndirectRenderer {
+    meshes: Vec<IndirectMesh>,
+    draws: Vec<DrawEntry>,
+    commands: Subbuffer<[DrawIndexedIndirectCommand]>,
+    transforms: Subbuffer<[[[f32; 4]; 4]]>,
+}
+
+impl IndirectRenderer {
+    /// Creates host-visible indirect and transform buffers for up to `capacity` draws.
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        capacity: u32,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let allocation_info = AllocationCreateInfo {
+            memory_type_filter: UPLOAD_MEMORY,
+            ..AllocationCreateInfo::default()
+        };
+        let len = capacity.max(1) as DeviceSize;
+        Ok(Self {
+            meshes: vec![],
+            draws: vec![],
+            commands: Buffer::new_slice(
+                allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::INDIRECT_BUFFER,
+                    ..BufferCreateInfo::default()
+                },
+                allocation_info.clone(),
+                len,
+            )?,
+            transforms: Buffer::new_slice(
+                allocator,
+                BufferCreateInfo {
+                    usage: BufferUsage::STORAGE_BUFFER,
+                    ..BufferCreateInfo::default()
+                },
+                allocation_info,
+                len,
+            )?,
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.commands.len() as u32
+    }
+
+    /// Whether another [`push`](Self::push) would exceed the capacity.
+    pub fn is_full(&self) -> bool {
+        self.draws.len() >= self.capacity() as usize
+    }
+
+    /// Returns the index to queue draws of `mesh` with.
+    pub fn add_mesh(&mut self, mesh: IndirectMesh) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    pub fn meshes(&self) -> &[IndirectMesh] {
+        &self.meshes
+    }
+
+    /// Forgets the queued draws, keeping the meshes.
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// Queues a draw.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the mesh was not added or the renderer is full.
+    pub fn push(&mut self, draw: DrawEntry) {
+        assert!(draw.mesh < self.meshes.len(), "unknown mesh {}", draw.mesh);
+        assert!(
+            !self.is_full(),
+            "more draws than the renderer was created for"
+        );
+        self.draws.push(draw);
+    }
+
+    pub fn draws(&self) -> &[DrawEntry] {
+        &self.draws
+    }
+
+    pub fn transforms(&self) -> &Subbuffer<[[[f32; 4]; 4]]> {
+        &self.transforms
+    }
+
+    /// Writes the indirect commands and transforms of the queued draws.
+    ///
+    /// The buffers must not be in use by commands that are still pending.
+    pub fn upload(&self) -> Result<(), HostAccessError> {
+        if self.draws.is_empty() {
+            return Ok(());
+        }
+        let mut commands = self.commands.write()?;
+        let mut transforms = self.transforms.write()?;
+        for (i, draw) in self.draws.iter().enumerate() {
+            let mesh = self.meshes[draw.mesh];
+            commands[i] = DrawIndexedIndirectCommand {
+                index_count: mesh.index_count,
+                instance_count: 1,
+                first_index: mesh.first_index,
+                vertex_offset: mesh.vertex_offset as u32,
+                first_instance: i as u32,
+            };
+            transforms[i] = draw.transform;
+        }
+        Ok(())
+    }
+
+    /// Records the draws written by the last [`upload`](Self::upload), with the pipeline,
+    /// descriptor sets, vertex and index buffers already bound inside a render pass.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), ThorusError> {
+        let count = self.draws.len() as DeviceSize;
+        if count == 0 {
+            return Ok(());
+        }
+        builder.draw_indexed_indirect(self.commands.clone().slice(0..count))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::device::Features;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn headless_triangle_is_not_blank() {
+        let extent = [64, 64];
+        let mut renderer = HeadlessRenderer::new(extent, RenderConfig::default()).unwrap();
+        let scene = [[-0.5, -0.5], [0.0, 0.5], [0.5, -0.25]].map(|position| MyVertex { position });
+        let pixels = renderer.render_frame(&scene).unwrap();
+
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+        assert!(pixels.iter().any(|&byte| byte != 0));
+        let center = (32 * 64 + 32) * 4;
+        assert_eq!(pixels[center..center + 4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn conditional_render_constructs_with_the_extension() {
+        let context = TestContext::with_extensions(
+            ConditionalRender::required_extensions(),
+            Features::empty(),
+        );
+        let conditional = ConditionalRender::new(context.memory_allocator.clone(), 8).unwrap();
+        assert_eq!(conditional.object_count(), 8);
+        assert_eq!(conditional.predicates().len(), 8);
+        assert_eq!(conditional.query_pool().query_count(), 8);
+
+        let without = TestContext::new();
+        if !without
+            .queue
+            .device()
+            .enabled_extensions()
+            .ext_conditional_rendering
+        {
+            assert!(matches!(
+                ConditionalRender::new(without.memory_allocator.clone(), 8),
+                Err(ThorusError::Missing(_))
+            ));
+        }
+    }
+}
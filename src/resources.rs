@@ -0,0 +1,103 @@
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Resources waiting for the GPU to stop using them.
+#[derive(Default)]
+pub struct ResourceGraveyard {
+    entries: Vec<(usize, Box<dyn Any + Send>)>,
+}
+
+/// Defers dropping GPU resources until the submission that last used them has completed.
+///
+/// Fence indices are expected to grow monotonically, e.g. a frame counter, so that completing
+/// one index implies every earlier one has completed too.
+#[derive(Clone, Default)]
+pub struct ResourceManager {
+    graveyard: Arc<Mutex<ResourceGraveyard>>,
+}
+
+impl ResourceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn retire<T: Send + 'static>(&self, resource: T, fence_index: usize) {
+        self.graveyard
+            .lock()
+            .unwrap()
+            .entries
+            .push((fence_index, Box::new(resource)));
+    }
+
+    /// Drops every resource retired at or before `completed_fence_index`.
+    pub fn gc(&self, completed_fence_index: usize) -> usize {
+        let freed: Vec<_> = {
+            let mut graveyard = self.graveyard.lock().unwrap();
+            let (freed, pending) = graveyard
+                .entries
+                .drain(..)
+                .partition(|&(fence_index, _)| fence_index <= completed_fence_index);
+            graveyard.entries = pending;
+            freed
+        };
+        // dropped outside of the lock, destructors may take a while
+        let count = freed.len();
+        drop(freed);
+        if count > 0 {
+            debug!("freed {count} resources up to fence {completed_fence_index}");
+        }
+        count
+    }
+
+    pub fn pending(&self) -> usize {
+        self.graveyard.lock().unwrap().entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts how many of its instances have been dropped.
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn resources_are_dropped_only_after_their_fence() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let manager = ResourceManager::new();
+        manager.retire(Tracked(dropped.clone()), 1);
+        manager.retire(Tracked(dropped.clone()), 2);
+        manager.retire(Tracked(dropped.clone()), 2);
+        manager.retire(Tracked(dropped.clone()), 5);
+        assert_eq!(manager.pending(), 4);
+
+        assert_eq!(manager.gc(0), 0);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        assert_eq!(manager.gc(1), 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+        assert_eq!(manager.gc(4), 2);
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+        assert_eq!(manager.pending(), 1);
+        assert_eq!(manager.gc(5), 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 4);
+        assert_eq!(manager.pending(), 0);
+    }
+
+    #[test]
+    fn clones_share_the_graveyard() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let manager = ResourceManager::new();
+        manager.clone().retire(Tracked(dropped.clone()), 3);
+        assert_eq!(manager.pending(), 1);
+        manager.gc(3);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,453 @@
+use crate::assets::{AssetCache, AssetError};
+use crate::buffer::UPLOAD_MEMORY;
+use crate::material::PbrMaterial;
+use crate::math::Mat4;
+use crate::mesh::Mesh;
+use crate::vertex::Vertex3D;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::buffer::{AllocateBufferError, Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::Validated;
+
+/// First bytes of every scene file.
+pub const SCENE_MAGIC: [u8; 4] = *b"THSC";
+/// Version of the scene format written by [`Scene::save_binary`], stored right after
+/// [`SCENE_MAGIC`] as a little-endian `u32`.
+pub const SCENE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    /// The file does not start with [`SCENE_MAGIC`].
+    NotAScene,
+    UnsupportedVersion(u32),
+    /// A node refers to a node, mesh or material the scene does not have.
+    InvalidReference(&'static str, usize),
+    Asset(AssetError),
+    Allocation(Validated<AllocateBufferError>),
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to access scene file: {e}"),
+            Self::Encoding(e) => write!(f, "failed to encode scene: {e}"),
+            Self::NotAScene => write!(f, "not a scene file"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported scene version {version}, expected {SCENE_VERSION}"
+            ),
+            Self::InvalidReference(kind, index) => {
+                write!(f, "scene refers to missing {kind} {index}")
+            }
+            Self::Asset(e) => write!(f, "failed to load scene asset: {e}"),
+            Self::Allocation(e) => write!(f, "failed to allocate scene buffers: {e}"),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Encoding(e) => Some(e),
+            Self::NotAScene | Self::UnsupportedVersion(_) | Self::InvalidReference(..) => None,
+            Self::Asset(e) => Some(e),
+            Self::Allocation(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SceneError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+impl From<AssetError> for SceneError {
+    fn from(e: AssetError) -> Self {
+        Self::Asset(e)
+    }
+}
+
+impl From<Validated<AllocateBufferError>> for SceneError {
+    fn from(e: Validated<AllocateBufferError>) -> Self {
+        Self::Allocation(e)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SceneNode {
+    /// Column-major transform relative to the parent node.
+    pub transform: [[f32; 4]; 4],
+    /// Index into [`Scene::meshes`]; nodes without a mesh only group their children.
+    pub mesh_id: Option<usize>,
+    /// Index into [`Scene::materials`].
+    pub material_id: Option<usize>,
+    /// Indices into [`Scene::nodes`].
+    pub children: Vec<usize>,
+}
+
+impl Default for SceneNode {
+    fn default() -> Self {
+        Self {
+            transform: Mat4::IDENTITY.0,
+            mesh_id: None,
+            material_id: None,
+            children: vec![],
+        }
+    }
+}
+
+/// Material as stored in a scene file: the factors of a [`PbrMaterial`] and the paths of its
+/// maps, relative to the scene file unless absolute.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MaterialDesc {
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: [f32; 3],
+    pub base_color_map: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub metallic_roughness_map: Option<PathBuf>,
+    pub ao_map: Option<PathBuf>,
+    pub emissive_map: Option<PathBuf>,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        let material = PbrMaterial::default();
+        Self {
+            base_color_factor: material.base_color_factor,
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            emissive_factor: material.emissive_factor,
+            base_color_map: None,
+            normal_map: None,
+            metallic_roughness_map: None,
+            ao_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+/// Node hierarchy with the meshes and materials its nodes refer to by index.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    /// Nodes without a parent.
+    pub roots: Vec<usize>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<MaterialDesc>,
+}
+
+/// Vertex and index buffers of a scene mesh.
+#[derive(Clone, Debug)]
+pub struct GpuMesh {
+    pub vertex_buffer: Subbuffer<[Vertex3D]>,
+    pub index_buffer: Subbuffer<[u32]>,
+}
+
+/// A [`Scene`] along with the GPU resources of its meshes and materials, at the same indices.
+pub struct LoadedScene {
+    pub scene: Scene,
+    pub meshes: Vec<GpuMesh>,
+    pub materials: Vec<PbrMaterial>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the index of the mesh.
+    pub fn add_mesh(&mut self, mesh: Mesh) -> usize {
+        self.meshes.push(mesh);
+        self.meshes.len() - 1
+    }
+
+    /// Returns the index of the material.
+    pub fn add_material(&mut self, material: MaterialDesc) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// Adds `node` as a child of `parent`, or as a root if `None`, and returns its index.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `parent` is not a node of the scene.
+    pub fn add_node(&mut self, node: SceneNode, parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(index),
+            None => self.roots.push(index),
+        }
+        self.nodes.push(node);
+        index
+    }
+
+    /// Writes the scene to `path` behind [`SCENE_MAGIC`] and [`SCENE_VERSION`].
+    pub fn save_binary(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&SCENE_MAGIC)?;
+        writer.write_all(&SCENE_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a scene written by [`save_binary`](Self::save_binary) without creating any GPU
+    /// resources.
+    pub fn read_binary(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != SCENE_MAGIC {
+            return Err(SceneError::NotAScene);
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(version));
+        }
+        let scene: Self = bincode::deserialize_from(reader)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Reads the scene at `path`, uploads its meshes through `allocator` and loads the maps of
+    /// its materials through `assets`.
+    pub fn load_binary(
+        path: impl AsRef<Path>,
+        allocator: Arc<dyn MemoryAllocator>,
+        assets: &mut AssetCache,
+    ) -> Result<LoadedScene, SceneError> {
+        let path = path.as_ref();
+        let scene = Self::read_binary(path)?;
+        debug!(
+            "loaded scene {path:?}: {} nodes, {} meshes, {} materials",
+            scene.nodes.len(),
+            scene.meshes.len(),
+            scene.materials.len()
+        );
+
+        let meshes = scene
+            .meshes
+            .iter()
+            .map(|mesh| upload_mesh(allocator.clone(), mesh))
+            .collect::<Result<_, _>>()?;
+
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let mut load_map = |map: &Option<PathBuf>| -> Result<_, SceneError> {
+            map.as_ref()
+                .map(|map| Ok(assets.load_texture(base_dir.join(map))?.view().clone()))
+                .transpose()
+        };
+        let materials = scene
+            .materials
+            .iter()
+            .map(|desc| {
+                Ok(PbrMaterial {
+                    base_color_factor: desc.base_color_factor,
+                    metallic_factor: desc.metallic_factor,
+                    roughness_factor: desc.roughness_factor,
+                    emissive_factor: desc.emissive_factor,
+                    base_color_map: load_map(&desc.base_color_map)?,
+                    normal_map: load_map(&desc.normal_map)?,
+                    metallic_roughness_map: load_map(&desc.metallic_roughness_map)?,
+                    ao_map: load_map(&desc.ao_map)?,
+                    emissive_map: load_map(&desc.emissive_map)?,
+                })
+            })
+            .collect::<Result<_, SceneError>>()?;
+
+        Ok(LoadedScene {
+            scene,
+            meshes,
+            materials,
+        })
+    }
+
+    /// Checks that every index refers to an existing node, mesh or material.
+    fn validate(&self) -> Result<(), SceneError> {
+        let check = |kind, index, len| {
+            if index < len {
+                Ok(())
+            } else {
+                Err(SceneError::InvalidReference(kind, index))
+            }
+        };
+        for &root in &self.roots {
+            check("node", root, self.nodes.len())?;
+        }
+        for node in &self.nodes {
+            for &child in &node.children {
+                check("node", child, self.nodes.len())?;
+            }
+            if let Some(mesh_id) = node.mesh_id {
+                check("mesh", mesh_id, self.meshes.len())?;
+            }
+            if let Some(material_id) = node.material_id {
+                check("material", material_id, self.materials.len())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn upload_mesh(
+    allocator: Arc<dyn MemoryAllocator>,
+    mesh: &Mesh,
+) -> Result<GpuMesh, Validated<AllocateBufferError>> {
+    let buffer = |usage| {
+        (
+            BufferCreateInfo {
+                usage,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+        )
+    };
+    let (create_info, allocation_info) = buffer(BufferUsage::VERTEX_BUFFER);
+    let vertex_buffer = Buffer::from_iter(
+        allocator.clone(),
+        create_info,
+        allocation_info,
+        mesh.vertices.iter().copied(),
+    )?;
+    let (create_info, allocation_info) = buffer(BufferUsage::INDEX_BUFFER);
+    let index_buffer = Buffer::from_iter(
+        allocator,
+        create_info,
+        allocation_info,
+        mesh.indices.iter().copied(),
+    )?;
+    Ok(GpuMesh {
+        vertex_buffer,
+        index_buffer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Quat;
+    use crate::mesh_gen::MeshGen;
+    use crate::testing::TestContext;
+    use std::env;
+    use std::fs;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("thorus-scene-{}-{name}", std::process::id()))
+    }
+
+    /// A root with two children, the second of them without a mesh.
+    fn three_node_scene() -> Scene {
+        let mut scene = Scene::new();
+        let (vertices, indices) = MeshGen::cuboid([1.0, 2.0, 3.0]);
+        let mesh_id = scene.add_mesh(Mesh::new(vertices, indices));
+        let material_id = scene.add_material(MaterialDesc {
+            base_color_factor: [0.5, 0.25, 1.0, 1.0],
+            ..MaterialDesc::default()
+        });
+        let root = scene.add_node(
+            SceneNode {
+                transform: Mat4::translate([1.0, 2.0, 3.0]).0,
+                mesh_id: Some(mesh_id),
+                material_id: Some(material_id),
+                ..SceneNode::default()
+            },
+            None,
+        );
+        scene.add_node(
+            SceneNode {
+                transform: Mat4::from_quat(Quat::from_axis_angle([0.0, 1.0, 0.0], 0.7)).0,
+                mesh_id: Some(mesh_id),
+                ..SceneNode::default()
+            },
+            Some(root),
+        );
+        scene.add_node(
+            SceneNode {
+                transform: Mat4::scale([0.5, 2.0, -1.0]).0,
+                ..SceneNode::default()
+            },
+            Some(root),
+        );
+        scene
+    }
+
+    #[test]
+    fn saved_scene_reads_back_unchanged() {
+        let scene = three_node_scene();
+        let path = temp_path("round-trip.scene");
+        scene.save_binary(&path).unwrap();
+        let read = Scene::read_binary(&path);
+        fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+
+        assert_eq!(read.nodes, scene.nodes);
+        assert_eq!(read.roots, scene.roots);
+        assert_eq!(read.materials, scene.materials);
+        assert_eq!(read.meshes.len(), 1);
+        assert_eq!(read.meshes[0].indices, scene.meshes[0].indices);
+    }
+
+    #[test]
+    fn other_files_are_rejected_by_magic_and_version() {
+        let path = temp_path("not-a.scene");
+        fs::write(&path, b"PNG\0\0\0\0\0").unwrap();
+        let not_a_scene = Scene::read_binary(&path);
+        let mut future = SCENE_MAGIC.to_vec();
+        future.extend((SCENE_VERSION + 1).to_le_bytes());
+        fs::write(&path, future).unwrap();
+        let unsupported = Scene::read_binary(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(not_a_scene, Err(SceneError::NotAScene)));
+        assert!(matches!(
+            unsupported,
+            Err(SceneError::UnsupportedVersion(version)) if version == SCENE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn loaded_scene_has_gpu_resources_at_the_same_indices() {
+        let context = TestContext::new();
+        let scene = three_node_scene();
+        let path = temp_path("load.scene");
+        scene.save_binary(&path).unwrap();
+        let mut assets = AssetCache::new(context.memory_allocator.clone(), context.queue.clone());
+        let loaded = Scene::load_binary(&path, context.memory_allocator.clone(), &mut assets);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.scene.nodes, scene.nodes);
+        assert_eq!(loaded.meshes.len(), 1);
+        assert_eq!(
+            loaded.meshes[0].index_buffer.len() as usize,
+            scene.meshes[0].indices.len()
+        );
+        assert_eq!(loaded.materials[0].base_color_factor, [0.5, 0.25, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::error::ThorusError;
+use std::ptr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::{Version, VulkanError, VulkanObject};
+
+/// Semaphore with a 64-bit counter that queues signal and wait for by value.
+///
+/// vulkano does not support timeline semaphores yet, so this goes through the raw functions of
+/// Vulkan 1.2 or `VK_KHR_timeline_semaphore`, whichever the device has.
+pub struct TimelineSemaphore {
+    device: Arc<Device>,
+    handle: ash::vk::Semaphore,
+}
+
+impl TimelineSemaphore {
+    /// Requires the `timeline_semaphore` feature to be enabled on `device`.
+    pub fn new(device: Arc<Device>, initial_value: u64) -> Result<Self, ThorusError> {
+        if !device.enabled_features().timeline_semaphore {
+            return Err(ThorusError::Missing("timeline semaphore support"));
+        }
+        let mut type_info = ash::vk::SemaphoreTypeCreateInfo {
+            semaphore_type: ash::vk::SemaphoreType::TIMELINE,
+            initial_value,
+            ..Default::default()
+        };
+        let create_info = ash::vk::SemaphoreCreateInfo {
+            p_next: ptr::addr_of_mut!(type_info).cast(),
+            ..Default::default()
+        };
+        let mut handle = ash::vk::Semaphore::null();
+        unsafe {
+            (device.fns().v1_0.create_semaphore)(
+                device.handle(),
+                &create_info,
+                ptr::null(),
+                &mut handle,
+            )
+        }
+        .result()
+        .map_err(VulkanError::from)?;
+        Ok(Self { device, handle })
+    }
+
+    pub fn handle(&self) -> ash::vk::Semaphore {
+        self.handle
+    }
+
+    /// Current value of the counter.
+    pub fn value(&self) -> Result<u64, ThorusError> {
+        let fns = self.device.fns();
+        let get_counter_value = if self.device.api_version() >= Version::V1_2 {
+            fns.v1_2.get_semaphore_counter_value
+        } else {
+            fns.khr_timeline_semaphore.get_semaphore_counter_value_khr
+        };
+        let mut value = 0;
+        unsafe { get_counter_value(self.device.handle(), self.handle, &mut value) }
+            .result()
+            .map_err(VulkanError::from)?;
+        Ok(value)
+    }
+
+    /// Blocks until the counter reaches `value`. Returns `false` if `timeout` ran out first;
+    /// `None` waits forever.
+    pub fn wait(&self, value: u64, timeout: Option<Duration>) -> Result<bool, ThorusError> {
+        let fns = self.device.fns();
+        let wait_semaphores = if self.device.api_version() >= Version::V1_2 {
+            fns.v1_2.wait_semaphores
+        } else {
+            fns.khr_timeline_semaphore.wait_semaphores_khr
+        };
+        let wait_info = ash::vk::SemaphoreWaitInfo {
+            semaphore_count: 1,
+            p_semaphores: &self.handle,
+            p_values: &value,
+            ..Default::default()
+        };
+        let timeout = timeout.map_or(u64::MAX, |timeout| {
+            timeout.as_nanos().try_into().unwrap_or(u64::MAX)
+        });
+        match unsafe { wait_semaphores(self.device.handle(), &wait_info, timeout) } {
+            ash::vk::Result::SUCCESS => Ok(true),
+            ash::vk::Result::TIMEOUT => Ok(false),
+            e => Err(VulkanError::from(e).into()),
+        }
+    }
+}
+
+impl Drop for TimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            (self.device.fns().v1_0.destroy_semaphore)(
+                self.device.handle(),
+                self.handle,
+                ptr::null(),
+            );
+        }
+    }
+}
+
+/// Submits compute work, such as the particle simulation, to a dedicated compute queue and
+/// the graphics work that consumes its results to the graphics queue, so the two can overlap
+/// across frames.
+///
+/// Each compute submission signals a value on the compute timeline, and a graphics submission
+/// waits for a value on it before vertex input, so vertex fetch never reads buffers the
+/// simulation is still writing. Buffers written on one queue and read on the other must be
+/// created with `Sharing::Concurrent` over both queue families, as no ownership transfers are
+/// recorded.
+///
+/// Command buffers are kept alive until their timeline has passed them. Submission bypasses
+/// vulkano's own resource tracking, so the command buffers must not rely on it across
+/// submissions.
+pub struct AsyncComputeScheduler {
+    graphics_queue: Arc<Queue>,
+    compute_queue: Arc<Queue>,
+    compute_timeline: TimelineSemaphore,
+    graphics_timeline: TimelineSemaphore,
+    compute_value: u64,
+    graphics_value: u64,
+    compute_in_flight: Vec<(u64, Arc<PrimaryAutoCommandBuffer>)>,
+    graphics_in_flight: Vec<(u64, Arc<PrimaryAutoCommandBuffer>)>,
+}
+
+impl AsyncComputeScheduler {
+    /// Both queues must belong to the same device, which needs the `timeline_semaphore`
+    /// feature. They may be the same queue if the device has no separate compute family.
+    pub fn new(graphics_queue: Arc<Queue>, compute_queue: Arc<Queue>) -> Result<Self, ThorusError> {
+        let device = graphics_queue.device().clone();
+        Ok(Self {
+            compute_timeline: TimelineSemaphore::new(device.clone(), 0)?,
+            graphics_timeline: TimelineSemaphore::new(device, 0)?,
+            graphics_queue,
+            compute_queue,
+            compute_value: 0,
+            graphics_value: 0,
+            compute_in_flight: vec![],
+            graphics_in_flight: vec![],
+        })
+    }
+
+    pub fn compute_timeline(&self) -> &TimelineSemaphore {
+        &self.compute_timeline
+    }
+
+    pub fn graphics_timeline(&self) -> &TimelineSemaphore {
+        &self.graphics_timeline
+    }
+
+    /// Submits `compute_cmd` to the compute queue, signalling `signal_value` on the compute
+    /// timeline once it completes. Values must increase from one call to the next.
+    ///
+    /// # Safety
+    ///
+    /// `compute_cmd` must have been recorded for the compute queue's family and must not be
+    /// pending elsewhere unless it allows simultaneous use.
+    pub unsafe fn submit_compute(
+        &mut self,
+        compute_cmd: Arc<PrimaryAutoCommandBuffer>,
+        signal_value: u64,
+    ) -> Result<(), ThorusError> {
+        debug_assert!(signal_value > self.compute_value);
+        self.cleanup()?;
+        submit(
+            &self.compute_queue,
+            compute_cmd.handle(),
+            None,
+            (self.compute_timeline.handle, signal_value),
+        )?;
+        self.compute_value = signal_value;
+        self.compute_in_flight.push((signal_value, compute_cmd));
+        Ok(())
+    }
+
+    /// Submits `graphics_cmd` to the graphics queue, starting vertex input only once the
+    /// compute timeline has reached `wait_value`.
+    ///
+    /// Returns the value the graphics timeline is signalled with when the submission
+    /// completes, to wait for before reusing what it reads or writes from the host.
+    ///
+    /// # Safety
+    ///
+    /// `graphics_cmd` must have been recorded for the graphics queue's family and must not be
+    /// pending elsewhere unless it allows simultaneous use. A compute submission signalling
+    /// `wait_value` or more must already have been made.
+    pub unsafe fn submit_graphics(
+        &mut self,
+        graphics_cmd: Arc<PrimaryAutoCommandBuffer>,
+        wait_value: u64,
+    ) -> Result<u64, ThorusError> {
+        debug_assert!(wait_value <= self.compute_value);
+        self.cleanup()?;
+        let signal_value = self.graphics_value + 1;
+        submit(
+            &self.graphics_queue,
+            graphics_cmd.handle(),
+            Some((
+                self.compute_timeline.handle,
+                wait_value,
+                ash::vk::PipelineStageFlags::VERTEX_INPUT,
+            )),
+            (self.graphics_timeline.handle, signal_value),
+        )?;
+        self.graphics_value = signal_value;
+        self.graphics_in_flight.push((signal_value, graphics_cmd));
+        Ok(signal_value)
+    }
+
+    /// Blocks until everything submitted so far has completed.
+    pub fn wait_idle(&mut self) -> Result<(), ThorusError> {
+        self.compute_timeline.wait(self.compute_value, None)?;
+        self.graphics_timeline.wait(self.graphics_value, None)?;
+        self.compute_in_flight.clear();
+        self.graphics_in_flight.clear();
+        Ok(())
+    }
+
+    /// Releases the command buffers whose submissions have completed.
+    fn cleanup(&mut self) -> Result<(), ThorusError> {
+        let compute_done = self.compute_timeline.value()?;
+        self.compute_in_flight
+            .retain(|(value, _)| *value > compute_done);
+        let graphics_done = self.graphics_timeline.value()?;
+        self.graphics_in_flight
+            .retain(|(value, _)| *value > graphics_done);
+        Ok(())
+    }
+}
+
+impl Drop for AsyncComputeScheduler {
+    fn drop(&mut self) {
+        if let Err(e) = self.wait_idle() {
+            warn!("failed to wait for async compute submissions: {e}");
+        }
+    }
+}
+
+/// Submits a single command buffer to `queue` with at most one timeline wait, given as
+/// semaphore, value and stages, and one timeline signal.
+unsafe fn submit(
+    queue: &Arc<Queue>,
+    command_buffer: ash::vk::CommandBuffer,
+    wait: Option<(ash::vk::Semaphore, u64, ash::vk::PipelineStageFlags)>,
+    (signal_semaphore, signal_value): (ash::vk::Semaphore, u64),
+) -> Result<(), ThorusError> {
+    let (wait_semaphores, wait_values, wait_stages) = match wait {
+        Some((semaphore, value, stages)) => (vec![semaphore], vec![value], vec![stages]),
+        None => (vec![], vec![], vec![]),
+    };
+    let mut timeline_info = ash::vk::TimelineSemaphoreSubmitInfo {
+        wait_semaphore_value_count: wait_values.len() as u32,
+        p_wait_semaphore_values: wait_values.as_ptr(),
+        signal_semaphore_value_count: 1,
+        p_signal_semaphore_values: &signal_value,
+        ..Default::default()
+    };
+    let submit_info = ash::vk::SubmitInfo {
+        p_next: ptr::addr_of_mut!(timeline_info).cast(),
+        wait_semaphore_count: wait_semaphores.len() as u32,
+        p_wait_semaphores: wait_semaphores.as_ptr(),
+        p_wait_dst_stage_mask: wait_stages.as_ptr(),
+        command_buffer_count: 1,
+        p_command_buffers: &command_buffer,
+        signal_semaphore_count: 1,
+        p_signal_semaphores: &signal_semaphore,
+        ..Default::default()
+    };
+    let device = queue.device();
+    // Holding the guard keeps other threads from submitting to the queue at the same time.
+    queue
+        .with(|_guard| {
+            (device.fns().v1_0.queue_submit)(
+                queue.handle(),
+                1,
+                &submit_info,
+                ash::vk::Fence::null(),
+            )
+        })
+        .result()
+        .map_err(VulkanError::from)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::device::{DeviceExtensions, Features};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn compute_and_graphics_submissions_complete() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions::empty(),
+            Features {
+                timeline_semaphore: true,
+                ..Features::empty()
+            },
+        );
+        let buffer = Buffer::new_slice::<u32>(
+            context.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..AllocationCreateInfo::default()
+            },
+            256,
+        )
+        .unwrap();
+        let command_buffer = |value| {
+            let mut builder = context.command_buffer();
+            builder.fill_buffer(buffer.clone(), value).unwrap();
+            builder.build().unwrap()
+        };
+        // a device without a separate compute family runs both on the same queue
+        let mut scheduler =
+            AsyncComputeScheduler::new(context.queue.clone(), context.queue.clone()).unwrap();
+
+        let mut graphics_value = 0;
+        for frame in 1..=3 {
+            unsafe {
+                scheduler.submit_compute(command_buffer(0), frame).unwrap();
+                graphics_value = scheduler.submit_graphics(command_buffer(1), frame).unwrap();
+            }
+        }
+        assert_eq!(graphics_value, 3);
+        let timeout = Some(Duration::from_secs(5));
+        assert!(scheduler.compute_timeline().wait(3, timeout).unwrap());
+        assert!(scheduler
+            .graphics_timeline()
+            .wait(graphics_value, timeout)
+            .unwrap());
+        scheduler.wait_idle().unwrap();
+        assert_eq!(scheduler.graphics_timeline().value().unwrap(), 3);
+    }
+}
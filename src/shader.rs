@@ -1,6 +1,24 @@
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SendError, Sender};
+use std::sync::Arc;
+use std::{fs, io, process, thread};
+use tracing::{debug, warn};
+use vulkano::device::Device;
+use vulkano::shader::spirv::{bytes_to_words, SpirvBytesNotMultipleOf4};
+use vulkano::shader::{ShaderModule, ShaderModuleCreateInfo};
+use vulkano::{Validated, VulkanError};
+
+// SPIR-V 1.0 loads on every device, whichever `FeatureSet` it was picked with; what a shader
+// needs beyond that is checked against the device's features when it is loaded.
 vulkano_shaders::shader! {
-    vulkan_version: "1.2",
-    spirv_version: "1.6",
+    vulkan_version: "1.0",
+    spirv_version: "1.0",
     shaders: {
         vertex: {
             ty: "vertex",
@@ -9,6 +27,588 @@ vulkano_shaders::shader! {
         fragment: {
             ty: "fragment",
             path: "shader/shader.frag"
+        },
+        noise: {
+            ty: "compute",
+            path: "shader/noise.comp"
+        },
+        terrain_vertex: {
+            ty: "vertex",
+            path: "shader/terrain.vert"
+        },
+        terrain_fragment: {
+            ty: "fragment",
+            path: "shader/terrain.frag"
+        },
+        ssr: {
+            ty: "compute",
+            path: "shader/ssr.comp"
+        },
+        fullscreen: {
+            ty: "vertex",
+            path: "shader/fullscreen.vert"
+        },
+        dof: {
+            ty: "fragment",
+            path: "shader/dof.frag"
+        },
+        motion_blur: {
+            ty: "fragment",
+            path: "shader/motion_blur.frag"
+        },
+        gbuffer_vertex: {
+            ty: "vertex",
+            path: "shader/gbuffer.vert"
+        },
+        gbuffer_fragment: {
+            ty: "fragment",
+            path: "shader/gbuffer.frag"
+        },
+        fog: {
+            ty: "compute",
+            path: "shader/fog.comp"
+        },
+        fog_composite: {
+            ty: "compute",
+            path: "shader/fog_composite.comp"
+        },
+        debug_line_vertex: {
+            ty: "vertex",
+            path: "shader/debug_line.vert"
+        },
+        debug_line_fragment: {
+            ty: "fragment",
+            path: "shader/debug_line.frag"
+        },
+        dispatch_count: {
+            ty: "compute",
+            path: "shader/dispatch_count.comp"
+        },
+        bindless_vertex: {
+            ty: "vertex",
+            path: "shader/bindless.vert"
+        },
+        bindless_fragment: {
+            ty: "fragment",
+            path: "shader/bindless.frag"
+        },
+        list_sum: {
+            ty: "compute",
+            path: "shader/list_sum.comp"
+        },
+        radix_histogram: {
+            ty: "compute",
+            path: "shader/radix_histogram.comp"
+        },
+        radix_scan: {
+            ty: "compute",
+            path: "shader/radix_scan.comp"
+        },
+        radix_scatter: {
+            ty: "compute",
+            path: "shader/radix_scatter.comp"
+        },
+        svo_trace: {
+            ty: "compute",
+            path: "shader/svo_trace.comp"
+        },
+        outline_vertex: {
+            ty: "vertex",
+            path: "shader/outline.vert"
+        },
+        outline_fragment: {
+            ty: "fragment",
+            path: "shader/outline.frag"
+        },
+        shadow_vertex: {
+            ty: "vertex",
+            path: "shader/shadow.vert"
+        },
+        sprite_vertex: {
+            ty: "vertex",
+            path: "shader/sprite.vert"
+        },
+        sprite_fragment: {
+            ty: "fragment",
+            path: "shader/sprite.frag"
+        },
+        capture_vertex: {
+            ty: "vertex",
+            path: "shader/capture.vert"
+        },
+        input_tint: {
+            ty: "fragment",
+            path: "shader/input_tint.frag"
+        },
+        stereo_vertex: {
+            ty: "vertex",
+            path: "shader/stereo.vert"
+        },
+        stereo_fragment: {
+            ty: "fragment",
+            path: "shader/stereo.frag"
+        },
+        skinning: {
+            ty: "compute",
+            path: "shader/skinning.comp"
+        },
+        drop_shadow_vertex: {
+            ty: "vertex",
+            path: "shader/drop_shadow.vert"
+        },
+        drop_shadow_fragment: {
+            ty: "fragment",
+            path: "shader/drop_shadow.frag"
+        },
+        taa: {
+            ty: "compute",
+            path: "shader/taa.comp"
+        },
+        sdf_shape_vertex: {
+            ty: "vertex",
+            path: "shader/sdf_shape.vert"
+        },
+        sdf_shape_fragment: {
+            ty: "fragment",
+            path: "shader/sdf_shape.frag"
+        },
+        tilemap_vertex: {
+            ty: "vertex",
+            path: "shader/tilemap.vert"
+        },
+        tilemap_fragment: {
+            ty: "fragment",
+            path: "shader/tilemap.frag"
+        }
+    }
+}
+
+pub use ray_tracing::{load_raytrace_closest_hit, load_raytrace_miss, load_raytrace_raygen};
+
+/// Shaders of [`RayTracingPipeline`](crate::raytracing::RayTracingPipeline). They need
+/// SPIR-V 1.4, which devices with `VK_KHR_ray_tracing_pipeline` load through the
+/// `VK_KHR_spirv_1_4` extension it depends on.
+pub mod ray_tracing {
+    vulkano_shaders::shader! {
+        vulkan_version: "1.2",
+        spirv_version: "1.4",
+        shaders: {
+            raytrace_raygen: {
+                ty: "raygen",
+                path: "shader/raytrace.rgen"
+            },
+            raytrace_miss: {
+                ty: "miss",
+                path: "shader/raytrace.rmiss"
+            },
+            raytrace_closest_hit: {
+                ty: "closesthit",
+                path: "shader/raytrace.rchit"
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(io::Error),
+    InvalidSpirv(SpirvBytesNotMultipleOf4),
+    Vulkan(Validated<VulkanError>),
+}
+
+impl Display for ShaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read shader: {e}"),
+            Self::InvalidSpirv(e) => write!(f, "invalid SPIR-V: {e}"),
+            Self::Vulkan(e) => write!(f, "failed to create shader module: {e}"),
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::InvalidSpirv(e) => Some(e),
+            Self::Vulkan(e) => Some(e),
+        }
+    }
+}
+
+impl From<Validated<VulkanError>> for ShaderError {
+    fn from(e: Validated<VulkanError>) -> Self {
+        Self::Vulkan(e)
+    }
+}
+
+/// Creates a shader module from a compiled SPIR-V file instead of the embedded shaders.
+pub fn load_spirv(
+    device: Arc<Device>,
+    path: impl AsRef<Path>,
+) -> Result<Arc<ShaderModule>, ShaderError> {
+    let bytes = fs::read(path).map_err(ShaderError::Io)?;
+    spirv_module(device, &bytes)
+}
+
+/// Creates a shader module from SPIR-V bytes, e.g. from [`AsyncShaderCompiler`].
+pub fn spirv_module(device: Arc<Device>, bytes: &[u8]) -> Result<Arc<ShaderModule>, ShaderError> {
+    let words = bytes_to_words(bytes).map_err(ShaderError::InvalidSpirv)?;
+    let module = unsafe { ShaderModule::new(device, ShaderModuleCreateInfo::new(&words)) }?;
+    debug!("loaded shader module: {module:?}");
+    Ok(module)
+}
+
+/// Stage a GLSL source is compiled for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ShaderType {
+    Vertex,
+    Fragment,
+    Compute,
+    RayGeneration,
+    Miss,
+    ClosestHit,
+}
+
+impl From<ShaderType> for shaderc::ShaderKind {
+    fn from(ty: ShaderType) -> Self {
+        match ty {
+            ShaderType::Vertex => Self::Vertex,
+            ShaderType::Fragment => Self::Fragment,
+            ShaderType::Compute => Self::Compute,
+            ShaderType::RayGeneration => Self::RayGeneration,
+            ShaderType::Miss => Self::Miss,
+            ShaderType::ClosestHit => Self::ClosestHit,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// shaderc could not create a compiler.
+    Unavailable,
+    ThreadPool(ThreadPoolBuildError),
+    Thread(io::Error),
+    Compile(shaderc::Error),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable => write!(f, "failed to create shader compiler"),
+            Self::ThreadPool(e) => write!(f, "failed to start shader compiler threads: {e}"),
+            Self::Thread(e) => write!(f, "failed to start shader compiler dispatcher: {e}"),
+            Self::Compile(e) => write!(f, "failed to compile shader: {e}"),
+        }
+    }
+}
+
+impl Error for CompileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Unavailable => None,
+            Self::ThreadPool(e) => Some(e),
+            Self::Thread(e) => Some(e),
+            Self::Compile(e) => Some(e),
+        }
+    }
+}
+
+/// GLSL source to compile on an [`AsyncShaderCompiler`].
+#[derive(Clone, Debug)]
+pub struct CompileRequest {
+    /// Identifies the shader in the response and in compiler messages.
+    pub name: String,
+    pub source: String,
+    pub ty: ShaderType,
+}
+
+/// Outcome of a [`CompileRequest`].
+#[derive(Debug)]
+pub struct CompiledShader {
+    pub name: String,
+    pub ty: ShaderType,
+    pub spirv: Result<Vec<u8>, CompileError>,
+}
+
+/// Whether a request came through [`AsyncShaderCompiler::submit`], which counts it as
+/// pending, or through a [`CompileRequestSender`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Origin {
+    Submitted,
+    Sent,
+}
+
+/// Submits [`CompileRequest`]s to an [`AsyncShaderCompiler`] from other threads.
+#[derive(Clone, Debug)]
+pub struct CompileRequestSender(Sender<(CompileRequest, Origin)>);
+
+impl CompileRequestSender {
+    /// Fails only if the compiler has been dropped.
+    pub fn send(&self, request: CompileRequest) -> Result<(), SendError<CompileRequest>> {
+        self.0
+            .send((request, Origin::Sent))
+            .map_err(|SendError((request, _))| SendError(request))
+    }
+}
+
+/// Compiles GLSL to SPIR-V on a pool of background threads.
+///
+/// Requests are submitted with [`submit`](Self::submit) or from other threads through
+/// [`sender`](Self::sender), and compiled concurrently; results are collected with
+/// [`poll`](Self::poll), typically once before every frame. With a cache directory, SPIR-V is
+/// stored there keyed by a hash of the source and stage, and later requests for the same
+/// shader are answered from disk.
+pub struct AsyncShaderCompiler {
+    requests: Sender<(CompileRequest, Origin)>,
+    responses: Receiver<(CompiledShader, Origin)>,
+    pending: usize,
+}
+
+impl AsyncShaderCompiler {
+    /// Starts `threads` compiler threads, or one per CPU if zero.
+    pub fn new(threads: usize, cache_dir: Option<PathBuf>) -> Result<Self, CompileError> {
+        let compiler = Arc::new(shaderc::Compiler::new().ok_or(CompileError::Unavailable)?);
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("shader-compiler-{i}"))
+            .build()
+            .map_err(CompileError::ThreadPool)?;
+        if let Some(dir) = &cache_dir {
+            if let Err(e) = fs::create_dir_all(dir) {
+                warn!("shader cache {} unavailable: {e}", dir.display());
+            }
+        }
+
+        let (requests, request_receiver) = mpsc::channel();
+        let (response_sender, responses) = mpsc::channel();
+        thread::Builder::new()
+            .name("shader-compiler".to_owned())
+            .spawn(move || dispatch(pool, compiler, cache_dir, request_receiver, response_sender))
+            .map_err(CompileError::Thread)?;
+        Ok(Self {
+            requests,
+            responses,
+            pending: 0,
+        })
+    }
+
+    /// Lets other threads submit requests; their results are returned by [`poll`](Self::poll)
+    /// like those of [`submit`](Self::submit), but are not counted as [`pending`](Self::pending).
+    pub fn sender(&self) -> CompileRequestSender {
+        CompileRequestSender(self.requests.clone())
+    }
+
+    pub fn submit(&mut self, name: impl Into<String>, source: impl Into<String>, ty: ShaderType) {
+        let request = CompileRequest {
+            name: name.into(),
+            source: source.into(),
+            ty,
+        };
+        // The dispatcher only stops once every sender, including ours, is gone.
+        if self.requests.send((request, Origin::Submitted)).is_ok() {
+            self.pending += 1;
         }
     }
+
+    /// Requests submitted through [`submit`](Self::submit) that have not been polled yet.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Returns the shaders that finished since the last call without blocking.
+    pub fn poll(&mut self) -> Vec<CompiledShader> {
+        let finished: Vec<_> = self.responses.try_iter().collect();
+        finished
+            .into_iter()
+            .map(|response| self.finish(response))
+            .collect()
+    }
+
+    /// Blocks until every request made through [`submit`](Self::submit) has finished; results
+    /// of other requests that arrive meanwhile are returned as well.
+    pub fn wait(&mut self) -> Vec<CompiledShader> {
+        let mut finished = self.poll();
+        while self.pending > 0 {
+            let Ok(response) = self.responses.recv() else {
+                break;
+            };
+            finished.push(self.finish(response));
+        }
+        finished
+    }
+
+    fn finish(&mut self, (shader, origin): (CompiledShader, Origin)) -> CompiledShader {
+        if origin == Origin::Submitted {
+            self.pending -= 1;
+        }
+        shader
+    }
+}
+
+fn dispatch(
+    pool: ThreadPool,
+    compiler: Arc<shaderc::Compiler>,
+    cache_dir: Option<PathBuf>,
+    requests: Receiver<(CompileRequest, Origin)>,
+    responses: Sender<(CompiledShader, Origin)>,
+) {
+    for (request, origin) in requests {
+        let compiler = compiler.clone();
+        let cache_path = cache_dir
+            .as_ref()
+            .map(|dir| dir.join(cache_file_name(&request)));
+        let responses = responses.clone();
+        pool.spawn(move || {
+            let spirv = compile_cached(&compiler, &request, cache_path.as_deref());
+            // The compiler may have been dropped while this was running.
+            let _ = responses.send((
+                CompiledShader {
+                    name: request.name,
+                    ty: request.ty,
+                    spirv,
+                },
+                origin,
+            ));
+        });
+    }
+}
+
+fn compile_cached(
+    compiler: &shaderc::Compiler,
+    request: &CompileRequest,
+    cache_path: Option<&Path>,
+) -> Result<Vec<u8>, CompileError> {
+    if let Some(bytes) = cache_path.and_then(|path| fs::read(path).ok()) {
+        debug!("shader {} loaded from cache", request.name);
+        return Ok(bytes);
+    }
+    let artifact = compiler
+        .compile_into_spirv(
+            &request.source,
+            request.ty.into(),
+            &request.name,
+            "main",
+            None,
+        )
+        .map_err(CompileError::Compile)?;
+    debug!("compiled shader {}", request.name);
+    let bytes = artifact.as_binary_u8().to_vec();
+    if let Some(path) = cache_path {
+        if let Err(e) = write_atomically(path, &bytes) {
+            warn!("failed to cache shader {}: {e}", request.name);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it into place, so that
+/// readers, including other processes sharing the cache, never see a partial file.
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+    let temp_path = path.with_extension(format!(
+        "{}-{}.tmp",
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temp_path, bytes)
+        .and_then(|()| fs::rename(&temp_path, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        })
+}
+
+/// Cache entries are only valid for the build that wrote them, as the hash is not stable
+/// across Rust versions.
+fn cache_file_name(request: &CompileRequest) -> String {
+    let mut hasher = DefaultHasher::new();
+    request.source.hash(&mut hasher);
+    request.ty.hash(&mut hasher);
+    format!("{:016x}.spv", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const SPIRV_MAGIC: [u8; 4] = 0x0723_0203u32.to_le_bytes();
+
+    fn request(name: &str, path: &str, ty: ShaderType) -> CompileRequest {
+        CompileRequest {
+            name: name.to_owned(),
+            source: fs::read_to_string(path).unwrap(),
+            ty,
+        }
+    }
+
+    #[test]
+    fn compiles_shaders_in_parallel() {
+        let mut compiler = AsyncShaderCompiler::new(2, None).unwrap();
+        for (name, path, ty) in [
+            ("vertex", "shader/shader.vert", ShaderType::Vertex),
+            ("fragment", "shader/shader.frag", ShaderType::Fragment),
+        ] {
+            let CompileRequest { name, source, ty } = request(name, path, ty);
+            compiler.submit(name, source, ty);
+        }
+        assert_eq!(compiler.pending(), 2);
+
+        let mut compiled = compiler.wait();
+        compiled.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(compiler.pending(), 0);
+        assert_eq!(compiled.len(), 2);
+        assert_eq!(compiled[0].name, "fragment");
+        assert_eq!(compiled[1].name, "vertex");
+        for shader in compiled {
+            let spirv = shader.spirv.unwrap();
+            assert_eq!(spirv[..4], SPIRV_MAGIC);
+        }
+    }
+
+    #[test]
+    fn sent_requests_are_not_pending() {
+        let mut compiler = AsyncShaderCompiler::new(1, None).unwrap();
+        compiler
+            .sender()
+            .send(request("sent", "shader/shader.frag", ShaderType::Fragment))
+            .unwrap();
+        let mut compiled = Vec::new();
+        while compiled.is_empty() {
+            compiled = compiler.poll();
+            thread::yield_now();
+        }
+        assert_eq!(compiled[0].name, "sent");
+        assert_eq!(compiler.pending(), 0);
+
+        compiler.submit(
+            "submitted",
+            fs::read_to_string("shader/shader.vert").unwrap(),
+            ShaderType::Vertex,
+        );
+        assert_eq!(compiler.pending(), 1);
+        assert_eq!(compiler.wait()[0].name, "submitted");
+    }
+
+    #[test]
+    fn cache_is_written_atomically_and_reused() {
+        let cache_dir = env::temp_dir().join(format!("thorus-shader-cache-{}", process::id()));
+        let _ = fs::remove_dir_all(&cache_dir);
+        let mut compiler = AsyncShaderCompiler::new(1, Some(cache_dir.clone())).unwrap();
+        let CompileRequest { name, source, ty } =
+            request("vertex", "shader/shader.vert", ShaderType::Vertex);
+        compiler.submit(name.clone(), source.clone(), ty);
+        let first = compiler.wait().remove(0).spirv.unwrap();
+
+        let files: Vec<_> = fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1, "temporary files left behind: {files:?}");
+        assert_eq!(fs::read(&files[0]).unwrap(), first);
+
+        compiler.submit(name, source, ty);
+        assert_eq!(compiler.wait().remove(0).spirv.unwrap(), first);
+        fs::remove_dir_all(cache_dir).unwrap();
+    }
 }
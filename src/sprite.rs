@@ -0,0 +1,307 @@
+use crate::ui::Rect;
+use crate::vertex::SpriteInstance;
+use std::sync::Arc;
+
+/// Sequence of frames cut from a sprite atlas, played at a fixed rate.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpriteAnimation {
+    /// Regions of the atlas in texels, in playback order.
+    pub frames: Vec<Rect>,
+    pub fps: f32,
+    /// Whether playback wraps around to the first frame instead of holding the last one.
+    pub looping: bool,
+}
+
+impl SpriteAnimation {
+    pub fn new(frames: Vec<Rect>, fps: f32, looping: bool) -> Self {
+        Self {
+            frames,
+            fps,
+            looping,
+        }
+    }
+
+    /// Seconds to play every frame once.
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 / self.fps
+    }
+
+    /// Index of the frame shown `elapsed` seconds into playback.
+    pub fn frame_index(&self, elapsed: f32) -> usize {
+        let count = self.frames.len();
+        if count == 0 || self.fps <= 0.0 {
+            return 0;
+        }
+        let index = (elapsed.max(0.0) * self.fps) as usize;
+        if self.looping {
+            index % count
+        } else {
+            index.min(count - 1)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+/// Playback position in a [`SpriteAnimation`], advanced by [`update`](Self::update) every
+/// frame and drawn with [`SpriteRenderer::draw_animated`](crate::ui::SpriteRenderer::draw_animated).
+#[derive(Clone, Default, Debug)]
+pub struct AnimationPlayer {
+    animation: Option<Arc<SpriteAnimation>>,
+    state: PlaybackState,
+    /// Seconds played since the animation started.
+    elapsed: f32,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plays `animation` from its first frame.
+    pub fn play(&mut self, animation: Arc<SpriteAnimation>) {
+        self.animation = Some(animation);
+        self.state = PlaybackState::Playing;
+        self.elapsed = 0.0;
+    }
+
+    /// Continues a paused animation.
+    pub fn resume(&mut self) {
+        if self.state == PlaybackState::Paused {
+            self.state = PlaybackState::Playing;
+        }
+    }
+
+    /// Holds the current frame until [`resume`](Self::resume).
+    pub fn pause(&mut self) {
+        if self.state == PlaybackState::Playing {
+            self.state = PlaybackState::Paused;
+        }
+    }
+
+    /// Rewinds to the first frame and stops.
+    pub fn stop(&mut self) {
+        self.state = PlaybackState::Stopped;
+        self.elapsed = 0.0;
+    }
+
+    /// Advances playback by `dt` seconds. A non-looping animation stops on its last frame.
+    pub fn update(&mut self, dt: f32) {
+        if self.state != PlaybackState::Playing {
+            return;
+        }
+        let Some(animation) = &self.animation else {
+            return;
+        };
+        self.elapsed += dt;
+        let duration = animation.duration();
+        if animation.looping {
+            if duration > 0.0 {
+                // keeps the precision of `elapsed` over long playback
+                self.elapsed %= duration;
+            }
+        } else if self.elapsed >= duration {
+            self.state = PlaybackState::Stopped;
+        }
+    }
+
+    pub fn animation(&self) -> Option<&Arc<SpriteAnimation>> {
+        self.animation.as_ref()
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        self.state
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.animation
+            .as_ref()
+            .map_or(0, |animation| animation.frame_index(self.elapsed))
+    }
+
+    /// Atlas region of the frame to draw, `None` without an animation or frames.
+    pub fn current_frame(&self) -> Option<&Rect> {
+        self.animation.as_ref()?.frames.get(self.frame_index())
+    }
+}
+
+/// Nine quads drawing the `texture_region` of a texture of `texture_extent` texels over
+/// `dest_rect` without distorting its borders: the `corner_size` texels of each corner are
+/// drawn unscaled, the edges between them stretch along the edge and the center stretches
+/// both ways. Corners are shrunk to half of the region or destination if they do not fit.
+///
+/// Quads are ordered row by row from the top left corner.
+pub fn nine_slice(
+    texture_region: Rect,
+    texture_extent: [u32; 2],
+    corner_size: [f32; 2],
+    dest_rect: Rect,
+    tint: [f32; 4],
+) -> [SpriteInstance; 9] {
+    // the four edges of the slices along one axis, in texels and pixels
+    let cuts = |axis: usize| {
+        let (start, length) = (
+            texture_region.origin[axis] as f32,
+            texture_region.extent[axis] as f32,
+        );
+        let (dest_start, dest_length) =
+            (dest_rect.origin[axis] as f32, dest_rect.extent[axis] as f32);
+        let corner = corner_size[axis]
+            .max(0.0)
+            .min(length / 2.0)
+            .min(dest_length / 2.0);
+        let texel = 1.0 / texture_extent[axis] as f32;
+        (
+            [
+                start,
+                start + corner,
+                start + length - corner,
+                start + length,
+            ]
+            .map(|t| t * texel),
+            [
+                dest_start,
+                dest_start + corner,
+                dest_start + dest_length - corner,
+                dest_start + dest_length,
+            ],
+        )
+    };
+    let (uv_x, x) = cuts(0);
+    let (uv_y, y) = cuts(1);
+    std::array::from_fn(|i| {
+        let (row, column) = (i / 3, i % 3);
+        SpriteInstance {
+            rect: [
+                x[column],
+                y[row],
+                x[column + 1] - x[column],
+                y[row + 1] - y[row],
+            ],
+            uv_rect: [
+                uv_x[column],
+                uv_y[row],
+                uv_x[column + 1] - uv_x[column],
+                uv_y[row + 1] - uv_y[row],
+            ],
+            color: tint,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn three_frames(looping: bool) -> Arc<SpriteAnimation> {
+        let frames = (0..3).map(|i| Rect::new([i * 16, 0], [16, 16])).collect();
+        Arc::new(SpriteAnimation::new(frames, 30.0, looping))
+    }
+
+    #[test]
+    fn frames_advance_at_the_animation_rate() {
+        let animation = three_frames(true);
+        let mut player = AnimationPlayer::new();
+        player.play(animation.clone());
+        let dt = 1.0 / 60.0;
+        let mut frames = vec![];
+        for _ in 0..4 {
+            player.update(dt);
+            frames.push(player.frame_index());
+        }
+        // two 60 fps frames per animation frame; the 4th ends on the boundary to frame 2
+        assert_eq!(frames[..3], [0, 1, 1]);
+        player.update(0.067 - 4.0 * dt);
+        assert_eq!(player.frame_index(), 2);
+        assert_eq!(player.current_frame(), Some(&animation.frames[2]));
+
+        // wraps around to the first frame
+        player.update(2.0 * dt);
+        assert_eq!(player.frame_index(), 0);
+    }
+
+    #[test]
+    fn non_looping_animation_stops_on_its_last_frame() {
+        let mut player = AnimationPlayer::new();
+        player.play(three_frames(false));
+        player.update(1.0);
+        assert_eq!(player.state(), PlaybackState::Stopped);
+        assert_eq!(player.frame_index(), 2);
+    }
+
+    #[test]
+    fn paused_player_holds_its_frame_and_stop_rewinds() {
+        let mut player = AnimationPlayer::new();
+        assert_eq!(player.current_frame(), None);
+        player.play(three_frames(true));
+        player.update(0.05);
+        player.pause();
+        player.update(0.05);
+        assert_eq!(player.state(), PlaybackState::Paused);
+        assert_eq!(player.frame_index(), 1);
+        player.resume();
+        player.update(0.025);
+        assert_eq!(player.frame_index(), 2);
+        player.stop();
+        player.update(0.05);
+        assert_eq!(player.frame_index(), 0);
+    }
+
+    #[test]
+    fn nine_slice_keeps_corners_and_stretches_edges_and_center() {
+        let quads = nine_slice(
+            Rect::new([0, 0], [64, 64]),
+            [64, 64],
+            [8.0, 8.0],
+            Rect::new([100, 50], [200, 120]),
+            [1.0; 4],
+        );
+        assert_eq!(quads.len(), 9);
+        let sizes = quads.map(|quad| [quad.rect[2], quad.rect[3]]);
+        assert_eq!(
+            sizes,
+            [
+                [8.0, 8.0],
+                [184.0, 8.0],
+                [8.0, 8.0],
+                [8.0, 104.0],
+                [184.0, 104.0],
+                [8.0, 104.0],
+                [8.0, 8.0],
+                [184.0, 8.0],
+                [8.0, 8.0],
+            ]
+        );
+        // corners sample 8 texels, the rest 48, of the 64 texel wide texture
+        let (corner, middle) = (8.0 / 64.0, 48.0 / 64.0);
+        assert_eq!(quads[0].uv_rect, [0.0, 0.0, corner, corner]);
+        assert_eq!(quads[4].uv_rect, [corner, corner, middle, middle]);
+        assert_eq!(
+            quads[8].uv_rect,
+            [corner + middle, corner + middle, corner, corner]
+        );
+        // the quads tile the destination
+        assert_eq!(quads[0].rect[..2], [100.0, 50.0]);
+        assert_eq!(quads[8].rect[0] + quads[8].rect[2], 300.0);
+        assert_eq!(quads[8].rect[1] + quads[8].rect[3], 170.0);
+    }
+
+    #[test]
+    fn nine_slice_shrinks_corners_that_do_not_fit() {
+        let quads = nine_slice(
+            Rect::new([0, 0], [64, 64]),
+            [64, 64],
+            [8.0, 8.0],
+            Rect::new([0, 0], [10, 40]),
+            [1.0; 4],
+        );
+        assert_eq!(quads[0].rect, [0.0, 0.0, 5.0, 8.0]);
+        assert_eq!(quads[4].rect, [5.0, 8.0, 0.0, 24.0]);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::compute::ComputePass;
+use crate::error::ThorusError;
+use crate::shader::load_ssr;
+use std::mem::size_of;
+use std::sync::Arc;
+use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::WriteDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Mirrors the push constant block of `shader/ssr.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SsrConfig {
+    pub max_steps: u32,
+    /// View-space distance advanced per step.
+    pub step_size: f32,
+    /// How far behind the depth buffer a ray may pass and still count as a hit.
+    pub thickness: f32,
+    /// Distance from the screen center, in NDC, where hits start fading to the sky color.
+    pub fade_start: f32,
+    pub fade_end: f32,
+}
+
+// std430 packs the five scalars without padding.
+const _: () = assert!(size_of::<SsrConfig>() == 20);
+
+impl Default for SsrConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: 64,
+            step_size: 0.1,
+            thickness: 0.2,
+            fade_start: 0.8,
+            fade_end: 1.0,
+        }
+    }
+}
+
+/// Mirrors the `SsrCamera` uniform block of `shader/ssr.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SsrCamera {
+    pub projection: [[f32; 4]; 4],
+    pub inv_projection: [[f32; 4]; 4],
+    /// Returned for rays that leave the screen or find no hit.
+    pub sky_color: [f32; 4],
+}
+
+/// Screen-space reflections traced against the G-buffer.
+///
+/// Rays are marched in view space (camera looking down -Z) and every step is projected back to
+/// the screen to test against the depth buffer. Hits sample the previous frame's color.
+pub struct SsrPass {
+    pass: ComputePass,
+    point_sampler: Arc<Sampler>,
+    linear_sampler: Arc<Sampler>,
+}
+
+impl SsrPass {
+    pub fn new(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let pass = ComputePass::new(device.clone(), load_ssr(device.clone())?)?;
+        let sampler = |filter| {
+            Sampler::new(
+                device.clone(),
+                SamplerCreateInfo {
+                    mag_filter: filter,
+                    min_filter: filter,
+                    address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                    ..SamplerCreateInfo::default()
+                },
+            )
+            .map_err(ThorusError::from)
+        };
+
+        Ok(Self {
+            point_sampler: sampler(Filter::Nearest)?,
+            linear_sampler: sampler(Filter::Linear)?,
+            pass,
+        })
+    }
+
+    /// Creates an image suitable for [`SsrPass::record`]'s output.
+    pub fn output_image(
+        allocator: Arc<dyn MemoryAllocator>,
+        extent: [u32; 2],
+    ) -> Result<Arc<ImageView>, ThorusError> {
+        let image = Image::new(
+            allocator,
+            ImageCreateInfo {
+                format: Format::R16G16B16A16_SFLOAT,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        ImageView::new_default(image).map_err(ThorusError::from)
+    }
+
+    /// Records the pass. `output` receives the reflected color, with alpha carried over from the
+    /// hit or the sky color; background pixels are cleared to zero.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        depth: Arc<ImageView>,
+        normal: Arc<ImageView>,
+        prev_color: Arc<ImageView>,
+        output: Arc<ImageView>,
+        camera: Subbuffer<SsrCamera>,
+        config: SsrConfig,
+    ) -> Result<(), ThorusError> {
+        let [width, height, _] = output.image().extent();
+        self.pass.bind(
+            builder,
+            [
+                WriteDescriptorSet::image_view_sampler(0, depth, self.point_sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(1, normal, self.point_sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(2, prev_color, self.linear_sampler.clone()),
+                WriteDescriptorSet::image_view(3, output),
+                WriteDescriptorSet::buffer(4, camera),
+            ],
+        )?;
+        self.pass.push_constants(builder, config)?;
+        self.pass.dispatch(
+            builder,
+            [
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::offset_of;
+
+    #[test]
+    fn config_matches_the_push_constant_block() {
+        // `uint max_steps; float step_size, thickness, fade_start, fade_end;` in std430
+        assert_eq!(size_of::<SsrConfig>(), 5 * 4);
+        assert_eq!(offset_of!(SsrConfig, max_steps), 0);
+        assert_eq!(offset_of!(SsrConfig, step_size), 4);
+        assert_eq!(offset_of!(SsrConfig, thickness), 8);
+        assert_eq!(offset_of!(SsrConfig, fade_start), 12);
+        assert_eq!(offset_of!(SsrConfig, fade_end), 16);
+    }
+}
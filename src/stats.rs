@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::Duration;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::PipelineStage;
+use vulkano::{Validated, ValidationError, VulkanError};
+
+/// Counters for one frame, reset at the start of every frame.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RenderStats {
+    pub draw_calls: u64,
+    pub triangle_count: u64,
+    pub vertex_count: u64,
+    pub gpu_time_ns: u64,
+    pub cpu_record_time_ns: u64,
+}
+
+impl RenderStats {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Counts one indexed triangle-list draw.
+    pub fn accumulate_draw(&mut self, index_count: u32, instance_count: u32) {
+        let vertices = index_count as u64 * instance_count as u64;
+        self.draw_calls += 1;
+        self.vertex_count += vertices;
+        self.triangle_count += vertices / 3;
+    }
+
+    pub fn set_cpu_record_time(&mut self, time: Duration) {
+        self.cpu_record_time_ns = time.as_nanos() as u64;
+    }
+
+    /// Takes the GPU time from `query` if its results are available.
+    pub fn update_gpu_time(
+        &mut self,
+        query: &TimestampQuery,
+    ) -> Result<(), Validated<VulkanError>> {
+        if let Some(elapsed) = query.elapsed_ns()? {
+            self.gpu_time_ns = elapsed;
+        }
+        Ok(())
+    }
+
+    pub fn overlay_text(&self) -> String {
+        format!(
+            "draw calls: {}\n\
+             triangles: {}\n\
+             vertices: {}\n\
+             gpu: {:.2} ms\n\
+             cpu record: {:.2} ms",
+            self.draw_calls,
+            self.triangle_count,
+            self.vertex_count,
+            self.gpu_time_ns as f64 / 1e6,
+            self.cpu_record_time_ns as f64 / 1e6,
+        )
+    }
+}
+
+/// A pair of GPU timestamps bracketing the commands recorded between them.
+pub struct TimestampQuery {
+    pool: Arc<QueryPool>,
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+}
+
+impl TimestampQuery {
+    pub fn new(device: Arc<Device>) -> Result<Self, Validated<VulkanError>> {
+        let period = device.physical_device().properties().timestamp_period as f64;
+        let pool = QueryPool::new(
+            device,
+            QueryPoolCreateInfo {
+                query_count: 2,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            },
+        )?;
+        Ok(Self { pool, period })
+    }
+
+    /// Resets the pool and writes the start timestamp; must be recorded outside a render pass.
+    pub fn write_start(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<ValidationError>> {
+        // SAFETY: the pool is only used by this query, and the reset makes both queries
+        // unavailable before the first one is written.
+        unsafe {
+            builder
+                .reset_query_pool(self.pool.clone(), 0..2)?
+                .write_timestamp(self.pool.clone(), 0, PipelineStage::TopOfPipe)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_end(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<ValidationError>> {
+        // SAFETY: `write_start` reset the query earlier in the same command buffer.
+        unsafe {
+            builder.write_timestamp(self.pool.clone(), 1, PipelineStage::BottomOfPipe)?;
+        }
+        Ok(())
+    }
+
+    /// Elapsed GPU time between the two timestamps, `None` until both have been written.
+    pub fn elapsed_ns(&self) -> Result<Option<u64>, Validated<VulkanError>> {
+        let mut timestamps = [0u64; 2];
+        let available = self
+            .pool
+            .get_results(0..2, &mut timestamps, QueryResultFlags::empty())?;
+        Ok(available
+            .then(|| (timestamps[1].saturating_sub(timestamps[0]) as f64 * self.period) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_draws_and_resets_between_frames() {
+        let mut stats = RenderStats::default();
+        stats.accumulate_draw(36, 10);
+        stats.accumulate_draw(6, 1);
+        stats.set_cpu_record_time(Duration::from_micros(1500));
+        assert_eq!(
+            stats,
+            RenderStats {
+                draw_calls: 2,
+                triangle_count: 122,
+                vertex_count: 366,
+                gpu_time_ns: 0,
+                cpu_record_time_ns: 1_500_000,
+            }
+        );
+
+        stats.reset();
+        assert_eq!(stats, RenderStats::default());
+        stats.accumulate_draw(3, 2);
+        assert_eq!(stats.draw_calls, 1);
+        assert_eq!(stats.triangle_count, 2);
+        assert_eq!(stats.vertex_count, 6);
+    }
+
+    #[test]
+    fn overlay_text_has_a_line_per_counter() {
+        let mut stats = RenderStats::default();
+        stats.accumulate_draw(3, 1);
+        stats.gpu_time_ns = 2_500_000;
+        assert_eq!(
+            stats.overlay_text(),
+            "draw calls: 1\ntriangles: 1\nvertices: 3\ngpu: 2.50 ms\ncpu record: 0.00 ms"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use crate::mesh::Mesh;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use tracing::{debug, warn};
+
+#[derive(Debug)]
+pub enum StreamingError {
+    ThreadPool(ThreadPoolBuildError),
+    Thread(io::Error),
+}
+
+impl Display for StreamingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ThreadPool(e) => write!(f, "failed to start chunk loader threads: {e}"),
+            Self::Thread(e) => write!(f, "failed to start chunk loader dispatcher: {e}"),
+        }
+    }
+}
+
+impl Error for StreamingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ThreadPool(e) => Some(e),
+            Self::Thread(e) => Some(e),
+        }
+    }
+}
+
+/// Cell of the streaming grid on the horizontal `xz` plane.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkCoord {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// The chunk `position` lies in.
+    pub fn containing(position: [f32; 3], chunk_size: f32) -> Self {
+        Self {
+            x: (position[0] / chunk_size).floor() as i32,
+            z: (position[2] / chunk_size).floor() as i32,
+        }
+    }
+
+    /// Center of the chunk on the `xz` plane, at height zero.
+    pub fn center(self, chunk_size: f32) -> [f32; 3] {
+        [
+            (self.x as f32 + 0.5) * chunk_size,
+            0.0,
+            (self.z as f32 + 0.5) * chunk_size,
+        ]
+    }
+
+    /// Horizontal distance from `position` to the center of the chunk.
+    fn distance(self, position: [f32; 3], chunk_size: f32) -> f32 {
+        let center = self.center(chunk_size);
+        (center[0] - position[0]).hypot(center[2] - position[2])
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub enum LoadState {
+    #[default]
+    Unloaded,
+    Loading,
+    Loaded(Mesh),
+}
+
+static UNLOADED: LoadState = LoadState::Unloaded;
+
+/// Produces the mesh of a chunk, e.g. by reading it from disk; called on loader threads.
+pub type ChunkLoader = dyn Fn(ChunkCoord) -> io::Result<Mesh> + Send + Sync;
+
+/// Keeps the chunks of a large scene around the camera loaded, loading them on background
+/// threads as the camera approaches and dropping them once it has moved away.
+///
+/// Requests go to the loader threads over a channel and finished meshes come back over
+/// another one, the way [`AsyncShaderCompiler`](crate::shader::AsyncShaderCompiler) compiles
+/// shaders. Chunks whose loader fails are left unloaded and requested again by a later
+/// [`update`](Self::update).
+pub struct MeshStreamer {
+    chunk_size: f32,
+    chunks: HashMap<ChunkCoord, LoadState>,
+    requests: Sender<ChunkCoord>,
+    responses: Receiver<(ChunkCoord, io::Result<Mesh>)>,
+}
+
+impl MeshStreamer {
+    /// Starts `threads` loader threads, or one per CPU if zero, running `loader` for chunks of
+    /// `chunk_size` by `chunk_size` world units.
+    pub fn new(
+        chunk_size: f32,
+        threads: usize,
+        loader: impl Fn(ChunkCoord) -> io::Result<Mesh> + Send + Sync + 'static,
+    ) -> Result<Self, StreamingError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("chunk-loader-{i}"))
+            .build()
+            .map_err(StreamingError::ThreadPool)?;
+        let loader: Arc<ChunkLoader> = Arc::new(loader);
+
+        let (requests, request_receiver) = mpsc::channel();
+        let (response_sender, responses) = mpsc::channel();
+        thread::Builder::new()
+            .name("chunk-loader".to_owned())
+            .spawn(move || dispatch(pool, loader, request_receiver, response_sender))
+            .map_err(StreamingError::Thread)?;
+        Ok(Self {
+            chunk_size,
+            chunks: HashMap::new(),
+            requests,
+            responses,
+        })
+    }
+
+    pub fn chunk_size(&self) -> f32 {
+        self.chunk_size
+    }
+
+    pub fn state(&self, coord: ChunkCoord) -> &LoadState {
+        self.chunks.get(&coord).unwrap_or(&UNLOADED)
+    }
+
+    /// Chunks requested but not loaded yet.
+    pub fn pending(&self) -> usize {
+        self.chunks
+            .values()
+            .filter(|state| matches!(state, LoadState::Loading))
+            .count()
+    }
+
+    /// Takes in the meshes loaded since the last call, requests the unloaded chunks whose
+    /// center is within `load_radius` of `camera_pos` and drops the chunks, loaded or not,
+    /// farther away than `unload_radius`.
+    ///
+    /// `unload_radius` should exceed `load_radius`, so chunks near the edge do not flip
+    /// between loaded and unloaded while the camera moves back and forth.
+    pub fn update(&mut self, camera_pos: [f32; 3], load_radius: f32, unload_radius: f32) {
+        debug_assert!(unload_radius >= load_radius);
+        self.poll();
+
+        let chunk_size = self.chunk_size;
+        self.chunks
+            .retain(|coord, _| coord.distance(camera_pos, chunk_size) <= unload_radius);
+
+        let center = ChunkCoord::containing(camera_pos, chunk_size);
+        let reach = (load_radius / chunk_size).ceil() as i32 + 1;
+        for z in center.z - reach..=center.z + reach {
+            for x in center.x - reach..=center.x + reach {
+                let coord = ChunkCoord::new(x, z);
+                if self.chunks.contains_key(&coord)
+                    || coord.distance(camera_pos, chunk_size) > load_radius
+                {
+                    continue;
+                }
+                // The dispatcher only stops once the streamer, holding the sender, is gone.
+                if self.requests.send(coord).is_ok() {
+                    self.chunks.insert(coord, LoadState::Loading);
+                }
+            }
+        }
+    }
+
+    /// Stores the meshes that finished loading, unless their chunk was dropped meanwhile.
+    fn poll(&mut self) {
+        for (coord, mesh) in self.responses.try_iter() {
+            let Some(state) = self.chunks.get_mut(&coord) else {
+                continue;
+            };
+            match mesh {
+                Ok(mesh) => {
+                    debug!("loaded chunk {coord:?}");
+                    *state = LoadState::Loaded(mesh);
+                }
+                Err(e) => {
+                    warn!("failed to load chunk {coord:?}: {e}");
+                    self.chunks.remove(&coord);
+                }
+            }
+        }
+    }
+
+    pub fn loaded(&self) -> impl Iterator<Item = (ChunkCoord, &Mesh)> {
+        self.chunks
+            .iter()
+            .filter_map(|(&coord, state)| match state {
+                LoadState::Loaded(mesh) => Some((coord, mesh)),
+                _ => None,
+            })
+    }
+
+    /// Meshes of every loaded chunk, in no particular order.
+    pub fn visible_meshes(&self) -> impl Iterator<Item = &Mesh> {
+        self.loaded().map(|(_, mesh)| mesh)
+    }
+}
+
+fn dispatch(
+    pool: ThreadPool,
+    loader: Arc<ChunkLoader>,
+    requests: Receiver<ChunkCoord>,
+    responses: Sender<(ChunkCoord, io::Result<Mesh>)>,
+) {
+    for coord in requests {
+        let loader = loader.clone();
+        let responses = responses.clone();
+        pool.spawn(move || {
+            // The streamer may have been dropped while this was running.
+            let _ = responses.send((coord, loader(coord)));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn chunk_goes_from_unloaded_through_loading_to_loaded() {
+        // The loader waits for the test, so the chunk is observably loading in between.
+        let (release, gate) = mpsc::channel::<()>();
+        let gate = Mutex::new(gate);
+        let mut streamer = MeshStreamer::new(10.0, 1, move |_| {
+            gate.lock().unwrap().recv().unwrap();
+            Ok(Mesh::new(Vec::new(), Vec::new()))
+        })
+        .unwrap();
+        let coord = ChunkCoord::new(0, 0);
+        let camera_pos = coord.center(streamer.chunk_size());
+        assert!(matches!(streamer.state(coord), LoadState::Unloaded));
+
+        // Only the chunk under the camera is within the load radius.
+        streamer.update(camera_pos, 1.0, 2.0);
+        assert!(matches!(streamer.state(coord), LoadState::Loading));
+        assert_eq!(streamer.pending(), 1);
+        assert_eq!(streamer.visible_meshes().count(), 0);
+
+        release.send(()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !matches!(streamer.state(coord), LoadState::Loaded(_)) {
+            assert!(Instant::now() < deadline, "chunk never finished loading");
+            thread::sleep(Duration::from_millis(1));
+            streamer.update(camera_pos, 1.0, 2.0);
+        }
+        assert_eq!(streamer.pending(), 0);
+        assert_eq!(streamer.visible_meshes().count(), 1);
+    }
+}
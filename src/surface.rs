@@ -0,0 +1,286 @@
+#[cfg(target_os = "linux")]
+pub use self::linux::*;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::error::{Context, ThorusError};
+    use std::fs::File;
+    use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+    use std::sync::Arc;
+    use tracing::debug;
+    use vulkano::command_buffer::{PrimaryCommandBufferAbstract, SemaphoreSubmitInfo, SubmitInfo};
+    use vulkano::device::{Device, DeviceExtensions, Queue};
+    use vulkano::format::Format;
+    use vulkano::image::sys::RawImage;
+    use vulkano::image::view::ImageView;
+    use vulkano::image::{Image, ImageCreateInfo, ImageTiling, ImageUsage, SubresourceLayout};
+    use vulkano::memory::{
+        DedicatedAllocation, DeviceMemory, ExternalMemoryHandleType, ExternalMemoryHandleTypes,
+        MemoryAllocateInfo, MemoryImportInfo, ResourceMemory,
+    };
+    use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+    use vulkano::sync::fence::{Fence, FenceCreateInfo};
+    use vulkano::sync::semaphore::{
+        ExternalSemaphoreHandleType, ExternalSemaphoreHandleTypes, Semaphore, SemaphoreCreateInfo,
+    };
+
+    const fn fourcc(code: &[u8; 4]) -> u32 {
+        u32::from_le_bytes(*code)
+    }
+
+    /// Vulkan format with the same memory layout as a DRM fourcc code. Only 32-bit RGB formats
+    /// are supported.
+    fn drm_format(code: u32) -> Option<Format> {
+        const XR24: u32 = fourcc(b"XR24");
+        const AR24: u32 = fourcc(b"AR24");
+        const XB24: u32 = fourcc(b"XB24");
+        const AB24: u32 = fourcc(b"AB24");
+        const XR30: u32 = fourcc(b"XR30");
+        const AR30: u32 = fourcc(b"AR30");
+        const XB30: u32 = fourcc(b"XB30");
+        const AB30: u32 = fourcc(b"AB30");
+        match code {
+            XR24 | AR24 => Some(Format::B8G8R8A8_UNORM),
+            XB24 | AB24 => Some(Format::R8G8B8A8_UNORM),
+            XR30 | AR30 => Some(Format::A2R10G10B10_UNORM_PACK32),
+            XB30 | AB30 => Some(Format::A2B10G10R10_UNORM_PACK32),
+            _ => None,
+        }
+    }
+
+    /// Color image backed by memory imported from another process, with a semaphore that is
+    /// signalled whenever rendering into it completes.
+    pub struct ExternalImageTarget {
+        image: Arc<Image>,
+        semaphore: Arc<Semaphore>,
+        signal_file: File,
+    }
+
+    impl ExternalImageTarget {
+        const BYTES_PER_PIXEL: u64 = 4;
+
+        /// Extensions `from_dmabuf` needs on the device in addition to Vulkan 1.1.
+        pub fn required_extensions() -> DeviceExtensions {
+            DeviceExtensions {
+                khr_external_memory_fd: true,
+                ext_external_memory_dma_buf: true,
+                ext_image_drm_format_modifier: true,
+                khr_external_semaphore_fd: true,
+                ..DeviceExtensions::empty()
+            }
+        }
+
+        /// Imports the single-plane DMA-buf `fd` as a `width` by `height` image.
+        ///
+        /// `fourcc` is the DRM format code of the buffer and `modifier` its DRM format
+        /// modifier. The plane is expected to start at offset 0 with tightly packed rows. The
+        /// device takes ownership of `fd`.
+        pub fn from_dmabuf(
+            device: Arc<Device>,
+            fd: OwnedFd,
+            fourcc: u32,
+            width: u32,
+            height: u32,
+            modifier: u64,
+        ) -> Result<Self, ThorusError> {
+            let format = drm_format(fourcc).ok_or(ThorusError::Missing("format for DRM fourcc"))?;
+            let raw_image = RawImage::new(
+                device.clone(),
+                ImageCreateInfo {
+                    format,
+                    extent: [width, height, 1],
+                    tiling: ImageTiling::DrmFormatModifier,
+                    usage: ImageUsage::COLOR_ATTACHMENT,
+                    drm_format_modifiers: vec![modifier],
+                    drm_format_modifier_plane_layouts: vec![SubresourceLayout {
+                        offset: 0,
+                        size: 0,
+                        row_pitch: width as u64 * Self::BYTES_PER_PIXEL,
+                        array_pitch: None,
+                        depth_pitch: None,
+                    }],
+                    external_memory_handle_types: ExternalMemoryHandleTypes::DMA_BUF,
+                    ..ImageCreateInfo::default()
+                },
+            )
+            .context("failed to create image for DMA-buf")?;
+
+            let file = File::from(fd);
+            // Safety: `file` is an open DMA-buf; the duplicate is consumed by the query.
+            let fd_properties = unsafe {
+                device.memory_fd_properties(ExternalMemoryHandleType::DmaBuf, file.try_clone()?)
+            }
+            .context("failed to query DMA-buf memory properties")?;
+            let requirements = raw_image.memory_requirements()[0];
+            let memory_type_index =
+                (requirements.memory_type_bits & fd_properties.memory_type_bits).trailing_zeros();
+            if memory_type_index == u32::BITS {
+                return Err(ThorusError::Missing("memory type for DMA-buf"));
+            }
+
+            // Safety: `file` is an open DMA-buf that is not used after being handed over.
+            let memory = unsafe {
+                DeviceMemory::import(
+                    device.clone(),
+                    MemoryAllocateInfo {
+                        allocation_size: requirements.layout.size(),
+                        memory_type_index,
+                        dedicated_allocation: Some(DedicatedAllocation::Image(&raw_image)),
+                        ..MemoryAllocateInfo::default()
+                    },
+                    MemoryImportInfo::Fd {
+                        handle_type: ExternalMemoryHandleType::DmaBuf,
+                        file,
+                    },
+                )
+            }
+            .context("failed to import DMA-buf")?;
+            let image = raw_image
+                .bind_memory([ResourceMemory::new_dedicated(memory)])
+                .map_err(|(e, _, _)| e)?;
+            let image = Arc::new(image);
+            debug!("imported DMA-buf image: {image:?}");
+
+            let semaphore = Arc::new(Semaphore::new(
+                device,
+                SemaphoreCreateInfo {
+                    export_handle_types: ExternalSemaphoreHandleTypes::OPAQUE_FD,
+                    ..SemaphoreCreateInfo::default()
+                },
+            )?);
+            let signal_file = semaphore
+                .export_fd(ExternalSemaphoreHandleType::OpaqueFd)
+                .context("failed to export semaphore")?;
+
+            Ok(Self {
+                image,
+                semaphore,
+                signal_file,
+            })
+        }
+
+        pub fn image(&self) -> &Arc<Image> {
+            &self.image
+        }
+
+        /// File descriptor of the semaphore signalled by [`submit`](Self::submit), to be handed
+        /// to the process that consumes the image. It stays owned by the target.
+        pub fn signal_fd(&self) -> RawFd {
+            self.signal_file.as_raw_fd()
+        }
+
+        /// Creates a framebuffer with the image as its only attachment.
+        pub fn framebuffer(
+            &self,
+            render_pass: Arc<RenderPass>,
+        ) -> Result<Arc<Framebuffer>, ThorusError> {
+            Ok(Framebuffer::new(
+                render_pass,
+                FramebufferCreateInfo {
+                    attachments: vec![ImageView::new_default(self.image.clone())?],
+                    ..FramebufferCreateInfo::default()
+                },
+            )?)
+        }
+
+        /// Submits `command_buffer`, which renders into the image, and signals the external
+        /// semaphore once it completes. The returned fence is signalled at the same time.
+        ///
+        /// # Safety
+        ///
+        /// - `command_buffer` must be valid to submit to `queue`, and must not be in use
+        ///   elsewhere unless it allows simultaneous use.
+        /// - The consumer must have waited on the previous signal before this is called again.
+        pub unsafe fn submit(
+            &self,
+            queue: &Arc<Queue>,
+            command_buffer: Arc<dyn PrimaryCommandBufferAbstract>,
+        ) -> Result<Arc<Fence>, ThorusError> {
+            let fence = Arc::new(Fence::new(
+                queue.device().clone(),
+                FenceCreateInfo::default(),
+            )?);
+            queue.with(|mut queue| {
+                queue.submit_unchecked(
+                    [SubmitInfo {
+                        command_buffers: vec![command_buffer],
+                        signal_semaphores: vec![SemaphoreSubmitInfo::semaphore(
+                            self.semaphore.clone(),
+                        )],
+                        ..SubmitInfo::default()
+                    }],
+                    Some(fence.clone()),
+                )
+            })?;
+            Ok(fence)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::testing::TestContext;
+        use vulkano::device::Features;
+        use vulkano::memory::MemoryPropertyFlags;
+
+        #[test]
+        fn drm_fourccs_map_to_32_bit_formats() {
+            assert_eq!(drm_format(fourcc(b"XR24")), Some(Format::B8G8R8A8_UNORM));
+            assert_eq!(drm_format(fourcc(b"AB24")), Some(Format::R8G8B8A8_UNORM));
+            assert_eq!(
+                drm_format(fourcc(b"AR30")),
+                Some(Format::A2R10G10B10_UNORM_PACK32)
+            );
+            // NV12 has two planes
+            assert_eq!(drm_format(fourcc(b"NV12")), None);
+        }
+
+        #[test]
+        #[ignore = "needs a Vulkan device with DMA-buf support"]
+        fn target_constructs_from_an_exported_dmabuf() {
+            const DRM_FORMAT_MOD_LINEAR: u64 = 0;
+            const SIZE: u32 = 64;
+
+            let context = TestContext::with_extensions(
+                ExternalImageTarget::required_extensions(),
+                Features::empty(),
+            );
+            let device = context.queue.device().clone();
+            let memory_type_index = device
+                .physical_device()
+                .memory_properties()
+                .memory_types
+                .iter()
+                .position(|memory_type| {
+                    memory_type
+                        .property_flags
+                        .intersects(MemoryPropertyFlags::DEVICE_LOCAL)
+                })
+                .unwrap() as u32;
+            let memory = DeviceMemory::allocate(
+                device.clone(),
+                MemoryAllocateInfo {
+                    allocation_size: (SIZE * SIZE) as u64 * ExternalImageTarget::BYTES_PER_PIXEL,
+                    memory_type_index,
+                    export_handle_types: ExternalMemoryHandleTypes::DMA_BUF,
+                    ..MemoryAllocateInfo::default()
+                },
+            )
+            .unwrap();
+            let dmabuf = memory.export_fd(ExternalMemoryHandleType::DmaBuf).unwrap();
+
+            let target = ExternalImageTarget::from_dmabuf(
+                device,
+                dmabuf.into(),
+                fourcc(b"AB24"),
+                SIZE,
+                SIZE,
+                DRM_FORMAT_MOD_LINEAR,
+            )
+            .unwrap();
+            assert_eq!(target.image().extent(), [SIZE, SIZE, 1]);
+            assert_eq!(target.image().format(), Format::R8G8B8A8_UNORM);
+            assert!(target.signal_fd() >= 0);
+        }
+    }
+}
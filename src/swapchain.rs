@@ -0,0 +1,370 @@
+use crate::error::{Context, ThorusError};
+use std::sync::Arc;
+use tracing::{debug, instrument, warn};
+use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageAspects, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+use vulkano::swapchain::{
+    PresentFuture, PresentMode, Surface, SurfaceCapabilities, Swapchain, SwapchainAcquireFuture,
+    SwapchainCreateInfo, SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::GpuFuture;
+use vulkano::{swapchain, Validated, VulkanError};
+use winit::window::Window;
+
+/// Consecutive [`SwapchainManager::recover_surface`] calls without a successful present in
+/// between before giving up.
+pub const MAX_SURFACE_RECOVERIES: u32 = 3;
+
+/// How many images a swapchain is created with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SwapchainConfig {
+    pub present_mode: PresentMode,
+    /// Upper bound on the image count below the surface maximum; never goes below the
+    /// surface minimum.
+    pub max_frames_in_flight: u32,
+}
+
+impl SwapchainConfig {
+    /// The image count for `present_mode` within the limits of `caps`.
+    ///
+    /// FIFO and mailbox get one image more than the minimum, so the application never waits
+    /// for the presentation engine to release an image; mailbox needs it to replace queued
+    /// images while one is on screen. Immediate presentation never holds on to images and
+    /// gets by with the minimum.
+    pub fn optimal_image_count(caps: &SurfaceCapabilities, present_mode: PresentMode) -> u32 {
+        image_count(caps.min_image_count, caps.max_image_count, present_mode)
+    }
+
+    /// [`optimal_image_count`](Self::optimal_image_count), limited by
+    /// [`max_frames_in_flight`](Self::max_frames_in_flight).
+    pub fn min_image_count(&self, caps: &SurfaceCapabilities) -> u32 {
+        Self::optimal_image_count(caps, self.present_mode)
+            .min(self.max_frames_in_flight)
+            .max(caps.min_image_count)
+    }
+}
+
+/// [`SwapchainConfig::optimal_image_count`] on the two fields of [`SurfaceCapabilities`] it
+/// reads, which cannot be constructed outside of vulkano.
+fn image_count(
+    min_image_count: u32,
+    max_image_count: Option<u32>,
+    present_mode: PresentMode,
+) -> u32 {
+    let count = match present_mode {
+        PresentMode::Immediate => min_image_count,
+        _ => min_image_count + 1,
+    };
+    count.min(max_image_count.unwrap_or(u32::MAX))
+}
+
+/// Outcome of [`SwapchainManager::acquire_next_image`].
+pub enum AcquireResult {
+    Ok(u32, SwapchainAcquireFuture),
+    /// The image can still be presented, but the swapchain should be recreated afterwards.
+    Suboptimal(u32, SwapchainAcquireFuture),
+    /// No image was acquired; the swapchain must be recreated first.
+    OutOfDate,
+    /// No image was acquired; the surface must be recreated, see [`PresentResult`].
+    SurfaceLost,
+}
+
+/// Outcome of [`SwapchainManager::present`].
+///
+/// `OutOfDate` and `SurfaceLost` differ in what is still usable. An out-of-date swapchain
+/// sits on a surface that is fine but changed, typically in size, so recreating the swapchain
+/// on the same surface is enough. A lost surface is gone altogether, for example because the
+/// native window was destroyed behind our back or the display server restarted; no swapchain
+/// can be created on it anymore and a new surface has to be created from the window first.
+pub enum PresentResult<F: GpuFuture> {
+    /// The image was queued for presentation; the future signals when the GPU is done.
+    Ok(FenceSignalFuture<F>),
+    /// Like `Ok`, but the swapchain no longer matches the surface exactly and should be
+    /// recreated. Reported when the image was acquired as suboptimal, since the present
+    /// future does not expose the result of the present itself.
+    Suboptimal(FenceSignalFuture<F>),
+    OutOfDate,
+    SurfaceLost,
+}
+
+/// Records fresh command buffers for the given framebuffers.
+pub type RebuildCommandBuffers<'a> =
+    dyn FnMut(&[Arc<Framebuffer>]) -> Result<Vec<Arc<PrimaryAutoCommandBuffer>>, ThorusError> + 'a;
+
+/// Owns a swapchain together with everything that has to be rebuilt when it is recreated.
+pub struct SwapchainManager {
+    swapchain: Arc<Swapchain>,
+    images: Vec<Arc<Image>>,
+    render_pass: Arc<RenderPass>,
+    allocator: Arc<dyn MemoryAllocator>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    suboptimal: bool,
+    surface_recoveries: u32,
+}
+
+impl SwapchainManager {
+    /// Creates one framebuffer per swapchain image; command buffers start out empty.
+    ///
+    /// See [`framebuffers`] for how the attachments of `render_pass` are filled.
+    pub fn new(
+        swapchain: Arc<Swapchain>,
+        images: Vec<Arc<Image>>,
+        render_pass: Arc<RenderPass>,
+        allocator: Arc<dyn MemoryAllocator>,
+    ) -> Result<Self, ThorusError> {
+        let framebuffers = framebuffers(&images, &render_pass, &allocator)?;
+        Ok(Self {
+            swapchain,
+            images,
+            render_pass,
+            allocator,
+            framebuffers,
+            command_buffers: vec![],
+            suboptimal: false,
+            surface_recoveries: 0,
+        })
+    }
+
+    pub fn swapchain(&self) -> &Arc<Swapchain> {
+        &self.swapchain
+    }
+
+    pub fn images(&self) -> &[Arc<Image>] {
+        &self.images
+    }
+
+    pub fn render_pass(&self) -> &Arc<RenderPass> {
+        &self.render_pass
+    }
+
+    pub fn framebuffers(&self) -> &[Arc<Framebuffer>] {
+        &self.framebuffers
+    }
+
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn command_buffer(&self, image_index: u32) -> &Arc<PrimaryAutoCommandBuffer> {
+        &self.command_buffers[image_index as usize]
+    }
+
+    /// Replaces the command buffers; one is expected per framebuffer.
+    pub fn set_command_buffers(&mut self, command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>) {
+        debug_assert_eq!(command_buffers.len(), self.framebuffers.len());
+        self.command_buffers = command_buffers;
+    }
+
+    /// Recreates the swapchain with a new extent and rebuilds the framebuffers.
+    ///
+    /// When `rebuild` is given it is called with the new framebuffers and its result replaces
+    /// the command buffers; otherwise the previous command buffers are kept. Errors of
+    /// `rebuild` are passed through unchanged.
+    pub fn recreate(
+        &mut self,
+        new_size: [u32; 2],
+        rebuild: Option<&mut RebuildCommandBuffers<'_>>,
+    ) -> Result<(), ThorusError> {
+        let (swapchain, images) = self
+            .swapchain
+            .recreate(SwapchainCreateInfo {
+                image_extent: new_size,
+                ..self.swapchain.create_info()
+            })
+            .map_err(ThorusError::Swapchain)?;
+        debug!("recreated swapchain: {swapchain:?}");
+        self.framebuffers = framebuffers(&images, &self.render_pass, &self.allocator)?;
+        self.swapchain = swapchain;
+        self.images = images;
+        if let Some(rebuild) = rebuild {
+            let command_buffers = rebuild(&self.framebuffers)?;
+            self.set_command_buffers(command_buffers);
+        }
+        Ok(())
+    }
+
+    pub fn acquire_next_image(&mut self) -> Result<AcquireResult, Validated<VulkanError>> {
+        let (image_index, suboptimal, future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(acquired) => acquired,
+                Err(Validated::Error(VulkanError::OutOfDate)) => {
+                    return Ok(AcquireResult::OutOfDate)
+                }
+                Err(Validated::Error(VulkanError::SurfaceLost)) => {
+                    return Ok(AcquireResult::SurfaceLost)
+                }
+                Err(e) => return Err(e),
+            };
+        self.suboptimal = suboptimal;
+        Ok(if suboptimal {
+            AcquireResult::Suboptimal(image_index, future)
+        } else {
+            AcquireResult::Ok(image_index, future)
+        })
+    }
+
+    /// Presents the acquired image `image_index` after `future` and flushes.
+    pub fn present<F: GpuFuture>(
+        &mut self,
+        future: F,
+        queue: Arc<Queue>,
+        image_index: u32,
+    ) -> Result<PresentResult<PresentFuture<F>>, Validated<VulkanError>> {
+        let presented = future
+            .then_swapchain_present(
+                queue,
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_index),
+            )
+            .then_signal_fence_and_flush();
+        match presented {
+            Ok(future) => {
+                self.surface_recoveries = 0;
+                Ok(if self.suboptimal {
+                    PresentResult::Suboptimal(future)
+                } else {
+                    PresentResult::Ok(future)
+                })
+            }
+            Err(Validated::Error(VulkanError::OutOfDate)) => Ok(PresentResult::OutOfDate),
+            Err(Validated::Error(VulkanError::SurfaceLost)) => Ok(PresentResult::SurfaceLost),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replaces a lost surface with a new one created from `window`, recreates the swapchain
+    /// on it with the current window size and rebuilds framebuffers and command buffers.
+    ///
+    /// Fails once called more than [`MAX_SURFACE_RECOVERIES`] times without a successful
+    /// [`present`](Self::present) in between, so a surface that cannot be recovered does not
+    /// loop forever.
+    pub fn recover_surface(
+        &mut self,
+        window: Arc<Window>,
+        rebuild: &mut RebuildCommandBuffers<'_>,
+    ) -> Result<(), ThorusError> {
+        self.surface_recoveries += 1;
+        if self.surface_recoveries > MAX_SURFACE_RECOVERIES {
+            return Err(
+                ThorusError::Swapchain(Validated::Error(VulkanError::SurfaceLost)).context(
+                    &format!("surface still lost after {MAX_SURFACE_RECOVERIES} recoveries"),
+                ),
+            );
+        }
+        warn!(
+            "surface lost, recreating it (attempt {}/{MAX_SURFACE_RECOVERIES})",
+            self.surface_recoveries
+        );
+
+        let device = self.swapchain.device().clone();
+        let image_extent = window.inner_size().into();
+        let surface = Surface::from_window(device.instance().clone(), window)
+            .map_err(ThorusError::Swapchain)
+            .context("failed to recreate surface")?;
+        let (swapchain, images) = Swapchain::new(
+            device,
+            surface,
+            SwapchainCreateInfo {
+                image_extent,
+                ..self.swapchain.create_info()
+            },
+        )
+        .map_err(ThorusError::Swapchain)?;
+        debug!("swapchain on recreated surface: {swapchain:?}");
+        self.framebuffers = framebuffers(&images, &self.render_pass, &self.allocator)?;
+        self.swapchain = swapchain;
+        self.images = images;
+        self.suboptimal = false;
+        let command_buffers = rebuild(&self.framebuffers)?;
+        self.set_command_buffers(command_buffers);
+        Ok(())
+    }
+}
+
+/// Creates one framebuffer per image with the image as attachment 0.
+///
+/// Any further attachments of `render_pass`, such as a multisampled color target or a depth
+/// buffer, get transient images of the same extent allocated from `allocator`.
+#[instrument(skip_all, fields(images = images.len()))]
+pub fn framebuffers(
+    images: &[Arc<Image>],
+    render_pass: &Arc<RenderPass>,
+    allocator: &Arc<dyn MemoryAllocator>,
+) -> Result<Vec<Arc<Framebuffer>>, ThorusError> {
+    images
+        .iter()
+        .map(|image| {
+            let view = ImageView::new_default(image.clone())?;
+            debug!("image view: {view:?}");
+            let mut attachments = vec![view];
+            for description in &render_pass.attachments()[1..] {
+                let usage = if description
+                    .format
+                    .aspects()
+                    .intersects(ImageAspects::DEPTH | ImageAspects::STENCIL)
+                {
+                    ImageUsage::DEPTH_STENCIL_ATTACHMENT
+                } else {
+                    ImageUsage::COLOR_ATTACHMENT
+                };
+                let transient = Image::new(
+                    allocator.clone(),
+                    ImageCreateInfo {
+                        format: description.format,
+                        samples: description.samples,
+                        extent: image.extent(),
+                        usage: usage | ImageUsage::TRANSIENT_ATTACHMENT,
+                        ..ImageCreateInfo::default()
+                    },
+                    AllocationCreateInfo {
+                        memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                        ..AllocationCreateInfo::default()
+                    },
+                )?;
+                attachments.push(ImageView::new_default(transient)?);
+            }
+            Ok(Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments,
+                    ..FramebufferCreateInfo::default()
+                },
+            )?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_and_mailbox_get_one_image_above_the_minimum() {
+        for present_mode in [PresentMode::Fifo, PresentMode::Mailbox] {
+            assert_eq!(image_count(2, None, present_mode), 3);
+            assert_eq!(image_count(2, Some(8), present_mode), 3);
+        }
+    }
+
+    #[test]
+    fn immediate_gets_the_minimum() {
+        assert_eq!(image_count(2, None, PresentMode::Immediate), 2);
+        assert_eq!(image_count(2, Some(8), PresentMode::Immediate), 2);
+    }
+
+    #[test]
+    fn every_mode_is_clamped_to_the_maximum() {
+        for present_mode in [
+            PresentMode::Fifo,
+            PresentMode::Mailbox,
+            PresentMode::Immediate,
+        ] {
+            assert_eq!(image_count(3, Some(3), present_mode), 3);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::buffer::UPLOAD_MEMORY;
+use crate::mesh::Mesh;
+use crate::vertex::Vertex3D;
+use std::sync::Arc;
+use vulkano::buffer::{
+    AllocateBufferError, Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer,
+};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::image::view::ImageView;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::{Validated, ValidationError};
+
+/// Mirrors the push constant block shared by `shader/terrain.vert` and `shader/terrain.frag`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct TerrainParams {
+    pub view_proj: [[f32; 4]; 4],
+    pub height_scale: f32,
+    pub world_size: f32,
+    /// How many times the splat layers repeat across the terrain.
+    pub splat_tiling: f32,
+}
+
+/// Flat grid displaced in the vertex shader by a heightmap.
+///
+/// The heightmap is bound at set 0, binding 0 and the splat layers (grass, rock, snow, sand)
+/// as a 2D array at set 0, binding 1.
+pub struct Terrain {
+    vertex_buffer: Subbuffer<[Vertex3D]>,
+    index_buffer: Subbuffer<[u32]>,
+    heightmap: Arc<ImageView>,
+    grid_resolution: u32,
+    world_size: f32,
+}
+
+impl Terrain {
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        grid_resolution: u32,
+        world_size: f32,
+        heightmap: Arc<ImageView>,
+    ) -> Result<Self, Validated<AllocateBufferError>> {
+        let Mesh { vertices, indices } = grid(grid_resolution, world_size);
+        let vertex_buffer = Buffer::from_iter(
+            allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            vertices,
+        )?;
+        let index_buffer = Buffer::from_iter(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            indices,
+        )?;
+
+        Ok(Self {
+            vertex_buffer,
+            index_buffer,
+            heightmap,
+            grid_resolution,
+            world_size,
+        })
+    }
+
+    pub fn heightmap(&self) -> &Arc<ImageView> {
+        &self.heightmap
+    }
+
+    pub fn grid_resolution(&self) -> u32 {
+        self.grid_resolution
+    }
+
+    pub fn world_size(&self) -> f32 {
+        self.world_size
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_buffer.len() as u32
+    }
+
+    /// Binds the grid buffers and records an indexed draw; the pipeline and its descriptor set
+    /// must already be bound.
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<ValidationError>> {
+        builder
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())?
+            .bind_index_buffer(self.index_buffer.clone())?
+            .draw_indexed(self.index_count(), 1, 0, 0, 0)?;
+        Ok(())
+    }
+}
+
+/// Builds a `resolution`×`resolution` vertex grid in the XZ plane, centered on the origin,
+/// with UVs spanning `[0, 1]`.
+pub fn grid(resolution: u32, world_size: f32) -> Mesh {
+    assert!(resolution >= 2, "terrain grid needs at least 2×2 vertices");
+    let step = 1.0 / (resolution - 1) as f32;
+    let vertices = (0..resolution)
+        .flat_map(|z| (0..resolution).map(move |x| [x as f32 * step, z as f32 * step]))
+        .map(|uv| Vertex3D {
+            position: [(uv[0] - 0.5) * world_size, 0.0, (uv[1] - 0.5) * world_size],
+            normal: [0.0, 1.0, 0.0],
+            uv,
+            tangent: [1.0, 0.0, 0.0, 1.0],
+        })
+        .collect();
+    let indices = (0..resolution - 1)
+        .flat_map(|z| (0..resolution - 1).map(move |x| z * resolution + x))
+        .flat_map(|i| {
+            let below = i + resolution;
+            [i, below, i + 1, i + 1, below, below + 1]
+        })
+        .collect();
+    Mesh::new(vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_of_64_by_64_vertices_has_two_triangles_per_cell() {
+        let grid = grid(64, 100.0);
+        assert_eq!(grid.vertices.len(), 64 * 64);
+        assert_eq!(grid.indices.len(), 63 * 63 * 6);
+        assert!(grid.indices.iter().all(|&index| index < 64 * 64));
+    }
+
+    #[test]
+    fn grid_spans_the_world_size_and_unit_uvs() {
+        let grid = grid(3, 10.0);
+        assert_eq!(grid.vertices[0].position, [-5.0, 0.0, -5.0]);
+        assert_eq!(grid.vertices[0].uv, [0.0, 0.0]);
+        assert_eq!(grid.vertices[8].position, [5.0, 0.0, 5.0]);
+        assert_eq!(grid.vertices[8].uv, [1.0, 1.0]);
+        // the first triangle faces up: the Y component of `(b - a) × (c - a)` is positive
+        let [a, b, c] = [0, 1, 2].map(|i| grid.vertices[grid.indices[i] as usize].position);
+        let (ab, ac) = ([b[0] - a[0], b[2] - a[2]], [c[0] - a[0], c[2] - a[2]]);
+        assert!(ab[1] * ac[0] - ab[0] * ac[1] > 0.0);
+    }
+}
@@ -0,0 +1,118 @@
+//! Setup shared by unit tests that need a Vulkan device.
+//!
+//! Such tests are `#[ignore]`d because most build machines have no Vulkan driver; run them with
+//! `cargo test -- --ignored` on a machine that has one, e.g. with lavapipe and `CI` set.
+
+use crate::device::{select_physical_device, FeatureSet};
+use crate::instance::InstanceBuilder;
+use std::sync::Arc;
+use vulkano::command_buffer::allocator::{
+    StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+    PrimaryCommandBufferAbstract,
+};
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo,
+};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::sync::GpuFuture;
+use vulkano::VulkanLibrary;
+
+/// A device with one graphics queue, reachable through `queue.device()`.
+pub(crate) struct TestContext {
+    pub queue: Arc<Queue>,
+    pub memory_allocator: Arc<StandardMemoryAllocator>,
+    pub command_buffer_allocator: StandardCommandBufferAllocator,
+}
+
+impl TestContext {
+    pub fn new() -> Self {
+        Self::with_extensions(DeviceExtensions::empty(), Features::empty())
+    }
+
+    /// Creates a device with `extensions` and `features` on top of what
+    /// [`FeatureSet::Minimum`] enables; panics if there is none.
+    pub fn with_extensions(extensions: DeviceExtensions, features: Features) -> Self {
+        let library = VulkanLibrary::new().expect("no Vulkan library");
+        let instance = InstanceBuilder::new(library)
+            .build()
+            .expect("failed to create instance");
+        let (physical_device, queue_family_index) =
+            select_physical_device(&instance, None, &extensions, FeatureSet::Minimum)
+                .expect("no suitable physical device");
+        let (optional_extensions, optional_features, _) =
+            FeatureSet::Minimum.device_setup(&physical_device);
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..QueueCreateInfo::default()
+                }],
+                enabled_extensions: extensions
+                    .union(&optional_extensions)
+                    .union(&InstanceBuilder::device_extensions(&physical_device)),
+                enabled_features: features.union(&optional_features),
+                ..DeviceCreateInfo::default()
+            },
+        )
+        .expect("failed to create device");
+        let queue = queues.next().unwrap();
+        Self {
+            memory_allocator: Arc::new(StandardMemoryAllocator::new_default(device.clone())),
+            command_buffer_allocator: StandardCommandBufferAllocator::new(
+                device.clone(),
+                StandardCommandBufferAllocatorCreateInfo::default(),
+            ),
+            queue,
+        }
+    }
+
+    pub fn command_buffer(&self) -> AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap()
+    }
+
+    /// Executes `builder` and waits for it to finish.
+    pub fn submit(&self, builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .build()
+            .unwrap()
+            .execute(self.queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+    }
+}
+
+/// Pseudo-random numbers from xorshift32, which repeats none within its period; `seed` must
+/// not be zero.
+pub(crate) fn xorshift32(seed: u32) -> impl FnMut() -> u32 {
+    let mut state = seed;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    }
+}
+
+/// Pseudo-random floats in `0.0..1.0` from [`xorshift32`].
+pub(crate) fn random_floats(seed: u32) -> impl FnMut() -> f32 {
+    let mut next = xorshift32(seed);
+    move || (next() >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Pseudo-random floats in `-1.0..1.0` from [`xorshift32`].
+pub(crate) fn random_signed_floats(seed: u32) -> impl FnMut() -> f32 {
+    let mut next = xorshift32(seed);
+    move || (next() >> 8) as f32 / (1 << 23) as f32 - 1.0
+}
@@ -0,0 +1,393 @@
+use crate::error::ThorusError;
+use crate::texture::upload_image;
+use crate::ui::Rect;
+use ab_glyph::{point, Font, FontArc, GlyphId, PxScale, ScaleFont};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::command_buffer::allocator::{
+    StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
+};
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageCreateInfo, ImageType, ImageUsage};
+use vulkano::memory::allocator::MemoryAllocator;
+
+/// Width and height of the atlas of a new [`DynamicGlyphCache`].
+pub const INITIAL_ATLAS_SIZE: u32 = 512;
+
+/// Pixel size glyphs are rendered at; the distance field scales them to any font size.
+pub const SDF_GLYPH_SIZE: f32 = 32.0;
+
+/// Pixels of the rendered glyph the distance field extends over on each side of the outline.
+pub const SDF_SPREAD: u32 = 4;
+
+/// Empty texels between glyphs, keeping filtering from bleeding into neighbours.
+const GLYPH_GAP: u32 = 1;
+
+/// Quad of one glyph of text laid out by [`DynamicGlyphCache::prepare_text`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GlyphQuad {
+    /// Left, top, width and height in pixels, relative to the start of the baseline.
+    pub rect: [f32; 4],
+    /// Left, top, width and height in atlas texture coordinates.
+    pub uv_rect: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CachedGlyph {
+    /// Region of the atlas in texels, empty for glyphs without an outline such as spaces.
+    rect: Rect,
+    /// Top left corner of `rect` relative to the pen position on the baseline, in pixels of
+    /// [`SDF_GLYPH_SIZE`].
+    offset: [f32; 2],
+}
+
+/// Row-by-row packer placing glyphs left to right on shelves as high as their tallest glyph.
+#[derive(Clone, Copy, Default, Debug)]
+struct Shelf {
+    x: u32,
+    y: u32,
+    height: u32,
+}
+
+impl Shelf {
+    /// Top left corner of a free `extent` region of an atlas `size` texels wide and high, if
+    /// it has one.
+    fn allocate(&mut self, extent: [u32; 2], size: u32) -> Option<[u32; 2]> {
+        let [width, height] = extent;
+        if self.x + width > size {
+            *self = Self {
+                x: 0,
+                y: self.y + self.height,
+                height: 0,
+            };
+        }
+        if self.x + width > size || self.y + height > size {
+            return None;
+        }
+        let origin = [self.x, self.y];
+        self.x += width + GLYPH_GAP;
+        self.height = self.height.max(height + GLYPH_GAP);
+        Some(origin)
+    }
+}
+
+/// Signed distance field glyphs rendered into an `R8_UNORM` atlas as text needs them.
+///
+/// The atlas starts empty at [`INITIAL_ATLAS_SIZE`] and doubles in width and height whenever
+/// a glyph no longer fits, keeping the glyphs already packed where they are. Texels hold the
+/// distance to the outline mapped to `0..=1`, 0.5 on the outline and increasing inwards over
+/// [`SDF_SPREAD`] pixels, so shaders draw text of any size with
+/// `smoothstep(0.5 - w, 0.5 + w, texture(atlas, uv).r)`.
+pub struct DynamicGlyphCache {
+    font: FontArc,
+    allocator: Arc<dyn MemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    queue: Arc<Queue>,
+    size: u32,
+    /// CPU copy of the atlas, uploaded whole whenever glyphs are added.
+    pixels: Vec<u8>,
+    glyphs: HashMap<GlyphId, CachedGlyph>,
+    shelf: Shelf,
+    view: Arc<ImageView>,
+}
+
+impl DynamicGlyphCache {
+    pub fn new(
+        font: FontArc,
+        allocator: Arc<dyn MemoryAllocator>,
+        queue: Arc<Queue>,
+    ) -> Result<Self, ThorusError> {
+        let command_buffer_allocator = StandardCommandBufferAllocator::new(
+            queue.device().clone(),
+            StandardCommandBufferAllocatorCreateInfo::default(),
+        );
+        let size = INITIAL_ATLAS_SIZE;
+        let pixels = vec![0; (size * size) as usize];
+        let view = upload_atlas(
+            allocator.clone(),
+            &command_buffer_allocator,
+            queue.clone(),
+            size,
+            &pixels,
+        )?;
+        Ok(Self {
+            font,
+            allocator,
+            command_buffer_allocator,
+            queue,
+            size,
+            pixels,
+            glyphs: HashMap::new(),
+            shelf: Shelf::default(),
+            view,
+        })
+    }
+
+    /// Renders the glyphs of `text` missing from the atlas, uploading it again if there were
+    /// any, and lays the text out on one line at `font_size` pixels. Control characters such
+    /// as line breaks are skipped.
+    pub fn prepare_text(
+        &mut self,
+        text: &str,
+        font_size: f32,
+    ) -> Result<Vec<GlyphQuad>, ThorusError> {
+        let ids: Vec<_> = text
+            .chars()
+            .filter(|c| !c.is_control())
+            .map(|c| self.font.glyph_id(c))
+            .collect();
+        let mut added = 0;
+        for &id in &ids {
+            if !self.glyphs.contains_key(&id) {
+                self.add_glyph(id);
+                added += 1;
+            }
+        }
+        if added > 0 {
+            debug!(
+                "added {added} glyphs, {} in a {size}x{size} atlas",
+                self.glyphs.len(),
+                size = self.size
+            );
+            self.view = upload_atlas(
+                self.allocator.clone(),
+                &self.command_buffer_allocator,
+                self.queue.clone(),
+                self.size,
+                &self.pixels,
+            )?;
+        }
+
+        // laid out only now, as the atlas size the UVs depend on may have changed
+        let font = self.font.as_scaled(PxScale::from(SDF_GLYPH_SIZE));
+        let scale = font_size / SDF_GLYPH_SIZE;
+        let texel = 1.0 / self.size as f32;
+        let mut pen = 0.0;
+        let mut previous = None;
+        let mut quads = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(previous) = previous {
+                pen += font.kern(previous, id);
+            }
+            let CachedGlyph { rect, offset } = self.glyphs[&id];
+            if !rect.is_empty() {
+                let [x, y] = rect.origin.map(|c| c as f32);
+                let [width, height] = rect.extent.map(|c| c as f32);
+                quads.push(GlyphQuad {
+                    rect: [
+                        (pen + offset[0]) * scale,
+                        offset[1] * scale,
+                        width * scale,
+                        height * scale,
+                    ],
+                    uv_rect: [x * texel, y * texel, width * texel, height * texel],
+                });
+            }
+            pen += font.h_advance(id);
+            previous = Some(id);
+        }
+        Ok(quads)
+    }
+
+    /// The atlas as of the last [`prepare_text`](Self::prepare_text); a new view after it
+    /// added glyphs.
+    pub fn atlas_view(&self) -> &Arc<ImageView> {
+        &self.view
+    }
+
+    /// Width and height of the atlas in texels.
+    pub fn atlas_size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    fn add_glyph(&mut self, id: GlyphId) {
+        let glyph = id.with_scale_and_position(PxScale::from(SDF_GLYPH_SIZE), point(0.0, 0.0));
+        let Some(outlined) = self.font.outline_glyph(glyph) else {
+            self.glyphs.insert(
+                id,
+                CachedGlyph {
+                    rect: Rect::default(),
+                    offset: [0.0; 2],
+                },
+            );
+            return;
+        };
+        let bounds = outlined.px_bounds();
+        let width = bounds.width().ceil() as u32;
+        let height = bounds.height().ceil() as u32;
+        let mut coverage = vec![0.0; (width * height) as usize];
+        outlined.draw(|x, y, c| {
+            if x < width && y < height {
+                coverage[(y * width + x) as usize] = c;
+            }
+        });
+        let (field, extent) = distance_field(&coverage, [width, height]);
+
+        let origin = loop {
+            if let Some(origin) = self.shelf.allocate(extent, self.size) {
+                break origin;
+            }
+            self.grow();
+        };
+        for (row, line) in field.chunks_exact(extent[0] as usize).enumerate() {
+            let start = ((origin[1] + row as u32) * self.size + origin[0]) as usize;
+            self.pixels[start..start + line.len()].copy_from_slice(line);
+        }
+        let spread = SDF_SPREAD as f32;
+        self.glyphs.insert(
+            id,
+            CachedGlyph {
+                rect: Rect::new(origin, extent),
+                offset: [bounds.min.x - spread, bounds.min.y - spread],
+            },
+        );
+    }
+
+    /// Doubles the atlas size, copying the packed glyphs to the same texels.
+    fn grow(&mut self) {
+        let size = self.size * 2;
+        let mut pixels = vec![0; (size * size) as usize];
+        for (old, new) in self
+            .pixels
+            .chunks_exact(self.size as usize)
+            .zip(pixels.chunks_exact_mut(size as usize))
+        {
+            new[..old.len()].copy_from_slice(old);
+        }
+        debug!("glyph atlas grown to {size}x{size}");
+        self.size = size;
+        self.pixels = pixels;
+    }
+}
+
+/// Signed distance field of a coverage mask, padded by [`SDF_SPREAD`] on every side, and its
+/// extent.
+fn distance_field(coverage: &[f32], [width, height]: [u32; 2]) -> (Vec<u8>, [u32; 2]) {
+    let spread = SDF_SPREAD as i32;
+    let inside = |x: i32, y: i32| {
+        (0..width as i32).contains(&x)
+            && (0..height as i32).contains(&y)
+            && coverage[(y as u32 * width + x as u32) as usize] >= 0.5
+    };
+    let extent = [width + 2 * SDF_SPREAD, height + 2 * SDF_SPREAD];
+    let mut field = Vec::with_capacity((extent[0] * extent[1]) as usize);
+    for y in 0..extent[1] as i32 {
+        for x in 0..extent[0] as i32 {
+            let (x, y) = (x - spread, y - spread);
+            let is_inside = inside(x, y);
+            // distance to the nearest texel on the other side of the outline, saturating
+            // the field when it is farther than the spread
+            let mut nearest = spread as f32 + 0.5;
+            for dy in -spread..=spread {
+                for dx in -spread..=spread {
+                    if inside(x + dx, y + dy) != is_inside {
+                        nearest = nearest.min(((dx * dx + dy * dy) as f32).sqrt());
+                    }
+                }
+            }
+            // the outline runs halfway between texels
+            let distance = nearest - 0.5;
+            let signed = if is_inside { distance } else { -distance };
+            let value = 0.5 + signed / (2.0 * spread as f32);
+            field.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+    (field, extent)
+}
+
+fn upload_atlas(
+    allocator: Arc<dyn MemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: Arc<Queue>,
+    size: u32,
+    pixels: &[u8],
+) -> Result<Arc<ImageView>, ThorusError> {
+    let image = upload_image(
+        allocator,
+        command_buffer_allocator,
+        queue,
+        ImageCreateInfo {
+            image_type: ImageType::Dim2d,
+            format: Format::R8_UNORM,
+            extent: [size, size, 1],
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            ..ImageCreateInfo::default()
+        },
+        &[pixels],
+    )?;
+    ImageView::new_default(image).map_err(ThorusError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_glyphs_fit_in_the_initial_atlas() {
+        // the largest field of a glyph within its em square of `SDF_GLYPH_SIZE` pixels
+        let side = SDF_GLYPH_SIZE as u32 + 2 * SDF_SPREAD;
+        let mut shelf = Shelf::default();
+        let mut packed: Vec<Rect> = vec![];
+        for _ in 0..128 {
+            let origin = shelf
+                .allocate([side, side], INITIAL_ATLAS_SIZE)
+                .expect("atlas full");
+            let rect = Rect::new(origin, [side, side]);
+            assert!(origin.iter().all(|&c| c + side <= INITIAL_ATLAS_SIZE));
+            assert!(packed.iter().all(|other| !overlap(*other, rect)));
+            packed.push(rect);
+        }
+    }
+
+    #[test]
+    fn full_atlas_fits_the_glyph_once_doubled() {
+        let mut shelf = Shelf::default();
+        let mut packed = vec![];
+        while let Some(origin) = shelf.allocate([100, 100], 512) {
+            packed.push(Rect::new(origin, [100, 100]));
+        }
+        assert_eq!(packed.len(), 25);
+        let rect = Rect::new(shelf.allocate([100, 100], 1024).unwrap(), [100, 100]);
+        assert!(rect.origin.iter().any(|&c| c + 100 > 512));
+        assert!(packed.iter().all(|other| !overlap(*other, rect)));
+    }
+
+    #[test]
+    fn distance_field_is_half_on_the_outline() {
+        // a 4×4 square in the middle of an 8×8 mask
+        let coverage: Vec<f32> = (0..64)
+            .map(|i| {
+                let (x, y) = (i % 8, i / 8);
+                if (2..6).contains(&x) && (2..6).contains(&y) {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let (field, extent) = distance_field(&coverage, [8, 8]);
+        let spread = SDF_SPREAD;
+        assert_eq!(extent, [8 + 2 * spread, 8 + 2 * spread]);
+        let at = |x: u32, y: u32| field[((y + spread) * extent[0] + x + spread) as usize];
+        let value = |signed: f32| ((0.5 + signed / (2.0 * spread as f32)) * 255.0).round() as u8;
+        // texels next to the outline are half a texel from it
+        assert_eq!(at(2, 3), value(0.5));
+        assert_eq!(at(1, 3), value(-0.5));
+        assert_eq!(at(3, 3), value(1.5));
+        assert_eq!(field[0], 0);
+    }
+
+    fn overlap(a: Rect, b: Rect) -> bool {
+        (0..2).all(|axis| {
+            a.origin[axis] < b.origin[axis] + b.extent[axis]
+                && b.origin[axis] < a.origin[axis] + a.extent[axis]
+        })
+    }
+}
@@ -0,0 +1,1130 @@
+use crate::buffer::UPLOAD_MEMORY;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, fs, io};
+use tracing::{debug, warn};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BufferImageCopy, CommandBufferUsage, CopyBufferToImageInfo,
+    PrimaryCommandBufferAbstract,
+};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::sampler::ycbcr::{
+    ChromaLocation, SamplerYcbcrConversion, SamplerYcbcrConversionCreateInfo,
+    SamplerYcbcrModelConversion, SamplerYcbcrRange,
+};
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
+use vulkano::image::{
+    Image, ImageAspects, ImageCreateInfo, ImageSubresourceLayers, ImageTiling, ImageType,
+    ImageUsage,
+};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::sync::GpuFuture;
+use vulkano::DeviceSize;
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    Ktx2(ktx2::ParseError),
+    Decode(image_dds::error::SurfaceError),
+    Image(image::ImageError),
+    LayerMismatch(String),
+    Supercompressed(ktx2::SupercompressionScheme),
+    UnsupportedFormat(String),
+    FeatureNotEnabled(&'static str),
+    Vulkan(Box<dyn Error + Send + Sync>),
+}
+
+impl Display for TextureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read texture: {e}"),
+            Self::Ktx2(e) => write!(f, "failed to parse KTX2 container: {e:?}"),
+            Self::Decode(e) => write!(f, "failed to decode texture: {e}"),
+            Self::Image(e) => write!(f, "failed to load image: {e}"),
+            Self::LayerMismatch(details) => write!(f, "texture array layers differ: {details}"),
+            Self::Supercompressed(scheme) => {
+                write!(
+                    f,
+                    "supercompressed KTX2 textures are not supported: {scheme:?}"
+                )
+            }
+            Self::UnsupportedFormat(format) => write!(f, "unsupported texture format: {format}"),
+            Self::FeatureNotEnabled(feature) => {
+                write!(f, "the {feature} device feature is not enabled")
+            }
+            Self::Vulkan(e) => write!(f, "failed to upload texture: {e}"),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Decode(e) => Some(e),
+            Self::Image(e) => Some(e),
+            Self::Vulkan(e) => Some(e.as_ref()),
+            Self::Ktx2(_)
+            | Self::LayerMismatch(_)
+            | Self::Supercompressed(_)
+            | Self::UnsupportedFormat(_)
+            | Self::FeatureNotEnabled(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextureError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ktx2::ParseError> for TextureError {
+    fn from(e: ktx2::ParseError) -> Self {
+        Self::Ktx2(e)
+    }
+}
+
+impl From<image_dds::error::SurfaceError> for TextureError {
+    fn from(e: image_dds::error::SurfaceError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+pub(crate) fn vulkan_error(e: impl Error + Send + Sync + 'static) -> TextureError {
+    TextureError::Vulkan(Box::new(e))
+}
+
+/// Sampled image living in device memory.
+#[derive(Clone, Debug)]
+pub struct Texture {
+    view: Arc<ImageView>,
+}
+
+impl Texture {
+    pub fn new(view: Arc<ImageView>) -> Self {
+        Self { view }
+    }
+
+    pub fn view(&self) -> &Arc<ImageView> {
+        &self.view
+    }
+
+    pub fn image(&self) -> &Arc<Image> {
+        self.view.image()
+    }
+
+    pub fn format(&self) -> Format {
+        self.view.format()
+    }
+
+    /// Multiplies the color channels of tightly packed RGBA8 texels by their alpha in place,
+    /// for drawing with [`BlendMode::PremultipliedAlpha`](crate::pipeline::BlendMode::PremultipliedAlpha).
+    ///
+    /// Works on the encoded values, like most image editors, rather than on linear colors.
+    pub fn premultiply_alpha(image: &mut [u8]) {
+        for texel in image.chunks_exact_mut(4) {
+            let alpha = u16::from(texel[3]);
+            for channel in &mut texel[..3] {
+                *channel = ((u16::from(*channel) * alpha + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Loads a KTX2 texture, or any other image the `image` crate can decode as RGBA8.
+    pub fn from_file(
+        path: impl AsRef<Path>,
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        if is_ktx2(path) {
+            return Self::from_ktx2(path, allocator, cmd_allocator, queue);
+        }
+        let image = image::open(path)?.into_rgba8();
+        debug!("image {path:?}: {}x{}", image.width(), image.height());
+        Self::from_rgba8(
+            [image.width(), image.height()],
+            image.as_raw(),
+            allocator,
+            cmd_allocator,
+            queue,
+        )
+    }
+
+    /// Uploads tightly packed `R8G8B8A8_SRGB` texels without mip levels.
+    pub fn from_rgba8(
+        extent: [u32; 2],
+        data: &[u8],
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, TextureError> {
+        let image = upload_image(
+            allocator,
+            cmd_allocator,
+            queue,
+            image_create_info(Format::R8G8B8A8_SRGB, [extent[0], extent[1], 1], 1, 1),
+            &[data],
+        )?;
+        Ok(Self::new(
+            ImageView::new_default(image).map_err(vulkan_error)?,
+        ))
+    }
+
+    /// Loads a KTX2 texture with all of its mip levels.
+    ///
+    /// Block-compressed data (BCn, ETC2, EAC) is uploaded as is when the device can sample the
+    /// format; otherwise BCn data is decompressed on the CPU and uploaded as RGBA8.
+    pub fn from_ktx2(
+        path: impl AsRef<Path>,
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        debug!("loading KTX2 texture {path:?}");
+        Self::from_ktx2_bytes(&fs::read(path)?, allocator, cmd_allocator, queue)
+    }
+
+    /// Like [`from_ktx2`](Self::from_ktx2) with the contents of the file.
+    pub fn from_ktx2_bytes(
+        bytes: &[u8],
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, TextureError> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        debug!("KTX2 header: {header:?}");
+
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(TextureError::Supercompressed(scheme));
+        }
+        let ktx2_format = header
+            .format
+            .ok_or_else(|| TextureError::UnsupportedFormat("undefined".to_owned()))?;
+        let format = vulkano_format(ktx2_format)
+            .ok_or_else(|| TextureError::UnsupportedFormat(format!("{ktx2_format:?}")))?;
+
+        let extent = [
+            header.pixel_width,
+            header.pixel_height.max(1),
+            header.pixel_depth.max(1),
+        ];
+        let array_layers = header.layer_count.max(1) * header.face_count.max(1);
+        let levels: Vec<&[u8]> = reader.levels().collect();
+
+        let selector = TextureFormatSelector::new(queue.device().physical_device().clone());
+        let tiling = selector.tiling(format).filter(|&tiling| {
+            tiling == ImageTiling::Optimal || (levels.len() == 1 && array_layers == 1)
+        });
+        if let Some(tiling) = tiling {
+            let image = upload_image(
+                allocator,
+                cmd_allocator,
+                queue,
+                ImageCreateInfo {
+                    tiling,
+                    ..image_create_info(format, extent, array_layers, levels.len() as u32)
+                },
+                &levels,
+            )?;
+            return Ok(Self::new(
+                ImageView::new_default(image).map_err(vulkan_error)?,
+            ));
+        }
+
+        let (dds_format, rgba_format) = decompression_formats(format)
+            .ok_or_else(|| TextureError::UnsupportedFormat(format!("{format:?}")))?;
+        warn!("{format:?} cannot be sampled by the device, decompressing it on the CPU");
+        let decoded = levels
+            .iter()
+            .enumerate()
+            .map(|(level, &data)| {
+                image_dds::Surface {
+                    width: mip_dimension(extent[0], level),
+                    height: mip_dimension(extent[1], level),
+                    depth: mip_dimension(extent[2], level),
+                    layers: array_layers,
+                    mipmaps: 1,
+                    image_format: dds_format,
+                    data,
+                }
+                .decode_rgba8()
+                .map(|surface| surface.data)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let decoded: Vec<&[u8]> = decoded.iter().map(Vec::as_slice).collect();
+
+        let image = upload_image(
+            allocator,
+            cmd_allocator,
+            queue,
+            image_create_info(rgba_format, extent, array_layers, decoded.len() as u32),
+            &decoded,
+        )?;
+        Ok(Self::new(
+            ImageView::new_default(image).map_err(vulkan_error)?,
+        ))
+    }
+}
+
+/// Whether [`Texture::from_file`] loads `path` as KTX2 rather than through the `image` crate.
+pub(crate) fn is_ktx2(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ktx2"))
+}
+
+/// Volumetric texture, e.g. a voxel grid or a density field.
+///
+/// Sampling it with [`Texture3D::trilinear_sampler`] interpolates between the eight nearest
+/// voxels:
+///
+/// ```glsl
+/// layout(set = 0, binding = 0) uniform sampler3D density;
+///
+/// float sample_density(vec3 world_pos, vec3 volume_min, vec3 volume_size) {
+///     vec3 uvw = (world_pos - volume_min) / volume_size;
+///     return texture(density, uvw).r;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Texture3D {
+    view: Arc<ImageView>,
+}
+
+impl Texture3D {
+    /// Uploads tightly packed `data` of `format`, laid out x fastest, then y, then z.
+    pub fn new(
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+        [width, height, depth]: [u32; 3],
+        format: Format,
+        data: &[u8],
+    ) -> Result<Self, TextureError> {
+        let expected =
+            width as DeviceSize * height as DeviceSize * depth as DeviceSize * format.block_size();
+        if format.compression().is_some() || data.len() as DeviceSize != expected {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "{format:?} volume of {width}x{height}x{depth} with {len} bytes of data",
+                len = data.len()
+            )));
+        }
+
+        let image = upload_image(
+            allocator,
+            cmd_allocator,
+            queue,
+            ImageCreateInfo {
+                image_type: ImageType::Dim3d,
+                ..image_create_info(format, [width, height, depth], 1, 1)
+            },
+            &[data],
+        )?;
+        let view = ImageView::new(
+            image.clone(),
+            ImageViewCreateInfo {
+                view_type: ImageViewType::Dim3d,
+                ..ImageViewCreateInfo::from_image(&image)
+            },
+        )
+        .map_err(vulkan_error)?;
+        Ok(Self { view })
+    }
+
+    /// Uploads a single channel float density field as `R32_SFLOAT`.
+    pub fn from_raw_f32(
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+        extent: [u32; 3],
+        values: &[f32],
+    ) -> Result<Self, TextureError> {
+        let data: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        Self::new(
+            allocator,
+            cmd_allocator,
+            queue,
+            extent,
+            Format::R32_SFLOAT,
+            &data,
+        )
+    }
+
+    pub fn view(&self) -> &Arc<ImageView> {
+        &self.view
+    }
+
+    pub fn extent(&self) -> [u32; 3] {
+        self.view.image().extent()
+    }
+
+    /// Linear filtering in all three dimensions, clamped to the volume edges.
+    pub fn trilinear_sampler(device: Arc<Device>) -> Result<Arc<Sampler>, TextureError> {
+        Sampler::new(
+            device,
+            SamplerCreateInfo {
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..SamplerCreateInfo::simple_repeat_linear_no_mipmap()
+            },
+        )
+        .map_err(vulkan_error)
+    }
+}
+
+/// Layered 2D texture, e.g. a sprite sheet or terrain splat maps, one image per layer.
+///
+/// ```glsl
+/// layout(set = 0, binding = 0) uniform sampler2DArray layers;
+///
+/// layout(location = 0) in vec2 v_uv;
+/// layout(location = 1) flat in uint v_layer;
+/// layout(location = 0) out vec4 f_color;
+///
+/// void main() {
+///     f_color = texture(layers, vec3(v_uv, float(v_layer)));
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextureArray {
+    view: Arc<ImageView>,
+}
+
+impl TextureArray {
+    /// Loads every image as one `R8G8B8A8_SRGB` layer; all images must have the same
+    /// dimensions and color type.
+    pub fn from_layers(
+        paths: &[&Path],
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, TextureError> {
+        let Some((first, rest)) = paths.split_first() else {
+            return Err(TextureError::LayerMismatch("no layers given".to_owned()));
+        };
+        let first_image = image::open(first)?;
+        let (width, height, color) = (
+            first_image.width(),
+            first_image.height(),
+            first_image.color(),
+        );
+        debug!("texture array layer {first:?}: {width}x{height} {color:?}");
+
+        let mut data = first_image.into_rgba8().into_raw();
+        for path in rest {
+            let layer = image::open(path)?;
+            if (layer.width(), layer.height(), layer.color()) != (width, height, color) {
+                return Err(TextureError::LayerMismatch(format!(
+                    "{path:?} is {layer_width}x{layer_height} {layer_color:?}, \
+                     {first:?} is {width}x{height} {color:?}",
+                    layer_width = layer.width(),
+                    layer_height = layer.height(),
+                    layer_color = layer.color(),
+                )));
+            }
+            data.extend_from_slice(layer.into_rgba8().as_raw());
+        }
+
+        let array_layers = paths.len() as u32;
+        let image = upload_image(
+            allocator,
+            cmd_allocator,
+            queue,
+            image_create_info(Format::R8G8B8A8_SRGB, [width, height, 1], array_layers, 1),
+            &[&data],
+        )?;
+        let view = ImageView::new(
+            image.clone(),
+            ImageViewCreateInfo {
+                view_type: ImageViewType::Dim2dArray,
+                ..ImageViewCreateInfo::from_image(&image)
+            },
+        )
+        .map_err(vulkan_error)?;
+        Ok(Self { view })
+    }
+
+    pub fn view(&self) -> &Arc<ImageView> {
+        &self.view
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.view.image().array_layers()
+    }
+}
+
+/// Video frame sampled as RGB, with the YCbCr to RGB conversion done by the sampler.
+///
+/// The luma and chroma planes live in one two-plane `G8_B8R8_2PLANE_420_UNORM` image, the
+/// layout NV12 maps to, since the hardware conversion only works on multi-planar images.
+/// Colors are converted with the BT.709 matrix from the narrow range video codecs produce.
+///
+/// The sampler has to be an immutable sampler of the descriptor set layout, see
+/// [`ImmutableSamplerLayout::with_sampler`](crate::descriptor::ImmutableSamplerLayout::with_sampler),
+/// and shaders sample the image through a plain `sampler2D`.
+#[derive(Clone, Debug)]
+pub struct YcbcrTexture {
+    view: Arc<ImageView>,
+    sampler: Arc<Sampler>,
+    conversion: Arc<SamplerYcbcrConversion>,
+}
+
+impl YcbcrTexture {
+    pub const FORMAT: Format = Format::G8_B8R8_2PLANE_420_UNORM;
+
+    /// Uploads an NV12 frame: `luma` holds one byte per pixel, `chroma` interleaved Cb and Cr
+    /// bytes for every 2x2 pixels. Both dimensions must be even.
+    ///
+    /// Requires the `sampler_ycbcr_conversion` feature to be enabled.
+    pub fn from_nv12(
+        luma_data: &[u8],
+        chroma_data: &[u8],
+        width: u32,
+        height: u32,
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, TextureError> {
+        let device = allocator.device().clone();
+        if !device.enabled_features().sampler_ycbcr_conversion {
+            return Err(TextureError::FeatureNotEnabled("sampler_ycbcr_conversion"));
+        }
+        let pixels = width as usize * height as usize;
+        if !width.is_multiple_of(2)
+            || !height.is_multiple_of(2)
+            || luma_data.len() != pixels
+            || chroma_data.len() != pixels / 2
+        {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "NV12 frame of {width}x{height} with {} luma and {} chroma bytes",
+                luma_data.len(),
+                chroma_data.len()
+            )));
+        }
+        let features = device
+            .physical_device()
+            .format_properties(Self::FORMAT)
+            .map_err(vulkan_error)?
+            .optimal_tiling_features;
+        // prefer the chroma siting of H.264 and HEVC, cosited horizontally and midpoint
+        // vertically, where the format allows it
+        let siting = |preferred, feature| {
+            if features.intersects(feature) {
+                preferred
+            } else if preferred == ChromaLocation::Midpoint {
+                ChromaLocation::CositedEven
+            } else {
+                ChromaLocation::Midpoint
+            }
+        };
+        let chroma_filter =
+            if features.intersects(FormatFeatures::SAMPLED_IMAGE_YCBCR_CONVERSION_LINEAR_FILTER) {
+                Filter::Linear
+            } else {
+                Filter::Nearest
+            };
+        let conversion = SamplerYcbcrConversion::new(
+            device.clone(),
+            SamplerYcbcrConversionCreateInfo {
+                format: Self::FORMAT,
+                ycbcr_model: SamplerYcbcrModelConversion::Ycbcr709,
+                ycbcr_range: SamplerYcbcrRange::ItuNarrow,
+                chroma_offset: [
+                    siting(
+                        ChromaLocation::CositedEven,
+                        FormatFeatures::COSITED_CHROMA_SAMPLES,
+                    ),
+                    siting(
+                        ChromaLocation::Midpoint,
+                        FormatFeatures::MIDPOINT_CHROMA_SAMPLES,
+                    ),
+                ],
+                chroma_filter,
+                ..SamplerYcbcrConversionCreateInfo::default()
+            },
+        )
+        .map_err(vulkan_error)?;
+        debug!("YCbCr conversion: {conversion:?}");
+
+        let plane = |aspects| ImageSubresourceLayers {
+            aspects,
+            mip_level: 0,
+            array_layers: 0..1,
+        };
+        let image = upload_image_regions(
+            allocator,
+            cmd_allocator,
+            queue,
+            image_create_info(Self::FORMAT, [width, height, 1], 1, 1),
+            &[
+                (luma_data, plane(ImageAspects::PLANE_0), [width, height, 1]),
+                (
+                    chroma_data,
+                    plane(ImageAspects::PLANE_1),
+                    [width / 2, height / 2, 1],
+                ),
+            ],
+            4,
+        )?;
+        let view = ImageView::new(
+            image.clone(),
+            ImageViewCreateInfo {
+                sampler_ycbcr_conversion: Some(conversion.clone()),
+                ..ImageViewCreateInfo::from_image(&image)
+            },
+        )
+        .map_err(vulkan_error)?;
+        let sampler = Sampler::new(
+            device,
+            SamplerCreateInfo {
+                mag_filter: chroma_filter,
+                min_filter: chroma_filter,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                sampler_ycbcr_conversion: Some(conversion.clone()),
+                ..SamplerCreateInfo::default()
+            },
+        )
+        .map_err(vulkan_error)?;
+        Ok(Self {
+            view,
+            sampler,
+            conversion,
+        })
+    }
+
+    pub fn view(&self) -> &Arc<ImageView> {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &Arc<Sampler> {
+        &self.sampler
+    }
+
+    pub fn conversion(&self) -> &Arc<SamplerYcbcrConversion> {
+        &self.conversion
+    }
+
+    /// The view and sampler to write into a combined image sampler descriptor.
+    pub fn view_sampler(&self) -> (Arc<ImageView>, Arc<Sampler>) {
+        (self.view.clone(), self.sampler.clone())
+    }
+}
+
+/// Chooses texture formats the device is able to sample and upload to.
+#[derive(Clone, Debug)]
+pub struct TextureFormatSelector {
+    physical_device: Arc<PhysicalDevice>,
+    required_features: FormatFeatures,
+}
+
+impl TextureFormatSelector {
+    pub const BC_RGBA: [Format; 2] = [Format::BC7_UNORM_BLOCK, Format::BC3_UNORM_BLOCK];
+    pub const BC_RGBA_SRGB: [Format; 2] = [Format::BC7_SRGB_BLOCK, Format::BC3_SRGB_BLOCK];
+    pub const ETC2_RGBA: [Format; 1] = [Format::ETC2_R8G8B8A8_UNORM_BLOCK];
+    pub const ETC2_RGBA_SRGB: [Format; 1] = [Format::ETC2_R8G8B8A8_SRGB_BLOCK];
+
+    pub fn new(physical_device: Arc<PhysicalDevice>) -> Self {
+        Self {
+            physical_device,
+            required_features: FormatFeatures::SAMPLED_IMAGE | FormatFeatures::TRANSFER_DST,
+        }
+    }
+
+    pub fn with_required_features(mut self, features: FormatFeatures) -> Self {
+        self.required_features |= features;
+        self
+    }
+
+    /// The tiling that supports every required feature, preferring optimal over linear.
+    pub fn tiling(&self, format: Format) -> Option<ImageTiling> {
+        let properties = self.physical_device.format_properties(format).ok()?;
+        if properties
+            .optimal_tiling_features
+            .contains(self.required_features)
+        {
+            Some(ImageTiling::Optimal)
+        } else if properties
+            .linear_tiling_features
+            .contains(self.required_features)
+        {
+            Some(ImageTiling::Linear)
+        } else {
+            None
+        }
+    }
+
+    /// The first supported candidate, candidates with optimal tiling winning over linear ones.
+    pub fn select(&self, candidates: &[Format]) -> Option<(Format, ImageTiling)> {
+        let supported: Vec<_> = candidates
+            .iter()
+            .filter_map(|&format| self.tiling(format).map(|tiling| (format, tiling)))
+            .collect();
+        supported
+            .iter()
+            .find(|(_, tiling)| *tiling == ImageTiling::Optimal)
+            .or_else(|| supported.first())
+            .copied()
+    }
+
+    /// The best RGBA format for color textures: BCn on desktop, ETC2 on mobile, RGBA8 otherwise.
+    pub fn rgba(&self, srgb: bool) -> Option<(Format, ImageTiling)> {
+        let (bc, etc2, uncompressed) = if srgb {
+            (
+                Self::BC_RGBA_SRGB,
+                Self::ETC2_RGBA_SRGB,
+                Format::R8G8B8A8_SRGB,
+            )
+        } else {
+            (Self::BC_RGBA, Self::ETC2_RGBA, Format::R8G8B8A8_UNORM)
+        };
+        let candidates: Vec<_> = bc.into_iter().chain(etc2).chain([uncompressed]).collect();
+        self.select(&candidates)
+    }
+}
+
+fn mip_dimension(base: u32, level: usize) -> u32 {
+    (base >> level).max(1)
+}
+
+fn image_create_info(
+    format: Format,
+    extent: [u32; 3],
+    array_layers: u32,
+    mip_levels: u32,
+) -> ImageCreateInfo {
+    ImageCreateInfo {
+        image_type: if extent[2] > 1 {
+            ImageType::Dim3d
+        } else {
+            ImageType::Dim2d
+        },
+        format,
+        extent,
+        array_layers,
+        mip_levels: mip_levels.max(1),
+        usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+        ..ImageCreateInfo::default()
+    }
+}
+
+fn vulkano_format(format: ktx2::Format) -> Option<Format> {
+    Some(match format {
+        ktx2::Format::R8G8B8A8_UNORM => Format::R8G8B8A8_UNORM,
+        ktx2::Format::R8G8B8A8_SRGB => Format::R8G8B8A8_SRGB,
+        ktx2::Format::BC1_RGB_UNORM_BLOCK => Format::BC1_RGB_UNORM_BLOCK,
+        ktx2::Format::BC1_RGB_SRGB_BLOCK => Format::BC1_RGB_SRGB_BLOCK,
+        ktx2::Format::BC1_RGBA_UNORM_BLOCK => Format::BC1_RGBA_UNORM_BLOCK,
+        ktx2::Format::BC1_RGBA_SRGB_BLOCK => Format::BC1_RGBA_SRGB_BLOCK,
+        ktx2::Format::BC2_UNORM_BLOCK => Format::BC2_UNORM_BLOCK,
+        ktx2::Format::BC2_SRGB_BLOCK => Format::BC2_SRGB_BLOCK,
+        ktx2::Format::BC3_UNORM_BLOCK => Format::BC3_UNORM_BLOCK,
+        ktx2::Format::BC3_SRGB_BLOCK => Format::BC3_SRGB_BLOCK,
+        ktx2::Format::BC4_UNORM_BLOCK => Format::BC4_UNORM_BLOCK,
+        ktx2::Format::BC4_SNORM_BLOCK => Format::BC4_SNORM_BLOCK,
+        ktx2::Format::BC5_UNORM_BLOCK => Format::BC5_UNORM_BLOCK,
+        ktx2::Format::BC5_SNORM_BLOCK => Format::BC5_SNORM_BLOCK,
+        ktx2::Format::BC6H_UFLOAT_BLOCK => Format::BC6H_UFLOAT_BLOCK,
+        ktx2::Format::BC6H_SFLOAT_BLOCK => Format::BC6H_SFLOAT_BLOCK,
+        ktx2::Format::BC7_UNORM_BLOCK => Format::BC7_UNORM_BLOCK,
+        ktx2::Format::BC7_SRGB_BLOCK => Format::BC7_SRGB_BLOCK,
+        ktx2::Format::ETC2_R8G8B8_UNORM_BLOCK => Format::ETC2_R8G8B8_UNORM_BLOCK,
+        ktx2::Format::ETC2_R8G8B8_SRGB_BLOCK => Format::ETC2_R8G8B8_SRGB_BLOCK,
+        ktx2::Format::ETC2_R8G8B8A1_UNORM_BLOCK => Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+        ktx2::Format::ETC2_R8G8B8A1_SRGB_BLOCK => Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+        ktx2::Format::ETC2_R8G8B8A8_UNORM_BLOCK => Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+        ktx2::Format::ETC2_R8G8B8A8_SRGB_BLOCK => Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+        ktx2::Format::EAC_R11_UNORM_BLOCK => Format::EAC_R11_UNORM_BLOCK,
+        ktx2::Format::EAC_R11_SNORM_BLOCK => Format::EAC_R11_SNORM_BLOCK,
+        ktx2::Format::EAC_R11G11_UNORM_BLOCK => Format::EAC_R11G11_UNORM_BLOCK,
+        ktx2::Format::EAC_R11G11_SNORM_BLOCK => Format::EAC_R11G11_SNORM_BLOCK,
+        _ => return None,
+    })
+}
+
+/// The CPU decoder input format and the format the decoded RGBA8 data is uploaded as.
+fn decompression_formats(format: Format) -> Option<(image_dds::ImageFormat, Format)> {
+    use image_dds::ImageFormat as Dds;
+
+    let unorm = Format::R8G8B8A8_UNORM;
+    let srgb = Format::R8G8B8A8_SRGB;
+    Some(match format {
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => (Dds::BC1RgbaUnorm, unorm),
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => (Dds::BC1RgbaUnormSrgb, srgb),
+        Format::BC2_UNORM_BLOCK => (Dds::BC2RgbaUnorm, unorm),
+        Format::BC2_SRGB_BLOCK => (Dds::BC2RgbaUnormSrgb, srgb),
+        Format::BC3_UNORM_BLOCK => (Dds::BC3RgbaUnorm, unorm),
+        Format::BC3_SRGB_BLOCK => (Dds::BC3RgbaUnormSrgb, srgb),
+        Format::BC4_UNORM_BLOCK => (Dds::BC4RUnorm, unorm),
+        Format::BC4_SNORM_BLOCK => (Dds::BC4RSnorm, unorm),
+        Format::BC5_UNORM_BLOCK => (Dds::BC5RgUnorm, unorm),
+        Format::BC5_SNORM_BLOCK => (Dds::BC5RgSnorm, unorm),
+        Format::BC6H_UFLOAT_BLOCK => (Dds::BC6hRgbUfloat, unorm),
+        Format::BC6H_SFLOAT_BLOCK => (Dds::BC6hRgbSfloat, unorm),
+        Format::BC7_UNORM_BLOCK => (Dds::BC7RgbaUnorm, unorm),
+        Format::BC7_SRGB_BLOCK => (Dds::BC7RgbaUnormSrgb, srgb),
+        _ => return None,
+    })
+}
+
+/// Creates an image and fills it through a staging buffer, one tightly packed slice per mip
+/// level covering every array layer, blocking until the copy has finished.
+pub(crate) fn upload_image(
+    allocator: Arc<dyn MemoryAllocator>,
+    cmd_allocator: &StandardCommandBufferAllocator,
+    queue: Arc<Queue>,
+    create_info: ImageCreateInfo,
+    levels: &[&[u8]],
+) -> Result<Arc<Image>, TextureError> {
+    let format = create_info.format;
+    let extent = create_info.extent;
+    let array_layers = create_info.array_layers;
+    let regions: Vec<_> = levels
+        .iter()
+        .enumerate()
+        .map(|(level, &data)| {
+            let subresource = ImageSubresourceLayers {
+                mip_level: level as u32,
+                ..ImageSubresourceLayers::from_parameters(format, array_layers)
+            };
+            (
+                data,
+                subresource,
+                extent.map(|dimension| mip_dimension(dimension, level)),
+            )
+        })
+        .collect();
+    // copy offsets must be a multiple of the texel block size and of 4
+    upload_image_regions(
+        allocator,
+        cmd_allocator,
+        queue,
+        create_info,
+        &regions,
+        format.block_size().max(4),
+    )
+}
+
+/// Creates an image and copies each tightly packed slice into the subresource and extent it
+/// comes with, staging them at offsets that are multiples of `alignment`.
+fn upload_image_regions(
+    allocator: Arc<dyn MemoryAllocator>,
+    cmd_allocator: &StandardCommandBufferAllocator,
+    queue: Arc<Queue>,
+    create_info: ImageCreateInfo,
+    regions: &[(&[u8], ImageSubresourceLayers, [u32; 3])],
+    alignment: DeviceSize,
+) -> Result<Arc<Image>, TextureError> {
+    let mut offsets = Vec::with_capacity(regions.len());
+    let mut staging_size: DeviceSize = 0;
+    for (data, _, _) in regions {
+        let offset = staging_size.next_multiple_of(alignment);
+        offsets.push(offset);
+        staging_size = offset + data.len() as DeviceSize;
+    }
+
+    let staging = Buffer::new_slice::<u8>(
+        allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..BufferCreateInfo::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: UPLOAD_MEMORY,
+            ..AllocationCreateInfo::default()
+        },
+        staging_size,
+    )
+    .map_err(vulkan_error)?;
+    {
+        let mut mapping = staging.write().map_err(vulkan_error)?;
+        for ((data, _, _), &offset) in regions.iter().zip(&offsets) {
+            let offset = offset as usize;
+            mapping[offset..offset + data.len()].copy_from_slice(data);
+        }
+    }
+
+    let image = Image::new(allocator, create_info, AllocationCreateInfo::default())
+        .map_err(vulkan_error)?;
+    debug!("uploading image: {image:?}");
+
+    let regions = regions
+        .iter()
+        .zip(offsets)
+        .map(
+            |((_, subresource, extent), buffer_offset)| BufferImageCopy {
+                buffer_offset,
+                image_subresource: subresource.clone(),
+                image_extent: *extent,
+                ..BufferImageCopy::default()
+            },
+        )
+        .collect();
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        cmd_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .map_err(vulkan_error)?;
+    builder
+        .copy_buffer_to_image(CopyBufferToImageInfo {
+            regions,
+            ..CopyBufferToImageInfo::buffer_image(staging, image.clone())
+        })
+        .map_err(vulkan_error)?;
+
+    builder
+        .build()
+        .map_err(vulkan_error)?
+        .execute(queue)
+        .map_err(vulkan_error)?
+        .then_signal_fence_and_flush()
+        .map_err(vulkan_error)?
+        .wait(None)
+        .map_err(vulkan_error)?;
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use std::env;
+    use vulkano::device::{DeviceExtensions, Features};
+
+    const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+    const VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK: u32 = 148;
+
+    /// A single level, single layer KTX2 file without a data format descriptor.
+    fn ktx2_file(vk_format: u32, [width, height]: [u32; 2], level: &[u8]) -> Vec<u8> {
+        const LEVEL_OFFSET: u64 = 80 + 24;
+
+        let mut bytes = b"\xABKTX 20\xBB\r\n\x1A\n".to_vec();
+        for field in [vk_format, 1, width, height, 0, 0, 1, 1, 0, 0, 0, 0, 0] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [0, 0, LEVEL_OFFSET, level.len() as u64, level.len() as u64] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes.extend_from_slice(level);
+        bytes
+    }
+
+    fn temp_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("thorus-texture-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn bc7_ktx2_maps_to_the_vulkano_format() {
+        let file = ktx2_file(VK_FORMAT_BC7_SRGB_BLOCK, [128, 128], &[0; 128 * 128]);
+        let reader = ktx2::Reader::new(file.as_slice()).unwrap();
+        let format = vulkano_format(reader.header().format.unwrap());
+        assert_eq!(format, Some(Format::BC7_SRGB_BLOCK));
+        assert_eq!(reader.levels().next().unwrap().len(), 128 * 128);
+        assert_eq!(
+            decompression_formats(Format::BC7_SRGB_BLOCK),
+            Some((
+                image_dds::ImageFormat::BC7RgbaUnormSrgb,
+                Format::R8G8B8A8_SRGB
+            ))
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn bc7_ktx2_is_loaded_compressed_when_supported() {
+        let context = TestContext::new();
+        // 32x32 blocks of 16 bytes
+        let path = temp_file(
+            "bc7.ktx2",
+            &ktx2_file(VK_FORMAT_BC7_SRGB_BLOCK, [128, 128], &[0; 128 * 128]),
+        );
+        let texture = Texture::from_file(
+            &path,
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+        )
+        .unwrap();
+        fs::remove_file(path).unwrap();
+
+        let selector = TextureFormatSelector::new(context.queue.device().physical_device().clone());
+        let expected = if selector.tiling(Format::BC7_SRGB_BLOCK).is_some() {
+            Format::BC7_SRGB_BLOCK
+        } else {
+            Format::R8G8B8A8_SRGB
+        };
+        assert_eq!(texture.format(), expected);
+        assert_eq!(texture.image().extent(), [128, 128, 1]);
+    }
+
+    #[test]
+    fn premultiplying_scales_color_by_alpha() {
+        let mut texels = [
+            255, 255, 255, 255, // opaque
+            200, 100, 50, 128, // half transparent
+            90, 180, 255, 0, // transparent
+        ];
+        Texture::premultiply_alpha(&mut texels);
+        assert_eq!(texels, [255, 255, 255, 255, 100, 50, 25, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn etc2_ktx2_maps_to_the_vulkano_format() {
+        let file = ktx2_file(
+            VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK,
+            [64, 64],
+            &[0; 64 * 64 / 2],
+        );
+        let reader = ktx2::Reader::new(file.as_slice()).unwrap();
+        let format = vulkano_format(reader.header().format.unwrap());
+        assert_eq!(format, Some(Format::ETC2_R8G8B8_SRGB_BLOCK));
+        // there is no CPU decoder for ETC2
+        assert_eq!(decompression_formats(Format::ETC2_R8G8B8_SRGB_BLOCK), None);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn etc2_ktx2_is_loaded_as_etc2_when_supported() {
+        let context = TestContext::new();
+        // 16x16 blocks of 8 bytes
+        let file = ktx2_file(
+            VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK,
+            [64, 64],
+            &[0; 64 * 64 / 2],
+        );
+        let texture = Texture::from_ktx2_bytes(
+            &file,
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+        );
+
+        let selector = TextureFormatSelector::new(context.queue.device().physical_device().clone());
+        if selector.tiling(Format::ETC2_R8G8B8_SRGB_BLOCK).is_some() {
+            assert_eq!(texture.unwrap().format(), Format::ETC2_R8G8B8_SRGB_BLOCK);
+        } else {
+            assert!(matches!(texture, Err(TextureError::UnsupportedFormat(_))));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn r32f_volume_is_created() {
+        let context = TestContext::new();
+        let values: Vec<f32> = (0..8 * 8 * 8).map(|index| index as f32 / 512.0).collect();
+        let texture = Texture3D::from_raw_f32(
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+            [8, 8, 8],
+            &values,
+        )
+        .unwrap();
+        assert_eq!(texture.extent(), [8, 8, 8]);
+        assert_eq!(texture.view().view_type(), ImageViewType::Dim3d);
+        assert_eq!(texture.view().format(), Format::R32_SFLOAT);
+        Texture3D::trilinear_sampler(context.queue.device().clone()).unwrap();
+
+        let too_short = Texture3D::from_raw_f32(
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+            [8, 8, 8],
+            &values[1..],
+        );
+        assert!(matches!(too_short, Err(TextureError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn three_pngs_are_loaded_as_layers() {
+        let context = TestContext::new();
+        let paths: Vec<_> = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+            .into_iter()
+            .enumerate()
+            .map(|(layer, color)| {
+                let path = env::temp_dir().join(format!(
+                    "thorus-texture-{}-layer{layer}.png",
+                    std::process::id()
+                ));
+                image::RgbaImage::from_pixel(64, 64, image::Rgba(color))
+                    .save(&path)
+                    .unwrap();
+                path
+            })
+            .collect();
+        let layers: Vec<&Path> = paths.iter().map(|path| path.as_path()).collect();
+        let array = TextureArray::from_layers(
+            &layers,
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+        )
+        .unwrap();
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(array.layer_count(), 3);
+        assert_eq!(array.view().view_type(), ImageViewType::Dim2dArray);
+        assert_eq!(array.view().image().extent(), [64, 64, 1]);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn nv12_frame_constructs_the_ycbcr_conversion() {
+        let context = TestContext::with_extensions(
+            DeviceExtensions::empty(),
+            Features {
+                sampler_ycbcr_conversion: true,
+                ..Features::empty()
+            },
+        );
+        let nv12 = |width: u32, height: u32| {
+            let pixels = (width * height) as usize;
+            YcbcrTexture::from_nv12(
+                &vec![128; pixels],
+                &vec![128; pixels / 2],
+                width,
+                height,
+                context.memory_allocator.clone(),
+                &context.command_buffer_allocator,
+                context.queue.clone(),
+            )
+        };
+
+        let texture = nv12(16, 16).unwrap();
+        assert_eq!(
+            texture.conversion().ycbcr_model(),
+            SamplerYcbcrModelConversion::Ycbcr709
+        );
+        assert_eq!(texture.view().format(), YcbcrTexture::FORMAT);
+        assert!(matches!(
+            nv12(15, 16),
+            Err(TextureError::UnsupportedFormat(_))
+        ));
+    }
+}
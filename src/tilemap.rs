@@ -0,0 +1,506 @@
+use crate::buffer::UPLOAD_MEMORY;
+use crate::error::ThorusError;
+use crate::pipeline::{BlendMode, CullMode, GraphicsPipelineBuilder};
+use crate::shader::{load_tilemap_fragment, load_tilemap_vertex};
+use crate::texture::TextureArray;
+use crate::vertex::TileInstance;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::rasterization::FrontFace;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::RenderPass;
+
+/// Tiles per side of the square chunks a [`Tilemap`] is culled and uploaded in.
+pub const CHUNK_SIZE: u32 = 32;
+
+/// Tile ID of cells without a tile; ID `n` is drawn with layer `n - 1` of the [`TileAtlas`].
+pub const EMPTY_TILE: u16 = 0;
+
+/// Mirrors the push constant block of `shader/tilemap.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct TilemapParams {
+    /// Left, top, width and height of the visible area in world units.
+    pub camera_bounds: [f32; 4],
+}
+
+/// Grid of tile IDs, `x` growing right and `y` down, with tiles `tile_size` world units wide
+/// and tile `(0, 0)` at the origin.
+#[derive(Clone, Debug)]
+pub struct Tilemap {
+    /// Rows of tile IDs.
+    tiles: Vec<Vec<u16>>,
+    tile_size: f32,
+    /// Bumped whenever a tile of the chunk changes, row by row.
+    chunk_versions: Vec<u64>,
+}
+
+impl Tilemap {
+    /// Creates a `width`×`height` map of [`EMPTY_TILE`]s.
+    pub fn new(width: u32, height: u32, tile_size: f32) -> Self {
+        let [chunks_x, chunks_y] = chunk_counts(width, height);
+        Self {
+            tiles: vec![vec![EMPTY_TILE; width as usize]; height as usize],
+            tile_size,
+            chunk_versions: vec![0; (chunks_x * chunks_y) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.tiles.first().map_or(0, |row| row.len() as u32)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.tiles.len() as u32
+    }
+
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the map.
+    pub fn get_tile(&self, x: u32, y: u32) -> u16 {
+        self.tiles[y as usize][x as usize]
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside the map.
+    pub fn set_tile(&mut self, x: u32, y: u32, id: u16) {
+        let tile = &mut self.tiles[y as usize][x as usize];
+        if *tile != id {
+            *tile = id;
+            let index = self.chunk_index([x / CHUNK_SIZE, y / CHUNK_SIZE]);
+            self.chunk_versions[index] += 1;
+        }
+    }
+
+    /// Chunks along x and y, the last ones possibly partial.
+    pub fn chunk_counts(&self) -> [u32; 2] {
+        chunk_counts(self.width(), self.height())
+    }
+
+    /// Changes whenever a tile of `chunk` does.
+    pub fn chunk_version(&self, chunk: [u32; 2]) -> u64 {
+        self.chunk_versions[self.chunk_index(chunk)]
+    }
+
+    /// Chunks overlapping `bounds`, left, top, width and height in world units.
+    pub fn chunks_in(&self, bounds: [f32; 4]) -> impl Iterator<Item = [u32; 2]> {
+        let chunk_extent = CHUNK_SIZE as f32 * self.tile_size;
+        let [chunks_x, chunks_y] = self.chunk_counts();
+        let range = |start: f32, length: f32, count: u32| {
+            let first = (start / chunk_extent).floor().max(0.0) as u32;
+            let end = (((start + length) / chunk_extent).ceil().max(0.0) as u32).min(count);
+            first..end
+        };
+        let xs = range(bounds[0], bounds[2], chunks_x);
+        range(bounds[1], bounds[3], chunks_y).flat_map(move |y| xs.clone().map(move |x| [x, y]))
+    }
+
+    /// A quad per non-empty tile of `chunk`.
+    pub fn chunk_instances(&self, chunk: [u32; 2]) -> Vec<TileInstance> {
+        let [x0, y0] = chunk.map(|c| (c * CHUNK_SIZE) as usize);
+        self.tiles
+            .iter()
+            .enumerate()
+            .skip(y0)
+            .take(CHUNK_SIZE as usize)
+            .flat_map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .skip(x0)
+                    .take(CHUNK_SIZE as usize)
+                    .filter(|&(_, &id)| id != EMPTY_TILE)
+                    .map(move |(x, &id)| TileInstance {
+                        rect: [
+                            x as f32 * self.tile_size,
+                            y as f32 * self.tile_size,
+                            self.tile_size,
+                            self.tile_size,
+                        ],
+                        layer: u32::from(id - 1),
+                    })
+            })
+            .collect()
+    }
+
+    fn chunk_index(&self, [x, y]: [u32; 2]) -> usize {
+        (y * self.chunk_counts()[0] + x) as usize
+    }
+}
+
+fn chunk_counts(width: u32, height: u32) -> [u32; 2] {
+    [width.div_ceil(CHUNK_SIZE), height.div_ceil(CHUNK_SIZE)]
+}
+
+/// Tile images of a [`Tilemap`], one per layer of a [`TextureArray`].
+#[derive(Clone, Debug)]
+pub struct TileAtlas {
+    array: TextureArray,
+}
+
+impl TileAtlas {
+    pub fn new(array: TextureArray) -> Self {
+        Self { array }
+    }
+
+    /// Loads the image of tile ID `n` from `paths[n - 1]`; see [`TextureArray::from_layers`].
+    pub fn from_tiles(
+        paths: &[&Path],
+        allocator: Arc<dyn MemoryAllocator>,
+        cmd_allocator: &StandardCommandBufferAllocator,
+        queue: Arc<Queue>,
+    ) -> Result<Self, ThorusError> {
+        let array = TextureArray::from_layers(paths, allocator, cmd_allocator, queue)?;
+        Ok(Self::new(array))
+    }
+
+    pub fn view(&self) -> &Arc<ImageView> {
+        self.array.view()
+    }
+
+    pub fn tile_count(&self) -> u32 {
+        self.array.layer_count()
+    }
+}
+
+/// Instance buffer of a chunk, for the version of the chunk it was built from.
+struct ChunkBuffer {
+    version: u64,
+    /// `None` for chunks without tiles.
+    instances: Option<Subbuffer<[TileInstance]>>,
+}
+
+/// Draws a [`Tilemap`] as alpha-blended quads, one instanced draw per visible chunk.
+///
+/// Chunk instance buffers are built the first time a chunk is visible and rebuilt only after
+/// its tiles change. Tiles are sampled without filtering, keeping pixel art sharp.
+pub struct TilemapRenderer {
+    pipeline: Arc<GraphicsPipeline>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    allocator: Arc<dyn MemoryAllocator>,
+    tilemap: Tilemap,
+    chunks: Vec<Option<ChunkBuffer>>,
+}
+
+impl TilemapRenderer {
+    /// Creates the pipeline for `subpass` of `render_pass`.
+    pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<dyn MemoryAllocator>,
+        render_pass: Arc<RenderPass>,
+        subpass: u32,
+        viewport: Viewport,
+        atlas: &TileAtlas,
+        tilemap: Tilemap,
+    ) -> Result<Self, ThorusError> {
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_tilemap_vertex(device.clone())?)
+            .fragment_shader(load_tilemap_fragment(device.clone())?)
+            .vertex_input(TileInstance::per_instance())
+            .topology(PrimitiveTopology::TriangleStrip)
+            .render_pass(render_pass, subpass)
+            .viewport(viewport)
+            .blend_mode(BlendMode::Alpha)
+            .culling(CullMode::None, FrontFace::CounterClockwise)
+            .build()?;
+        debug!("tilemap pipeline: {pipeline:?}");
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..SamplerCreateInfo::default()
+            },
+        )?;
+        let descriptor_set = PersistentDescriptorSet::new(
+            &StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+            pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                atlas.view().clone(),
+                sampler,
+            )],
+            [],
+        )?;
+        let [chunks_x, chunks_y] = tilemap.chunk_counts();
+        Ok(Self {
+            pipeline,
+            descriptor_set,
+            allocator,
+            chunks: (0..chunks_x * chunks_y).map(|_| None).collect(),
+            tilemap,
+        })
+    }
+
+    pub fn tilemap(&self) -> &Tilemap {
+        &self.tilemap
+    }
+
+    /// The map, for [`Tilemap::set_tile`]; changed chunks are rebuilt by the next
+    /// [`render`](Self::render) they are visible in.
+    pub fn tilemap_mut(&mut self) -> &mut Tilemap {
+        &mut self.tilemap
+    }
+
+    /// Draws the chunks overlapping `camera_bounds`, left, top, width and height in world
+    /// units mapped to the whole viewport, and returns the number of draw calls recorded.
+    pub fn render(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        camera_bounds: [f32; 4],
+    ) -> Result<usize, ThorusError> {
+        let [chunks_x, _] = self.tilemap.chunk_counts();
+        let mut visible = vec![];
+        for chunk in self.tilemap.chunks_in(camera_bounds) {
+            let version = self.tilemap.chunk_version(chunk);
+            let index = (chunk[1] * chunks_x + chunk[0]) as usize;
+            let current = self.chunks[index]
+                .as_ref()
+                .is_some_and(|buffer| buffer.version == version);
+            if !current {
+                self.chunks[index] = Some(ChunkBuffer {
+                    version,
+                    instances: self.build_chunk(chunk)?,
+                });
+            }
+            if let Some(instances) = self.chunks[index]
+                .as_ref()
+                .and_then(|buffer| buffer.instances.clone())
+            {
+                visible.push(instances);
+            }
+        }
+        if visible.is_empty() {
+            return Ok(0);
+        }
+
+        let layout = self.pipeline.layout().clone();
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                layout.clone(),
+                0,
+                self.descriptor_set.clone(),
+            )?
+            .push_constants(layout, 0, TilemapParams { camera_bounds })?;
+        for instances in &visible {
+            let count = instances.len() as u32;
+            builder
+                .bind_vertex_buffers(0, instances.clone())?
+                .draw(4, count, 0, 0)?;
+        }
+        Ok(visible.len())
+    }
+
+    fn build_chunk(
+        &self,
+        chunk: [u32; 2],
+    ) -> Result<Option<Subbuffer<[TileInstance]>>, ThorusError> {
+        let instances = self.tilemap.chunk_instances(chunk);
+        if instances.is_empty() {
+            return Ok(None);
+        }
+        debug!("tilemap chunk {chunk:?}: {} tiles", instances.len());
+        Buffer::from_iter(
+            self.allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: UPLOAD_MEMORY,
+                ..AllocationCreateInfo::default()
+            },
+            instances,
+        )
+        .map(Some)
+        .map_err(ThorusError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::RenderPassBuilder;
+    use crate::testing::TestContext;
+    use image::RgbaImage;
+    use std::env;
+    use vulkano::command_buffer::{
+        RenderPassBeginInfo, SubpassBeginInfo, SubpassContents, SubpassEndInfo,
+    };
+    use vulkano::format::{ClearValue, Format};
+    use vulkano::image::{Image, ImageCreateInfo, ImageLayout, ImageUsage, SampleCount};
+    use vulkano::render_pass::{
+        AttachmentLoadOp, AttachmentStoreOp, Framebuffer, FramebufferCreateInfo,
+    };
+
+    /// A 100×100 map, 4×4 chunks with the last row and column partial, with a tile in
+    /// chunks `(0, 0)`, `(1, 0)`, `(2, 2)` and `(3, 3)`.
+    fn sparse_map() -> Tilemap {
+        let mut tilemap = Tilemap::new(100, 100, 1.0);
+        for (x, y) in [(0, 0), (40, 5), (70, 70), (99, 99)] {
+            tilemap.set_tile(x, y, 1);
+        }
+        tilemap
+    }
+
+    /// Chunks that would be drawn for `bounds`: the visible ones with tiles.
+    fn drawn_chunks(tilemap: &Tilemap, bounds: [f32; 4]) -> Vec<[u32; 2]> {
+        tilemap
+            .chunks_in(bounds)
+            .filter(|&chunk| !tilemap.chunk_instances(chunk).is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn only_chunks_within_the_camera_bounds_are_drawn() {
+        let tilemap = sparse_map();
+        assert_eq!(tilemap.chunk_counts(), [4, 4]);
+        assert_eq!(
+            drawn_chunks(&tilemap, [0.0, 0.0, 64.0, 32.0]),
+            [[0, 0], [1, 0]]
+        );
+        assert_eq!(drawn_chunks(&tilemap, [35.0, 0.0, 10.0, 10.0]), [[1, 0]]);
+        assert_eq!(
+            drawn_chunks(&tilemap, [60.0, 60.0, 100.0, 100.0]),
+            [[2, 2], [3, 3]]
+        );
+        assert!(drawn_chunks(&tilemap, [-10.0, -10.0, 5.0, 5.0]).is_empty());
+        assert_eq!(tilemap.chunks_in([0.0, 0.0, 1000.0, 1000.0]).count(), 16);
+    }
+
+    #[test]
+    fn chunk_instances_place_tiles_in_world_units() {
+        let mut tilemap = Tilemap::new(40, 40, 2.0);
+        tilemap.set_tile(33, 1, 3);
+        assert_eq!(
+            tilemap.chunk_instances([1, 0]),
+            [TileInstance {
+                rect: [66.0, 2.0, 2.0, 2.0],
+                layer: 2,
+            }]
+        );
+        assert!(tilemap.chunk_instances([0, 0]).is_empty());
+    }
+
+    #[test]
+    fn only_changed_tiles_bump_their_chunk_version() {
+        let mut tilemap = sparse_map();
+        let before = tilemap.chunk_version([1, 0]);
+        tilemap.set_tile(40, 5, 1);
+        assert_eq!(tilemap.chunk_version([1, 0]), before);
+        tilemap.set_tile(40, 5, 2);
+        assert_eq!(tilemap.chunk_version([1, 0]), before + 1);
+        assert_eq!(tilemap.get_tile(40, 5), 2);
+        assert_eq!(tilemap.chunk_version([0, 0]), 1);
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn render_draws_the_visible_chunks_with_tiles() {
+        let context = TestContext::new();
+        let device = context.queue.device().clone();
+        let render_pass = RenderPassBuilder::new(device.clone())
+            .add_attachment(
+                Format::R8G8B8A8_UNORM,
+                SampleCount::Sample1,
+                AttachmentLoadOp::Clear,
+                AttachmentStoreOp::Store,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .add_subpass(&[0], &[], None)
+            .build()
+            .unwrap();
+        let target = Image::new(
+            context.memory_allocator.clone(),
+            ImageCreateInfo {
+                format: Format::R8G8B8A8_UNORM,
+                extent: [64, 64, 1],
+                usage: ImageUsage::COLOR_ATTACHMENT,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![ImageView::new_default(target).unwrap()],
+                ..FramebufferCreateInfo::default()
+            },
+        )
+        .unwrap();
+        let tile = env::temp_dir().join(format!("thorus-tilemap-{}.png", std::process::id()));
+        RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]))
+            .save(&tile)
+            .unwrap();
+        let atlas = TileAtlas::from_tiles(
+            &[&tile],
+            context.memory_allocator.clone(),
+            &context.command_buffer_allocator,
+            context.queue.clone(),
+        );
+        std::fs::remove_file(&tile).unwrap();
+        let mut renderer = TilemapRenderer::new(
+            device,
+            context.memory_allocator.clone(),
+            render_pass,
+            0,
+            Viewport {
+                offset: [0.0, 0.0],
+                extent: [64.0, 64.0],
+                depth_range: 0.0..=1.0,
+            },
+            &atlas.unwrap(),
+            sparse_map(),
+        )
+        .unwrap();
+
+        let mut builder = context.command_buffer();
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(ClearValue::Float([0.0; 4]))],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..SubpassBeginInfo::default()
+                },
+            )
+            .unwrap();
+        let draws = [
+            [0.0, 0.0, 64.0, 32.0],
+            [35.0, 0.0, 10.0, 10.0],
+            [-10.0, -10.0, 5.0, 5.0],
+        ]
+        .map(|bounds| renderer.render(&mut builder, bounds).unwrap());
+        builder.end_render_pass(SubpassEndInfo::default()).unwrap();
+        context.submit(builder);
+        assert_eq!(draws, [2, 1, 0]);
+    }
+}
@@ -0,0 +1,794 @@
+use crate::error::ThorusError;
+use crate::pipeline::{BlendMode, CullMode, GraphicsPipelineBuilder};
+use crate::shader::{
+    load_drop_shadow_fragment, load_drop_shadow_vertex, load_sdf_shape_fragment,
+    load_sdf_shape_vertex, load_sprite_fragment, load_sprite_vertex,
+};
+use crate::sprite::{nine_slice, AnimationPlayer};
+use crate::vertex::{SdfShape, SdfShapeType, SpriteInstance};
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::buffer::allocator::SubbufferAllocator;
+use vulkano::buffer::BufferContents;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::{
+    StandardDescriptorSetAllocator, StandardDescriptorSetAllocatorCreateInfo,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::image::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::rasterization::FrontFace;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Scissor, Viewport};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::RenderPass;
+
+/// Axis-aligned rectangle in framebuffer pixels.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Rect {
+    pub origin: [u32; 2],
+    pub extent: [u32; 2],
+}
+
+impl Rect {
+    pub fn new(origin: [u32; 2], extent: [u32; 2]) -> Self {
+        Self { origin, extent }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extent[0] == 0 || self.extent[1] == 0
+    }
+
+    /// The overlap of both rectangles; empty, at the larger origin, if there is none.
+    pub fn intersect(&self, other: &Self) -> Self {
+        let origin = [0, 1].map(|i| self.origin[i].max(other.origin[i]));
+        let end = [0, 1]
+            .map(|i| (self.origin[i] + self.extent[i]).min(other.origin[i] + other.extent[i]));
+        Self {
+            origin,
+            extent: [0, 1].map(|i| end[i].saturating_sub(origin[i])),
+        }
+    }
+
+    pub fn contains(&self, point: [u32; 2]) -> bool {
+        (0..2).all(|i| point[i] >= self.origin[i] && point[i] - self.origin[i] < self.extent[i])
+    }
+}
+
+impl From<Rect> for Scissor {
+    fn from(rect: Rect) -> Self {
+        Scissor {
+            offset: rect.origin,
+            extent: rect.extent,
+        }
+    }
+}
+
+/// Nested clipping regions of UI panels; each pushed rectangle is clipped to the ones below
+/// it, so children never draw outside any of their ancestors.
+#[derive(Clone, Default, Debug)]
+pub struct ClipStack {
+    rects: Vec<Rect>,
+}
+
+impl ClipStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes `rect` and returns the region now in effect.
+    pub fn push(&mut self, rect: Rect) -> Rect {
+        let clipped = match self.rects.last() {
+            Some(top) => top.intersect(&rect),
+            None => rect,
+        };
+        self.rects.push(clipped);
+        clipped
+    }
+
+    /// Removes the innermost region and returns it.
+    pub fn pop(&mut self) -> Option<Rect> {
+        self.rects.pop()
+    }
+
+    /// The region in effect, or `None` when nothing is clipped.
+    pub fn current(&self) -> Option<Rect> {
+        self.rects.last().copied()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.rects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+}
+
+/// Mirrors the push constant block of `shader/sprite.vert` and `shader/sdf_shape.vert`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SpriteParams {
+    pub screen_size: [f32; 2],
+}
+
+/// Mirrors the push constant block shared by `shader/drop_shadow.vert` and
+/// `shader/drop_shadow.frag`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DropShadowParams {
+    /// Left, top, width and height of the quad in pixels.
+    pub bounds: [f32; 4],
+    /// Center and half size of the rounded box casting the shadow, in pixels.
+    pub shape: [f32; 4],
+    pub color: [f32; 4],
+    pub screen_size: [f32; 2],
+    pub corner_radius: f32,
+    pub blur_radius: f32,
+}
+
+impl DropShadowParams {
+    /// Parameters drawing the shadow of `rect`, see
+    /// [`SpriteRenderer::draw_drop_shadow`].
+    pub fn new(
+        rect: Rect,
+        blur_radius: f32,
+        offset: [f32; 2],
+        color: [f32; 4],
+        corner_radius: f32,
+        screen_size: [f32; 2],
+    ) -> Self {
+        let half_size = rect.extent.map(|dimension| dimension as f32 / 2.0);
+        Self {
+            bounds: drop_shadow_bounds(rect, blur_radius, offset),
+            shape: [
+                rect.origin[0] as f32 + offset[0] + half_size[0],
+                rect.origin[1] as f32 + offset[1] + half_size[1],
+                half_size[0],
+                half_size[1],
+            ],
+            color,
+            screen_size,
+            corner_radius,
+            blur_radius,
+        }
+    }
+}
+
+/// Quad covering the shadow of `rect` moved by `offset`: the moved rectangle grown by
+/// `blur_radius` on every side, as left, top, width and height in pixels.
+pub fn drop_shadow_bounds(rect: Rect, blur_radius: f32, offset: [f32; 2]) -> [f32; 4] {
+    let blur_radius = blur_radius.max(0.0);
+    [
+        rect.origin[0] as f32 + offset[0] - blur_radius,
+        rect.origin[1] as f32 + offset[1] - blur_radius,
+        rect.extent[0] as f32 + 2.0 * blur_radius,
+        rect.extent[1] as f32 + 2.0 * blur_radius,
+    ]
+}
+
+/// Draws alpha-blended, textured screen-space quads, clipped to the regions of a
+/// [`ClipStack`] with a dynamic scissor.
+///
+/// The pipeline is made for a fixed target extent and has to be recreated when it changes.
+pub struct SpriteRenderer {
+    pipeline: Arc<GraphicsPipeline>,
+    shadow_pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    extent: [u32; 2],
+    clip_stack: ClipStack,
+}
+
+impl SpriteRenderer {
+    /// Creates the pipeline for `subpass` of `render_pass` drawing into `extent` pixels.
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        subpass: u32,
+        extent: [u32; 2],
+    ) -> Result<Self, ThorusError> {
+        let viewport = Viewport {
+            offset: [0.0, 0.0],
+            extent: extent.map(|dimension| dimension as f32),
+            depth_range: 0.0..=1.0,
+        };
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_sprite_vertex(device.clone())?)
+            .fragment_shader(load_sprite_fragment(device.clone())?)
+            .vertex_input(SpriteInstance::per_instance())
+            .topology(PrimitiveTopology::TriangleStrip)
+            .render_pass(render_pass.clone(), subpass)
+            .viewport(viewport.clone())
+            .blend_mode(BlendMode::Alpha)
+            .with_dynamic_scissor()
+            .culling(CullMode::None, FrontFace::CounterClockwise)
+            .build()?;
+        debug!("sprite pipeline: {pipeline:?}");
+        let shadow_pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_drop_shadow_vertex(device.clone())?)
+            .fragment_shader(load_drop_shadow_fragment(device.clone())?)
+            .topology(PrimitiveTopology::TriangleStrip)
+            .render_pass(render_pass, subpass)
+            .viewport(viewport)
+            .blend_mode(BlendMode::Alpha)
+            .with_dynamic_scissor()
+            .culling(CullMode::None, FrontFace::CounterClockwise)
+            .build()?;
+        debug!("drop shadow pipeline: {shadow_pipeline:?}");
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::simple_repeat_linear())?;
+        Ok(Self {
+            pipeline,
+            shadow_pipeline,
+            sampler,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(
+                device,
+                StandardDescriptorSetAllocatorCreateInfo::default(),
+            ),
+            extent,
+            clip_stack: ClipStack::new(),
+        })
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    pub fn clip_stack(&self) -> &ClipStack {
+        &self.clip_stack
+    }
+
+    /// Clips the following draws to `rect`, within any region that is already active.
+    pub fn begin_clip(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        rect: Rect,
+    ) -> Result<(), ThorusError> {
+        self.clip_stack.push(rect);
+        self.set_scissor(builder)
+    }
+
+    /// Returns to the region active before the matching [`begin_clip`](Self::begin_clip),
+    /// which is the full viewport once every region has been ended.
+    pub fn end_clip(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), ThorusError> {
+        self.clip_stack.pop();
+        self.set_scissor(builder)
+    }
+
+    /// Uploads `sprites` through `allocator`, which must hand out host-visible
+    /// `VERTEX_BUFFER` memory, and draws them with `texture` inside the active clip region.
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        allocator: &SubbufferAllocator,
+        texture: Arc<ImageView>,
+        sprites: &[SpriteInstance],
+    ) -> Result<(), ThorusError> {
+        if sprites.is_empty() {
+            return Ok(());
+        }
+        let buffer = allocator.allocate_slice::<SpriteInstance>(sprites.len() as u64)?;
+        buffer.write()?.copy_from_slice(sprites);
+
+        let layout = self.pipeline.layout().clone();
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                texture,
+                self.sampler.clone(),
+            )],
+            [],
+        )?;
+
+        builder.bind_pipeline_graphics(self.pipeline.clone())?;
+        self.set_scissor(builder)?;
+        builder
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )?
+            .push_constants(
+                layout,
+                0,
+                SpriteParams {
+                    screen_size: self.extent.map(|dimension| dimension as f32),
+                },
+            )?
+            .bind_vertex_buffers(0, buffer)?
+            .draw(4, sprites.len() as u32, 0, 0)?;
+        Ok(())
+    }
+
+    /// Draws the current frame of `player` over `rect`, left, top, width and height in pixels,
+    /// from the atlas `texture`; nothing is drawn while `player` has no frame.
+    pub fn draw_animated(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        allocator: &SubbufferAllocator,
+        texture: Arc<ImageView>,
+        player: &AnimationPlayer,
+        rect: [f32; 4],
+        color: [f32; 4],
+    ) -> Result<(), ThorusError> {
+        let Some(frame) = player.current_frame() else {
+            return Ok(());
+        };
+        let [width, height, _] = texture.image().extent();
+        let sprite = SpriteInstance {
+            rect,
+            uv_rect: [
+                frame.origin[0] as f32 / width as f32,
+                frame.origin[1] as f32 / height as f32,
+                frame.extent[0] as f32 / width as f32,
+                frame.extent[1] as f32 / height as f32,
+            ],
+            color,
+        };
+        self.draw(builder, allocator, texture, &[sprite])
+    }
+
+    /// Draws `texture_region` of `texture`, in texels, over `dest_rect` as a 9-slice panel
+    /// whose corners of `corner_size` keep their size; see [`nine_slice`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_9slice(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        allocator: &SubbufferAllocator,
+        texture: Arc<ImageView>,
+        texture_region: Rect,
+        corner_size: [f32; 2],
+        dest_rect: Rect,
+        tint: [f32; 4],
+    ) -> Result<(), ThorusError> {
+        if texture_region.is_empty() || dest_rect.is_empty() {
+            return Ok(());
+        }
+        let [width, height, _] = texture.image().extent();
+        let quads = nine_slice(
+            texture_region,
+            [width, height],
+            corner_size,
+            dest_rect,
+            tint,
+        );
+        self.draw(builder, allocator, texture, &quads)
+    }
+
+    /// Draws a soft shadow under `rect`, e.g. before the sprites of a dialog occupying it,
+    /// inside the active clip region.
+    ///
+    /// The shadow is the rounded rectangle `rect` with corners of `corner_radius`, moved by
+    /// `offset` pixels and faded out over `blur_radius` pixels on both sides of its edge, on a
+    /// quad given by [`drop_shadow_bounds`].
+    pub fn draw_drop_shadow(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        rect: Rect,
+        blur_radius: f32,
+        offset: [f32; 2],
+        color: [f32; 4],
+        corner_radius: f32,
+    ) -> Result<(), ThorusError> {
+        if rect.is_empty() {
+            return Ok(());
+        }
+        let params = DropShadowParams::new(
+            rect,
+            blur_radius,
+            offset,
+            color,
+            corner_radius,
+            self.extent.map(|dimension| dimension as f32),
+        );
+        builder.bind_pipeline_graphics(self.shadow_pipeline.clone())?;
+        self.set_scissor(builder)?;
+        builder
+            .push_constants(self.shadow_pipeline.layout().clone(), 0, params)?
+            .draw(4, 1, 0, 0)?;
+        Ok(())
+    }
+
+    fn set_scissor(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), ThorusError> {
+        let rect = self
+            .clip_stack
+            .current()
+            .unwrap_or(Rect::new([0, 0], self.extent));
+        builder.set_scissor(0, [rect.into()].into_iter().collect())?;
+        Ok(())
+    }
+}
+
+/// Draws rounded rectangles, circles and rings from their signed distance fields, sharp and
+/// antialiased at any size. Shapes are batched and drawn as one instanced draw by
+/// [`flush`](Self::flush).
+///
+/// The pipeline is made for a fixed target extent and has to be recreated when it changes.
+pub struct SdfRenderer {
+    pipeline: Arc<GraphicsPipeline>,
+    extent: [u32; 2],
+    shapes: Vec<SdfShape>,
+}
+
+impl SdfRenderer {
+    /// Creates the pipeline for `subpass` of `render_pass` drawing into `extent` pixels.
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<RenderPass>,
+        subpass: u32,
+        extent: [u32; 2],
+    ) -> Result<Self, ThorusError> {
+        let pipeline = GraphicsPipelineBuilder::new(device.clone())
+            .vertex_shader(load_sdf_shape_vertex(device.clone())?)
+            .fragment_shader(load_sdf_shape_fragment(device)?)
+            .vertex_input(SdfShape::per_instance())
+            .topology(PrimitiveTopology::TriangleStrip)
+            .render_pass(render_pass, subpass)
+            .viewport(Viewport {
+                offset: [0.0, 0.0],
+                extent: extent.map(|dimension| dimension as f32),
+                depth_range: 0.0..=1.0,
+            })
+            .blend_mode(BlendMode::Alpha)
+            .culling(CullMode::None, FrontFace::CounterClockwise)
+            .build()?;
+        debug!("sdf shape pipeline: {pipeline:?}");
+        Ok(Self {
+            pipeline,
+            extent,
+            shapes: vec![],
+        })
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        &self.pipeline
+    }
+
+    /// Shapes waiting for the next [`flush`](Self::flush).
+    pub fn shapes(&self) -> &[SdfShape] {
+        &self.shapes
+    }
+
+    pub fn push(&mut self, shape: SdfShape) {
+        self.shapes.push(shape);
+    }
+
+    pub fn draw_rounded_rect(
+        &mut self,
+        position: [f32; 2],
+        size: [f32; 2],
+        corner_radius: f32,
+        color: [f32; 4],
+    ) {
+        self.push(SdfShape {
+            position,
+            size,
+            corner_radius,
+            fill_color: color,
+            shape_type: SdfShapeType::Rect as u32,
+            ..SdfShape::default()
+        });
+    }
+
+    pub fn draw_circle(&mut self, center: [f32; 2], radius: f32, color: [f32; 4]) {
+        self.push(SdfShape {
+            position: center.map(|c| c - radius),
+            size: [2.0 * radius; 2],
+            fill_color: color,
+            shape_type: SdfShapeType::Circle as u32,
+            ..SdfShape::default()
+        });
+    }
+
+    /// Draws a ring of outer `radius` and `width` pixels thick.
+    pub fn draw_ring(&mut self, center: [f32; 2], radius: f32, width: f32, color: [f32; 4]) {
+        self.push(SdfShape {
+            position: center.map(|c| c - radius),
+            size: [2.0 * radius; 2],
+            border_width: width,
+            fill_color: color,
+            shape_type: SdfShapeType::Ring as u32,
+            ..SdfShape::default()
+        });
+    }
+
+    /// Uploads the batch through `allocator`, which must hand out host-visible
+    /// `VERTEX_BUFFER` memory, draws it in the order the shapes were added and empties it.
+    pub fn flush(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        allocator: &SubbufferAllocator,
+    ) -> Result<(), ThorusError> {
+        if self.shapes.is_empty() {
+            return Ok(());
+        }
+        let buffer = allocator.allocate_slice::<SdfShape>(self.shapes.len() as u64)?;
+        buffer.write()?.copy_from_slice(&self.shapes);
+
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())?
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                SpriteParams {
+                    screen_size: self.extent.map(|dimension| dimension as f32),
+                },
+            )?
+            .bind_vertex_buffers(0, buffer)?
+            .draw(4, self.shapes.len() as u32, 0, 0)?;
+        self.shapes.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::RenderPassBuilder;
+    use crate::testing::TestContext;
+    use vulkano::buffer::allocator::SubbufferAllocatorCreateInfo;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::{
+        ClearColorImageInfo, CopyImageToBufferInfo, RenderPassBeginInfo, SubpassBeginInfo,
+        SubpassContents, SubpassEndInfo,
+    };
+    use vulkano::format::{ClearColorValue, Format};
+    use vulkano::image::{Image, ImageCreateInfo, ImageLayout, ImageUsage, SampleCount};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+    use vulkano::render_pass::{
+        AttachmentLoadOp, AttachmentStoreOp, Framebuffer, FramebufferCreateInfo,
+    };
+
+    /// Headless color target the UI renderers draw into, cleared to transparent black.
+    struct TestTarget {
+        render_pass: Arc<RenderPass>,
+        framebuffer: Arc<Framebuffer>,
+        image: Arc<Image>,
+        vertex_allocator: SubbufferAllocator,
+    }
+
+    impl TestTarget {
+        const SIZE: u32 = 64;
+
+        fn new(context: &TestContext) -> Self {
+            let image = Image::new(
+                context.memory_allocator.clone(),
+                ImageCreateInfo {
+                    format: Format::R8G8B8A8_UNORM,
+                    extent: [Self::SIZE, Self::SIZE, 1],
+                    usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                    ..ImageCreateInfo::default()
+                },
+                AllocationCreateInfo::default(),
+            )
+            .unwrap();
+            let render_pass = RenderPassBuilder::new(context.queue.device().clone())
+                .add_attachment(
+                    Format::R8G8B8A8_UNORM,
+                    SampleCount::Sample1,
+                    AttachmentLoadOp::Clear,
+                    AttachmentStoreOp::Store,
+                    ImageLayout::ColorAttachmentOptimal,
+                )
+                .add_subpass(&[0], &[], None)
+                .build()
+                .unwrap();
+            let framebuffer = Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![ImageView::new_default(image.clone()).unwrap()],
+                    ..FramebufferCreateInfo::default()
+                },
+            )
+            .unwrap();
+            let vertex_allocator = SubbufferAllocator::new(
+                context.memory_allocator.clone(),
+                SubbufferAllocatorCreateInfo {
+                    buffer_usage: BufferUsage::VERTEX_BUFFER,
+                    memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..SubbufferAllocatorCreateInfo::default()
+                },
+            );
+            Self {
+                render_pass,
+                framebuffer,
+                image,
+                vertex_allocator,
+            }
+        }
+
+        fn begin(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+            builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![Some([0.0; 4].into())],
+                        ..RenderPassBeginInfo::framebuffer(self.framebuffer.clone())
+                    },
+                    SubpassBeginInfo {
+                        contents: SubpassContents::Inline,
+                        ..SubpassBeginInfo::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        /// Ends the render pass, executes `builder` and reads the pixels back row by row.
+        fn finish(
+            &self,
+            context: &TestContext,
+            mut builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        ) -> Vec<[u8; 4]> {
+            let readback = Buffer::new_slice::<[u8; 4]>(
+                context.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_DST,
+                    ..BufferCreateInfo::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                        | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                    ..AllocationCreateInfo::default()
+                },
+                (Self::SIZE * Self::SIZE) as u64,
+            )
+            .unwrap();
+            builder
+                .end_render_pass(SubpassEndInfo::default())
+                .unwrap()
+                .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                    self.image.clone(),
+                    readback.clone(),
+                ))
+                .unwrap();
+            context.submit(builder);
+            let pixels = readback.read().unwrap().to_vec();
+            pixels
+        }
+    }
+
+    #[test]
+    fn shadow_quad_is_the_rect_grown_by_the_blur_radius() {
+        let rect = Rect::new([100, 50], [200, 80]);
+        let params = DropShadowParams::new(rect, 12.0, [0.0, 0.0], [0.0; 4], 8.0, [800.0, 600.0]);
+        assert_eq!(params.bounds, [88.0, 38.0, 224.0, 104.0]);
+        assert_eq!(params.shape, [200.0, 90.0, 100.0, 40.0]);
+
+        // the offset moves the quad and the box casting the shadow alike
+        let moved = DropShadowParams::new(rect, 12.0, [4.0, 6.0], [0.0; 4], 8.0, [800.0, 600.0]);
+        assert_eq!(moved.bounds, [92.0, 44.0, 224.0, 104.0]);
+        assert_eq!(moved.shape, [204.0, 96.0, 100.0, 40.0]);
+    }
+
+    #[test]
+    fn negative_blur_radius_does_not_shrink_the_quad() {
+        let rect = Rect::new([10, 20], [30, 40]);
+        assert_eq!(
+            drop_shadow_bounds(rect, -5.0, [0.0, 0.0]),
+            [10.0, 20.0, 30.0, 40.0]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn sprites_do_not_draw_outside_the_clip_region() {
+        let context = TestContext::new();
+        let target = TestTarget::new(&context);
+        let texture = Image::new(
+            context.memory_allocator.clone(),
+            ImageCreateInfo {
+                format: Format::R8G8B8A8_UNORM,
+                extent: [1, 1, 1],
+                usage: ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+        let mut renderer = SpriteRenderer::new(
+            context.queue.device().clone(),
+            target.render_pass.clone(),
+            0,
+            [TestTarget::SIZE; 2],
+        )
+        .unwrap();
+
+        let mut builder = context.command_buffer();
+        builder
+            .clear_color_image(ClearColorImageInfo {
+                clear_value: ClearColorValue::Float([1.0; 4]),
+                ..ClearColorImageInfo::image(texture.clone())
+            })
+            .unwrap();
+        target.begin(&mut builder);
+        // the left half is clipped in, the sprite covers the whole target
+        let size = TestTarget::SIZE;
+        renderer
+            .begin_clip(&mut builder, Rect::new([0, 0], [size / 2, size]))
+            .unwrap();
+        renderer
+            .draw(
+                &mut builder,
+                &target.vertex_allocator,
+                ImageView::new_default(texture).unwrap(),
+                &[SpriteInstance {
+                    rect: [0.0, 0.0, size as f32, size as f32],
+                    uv_rect: [0.0, 0.0, 1.0, 1.0],
+                    color: [1.0; 4],
+                }],
+            )
+            .unwrap();
+        renderer.end_clip(&mut builder).unwrap();
+        assert!(renderer.clip_stack().is_empty());
+        let pixels = target.finish(&context, builder);
+
+        for (i, pixel) in pixels.iter().enumerate() {
+            let x = i as u32 % size;
+            let expected = if x < size / 2 { [255; 4] } else { [0; 4] };
+            assert_eq!(*pixel, expected, "pixel ({x}, {})", i as u32 / size);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn sdf_batch_is_drawn_and_emptied_on_flush() {
+        let context = TestContext::new();
+        let target = TestTarget::new(&context);
+        let mut renderer = SdfRenderer::new(
+            context.queue.device().clone(),
+            target.render_pass.clone(),
+            0,
+            [TestTarget::SIZE; 2],
+        )
+        .unwrap();
+        renderer.draw_circle([16.0, 32.0], 12.0, [1.0; 4]);
+        renderer.draw_ring([48.0, 32.0], 12.0, 3.0, [1.0; 4]);
+        renderer.draw_rounded_rect([0.0, 0.0], [0.0, 0.0], 0.0, [1.0; 4]);
+        let types: Vec<_> = renderer
+            .shapes()
+            .iter()
+            .map(|shape| shape.shape_type)
+            .collect();
+        assert_eq!(
+            types,
+            [
+                SdfShapeType::Circle as u32,
+                SdfShapeType::Ring as u32,
+                SdfShapeType::Rect as u32
+            ]
+        );
+
+        let mut builder = context.command_buffer();
+        target.begin(&mut builder);
+        renderer
+            .flush(&mut builder, &target.vertex_allocator)
+            .unwrap();
+        assert!(renderer.shapes().is_empty());
+        // an empty batch records nothing
+        renderer
+            .flush(&mut builder, &target.vertex_allocator)
+            .unwrap();
+        let pixels = target.finish(&context, builder);
+
+        let pixel = |x: u32, y: u32| pixels[(y * TestTarget::SIZE + x) as usize];
+        assert_eq!(pixel(16, 32), [255; 4], "inside the circle");
+        assert_eq!(pixel(37, 32), [255; 4], "on the ring");
+        assert_eq!(pixel(48, 32), [0; 4], "inside the ring");
+        assert_eq!(pixel(0, 0), [0; 4], "outside every shape");
+    }
+}
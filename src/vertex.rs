@@ -1,9 +1,141 @@
+use serde::{Deserialize, Serialize};
 use vulkano::buffer::BufferContents;
 use vulkano::pipeline::graphics::vertex_input::Vertex;
 
-#[derive(BufferContents, Vertex, Debug)]
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
 #[repr(C)]
 pub struct MyVertex {
     #[format(R32G32_SFLOAT)]
     pub position: [f32; 2],
 }
+
+#[derive(
+    BufferContents, Vertex, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug,
+)]
+#[repr(C)]
+pub struct Vertex3D {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    pub uv: [f32; 2],
+    /// Direction of increasing `u` in `xyz`; `w` is the sign of the bitangent,
+    /// `cross(normal, tangent.xyz) * w`.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub tangent: [f32; 4],
+}
+
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct DebugVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4],
+}
+
+/// Per-instance index into a [`BindlessTextureArray`](crate::descriptor::BindlessTextureArray).
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct TextureInstance {
+    #[format(R32_UINT)]
+    pub texture_index: u32,
+}
+
+/// Per-instance quad of a [`SpriteRenderer`](crate::ui::SpriteRenderer).
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct SpriteInstance {
+    /// Left, top, width and height in pixels.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub rect: [f32; 4],
+    /// Left, top, width and height in texture coordinates.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub uv_rect: [f32; 4],
+    /// Multiplied with the texture.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4],
+}
+
+/// Shape drawn by an [`SdfRenderer`](crate::ui::SdfRenderer).
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum SdfShapeType {
+    /// Rectangle with corners rounded by `corner_radius`.
+    #[default]
+    Rect = 0,
+    /// Circle filling the smaller side of the shape.
+    Circle = 1,
+    /// Band of `border_width` inside the circle, drawn in `fill_color`.
+    Ring = 2,
+}
+
+/// Per-instance shape of an [`SdfRenderer`](crate::ui::SdfRenderer).
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct SdfShape {
+    /// Left and top in pixels.
+    #[format(R32G32_SFLOAT)]
+    pub position: [f32; 2],
+    /// Width and height in pixels.
+    #[format(R32G32_SFLOAT)]
+    pub size: [f32; 2],
+    #[format(R32_SFLOAT)]
+    pub corner_radius: f32,
+    /// Width of the border drawn inside the edge; zero draws none.
+    #[format(R32_SFLOAT)]
+    pub border_width: f32,
+    #[format(R32G32B32A32_SFLOAT)]
+    pub fill_color: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    pub border_color: [f32; 4],
+    /// An [`SdfShapeType`] as `u32`.
+    #[format(R32_UINT)]
+    pub shape_type: u32,
+}
+
+/// Per-instance tile of a [`TilemapRenderer`](crate::tilemap::TilemapRenderer).
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct TileInstance {
+    /// Left, top, width and height in world units.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub rect: [f32; 4],
+    /// Layer of the [`TileAtlas`](crate::tilemap::TileAtlas).
+    #[format(R32_UINT)]
+    pub layer: u32,
+}
+
+/// Vertex written by a [`TransformFeedbackPass`](crate::pipeline::TransformFeedbackPass),
+/// laid out so the capture buffer can be bound as a vertex buffer afterwards.
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct CapturedVertex {
+    #[format(R32G32B32A32_SFLOAT)]
+    pub position: [f32; 4],
+    /// `w` is always zero.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub normal: [f32; 4],
+}
+
+/// Bind-pose vertex influenced by up to four bones, skinned into a [`Vertex3D`] by a
+/// [`GpuSkinningPass`](crate::animation::GpuSkinningPass).
+#[derive(BufferContents, Vertex, Clone, Copy, Default, PartialEq, Debug)]
+#[repr(C)]
+pub struct SkinnedVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    pub uv: [f32; 2],
+    #[format(R32G32B32A32_SFLOAT)]
+    pub tangent: [f32; 4],
+    /// Indices into the bone matrices.
+    #[format(R32G32B32A32_UINT)]
+    pub joints: [u32; 4],
+    /// Influence of each joint, summing to one.
+    #[format(R32G32B32A32_SFLOAT)]
+    pub weights: [f32; 4],
+}
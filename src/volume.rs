@@ -0,0 +1,196 @@
+use crate::compute::ComputePass;
+use crate::error::ThorusError;
+use crate::shader::{load_fog, load_fog_composite};
+use std::sync::Arc;
+use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::WriteDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Mirrors the push constant block of `shader/fog.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct FogConfig {
+    /// Extinction per world unit where the density volume reads 1.
+    pub density: f32,
+    /// Henyey-Greenstein `g`, positive values scatter forward into light shafts.
+    pub anisotropy: f32,
+    pub step_count: u32,
+    /// Density volume repeats per world unit.
+    pub noise_scale: f32,
+}
+
+impl Default for FogConfig {
+    fn default() -> Self {
+        Self {
+            density: 0.02,
+            anisotropy: 0.6,
+            step_count: 32,
+            noise_scale: 0.05,
+        }
+    }
+}
+
+/// Mirrors the `FogUniforms` block of `shader/fog.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct FogUniforms {
+    pub inv_view_proj: [[f32; 4]; 4],
+    /// Matrix the shadow map was rendered with.
+    pub light_view_proj: [[f32; 4]; 4],
+    pub camera_position: [f32; 4],
+    /// Direction the light travels in.
+    pub light_direction: [f32; 4],
+    pub light_color: [f32; 4],
+}
+
+/// Ray-marched fog and light shafts of a directional light.
+///
+/// [`VolumetricFog::record`] marches every pixel of a half-resolution target through a tiling
+/// 3D density texture, then blends the result into the HDR image.
+pub struct VolumetricFog {
+    march: ComputePass,
+    composite: ComputePass,
+    point_sampler: Arc<Sampler>,
+    repeat_sampler: Arc<Sampler>,
+    clamp_sampler: Arc<Sampler>,
+}
+
+impl VolumetricFog {
+    pub fn new(device: Arc<Device>) -> Result<Self, ThorusError> {
+        let march = ComputePass::new(device.clone(), load_fog(device.clone())?)?;
+        let composite = ComputePass::new(device.clone(), load_fog_composite(device.clone())?)?;
+
+        let sampler = |filter, address_mode| {
+            Sampler::new(
+                device.clone(),
+                SamplerCreateInfo {
+                    mag_filter: filter,
+                    min_filter: filter,
+                    address_mode: [address_mode; 3],
+                    ..SamplerCreateInfo::default()
+                },
+            )
+            .map_err(ThorusError::from)
+        };
+
+        Ok(Self {
+            point_sampler: sampler(Filter::Nearest, SamplerAddressMode::ClampToEdge)?,
+            repeat_sampler: sampler(Filter::Linear, SamplerAddressMode::Repeat)?,
+            clamp_sampler: sampler(Filter::Linear, SamplerAddressMode::ClampToEdge)?,
+            march,
+            composite,
+        })
+    }
+
+    /// Creates the half-resolution color and opacity target for a `full_extent` HDR image.
+    pub fn fog_image(
+        allocator: Arc<dyn MemoryAllocator>,
+        full_extent: [u32; 2],
+    ) -> Result<Arc<ImageView>, ThorusError> {
+        let image = Image::new(
+            allocator,
+            ImageCreateInfo {
+                format: Format::R16G16B16A16_SFLOAT,
+                extent: [
+                    full_extent[0].div_ceil(2).max(1),
+                    full_extent[1].div_ceil(2).max(1),
+                    1,
+                ],
+                usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+                ..ImageCreateInfo::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        ImageView::new_default(image).map_err(ThorusError::from)
+    }
+
+    /// Records the march into `fog` followed by the composite into `hdr`, an
+    /// `R16G16B16A16_SFLOAT` storage image.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        depth: Arc<ImageView>,
+        density: Arc<ImageView>,
+        shadow_map: Arc<ImageView>,
+        fog: Arc<ImageView>,
+        hdr: Arc<ImageView>,
+        uniforms: Subbuffer<FogUniforms>,
+        config: FogConfig,
+    ) -> Result<(), ThorusError> {
+        let [fog_width, fog_height, _] = fog.image().extent();
+        let [width, height, _] = hdr.image().extent();
+
+        self.march.bind(
+            builder,
+            [
+                WriteDescriptorSet::image_view_sampler(0, depth, self.point_sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(1, density, self.repeat_sampler.clone()),
+                WriteDescriptorSet::image_view_sampler(2, shadow_map, self.point_sampler.clone()),
+                WriteDescriptorSet::image_view(3, fog.clone()),
+                WriteDescriptorSet::buffer(4, uniforms),
+            ],
+        )?;
+        self.march.push_constants(builder, config)?;
+        self.march.dispatch(
+            builder,
+            [
+                fog_width.div_ceil(WORKGROUP_SIZE),
+                fog_height.div_ceil(WORKGROUP_SIZE),
+                1,
+            ],
+        )?;
+
+        self.composite.bind(
+            builder,
+            [
+                WriteDescriptorSet::image_view_sampler(0, fog, self.clamp_sampler.clone()),
+                WriteDescriptorSet::image_view(1, hdr),
+            ],
+        )?;
+        self.composite.dispatch(
+            builder,
+            [
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+    use std::mem::size_of;
+    use vulkano::pipeline::Pipeline;
+
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn fog_shaders_compile_into_pipelines() {
+        let context = TestContext::new();
+        let fog = VolumetricFog::new(context.queue.device().clone()).unwrap();
+
+        let march = fog.march.pipeline().layout();
+        assert_eq!(march.set_layouts()[0].bindings().len(), 5);
+        assert_eq!(
+            march.push_constant_ranges()[0].size as usize,
+            size_of::<FogConfig>()
+        );
+        let composite = fog.composite.pipeline().layout();
+        assert_eq!(composite.set_layouts()[0].bindings().len(), 2);
+
+        let image =
+            VolumetricFog::fog_image(context.memory_allocator.clone(), [1281, 720]).unwrap();
+        assert_eq!(image.image().extent(), [641, 360, 1]);
+    }
+}
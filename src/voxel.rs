@@ -0,0 +1,337 @@
+use crate::error::ThorusError;
+use std::sync::Arc;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryAllocator, MemoryTypeFilter};
+
+/// Depth limit keeping voxel coordinates within `i32` and the traversal stack of
+/// `shader/svo_trace.comp` bounded.
+pub const MAX_OCTREE_DEPTH: u32 = 16;
+
+/// Child index of an absent child; the root is never anyone's child.
+pub const EMPTY_CHILD: u32 = 0;
+
+/// Node of a [`SparseVoxelOctree`], laid out as `shader/svo_trace.comp` reads it.
+///
+/// Children are ordered by the bits `x | y << 1 | z << 2` of the octant. Only leaves, the
+/// nodes at the maximum depth, carry data.
+#[derive(BufferContents, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct OctreeNode {
+    pub children: [u32; 8],
+    pub data: u32,
+}
+
+/// Mirrors the push constant block of `shader/svo_trace.comp`.
+#[derive(BufferContents, Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SvoTraceParams {
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub camera_position: [f32; 4],
+    pub world_size: f32,
+    pub max_depth: u32,
+    pub _padding: [u32; 2],
+}
+
+/// Voxels of a `2^max_depth` cube grid covering `[0, world_size)` on every axis, stored as an
+/// octree in a flat node list with index links, ready to upload as is.
+#[derive(Clone, Debug)]
+pub struct SparseVoxelOctree {
+    nodes: Vec<OctreeNode>,
+    world_size: f32,
+    max_depth: u32,
+    voxel_count: usize,
+}
+
+impl SparseVoxelOctree {
+    /// # Panics
+    ///
+    /// - Panics if `max_depth` is zero or above [`MAX_OCTREE_DEPTH`].
+    pub fn new(world_size: f32, max_depth: u32) -> Self {
+        assert!(
+            (1..=MAX_OCTREE_DEPTH).contains(&max_depth),
+            "octree depth must be between 1 and {MAX_OCTREE_DEPTH}"
+        );
+        Self {
+            nodes: vec![OctreeNode::default()],
+            world_size,
+            max_depth,
+            voxel_count: 0,
+        }
+    }
+
+    pub fn world_size(&self) -> f32 {
+        self.world_size
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    /// Voxels along each axis.
+    pub fn resolution(&self) -> i32 {
+        1 << self.max_depth
+    }
+
+    pub fn voxel_size(&self) -> f32 {
+        self.world_size / self.resolution() as f32
+    }
+
+    pub fn nodes(&self) -> &[OctreeNode] {
+        &self.nodes
+    }
+
+    pub fn voxel_count(&self) -> usize {
+        self.voxel_count
+    }
+
+    fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        let range = 0..self.resolution();
+        range.contains(&x) && range.contains(&y) && range.contains(&z)
+    }
+
+    fn octant(&self, [x, y, z]: [i32; 3], depth: u32) -> usize {
+        let bit = self.max_depth - 1 - depth;
+        (((x >> bit) & 1) | ((y >> bit) & 1) << 1 | ((z >> bit) & 1) << 2) as usize
+    }
+
+    /// Sets the voxel at grid coordinates `x`, `y`, `z`, replacing any previous data.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the coordinates are outside `[0, resolution)`.
+    pub fn insert(&mut self, x: i32, y: i32, z: i32, voxel_data: u32) {
+        assert!(
+            self.contains(x, y, z),
+            "voxel ({x}, {y}, {z}) is outside the octree"
+        );
+        let mut node = 0;
+        let mut created = false;
+        for depth in 0..self.max_depth {
+            let octant = self.octant([x, y, z], depth);
+            let child = self.nodes[node].children[octant];
+            node = if child == EMPTY_CHILD {
+                created = true;
+                self.nodes.push(OctreeNode::default());
+                let child = self.nodes.len() - 1;
+                self.nodes[node].children[octant] = child as u32;
+                child
+            } else {
+                child as usize
+            };
+        }
+        self.nodes[node].data = voxel_data;
+        if created {
+            self.voxel_count += 1;
+        }
+    }
+
+    /// Data of the voxel at grid coordinates `x`, `y`, `z`, if set.
+    pub fn query(&self, x: i32, y: i32, z: i32) -> Option<u32> {
+        if !self.contains(x, y, z) {
+            return None;
+        }
+        let mut node = 0;
+        for depth in 0..self.max_depth {
+            match self.nodes[node].children[self.octant([x, y, z], depth)] {
+                EMPTY_CHILD => return None,
+                child => node = child as usize,
+            }
+        }
+        Some(self.nodes[node].data)
+    }
+
+    /// First voxel hit by the ray from `origin` along `direction`, both in world units, as
+    /// the distance to where the ray enters it, its grid coordinates and its data. A voxel
+    /// containing `origin` is hit at distance 0.
+    pub fn raycast(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<(f32, [i32; 3], u32)> {
+        let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+        if length <= f32::EPSILON {
+            return None;
+        }
+        let ray = Ray {
+            origin,
+            inv_direction: direction.map(|c| {
+                let c = c / length;
+                if c == 0.0 {
+                    f32::INFINITY
+                } else {
+                    1.0 / c
+                }
+            }),
+        };
+        self.raycast_node(&ray, 0, [0.0; 3], self.world_size, 0)
+    }
+
+    fn raycast_node(
+        &self,
+        ray: &Ray,
+        node: usize,
+        min: [f32; 3],
+        size: f32,
+        depth: u32,
+    ) -> Option<(f32, [i32; 3], u32)> {
+        let (enter, _) = ray.intersect(min, size)?;
+        if depth == self.max_depth {
+            let voxel_size = self.voxel_size();
+            let coordinates = min.map(|c| (c / voxel_size).round() as i32);
+            return Some((enter, coordinates, self.nodes[node].data));
+        }
+
+        let half = size / 2.0;
+        let mut children = self.nodes[node]
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, &child)| child != EMPTY_CHILD)
+            .filter_map(|(octant, &child)| {
+                let child_min = [0, 1, 2]
+                    .map(|axis| min[axis] + if octant >> axis & 1 == 1 { half } else { 0.0 });
+                let (enter, _) = ray.intersect(child_min, half)?;
+                Some((enter, child as usize, child_min))
+            })
+            .collect::<Vec<_>>();
+        children.sort_by(|(a, ..), (b, ..)| a.total_cmp(b));
+        children.into_iter().find_map(|(_, child, child_min)| {
+            self.raycast_node(ray, child, child_min, half, depth + 1)
+        })
+    }
+
+    /// Copies the nodes into a storage buffer for `shader/svo_trace.comp`.
+    pub fn upload(
+        &self,
+        allocator: Arc<dyn MemoryAllocator>,
+    ) -> Result<Subbuffer<[OctreeNode]>, ThorusError> {
+        Buffer::from_iter(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..BufferCreateInfo::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..AllocationCreateInfo::default()
+            },
+            self.nodes.iter().copied(),
+        )
+        .map_err(ThorusError::from)
+    }
+}
+
+struct Ray {
+    origin: [f32; 3],
+    /// Reciprocal of the normalized direction; infinite along axes it does not move on.
+    inv_direction: [f32; 3],
+}
+
+impl Ray {
+    /// Distances at which the ray enters and leaves the cube, if it hits it in front of the
+    /// origin; the enter distance is clamped to 0 when the origin is inside.
+    fn intersect(&self, min: [f32; 3], size: f32) -> Option<(f32, f32)> {
+        let mut enter = 0.0f32;
+        let mut leave = f32::INFINITY;
+        for ((min, origin), inv) in min.into_iter().zip(self.origin).zip(self.inv_direction) {
+            if inv.is_infinite() {
+                if origin < min || origin >= min + size {
+                    return None;
+                }
+                continue;
+            }
+            let near = (min - origin) * inv;
+            let far = (min + size - origin) * inv;
+            enter = enter.max(near.min(far));
+            leave = leave.min(near.max(far));
+        }
+        (enter < leave).then_some((enter, leave))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::xorshift32;
+    use std::collections::HashMap;
+
+    fn random_octree(count: usize) -> (SparseVoxelOctree, HashMap<[i32; 3], u32>) {
+        let mut random = xorshift32(0x9e37_79b9);
+        let mut octree = SparseVoxelOctree::new(32.0, 6);
+        let mut voxels = HashMap::new();
+        while voxels.len() < count {
+            let coordinates = [0; 3].map(|_| (random() % 64) as i32);
+            let data = random();
+            let [x, y, z] = coordinates;
+            octree.insert(x, y, z, data);
+            voxels.insert(coordinates, data);
+        }
+        (octree, voxels)
+    }
+
+    #[test]
+    fn inserted_voxels_are_found_by_query_and_raycast() {
+        let (octree, voxels) = random_octree(1000);
+        assert_eq!(octree.voxel_count(), 1000);
+        let size = octree.voxel_size();
+        let directions = [[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.3, 0.5, -0.8]];
+        for (&coordinates, &data) in &voxels {
+            let [x, y, z] = coordinates;
+            assert_eq!(octree.query(x, y, z), Some(data));
+            let center = coordinates.map(|c| (c as f32 + 0.5) * size);
+            for direction in directions {
+                assert_eq!(
+                    octree.raycast(center, direction),
+                    Some((0.0, coordinates, data))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn raycast_finds_the_nearest_voxel() {
+        let (octree, voxels) = random_octree(1000);
+        let size = octree.voxel_size();
+        let mut random = xorshift32(0x85eb_ca6b);
+        let mut unit = || random() as f32 / u32::MAX as f32;
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = [0; 3].map(|_| unit() * 48.0 - 8.0);
+            let target = [0; 3].map(|_| unit() * 32.0);
+            let direction = [0, 1, 2].map(|i| target[i] - origin[i]);
+            let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+            let ray = Ray {
+                origin,
+                inv_direction: direction.map(|c| length / c),
+            };
+            let nearest = voxels
+                .keys()
+                .filter_map(|coordinates| {
+                    let min = coordinates.map(|c| c as f32 * size);
+                    Some(ray.intersect(min, size)?.0)
+                })
+                .min_by(f32::total_cmp);
+            match (octree.raycast(origin, direction), nearest) {
+                (None, None) => {}
+                (Some((t, [x, y, z], data)), Some(nearest)) => {
+                    hits += 1;
+                    assert!(
+                        (t - nearest).abs() < 1e-4,
+                        "hit at {t}, nearest at {nearest}"
+                    );
+                    assert_eq!(voxels.get(&[x, y, z]), Some(&data));
+                }
+                (hit, nearest) => panic!("raycast {hit:?}, nearest voxel at {nearest:?}"),
+            }
+        }
+        assert!(hits > 100, "only {hits} rays hit a voxel");
+    }
+
+    #[test]
+    fn reinserting_replaces_the_data() {
+        let mut octree = SparseVoxelOctree::new(1.0, 2);
+        octree.insert(1, 2, 3, 7);
+        octree.insert(1, 2, 3, 9);
+        assert_eq!(octree.voxel_count(), 1);
+        assert_eq!(octree.query(1, 2, 3), Some(9));
+        assert_eq!(octree.query(3, 2, 1), None);
+        assert_eq!(octree.query(-1, 0, 0), None);
+    }
+}
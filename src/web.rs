@@ -0,0 +1,41 @@
+//! Browser entry point, built for `wasm32` with the `web` feature.
+//!
+//! vulkano has no WebGPU backend and browsers expose no Vulkan, so nothing is rendered yet:
+//! [`run`] sets up logging and winit's canvas backend and leaves the Vulkan initialization
+//! of the native binary out.
+
+use tracing::{debug, warn};
+use wasm_bindgen::prelude::*;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::platform::web::WindowExtWebSys;
+use winit::window::WindowBuilder;
+
+/// Opens a canvas at the end of the page body and runs the event loop until it is closed.
+#[wasm_bindgen]
+pub fn run() -> Result<(), JsValue> {
+    tracing_wasm::set_as_global_default();
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("thorus")
+        .build(&event_loop)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let body = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.body())
+        .ok_or("the page has no body")?;
+    body.append_child(&window.canvas())?;
+    debug!("canvas created");
+    warn!("Vulkan is not available in browsers, the canvas stays empty");
+
+    event_loop.run(move |event, _, control_flow| {
+        if let Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } = event
+        {
+            *control_flow = ControlFlow::Exit;
+        }
+    })
+}
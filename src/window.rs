@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use tracing::debug;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+/// Window that keeps track of its scale factor, so the swapchain and viewport can follow the
+/// physical size while UI layout and the camera work in logical units.
+///
+/// On HiDPI displays winit reports sizes in physical pixels, which are `scale_factor` times
+/// the logical size the desktop lays windows out in.
+pub struct DpiAwareWindow {
+    window: Arc<Window>,
+    scale_factor: f64,
+    physical_size: [u32; 2],
+    viewport: Viewport,
+}
+
+impl DpiAwareWindow {
+    pub fn new(window: Arc<Window>) -> Self {
+        let scale_factor = window.scale_factor();
+        let physical_size = window.inner_size().into();
+        Self {
+            window,
+            scale_factor,
+            physical_size,
+            viewport: full_viewport(physical_size),
+        }
+    }
+
+    pub fn window(&self) -> &Arc<Window> {
+        &self.window
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Size of the framebuffer in pixels, the extent of the swapchain images.
+    pub fn physical_size(&self) -> [u32; 2] {
+        self.physical_size
+    }
+
+    /// Size in logical units, for UI layout.
+    pub fn logical_size(&self) -> [f32; 2] {
+        to_logical(self.physical_size, self.scale_factor)
+    }
+
+    /// Width over height, for the camera projection; the same in logical and physical units.
+    pub fn aspect_ratio(&self) -> f32 {
+        let [width, height] = self.logical_size();
+        width / height.max(f32::MIN_POSITIVE)
+    }
+
+    /// Viewport covering the whole framebuffer.
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// Takes the size of a `WindowEvent::Resized`.
+    pub fn handle_resized(&mut self, size: PhysicalSize<u32>) {
+        self.physical_size = size.into();
+        self.viewport = full_viewport(self.physical_size);
+    }
+
+    /// Takes the scale factor of a `WindowEvent::ScaleFactorChanged`, e.g. when the window
+    /// moves to a display with different scaling, and updates the viewport to the new
+    /// physical size.
+    pub fn handle_scale_factor_changed(&mut self, new_factor: f64) {
+        debug!(
+            "scale factor changed from {} to {new_factor}",
+            self.scale_factor
+        );
+        self.scale_factor = new_factor;
+        self.handle_resized(self.window.inner_size());
+    }
+}
+
+/// `physical_size` divided by `scale_factor`.
+pub fn to_logical(physical_size: [u32; 2], scale_factor: f64) -> [f32; 2] {
+    physical_size.map(|dimension| (dimension as f64 / scale_factor) as f32)
+}
+
+fn full_viewport(physical_size: [u32; 2]) -> Viewport {
+    Viewport {
+        offset: [0.0, 0.0],
+        extent: physical_size.map(|dimension| dimension as f32),
+        depth_range: 0.0..=1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_scale_factor_halves_the_logical_size() {
+        assert_eq!(to_logical([1600, 900], 2.0), [800.0, 450.0]);
+        assert_eq!(to_logical([1600, 900], 1.0), [1600.0, 900.0]);
+        assert_eq!(to_logical([1600, 900], 1.25), [1280.0, 720.0]);
+    }
+
+    #[test]
+    fn viewport_covers_the_physical_size() {
+        let viewport = full_viewport([1600, 900]);
+        assert_eq!(viewport.offset, [0.0, 0.0]);
+        assert_eq!(viewport.extent, [1600.0, 900.0]);
+    }
+}